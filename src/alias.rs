@@ -0,0 +1,72 @@
+use crate::config::GitConfig;
+use crate::GitError;
+
+// Curated alias packs that can be imported in one go
+const ALIAS_PACKS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "shortcuts",
+        &[
+            ("co", "checkout"),
+            ("br", "branch"),
+            ("ci", "commit"),
+            ("st", "status"),
+            ("sw", "switch"),
+            ("unstage", "reset HEAD --"),
+            ("last", "log -1 HEAD"),
+            ("amend", "commit --amend --no-edit"),
+        ],
+    ),
+    (
+        "log-beauty",
+        &[
+            ("lg", "log --graph --abbrev-commit --decorate --format=format:'%C(bold blue)%h%C(reset) - %C(bold green)(%ar)%C(reset) %C(white)%s%C(reset) %C(dim white)- %an%C(reset)%C(auto)%d%C(reset)'"),
+            ("lol", "log --graph --oneline --decorate"),
+            ("lola", "log --graph --oneline --decorate --all"),
+            ("hist", "log --pretty=format:'%h %ad | %s%d [%an]' --graph --date=short"),
+        ],
+    ),
+];
+
+// Function to create or replace a global alias
+pub fn set_alias(name: &str, command: &str) -> Result<(), GitError> {
+    GitConfig::global().set(&format!("alias.{}", name), command)
+}
+
+// Function to remove a global alias
+pub fn remove_alias(name: &str) -> Result<(), GitError> {
+    GitConfig::global().unset(&format!("alias.{}", name))
+}
+
+// Function to list global aliases as (name, command) pairs
+pub fn list_aliases() -> Result<Vec<(String, String)>, GitError> {
+    Ok(GitConfig::global().list()?
+        .into_iter()
+        .filter_map(|(key, value)| key.strip_prefix("alias.").map(|name| (name.to_string(), value)))
+        .collect())
+}
+
+// Function to get the names of the available alias packs
+pub fn alias_packs() -> Vec<&'static str> {
+    ALIAS_PACKS.iter().map(|(name, _)| *name).collect()
+}
+
+// Function to install every alias from a curated pack, returning the aliases it set
+pub fn import_alias_pack(pack: &str) -> Result<Vec<(String, String)>, GitError> {
+    let (_, aliases) = ALIAS_PACKS
+        .iter()
+        .find(|(name, _)| *name == pack)
+        .ok_or_else(|| {
+            GitError::InvalidInput(format!(
+                "unknown alias pack '{}' (available: {})",
+                pack,
+                alias_packs().join(", ")
+            ))
+        })?;
+
+    let mut imported = Vec::new();
+    for (name, command) in aliases.iter() {
+        set_alias(name, command)?;
+        imported.push((name.to_string(), command.to_string()));
+    }
+    Ok(imported)
+}
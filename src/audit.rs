@@ -0,0 +1,179 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::git::run_git;
+use crate::GitError;
+
+// How much a broken rule matters; only errors make an audit fail
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Error,
+    Warning,
+}
+
+// An organisation's baseline for git settings, read from a TOML file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+}
+
+// A requirement on one config key; value conditions apply to the value git actually uses
+// (the last one set), while `forbidden` applies to every value, as for credential helpers
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub key: String,
+    #[serde(default)]
+    pub required: bool,
+    // The key must not be set at all
+    #[serde(default)]
+    pub absent: bool,
+    pub equals: Option<String>,
+    pub one_of: Option<Vec<String>>,
+    #[serde(default)]
+    pub forbidden: Vec<String>,
+    pub starts_with: Option<String>,
+    pub ends_with: Option<String>,
+    #[serde(default)]
+    pub severity: Severity,
+    // Explanation shown alongside a violation, e.g. a link to the internal guideline
+    pub message: Option<String>,
+}
+
+// A rule the current configuration breaks
+#[derive(Debug, Clone, Serialize)]
+pub struct Violation {
+    pub key: String,
+    pub severity: Severity,
+    // What the rule expects, e.g. "must end with @corp.com"
+    pub expected: String,
+    // Values the condition looked at, empty when the key is unset
+    pub actual: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+// Function to read a policy file, rejecting unknown fields and rules without a condition
+pub fn load_policy(path: &Path) -> Result<Policy, GitError> {
+    let contents = fs::read_to_string(path)?;
+    let policy: Policy = toml::from_str(&contents)
+        .map_err(|e| GitError::Parse(format!("{}: {}", path.display(), e)))?;
+    for rule in &policy.rules {
+        if rule.conditions().is_empty() {
+            return Err(GitError::Parse(format!(
+                "{}: the rule for '{}' has no condition",
+                path.display(),
+                rule.key
+            )));
+        }
+    }
+    Ok(policy)
+}
+
+// Function to check the configuration git uses in the current directory against a policy,
+// returning every violation
+pub fn audit(policy: &Policy) -> Result<Vec<Violation>, GitError> {
+    let mut violations = Vec::new();
+    for rule in &policy.rules {
+        let values = config_values(&rule.key)?;
+        let effective = &values[values.len().saturating_sub(1)..];
+        for (expected, all_values, holds) in rule.conditions() {
+            let checked = if all_values { &values[..] } else { effective };
+            if !holds(checked) {
+                violations.push(Violation {
+                    key: rule.key.clone(),
+                    severity: rule.severity,
+                    expected,
+                    actual: checked.to_vec(),
+                    message: rule.message.clone(),
+                });
+            }
+        }
+    }
+    Ok(violations)
+}
+
+type Condition<'a> = Box<dyn Fn(&[String]) -> bool + 'a>;
+
+impl Rule {
+    // Each condition the rule sets: its description, whether it looks at every value rather
+    // than just the effective one, and the check itself
+    fn conditions(&self) -> Vec<(String, bool, Condition<'_>)> {
+        let mut conditions: Vec<(String, bool, Condition<'_>)> = Vec::new();
+        if self.required {
+            conditions.push(("must be set".to_string(), true, Box::new(|values| !values.is_empty())));
+        }
+        if self.absent {
+            conditions.push(("must not be set".to_string(), true, Box::new(|values| values.is_empty())));
+        }
+        if let Some(expected) = &self.equals {
+            conditions.push((
+                format!("must be {}", expected),
+                false,
+                Box::new(move |values| !values.is_empty() && values.iter().all(|v| same(v, expected))),
+            ));
+        }
+        if let Some(allowed) = &self.one_of {
+            conditions.push((
+                format!("must be one of {}", allowed.join(", ")),
+                false,
+                Box::new(move |values| {
+                    !values.is_empty() && values.iter().all(|v| allowed.iter().any(|a| same(v, a)))
+                }),
+            ));
+        }
+        if !self.forbidden.is_empty() {
+            conditions.push((
+                format!("must not be {}", self.forbidden.join(" or ")),
+                true,
+                Box::new(|values| !values.iter().any(|v| self.forbidden.iter().any(|f| same(v, f)))),
+            ));
+        }
+        if let Some(prefix) = &self.starts_with {
+            conditions.push((
+                format!("must start with {}", prefix),
+                false,
+                Box::new(move |values| !values.is_empty() && values.iter().all(|v| v.starts_with(prefix.as_str()))),
+            ));
+        }
+        if let Some(suffix) = &self.ends_with {
+            conditions.push((
+                format!("must end with {}", suffix),
+                false,
+                Box::new(move |values| !values.is_empty() && values.iter().all(|v| v.ends_with(suffix.as_str()))),
+            ));
+        }
+        conditions
+    }
+}
+
+// Config values compare like git compares booleans: "true", "yes", "on" and "1" are equal
+fn same(value: &str, expected: &str) -> bool {
+    let normalize = |s: &str| match s.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => "true".to_string(),
+        "false" | "no" | "off" | "0" => "false".to_string(),
+        _ => s.to_string(),
+    };
+    value == expected || normalize(value) == normalize(expected)
+}
+
+// Every value of a key across all config files, in the order git reads them
+fn config_values(key: &str) -> Result<Vec<String>, GitError> {
+    let args = ["config", "--get-all", key];
+    let output = run_git(&args)?;
+    match output.status.code() {
+        Some(0) => Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(String::from)
+            .collect()),
+        // git exits with 1 when the key isn't set
+        Some(1) => Ok(Vec::new()),
+        _ => Err(GitError::command_failed("git", &args, &output)),
+    }
+}
@@ -0,0 +1,526 @@
+use std::env;
+use std::fmt;
+use std::iter;
+use std::str::FromStr;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use crate::backup::hostname;
+use crate::config::GitConfig;
+use crate::dry_run::DryRun;
+use crate::git::{run_mutating, run_with_input};
+use crate::secrets::{get_secret, set_secret};
+use crate::signing::{signing_key, SigningKey};
+use crate::GitError;
+
+// A Git hosting service whose API tokens gitup can set up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    GitHub,
+    GitLab,
+    // bitbucket.org only; it takes the account's user name with an app password rather than a
+    // token, so for Bitbucket the token passed around here is "<user name>:<app password>"
+    Bitbucket,
+}
+
+impl Provider {
+    // User name git sends alongside a token; GitHub and GitLab ignore it for token auth, while
+    // Bitbucket needs the account's own
+    fn token_username(&self) -> Option<&'static str> {
+        match self {
+            Provider::GitHub => Some("x-access-token"),
+            Provider::GitLab => Some("oauth2"),
+            Provider::Bitbucket => None,
+        }
+    }
+
+    // The host of the public service
+    pub fn default_host(&self) -> &'static str {
+        match self {
+            Provider::GitHub => "github.com",
+            Provider::GitLab => "gitlab.com",
+            Provider::Bitbucket => "bitbucket.org",
+        }
+    }
+
+    fn api_url(&self, host: &str) -> String {
+        match (self, host) {
+            (Provider::GitHub, "github.com") => "https://api.github.com".to_string(),
+            (Provider::GitHub, _) => format!("https://{}/api/v3", host),
+            (Provider::GitLab, _) => format!("https://{}/api/v4", host),
+            (Provider::Bitbucket, _) => "https://api.bitbucket.org/2.0".to_string(),
+        }
+    }
+
+    fn user_url(&self, host: &str) -> String {
+        format!("{}/user", self.api_url(host))
+    }
+
+    fn authorization(&self, token: &str) -> String {
+        match self {
+            Provider::Bitbucket => format!("Basic {}", STANDARD.encode(token)),
+            _ => format!("Bearer {}", token),
+        }
+    }
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Provider::GitHub => f.write_str("github"),
+            Provider::GitLab => f.write_str("gitlab"),
+            Provider::Bitbucket => f.write_str("bitbucket"),
+        }
+    }
+}
+
+impl FromStr for Provider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "github" => Ok(Provider::GitHub),
+            "gitlab" => Ok(Provider::GitLab),
+            "bitbucket" => Ok(Provider::Bitbucket),
+            other => Err(format!("unknown provider '{}' (expected github, gitlab or bitbucket)", other)),
+        }
+    }
+}
+
+// Who a token belongs to and what it may do
+#[derive(Debug, Clone)]
+pub struct TokenInfo {
+    pub provider: Provider,
+    pub host: String,
+    pub username: String,
+    // Numeric account ID, which unlike the user name never changes
+    pub id: Option<u64>,
+    pub scopes: Vec<String>,
+}
+
+// Function to check a token against the provider's API
+pub fn verify_token(provider: Provider, host: &str, token: &str) -> Result<TokenInfo, GitError> {
+    let (headers, user) = api_get(provider, &provider.user_url(host), token)?;
+    let login_field = match provider {
+        Provider::GitHub => "login",
+        Provider::GitLab | Provider::Bitbucket => "username",
+    };
+    let username = user[login_field].as_str().unwrap_or_default().to_string();
+    let id = user["id"].as_u64();
+
+    let scopes = match provider {
+        // GitHub lists classic token scopes in a response header
+        Provider::GitHub => headers
+            .lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.eq_ignore_ascii_case("x-oauth-scopes").then(|| value.to_string())
+            })
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|scope| scope.trim().to_string())
+                    .filter(|scope| !scope.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        Provider::GitLab => {
+            let url = format!("https://{}/api/v4/personal_access_tokens/self", host);
+            let (_, token) = api_get(provider, &url, token)?;
+            token["scopes"]
+                .as_array()
+                .map(|scopes| scopes.iter().filter_map(|s| s.as_str().map(String::from)).collect())
+                .unwrap_or_default()
+        }
+        // App password permissions can't be read back
+        Provider::Bitbucket => Vec::new(),
+    };
+
+    Ok(TokenInfo {
+        provider,
+        host: host.to_string(),
+        username,
+        id,
+        scopes,
+    })
+}
+
+// Function to verify a token, store it in the keyring and make git use it for the host
+pub fn login(provider: Provider, host: &str, token: &str) -> Result<TokenInfo, GitError> {
+    let info = verify_token(provider, host, token)?;
+    let (username, secret) = match provider.token_username() {
+        Some(username) => (username, token),
+        None => token.split_once(':').ok_or_else(|| {
+            GitError::InvalidInput(format!("{} needs the account's user name with the app password", provider))
+        })?,
+    };
+    set_secret(&secret_name(host), secret)?;
+
+    // An empty helper first drops helpers inherited from credential.helper for this host
+    let key = format!("credential.https://{}.helper", host);
+    let helper = format!("!{} auth credential --host {}", gitup_command(), host);
+    run_mutating("git", &["config", "--global", "--replace-all", &key, ""])?;
+    run_mutating("git", &["config", "--global", "--add", &key, &helper])?;
+    GitConfig::global().set(&format!("credential.https://{}.username", host), username)?;
+    Ok(info)
+}
+
+// A key on a provider account
+#[derive(Debug, Clone)]
+pub struct PublishedKey {
+    pub provider: Provider,
+    pub host: String,
+    pub username: String,
+    // The provider's ID for the key
+    pub id: Option<String>,
+    // Whether it was uploaded now rather than already on the account
+    pub added: bool,
+}
+
+// Function to upload the user's commit signing key (SSH or GPG) to their account on a provider,
+// with the token stored by `login`, so the provider shows their signed commits as verified.
+// The title defaults to naming this machine. A key already on the account isn't uploaded again.
+// The key is looked up on the account afterwards, so a key the provider silently dropped is
+// reported rather than assumed
+pub fn publish_signing_key(
+    provider: Provider,
+    host: &str,
+    title: Option<&str>,
+) -> Result<(SigningKey, PublishedKey), GitError> {
+    if provider == Provider::Bitbucket {
+        return Err(GitError::InvalidInput("Bitbucket can't verify signed commits".to_string()));
+    }
+    let token = stored_token(provider, host)?;
+    let key = signing_key()?;
+    let ssh = key.format == "ssh";
+    // Classic GitHub tokens and GitLab tokens list their scopes; fine-grained GitHub ones don't
+    let scopes: &[&str] = match (provider, ssh) {
+        (Provider::GitHub, true) => &["write:ssh_signing_key", "admin:ssh_signing_key"],
+        (Provider::GitHub, false) => &["write:gpg_key", "admin:gpg_key"],
+        _ => &["api"],
+    };
+    let url = match (provider, ssh) {
+        (Provider::GitHub, true) => format!("{}/user/ssh_signing_keys", provider.api_url(host)),
+        (_, true) => format!("{}/user/keys", provider.api_url(host)),
+        (_, false) => format!("{}/user/gpg_keys", provider.api_url(host)),
+    };
+    let title = key_title(title);
+    let body = match (provider, ssh) {
+        (Provider::GitHub, true) => json!({ "title": title, "key": key.public_key }),
+        (_, true) => json!({ "title": title, "key": key.public_key, "usage_type": "signing" }),
+        (Provider::GitHub, false) => json!({ "name": title, "armored_public_key": key.public_key }),
+        (_, false) => json!({ "key": key.public_key }),
+    };
+    let upload = Upload {
+        provider,
+        host,
+        url: &url,
+        description: format!("{} signing key {}", key.format, key.key),
+        scopes,
+        body,
+    };
+    let published = upload.run(&token, |entry| match entry["usage_type"].as_str() {
+        // GitLab keeps one list for authentication and signing keys
+        Some("auth") if same_key(provider, &key, entry) => Err(GitError::Conflict(format!(
+            "the key is on your {} account for authentication only; change its usage to include signing there",
+            host
+        ))),
+        _ => Ok(same_key(provider, &key, entry)),
+    })?;
+    Ok((key, published))
+}
+
+// Function to upload an SSH public key (a "<type> <key> [comment]" line) to the user's account
+// on a provider for authentication, with the token stored by `login`. Like `publish_signing_key`,
+// a key already on the account isn't uploaded again and the upload is checked afterwards
+pub fn publish_ssh_key(
+    provider: Provider,
+    host: &str,
+    public_key: &str,
+    title: Option<&str>,
+) -> Result<PublishedKey, GitError> {
+    let token = stored_token(provider, host)?;
+    let scopes: &[&str] = match provider {
+        Provider::GitHub => &["write:public_key", "admin:public_key"],
+        Provider::GitLab => &["api"],
+        Provider::Bitbucket => &[],
+    };
+    let url = match provider {
+        Provider::Bitbucket => {
+            let username = token.split_once(':').map_or("", |(username, _)| username);
+            format!("{}/users/{}/ssh-keys", provider.api_url(host), username)
+        }
+        _ => format!("{}/user/keys", provider.api_url(host)),
+    };
+    let title = key_title(title);
+    let body = match provider {
+        Provider::Bitbucket => json!({ "label": title, "key": public_key }),
+        _ => json!({ "title": title, "key": public_key }),
+    };
+    let upload = Upload {
+        provider,
+        host,
+        url: &url,
+        description: "SSH key".to_string(),
+        scopes,
+        body,
+    };
+    upload.run(&token, |entry| match entry["usage_type"].as_str() {
+        Some("signing") if same_ssh_key(entry, public_key) => Err(GitError::Conflict(format!(
+            "the key is on your {} account for signing only; change its usage to include authentication there",
+            host
+        ))),
+        _ => Ok(same_ssh_key(entry, public_key)),
+    })
+}
+
+// A key to put on an account, unless the list at `url` already has it
+struct Upload<'a> {
+    provider: Provider,
+    host: &'a str,
+    url: &'a str,
+    // What is uploaded, for dry runs
+    description: String,
+    // Any of these lets a token add the key
+    scopes: &'a [&'a str],
+    body: Value,
+}
+
+impl Upload<'_> {
+    fn run(&self, token: &str, matches: impl Fn(&Value) -> Result<bool, GitError>) -> Result<PublishedKey, GitError> {
+        let info = verify_token(self.provider, self.host, token)?;
+        if !self.scopes.is_empty()
+            && !info.scopes.is_empty()
+            && !self.scopes.iter().any(|scope| info.scopes.iter().any(|s| s == scope))
+        {
+            return Err(GitError::InvalidInput(format!(
+                "the token for {} can't add this key; give it the {} scope and run `gitup auth {}` again",
+                self.host, self.scopes[0], self.provider
+            )));
+        }
+
+        let find = || -> Result<Option<String>, GitError> {
+            for entry in self.list(token)? {
+                if matches(&entry)? {
+                    // GitHub and GitLab number keys; Bitbucket gives them UUIDs
+                    let id = entry["id"].as_u64().map(|id| id.to_string());
+                    return Ok(Some(id.or_else(|| entry["uuid"].as_str().map(str::to_string)).unwrap_or_default()));
+                }
+            }
+            Ok(None)
+        };
+        let mut published = PublishedKey {
+            provider: self.provider,
+            host: self.host.to_string(),
+            username: info.username,
+            id: find()?,
+            added: false,
+        };
+        if published.id.is_some() {
+            return Ok(published);
+        }
+        published.added = true;
+        if DryRun::is_enabled() {
+            DryRun::record(format!("upload: {} to {}", self.description, self.url));
+            return Ok(published);
+        }
+        api_request(self.provider, "POST", self.url, token, Some(&self.body))?;
+        published.id = find()?;
+        if published.id.is_none() {
+            return Err(GitError::InvalidInput(format!(
+                "{} accepted the key but doesn't list it on the account",
+                self.host
+            )));
+        }
+        Ok(published)
+    }
+
+    // The keys on the account; GitHub and GitLab return a page as an array, Bitbucket as
+    // {"values": [...], "next": <url>}
+    fn list(&self, token: &str) -> Result<Vec<Value>, GitError> {
+        let page_size = match self.provider {
+            Provider::Bitbucket => "pagelen=100",
+            _ => "per_page=100",
+        };
+        let mut url = Some(format!("{}?{}", self.url, page_size));
+        let mut keys = Vec::new();
+        while let Some(page) = url.take() {
+            let (_, body) = api_get(self.provider, &page, token)?;
+            match body {
+                Value::Array(entries) => keys.extend(entries),
+                body => {
+                    keys.extend(body["values"].as_array().cloned().unwrap_or_default());
+                    url = body["next"].as_str().map(str::to_string);
+                }
+            }
+        }
+        Ok(keys)
+    }
+}
+
+// The token stored by `login` for a host; for Bitbucket with the user name git sends it with
+fn stored_token(provider: Provider, host: &str) -> Result<String, GitError> {
+    let token = get_secret(&secret_name(host))?.ok_or_else(|| {
+        GitError::InvalidInput(format!("no token is stored for {}; run `gitup auth {}` first", host, provider))
+    })?;
+    Ok(match provider.token_username() {
+        Some(_) => token,
+        None => {
+            let username = GitConfig::global()
+                .get(&format!("credential.https://{}.username", host))
+                .unwrap_or_default();
+            format!("{}:{}", username, token)
+        }
+    })
+}
+
+// Keys are named after the machine they're for
+fn key_title(title: Option<&str>) -> String {
+    match title {
+        Some(title) => title.to_string(),
+        None => hostname().map_or_else(|| "gitup".to_string(), |host| format!("{} (gitup)", host)),
+    }
+}
+
+// Whether a key listed by the provider is the signing key: SSH keys by type and key data,
+// GitHub's GPG keys by key ID, and GitLab's, which come back as uploaded, by their armored data
+fn same_key(provider: Provider, key: &SigningKey, entry: &Value) -> bool {
+    if key.format == "ssh" {
+        return same_ssh_key(entry, &key.public_key);
+    }
+    let listed = entry["key"].as_str().unwrap_or_default();
+    match provider {
+        Provider::GitHub => {
+            let fingerprint = key.fingerprint.as_deref().unwrap_or_default().to_uppercase();
+            let subkeys = entry["subkeys"].as_array().into_iter().flatten();
+            let ids = iter::once(&entry["key_id"]).chain(subkeys.map(|subkey| &subkey["key_id"]));
+            ids.filter_map(Value::as_str)
+                .any(|id| !id.is_empty() && fingerprint.ends_with(&id.to_uppercase()))
+        }
+        _ => {
+            let armor = |text: &str| -> String {
+                text.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.starts_with("-----") && !line.contains(':'))
+                    .collect()
+            };
+            !listed.is_empty() && armor(listed) == armor(&key.public_key)
+        }
+    }
+}
+
+// SSH keys are listed without their comment, or with another one
+fn same_ssh_key(entry: &Value, public_key: &str) -> bool {
+    let data = |line: &str| line.split_whitespace().take(2).collect::<Vec<_>>().join(" ");
+    let listed = entry["key"].as_str().unwrap_or_default();
+    !listed.is_empty() && data(listed) == data(public_key)
+}
+
+// Function to answer a git credential helper request for a host, returning the
+// lines to print (empty when gitup has no token for it)
+pub fn credential_response(host: &str, operation: &str) -> Result<String, GitError> {
+    // Tokens are managed with `gitup auth`, so git's store/erase requests are ignored
+    if operation != "get" {
+        return Ok(String::new());
+    }
+    Ok(match get_secret(&secret_name(host))? {
+        Some(token) => format!("password={}\n", token),
+        None => String::new(),
+    })
+}
+
+// Keyring entry holding the token for a host
+pub fn secret_name(host: &str) -> String {
+    format!("token:{}", host)
+}
+
+// GET a JSON API endpoint
+fn api_get(provider: Provider, url: &str, token: &str) -> Result<(String, Value), GitError> {
+    api_request(provider, "GET", url, token, None)
+}
+
+// Call a JSON API endpoint, passing the token on stdin so it never shows up in `ps`
+fn api_request(
+    provider: Provider,
+    method: &str,
+    url: &str,
+    token: &str,
+    body: Option<&Value>,
+) -> Result<(String, Value), GitError> {
+    let data = body.map(Value::to_string);
+    let mut args = vec!["--silent", "--show-error", "--dump-header", "-", "--header", "@-", "--request", method];
+    if let Some(data) = &data {
+        args.extend(["--header", "Content-Type: application/json", "--data-binary", data]);
+    }
+    args.push(url);
+    let output = run_with_input("curl", &args, &format!("Authorization: {}\n", provider.authorization(token)))
+        .map_err(|e| GitError::spawn_failed("curl", e))?;
+    if !output.status.success() {
+        return Err(GitError::command_failed("curl", &args, &output));
+    }
+
+    // Redirects and 100-continue produce several header blocks; the last one is the response
+    let stdout = String::from_utf8_lossy(&output.stdout).replace("\r\n", "\n");
+    let (mut headers, mut body) = ("", stdout.as_str());
+    while body.starts_with("HTTP/") {
+        (headers, body) = body.split_once("\n\n").unwrap_or((body, ""));
+    }
+    let status = headers
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .unwrap_or_default();
+
+    match status {
+        200..=299 => {}
+        401 | 403 => {
+            return Err(GitError::InvalidInput(format!(
+                "the token was rejected by {} (HTTP {})",
+                url, status
+            )))
+        }
+        _ => {
+            // The APIs explain a rejected request in a message
+            let reason = serde_json::from_str::<Value>(body).ok().and_then(|body| error_message(&body));
+            return Err(GitError::InvalidInput(format!(
+                "unexpected response from {} (HTTP {}){}",
+                url,
+                status,
+                reason.map(|reason| format!(": {}", reason)).unwrap_or_default()
+            )));
+        }
+    }
+    let body = serde_json::from_str(body)
+        .map_err(|e| GitError::Parse(format!("response from {}: {}", url, e)))?;
+    Ok((headers.to_string(), body))
+}
+
+// GitHub: {"message": "Validation Failed", "errors": [{"message": "key is already in use"}]};
+// GitLab: {"message": {"key": ["has already been taken"]}} or {"message": "..."};
+// Bitbucket: {"type": "error", "error": {"message": "..."}}
+fn error_message(body: &Value) -> Option<String> {
+    let mut parts = Vec::new();
+    match &body["message"] {
+        Value::String(message) => parts.push(message.clone()),
+        Value::Object(fields) => parts.extend(fields.iter().map(|(field, problems)| {
+            let problems = problems.as_array().into_iter().flatten().filter_map(Value::as_str).collect::<Vec<_>>();
+            format!("{} {}", field, problems.join(", "))
+        })),
+        _ => {}
+    }
+    let errors = body["errors"].as_array().into_iter().flatten();
+    parts.extend(errors.filter_map(|error| error["message"].as_str().map(str::to_string)));
+    parts.extend(body["error"]["message"].as_str().map(str::to_string));
+    (!parts.is_empty()).then(|| parts.join("; "))
+}
+
+// The command git should run to reach this gitup binary
+fn gitup_command() -> String {
+    let exe = env::current_exe()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "gitup".to_string());
+    format!("'{}'", exe.replace('\'', r"'\''"))
+}
@@ -0,0 +1,145 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::dry_run::write_file;
+use crate::git::{repository_root, run_checked, run_git, run_mutating};
+use crate::GitError;
+
+// An unpushed commit whose author gets corrected
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthorCommit {
+    pub commit: String,
+    pub subject: String,
+}
+
+// What `fix_author` would rewrite on the current branch of a repository
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthorFix {
+    pub repository: PathBuf,
+    pub branch: String,
+    // The newest pushed commit the rewrite starts after; None when nothing has been pushed
+    pub base: Option<String>,
+    pub from: String,
+    pub commits: Vec<AuthorCommit>,
+}
+
+// Function to find the commits on the current branch that are authored by `from` and not on
+// any remote yet. Pushed commits are never rewritten; use a .mailmap entry for those
+pub fn plan_author_fix(repo: &Path, from: &str) -> Result<AuthorFix, GitError> {
+    let dir = repo.to_string_lossy();
+    let branch = git_output(&dir, &["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .map_err(|_| GitError::InvalidInput("HEAD is detached; check out the branch to fix first".to_string()))?;
+    if !git_output(&dir, &["status", "--porcelain", "--untracked-files=no"])?.is_empty() {
+        return Err(GitError::InvalidInput(
+            "the working tree has uncommitted changes; commit or stash them first".to_string(),
+        ));
+    }
+
+    // A single boundary commit is where the unpushed part of the history starts; without one
+    // nothing has been pushed, and with several the branch merges more than one pushed line
+    let boundary = git_output(&dir, &["rev-list", "--boundary", "HEAD", "--not", "--remotes"])?;
+    let bases: Vec<&str> = boundary.lines().filter_map(|line| line.strip_prefix('-')).collect();
+    let base = match bases.as_slice() {
+        [] => None,
+        [base] => Some(base.to_string()),
+        _ => {
+            return Err(GitError::InvalidInput(
+                "the unpushed commits merge several pushed branches; rewrite them by hand".to_string(),
+            ))
+        }
+    };
+
+    let log = git_output(&dir, &["log", "--format=%H%x00%ae%x00%s", "HEAD", "--not", "--remotes"])?;
+    let commits = log
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\0');
+            let (commit, email, subject) = (fields.next()?, fields.next()?, fields.next()?);
+            (email == from).then(|| AuthorCommit {
+                commit: commit.to_string(),
+                subject: subject.to_string(),
+            })
+        })
+        .collect();
+
+    Ok(AuthorFix {
+        repository: repo.to_path_buf(),
+        branch,
+        base,
+        from: from.to_string(),
+        commits,
+    })
+}
+
+// Function to rewrite the planned commits with a new author, keeping their author dates. The
+// branch is saved as refs/gitup/fix-author/<branch> first; returns that backup ref
+pub fn fix_author(plan: &AuthorFix, name: &str, email: &str) -> Result<String, GitError> {
+    let dir = plan.repository.to_string_lossy();
+    let backup = format!("refs/gitup/fix-author/{}", plan.branch);
+    run_mutating("git", &["-C", &dir, "update-ref", &backup, "HEAD"])?;
+
+    // Every commit is replayed, but only the ones by `from` are amended
+    let exec = format!(
+        "if test \"$(git log -1 --format=%ae)\" = {}; then git commit --quiet --amend --no-edit --no-verify --allow-empty --author={}; fi",
+        shell_quote(&plan.from),
+        shell_quote(&format!("{} <{}>", name, email))
+    );
+    // Replayed commits are committed as the new identity too, which also works where no
+    // identity is configured; -c settings carry over to the git commands --exec runs
+    let user_name = format!("user.name={}", name);
+    let user_email = format!("user.email={}", email);
+    let mut args = vec![
+        "-C",
+        &dir,
+        "-c",
+        &user_name,
+        "-c",
+        &user_email,
+        "rebase",
+        "--quiet",
+        "--rebase-merges",
+        "--exec",
+        &exec,
+    ];
+    match &plan.base {
+        Some(base) => args.push(base),
+        None => args.push("--root"),
+    }
+    if let Err(e) = run_mutating("git", &args) {
+        // Leave the branch as it was rather than halfway through a rebase
+        let _ = run_git(&["-C", &dir, "rebase", "--abort"]);
+        return Err(e);
+    }
+    Ok(backup)
+}
+
+// Function to add a .mailmap entry so git log, shortlog and blame show `from` as the new
+// identity, without rewriting any commits. Returns the .mailmap path
+pub fn add_mailmap(repo: &Path, from: &str, name: &str, email: &str) -> Result<PathBuf, GitError> {
+    let path = repository_root(repo)?.join(".mailmap");
+    let entry = format!("{} <{}> <{}>", name, email, from);
+
+    let mut contents = fs::read_to_string(&path).unwrap_or_default();
+    if contents.lines().any(|line| line.trim() == entry) {
+        return Ok(path);
+    }
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&entry);
+    contents.push('\n');
+    write_file(&path, &contents)?;
+    Ok(path)
+}
+
+fn git_output(dir: &str, args: &[&str]) -> Result<String, GitError> {
+    let args: Vec<&str> = ["-C", dir].iter().chain(args).copied().collect();
+    let output = run_checked("git", &args)?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
@@ -0,0 +1,504 @@
+pub mod target;
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::GitConfig;
+use crate::dry_run::DryRun;
+use crate::git::{get_git_version, global_config_path, run_command, set_git_configs, Scope};
+use crate::signing::{sign, verify, Signature};
+use crate::snapshot::{rollback, take_snapshot, Snapshot};
+use crate::suggest::glob_match;
+use crate::validate::is_unsafe_setting;
+use crate::GitError;
+
+use self::target::BackupTarget;
+
+// First line of backups in the older key=value format; files without it are from the
+// name/email-only format before that
+const BACKUP_HEADER: &str = "# gitup backup v1";
+
+// Marks a structured backup, in TOML or JSON
+const BACKUP_FORMAT: &str = "gitup-backup";
+
+// Version of the structured format; the key=value format was version 1
+const BACKUP_VERSION: u32 = 2;
+
+// What backup signatures are made for, so a backup signature can't pass for a commit's
+const SIGNATURE_NAMESPACE: &str = "gitup-backup";
+
+// Where and when a backup was taken. Backups in the older formats carry none of it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BackupInfo {
+    // Seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_version: Option<String>,
+    // "global" for the backups gitup writes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // The config file the settings were read from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BackupFile {
+    format: String,
+    version: u32,
+    #[serde(flatten)]
+    info: BackupInfo,
+    // SHA-256 of the entries, so a damaged or edited backup is noticed before it is restored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checksum: Option<String>,
+    // Made over everything else in the file, with the key git signs commits with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<Signature>,
+    #[serde(default)]
+    entries: Vec<BackupEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BackupEntry {
+    key: String,
+    value: String,
+}
+
+// Function to write the entire global Git configuration to a backup, in a file or remote
+// storage, with where and when it was taken: JSON when the name ends in .json, TOML otherwise.
+// With `signed` the backup is signed with the user's commit signing key, for `restore_config`
+// to verify
+pub fn backup_config(target: &BackupTarget, signed: bool) -> Result<usize, GitError> {
+    let entries = GitConfig::global().list()?;
+    let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).ok();
+    let mut backup = BackupFile {
+        format: BACKUP_FORMAT.to_string(),
+        version: BACKUP_VERSION,
+        info: BackupInfo {
+            created,
+            hostname: hostname(),
+            git_version: get_git_version().ok().map(|version| version.to_string()),
+            scope: Some(Scope::Global.name().to_string()),
+            source: Some(global_config_path().to_string_lossy().into_owned()),
+        },
+        checksum: Some(checksum(&entries)),
+        signature: None,
+        entries: entries
+            .iter()
+            .map(|(key, value)| BackupEntry {
+                key: key.clone(),
+                value: value.clone(),
+            })
+            .collect(),
+    };
+    if signed {
+        backup.signature = Some(sign(&signed_payload(&backup)?, SIGNATURE_NAMESPACE)?);
+    }
+
+    let contents = if target.is_json() {
+        serde_json::to_string_pretty(&backup).map_err(|e| GitError::Parse(e.to_string()))? + "\n"
+    } else {
+        toml::to_string(&backup).map_err(|e| GitError::Parse(e.to_string()))?
+    };
+    target.write(&contents)?;
+    Ok(entries.len())
+}
+
+// Function to read the key/value pairs stored in a backup file, in order
+pub fn read_backup(path: &Path) -> Result<Vec<(String, String)>, GitError> {
+    load_backup(path).map(|(_, entries)| entries)
+}
+
+// Function to read a backup file: where and when it was taken, and its key/value pairs in
+// order. Besides gitup's TOML and JSON backups this accepts the older key=value backups,
+// hand-written key=value files and gitconfig-style [section] blocks. A structured backup whose
+// entries don't match its checksum is refused
+pub fn load_backup(path: &Path) -> Result<(BackupInfo, Vec<(String, String)>), GitError> {
+    let contents = fs::read_to_string(path)?;
+    let loaded = read_backup_file(&path.display().to_string(), &contents, false)?;
+    Ok((loaded.info, loaded.entries))
+}
+
+struct LoadedBackup {
+    info: BackupInfo,
+    entries: Vec<(String, String)>,
+    signed_by: Option<String>,
+}
+
+// A backup's metadata and entries, and with `verify_signature` who signed it; a backup that
+// isn't signed, or whose signature doesn't check out, is then refused
+fn read_backup_file(name: &str, contents: &str, verify_signature: bool) -> Result<LoadedBackup, GitError> {
+    let error = |message: String| GitError::Parse(format!("{}: {}", name, message));
+
+    let backup: Option<BackupFile> = if contents.trim_start().starts_with('{') {
+        Some(serde_json::from_str(contents).map_err(|e| error(e.to_string()))?)
+    } else {
+        // Plain key=value lines can be valid TOML too, so only a file marked as a backup counts
+        match toml::from_str::<toml::Table>(contents) {
+            Ok(table) if table.contains_key("format") => {
+                Some(toml::from_str(contents).map_err(|e: toml::de::Error| error(e.message().to_string()))?)
+            }
+            _ => None,
+        }
+    };
+    let Some(backup) = backup else {
+        if verify_signature {
+            return Err(error("only signed backups can be verified, and this one is in the older key=value format".to_string()));
+        }
+        return Ok(LoadedBackup {
+            info: BackupInfo::default(),
+            entries: read_key_values(name, contents)?,
+            signed_by: None,
+        });
+    };
+
+    if backup.format != BACKUP_FORMAT {
+        return Err(error(format!("'{}' is not a gitup backup format", backup.format)));
+    }
+    if backup.version > BACKUP_VERSION {
+        return Err(error(format!(
+            "the backup is in format version {}, written by a newer gitup (this one reads up to {}); upgrade gitup",
+            backup.version, BACKUP_VERSION
+        )));
+    }
+    let signed_by = match &backup.signature {
+        Some(signature) if verify_signature => Some(verify(&signed_payload(&backup)?, SIGNATURE_NAMESPACE, signature)?),
+        None if verify_signature => return Err(error("the backup isn't signed, so it can't be verified".to_string())),
+        _ => None,
+    };
+    let entries: Vec<(String, String)> = backup.entries.into_iter().map(|entry| (entry.key, entry.value)).collect();
+    if let Some(expected) = &backup.checksum {
+        if !expected.eq_ignore_ascii_case(&checksum(&entries)) {
+            return Err(error(
+                "the settings don't match the backup's checksum, so it was changed or damaged since it was taken; \
+                 remove the checksum line to use it anyway"
+                    .to_string(),
+            ));
+        }
+    }
+    Ok(LoadedBackup {
+        info: backup.info,
+        entries,
+        signed_by,
+    })
+}
+
+// What a backup's signature is made over: the whole backup without the signature, in a form
+// that doesn't depend on whether the file is TOML or JSON
+fn signed_payload(backup: &BackupFile) -> Result<String, GitError> {
+    let unsigned = BackupFile {
+        signature: None,
+        ..backup.clone()
+    };
+    serde_json::to_string(&unsigned).map_err(|e| GitError::Parse(e.to_string()))
+}
+
+// Each entry as key, newline, value, NUL, the way `git config --list --null` prints them
+fn checksum(entries: &[(String, String)]) -> String {
+    let mut hasher = Sha256::new();
+    for (key, value) in entries {
+        hasher.update(key.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(value.as_bytes());
+        hasher.update(b"\0");
+    }
+    hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub(crate) fn hostname() -> Option<String> {
+    let from_env = env::var(if cfg!(windows) { "COMPUTERNAME" } else { "HOSTNAME" }).ok();
+    from_env
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| {
+            let output = run_command("hostname", &[]).ok().filter(|output| output.status.success())?;
+            Some(String::from_utf8_lossy(&output.stdout).into_owned())
+        })
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+// The key=value formats, and gitconfig-style [section] blocks
+fn read_key_values(name: &str, contents: &str) -> Result<Vec<(String, String)>, GitError> {
+    let versioned = contents.lines().next() == Some(BACKUP_HEADER);
+    let invalid = |line: &str| GitError::Parse(format!("{}: invalid backup line: {}", name, line));
+
+    let mut entries = Vec::new();
+    let mut section = None;
+    for line in contents.lines() {
+        let line = line.trim_end_matches('\r');
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+        if trimmed.starts_with('[') {
+            section = Some(parse_section_header(trimmed).ok_or_else(|| invalid(line))?);
+            continue;
+        }
+
+        let (key, value) = match &section {
+            // Inside a section the usual gitconfig rules apply, and a bare name means true
+            Some(section) => {
+                let (name, value) = match trimmed.split_once('=') {
+                    Some((name, value)) => (name.trim(), parse_value(value).ok_or_else(|| invalid(line))?),
+                    None => (trimmed, "true".to_string()),
+                };
+                (format!("{}.{}", section, name), value)
+            }
+            None => {
+                let (key, value) = split_entry(line).ok_or_else(|| invalid(line))?;
+                let value = if versioned {
+                    unescape_value(value)
+                } else if value.trim().len() >= 2 && value.trim().starts_with('"') && value.trim().ends_with('"') {
+                    parse_value(value).ok_or_else(|| invalid(line))?
+                } else {
+                    value.to_string()
+                };
+                (key.trim().to_string(), value)
+            }
+        };
+        entries.push((key, value));
+    }
+    Ok(entries)
+}
+
+// Which keys of a backup to restore, by glob patterns such as user.* or credential.*; '*'
+// matches any run of characters and keys match regardless of case. The default restores all
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyFilter {
+    // Restore only keys matching one of these; empty means every key
+    pub only: Vec<String>,
+    // Never restore keys matching one of these, even when `only` matches them
+    pub exclude: Vec<String>,
+}
+
+impl KeyFilter {
+    // Function to check whether a key is restored
+    pub fn matches(&self, key: &str) -> bool {
+        let key = key.to_lowercase();
+        let matches = |pattern: &String| glob_match(&pattern.to_lowercase(), &key);
+        (self.only.is_empty() || self.only.iter().any(matches)) && !self.exclude.iter().any(matches)
+    }
+}
+
+// Outcome of a restore: the keys written, those the filter left out, those left out because
+// they could run commands, the snapshot that can undo them, where the backup came from and,
+// when its signature was verified, who signed it
+#[derive(Debug)]
+pub struct RestoreReport {
+    pub snapshot: Snapshot,
+    pub restored: Vec<String>,
+    pub skipped: Vec<String>,
+    pub blocked: Vec<String>,
+    pub backup: BackupInfo,
+    pub signed_by: Option<String>,
+}
+
+// Function to restore the keys `filter` lets through from a backup, in a file or remote
+// storage, into the global Git configuration, snapshotting the current configuration first so
+// the restore can be undone. Either every key is restored or the configuration is left as it
+// was. With `verify_signature` only a backup signed by a trusted key is restored. Keys that
+// would run commands (see `is_unsafe_setting`) are only restored with `allow_unsafe`, as
+// a backup from remote storage or a teammate could otherwise take over the machine
+pub fn restore_config(
+    target: &BackupTarget,
+    verify_signature: bool,
+    filter: &KeyFilter,
+    allow_unsafe: bool,
+) -> Result<RestoreReport, GitError> {
+    let contents = target.read()?;
+    let LoadedBackup {
+        info: backup,
+        entries,
+        signed_by,
+    } = read_backup_file(&target.to_string(), &contents, verify_signature)?;
+    if let Some(scope) = backup.scope.as_deref().filter(|scope| *scope != Scope::Global.name()) {
+        return Err(GitError::InvalidInput(format!(
+            "{} is a backup of the {} config; restore only writes the global config",
+            target,
+            scope
+        )));
+    }
+    let (entries, skipped): (Vec<_>, Vec<_>) = entries.into_iter().partition(|(key, _)| filter.matches(key));
+    let (blocked, entries): (Vec<_>, Vec<_>) =
+        entries.into_iter().partition(|(key, value)| !allow_unsafe && is_unsafe_setting(key, value));
+    if entries.is_empty() && !blocked.is_empty() {
+        return Err(GitError::InvalidInput(format!(
+            "every key in {} that matches the filters can run commands; pass --allow-unsafe to restore them",
+            target
+        )));
+    }
+    if entries.is_empty() {
+        return Err(GitError::InvalidInput(format!("no key in {} matches the filters", target)));
+    }
+    let snapshot = take_snapshot("restore")?;
+
+    // The first occurrence replaces whatever is set; repeats are multi-valued keys. The keys are
+    // applied to the file in memory and swapped in at once; should git apply them one at a
+    // time instead (a runner is set, or the git backend was chosen), a failure puts the
+    // snapshot back
+    if let Err(e) = set_git_configs(&entries, Scope::Global) {
+        if !DryRun::is_enabled() {
+            rollback(&snapshot.id)?;
+        }
+        return Err(e);
+    }
+    Ok(RestoreReport {
+        snapshot,
+        restored: distinct_keys(entries),
+        skipped: distinct_keys(skipped),
+        blocked: distinct_keys(blocked),
+        backup,
+        signed_by,
+    })
+}
+
+// Each key once, in the order they first appear; multi-valued keys repeat
+fn distinct_keys(entries: Vec<(String, String)>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    entries.into_iter().map(|(key, _)| key).filter(|key| seen.insert(key.clone())).collect()
+}
+
+// Split a key=value line at the first '=' that ends a plausible key; subsections such as
+// url.<base>.insteadOf may themselves contain '=', but variable names never do
+fn split_entry(line: &str) -> Option<(&str, &str)> {
+    let is_name = |name: &str| {
+        name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    line.match_indices('=')
+        .map(|(i, _)| (&line[..i], &line[i + 1..]))
+        .find(|(key, _)| key.trim().rsplit_once('.').is_some_and(|(_, name)| is_name(name)))
+        .or_else(|| line.split_once('='))
+}
+
+// Turn "[section]", "[section \"subsection\"]" or the older "[section.subsection]" into a key prefix
+fn parse_section_header(line: &str) -> Option<String> {
+    let inner = line.strip_prefix('[')?;
+    let inner = &inner[..inner.rfind(']')?];
+    match inner.split_once('"') {
+        Some((section, quoted)) => {
+            let subsection = quoted.strip_suffix('"')?;
+            let subsection = subsection.replace("\\\"", "\"").replace("\\\\", "\\");
+            Some(format!("{}.{}", section.trim(), subsection))
+        }
+        None => Some(inner.trim().to_string()),
+    }
+}
+
+// Parse a gitconfig value: double quotes group text (keeping its whitespace), backslash
+// escapes are resolved, and an unquoted '#' or ';' starts a comment
+fn parse_value(value: &str) -> Option<String> {
+    let mut result = String::new();
+    // Length of the result without trailing unquoted whitespace
+    let mut keep = 0;
+    let mut quoted = false;
+    let mut chars = value.trim_start().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' => {
+                result.push(match chars.next()? {
+                    'n' => '\n',
+                    't' => '\t',
+                    'b' => '\u{8}',
+                    other => other,
+                });
+                keep = result.len();
+            }
+            '#' | ';' if !quoted => break,
+            c => {
+                result.push(c);
+                if quoted || !c.is_whitespace() {
+                    keep = result.len();
+                }
+            }
+        }
+    }
+    if quoted {
+        return None;
+    }
+    result.truncate(keep);
+    Some(result)
+}
+
+// The older key=value backups kept multi-line values on a single line
+fn unescape_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => result.push('\n'),
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn read_key_values_reads_versioned_backups() {
+        let contents = format!(
+            "{}\n# taken today\n\nuser.name=Jane Doe\ncommit.template=line one\\nline two\n",
+            BACKUP_HEADER
+        );
+        assert_eq!(
+            read_key_values("backup", &contents).unwrap(),
+            pairs(&[("user.name", "Jane Doe"), ("commit.template", "line one\nline two")])
+        );
+    }
+
+    #[test]
+    fn split_entry_finds_the_key_when_subsections_hold_equals_signs() {
+        assert_eq!(split_entry("user.name=Jane Doe"), Some(("user.name", "Jane Doe")));
+        assert_eq!(split_entry("core.pager=less -R=x"), Some(("core.pager", "less -R=x")));
+        assert_eq!(
+            split_entry("url.https://example.com/?a=b.insteadof=git@example.com:"),
+            Some(("url.https://example.com/?a=b.insteadof", "git@example.com:"))
+        );
+        assert_eq!(split_entry("no equals sign"), None);
+    }
+
+    #[test]
+    fn read_key_values_reads_older_and_gitconfig_style_backups() {
+        let contents = "alias.st=\"status  -sb\"\r\npath.raw=C:\\new\n[core]\n\tautocrlf = input ; comment\n\tbare\n\
+                        [remote \"origin\"]\n\turl = \"git@example.com:a.git\"\n";
+        assert_eq!(
+            read_key_values("backup", contents).unwrap(),
+            pairs(&[
+                ("alias.st", "status  -sb"),
+                ("path.raw", "C:\\new"),
+                ("core.autocrlf", "input"),
+                ("core.bare", "true"),
+                ("remote.origin.url", "git@example.com:a.git"),
+            ])
+        );
+    }
+
+    #[test]
+    fn read_key_values_rejects_malformed_lines() {
+        assert!(read_key_values("backup", "user.name Jane").is_err());
+        assert!(read_key_values("backup", "[user\nname = Jane").is_err());
+        assert!(read_key_values("backup", "[user]\nname = \"Jane").is_err());
+    }
+}
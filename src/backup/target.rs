@@ -0,0 +1,219 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use sha2::{Digest, Sha256};
+
+use crate::dry_run::{write_file, DryRun};
+use crate::git::run_with_input;
+use crate::secrets::get_secret;
+use crate::GitError;
+
+// Credentials for S3 targets, as the AWS tools read them; the keyring entries
+// s3-access-key-id and s3-secret-access-key are used when these aren't set
+pub const S3_ACCESS_KEY_ENV: &str = "AWS_ACCESS_KEY_ID";
+pub const S3_SECRET_KEY_ENV: &str = "AWS_SECRET_ACCESS_KEY";
+pub const S3_SESSION_TOKEN_ENV: &str = "AWS_SESSION_TOKEN";
+pub const S3_REGION_ENV: &str = "AWS_REGION";
+// Endpoint of an S3-compatible service (MinIO, R2, ...); without it the target is AWS S3
+pub const S3_ENDPOINT_ENV: &str = "AWS_ENDPOINT_URL";
+
+// Credentials for HTTP targets: a bearer token, or a user name and password. The keyring
+// entries backup-token and backup-password are used when the token or password isn't set
+pub const HTTP_TOKEN_ENV: &str = "GITUP_BACKUP_TOKEN";
+pub const HTTP_USER_ENV: &str = "GITUP_BACKUP_USER";
+pub const HTTP_PASSWORD_ENV: &str = "GITUP_BACKUP_PASSWORD";
+
+const DEFAULT_REGION: &str = "us-east-1";
+
+// Where a backup is written to or read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackupTarget {
+    File(PathBuf),
+    // s3://bucket/key, on AWS or an S3-compatible service
+    S3 { bucket: String, key: String },
+    // An http(s) URL taking PUT and GET, such as a WebDAV share
+    Http(String),
+}
+
+impl FromStr for BackupTarget {
+    type Err = GitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(rest) = s.strip_prefix("s3://") {
+            return match rest.split_once('/') {
+                Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() && !key.ends_with('/') => {
+                    Ok(BackupTarget::S3 {
+                        bucket: bucket.to_string(),
+                        key: key.to_string(),
+                    })
+                }
+                _ => Err(GitError::InvalidInput(format!(
+                    "'{}' is not an S3 object; use s3://bucket/path/to/backup.toml",
+                    s
+                ))),
+            };
+        }
+        if s.starts_with("https://") || s.starts_with("http://") {
+            return Ok(BackupTarget::Http(s.to_string()));
+        }
+        if s.contains("://") {
+            return Err(GitError::InvalidInput(format!(
+                "unsupported backup target '{}' (expected a file, s3://bucket/path or an http(s) URL)",
+                s
+            )));
+        }
+        Ok(BackupTarget::File(PathBuf::from(s)))
+    }
+}
+
+impl fmt::Display for BackupTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackupTarget::File(path) => write!(f, "{}", path.display()),
+            BackupTarget::S3 { bucket, key } => write!(f, "s3://{}/{}", bucket, key),
+            BackupTarget::Http(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+impl BackupTarget {
+    // Function to check whether the target names a .json file, which backups are written as JSON to
+    pub fn is_json(&self) -> bool {
+        let name = self.to_string();
+        let name = name.split(['?', '#']).next().unwrap_or_default();
+        name.to_lowercase().ends_with(".json")
+    }
+
+    // Function to store `contents` at the target, replacing what is there; in dry-run mode the
+    // upload is only recorded
+    pub fn write(&self, contents: &str) -> Result<(), GitError> {
+        let url = match self {
+            BackupTarget::File(path) => return write_file(path, contents),
+            _ => self.url(),
+        };
+        if DryRun::is_enabled() {
+            DryRun::record(format!("upload: {} ({} bytes)", self, contents.len()));
+            return Ok(());
+        }
+        let mut config = self.credentials(contents)?;
+        config.push_str("request = \"PUT\"\n");
+        config.push_str(&format!("data-raw = {}\n", quote(contents)));
+        config.push_str("header = \"Content-Type: application/octet-stream\"\n");
+        curl(&url, &config).map(|_| ())
+    }
+
+    // Function to fetch the backup stored at the target
+    pub fn read(&self) -> Result<String, GitError> {
+        match self {
+            BackupTarget::File(path) => Ok(fs::read_to_string(path)?),
+            _ => curl(&self.url(), &self.credentials("")?),
+        }
+    }
+
+    fn url(&self) -> String {
+        match self {
+            BackupTarget::File(path) => path.display().to_string(),
+            BackupTarget::Http(url) => url.clone(),
+            // Path-style for other services; AWS wants the bucket in the host name
+            BackupTarget::S3 { bucket, key } => match env::var(S3_ENDPOINT_ENV).ok().filter(|url| !url.is_empty()) {
+                Some(endpoint) => format!("{}/{}/{}", endpoint.trim_end_matches('/'), bucket, encode_path(key)),
+                None => format!("https://{}.s3.{}.amazonaws.com/{}", bucket, region(), encode_path(key)),
+            },
+        }
+    }
+
+    // curl options authenticating a request with `body`, passed on stdin so the secrets never
+    // show up in `ps`
+    fn credentials(&self, body: &str) -> Result<String, GitError> {
+        let mut config = String::new();
+        match self {
+            BackupTarget::File(_) => {}
+            BackupTarget::S3 { .. } => {
+                let access_key = credential(S3_ACCESS_KEY_ENV, "s3-access-key-id");
+                let secret_key = credential(S3_SECRET_KEY_ENV, "s3-secret-access-key");
+                let (Some(access_key), Some(secret_key)) = (access_key, secret_key) else {
+                    return Err(GitError::InvalidInput(format!(
+                        "S3 needs credentials; set {} and {}, or store them with `gitup secret set s3-access-key-id` \
+                         and `gitup secret set s3-secret-access-key`",
+                        S3_ACCESS_KEY_ENV, S3_SECRET_KEY_ENV
+                    )));
+                };
+                config.push_str(&format!("aws-sigv4 = {}\n", quote(&format!("aws:amz:{}:s3", region()))));
+                config.push_str(&format!("user = {}\n", quote(&format!("{}:{}", access_key, secret_key))));
+                // S3 wants the payload hash signed along with the request
+                let hash: String = Sha256::digest(body.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect();
+                config.push_str(&format!("header = {}\n", quote(&format!("x-amz-content-sha256: {}", hash))));
+                if let Some(token) = env::var(S3_SESSION_TOKEN_ENV).ok().filter(|token| !token.is_empty()) {
+                    config.push_str(&format!("header = {}\n", quote(&format!("x-amz-security-token: {}", token))));
+                }
+            }
+            BackupTarget::Http(_) => {
+                if let Some(token) = credential(HTTP_TOKEN_ENV, "backup-token") {
+                    config.push_str(&format!("header = {}\n", quote(&format!("Authorization: Bearer {}", token))));
+                } else if let Some(user) = env::var(HTTP_USER_ENV).ok().filter(|user| !user.is_empty()) {
+                    let password = credential(HTTP_PASSWORD_ENV, "backup-password").unwrap_or_default();
+                    config.push_str(&format!("user = {}\n", quote(&format!("{}:{}", user, password))));
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+// An environment variable, or failing that a keyring entry. Without a usable keyring (a
+// headless machine, CI) only the environment counts
+fn credential(variable: &str, secret: &str) -> Option<String> {
+    env::var(variable)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .or_else(|| get_secret(secret).ok().flatten())
+}
+
+fn region() -> String {
+    env::var(S3_REGION_ENV)
+        .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+        .ok()
+        .filter(|region| !region.is_empty())
+        .unwrap_or_else(|| DEFAULT_REGION.to_string())
+}
+
+// Run curl on `url` with the rest of its options in `config`, returning the response body
+fn curl(url: &str, config: &str) -> Result<String, GitError> {
+    let args = ["--silent", "--show-error", "--fail", "--config", "-", url];
+    let output = run_with_input("curl", &args, config).map_err(|e| GitError::spawn_failed("curl", e))?;
+    if !output.status.success() {
+        return Err(GitError::command_failed("curl", &args, &output));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// A string in a curl config file
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// Percent-encode an object key the way S3 signs it, keeping the slashes
+fn encode_path(key: &str) -> String {
+    key.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::commit_template::install_template;
+use crate::config::GitConfig;
+use crate::dry_run::{create_dir_all, remove_file};
+use crate::git::{run_git, run_mutating};
+use crate::secrets::resolve_secret;
+use crate::profile::{get_profile, GitProfile};
+use crate::{config_dir, GitError};
+
+// A directory whose repositories automatically use a profile via includeIf
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub directory: String,
+    pub profile: String,
+    pub config_file: PathBuf,
+}
+
+// Function to get the directory holding generated per-profile config files
+pub fn fragments_dir() -> PathBuf {
+    config_dir()
+}
+
+// Function to get the generated config file for a profile
+pub fn fragment_path(profile_name: &str) -> PathBuf {
+    fragments_dir().join(format!("{}.gitconfig", profile_name))
+}
+
+// Function to write a profile's settings into its standalone config file
+pub fn write_profile_fragment(profile_name: &str, profile: &GitProfile) -> Result<PathBuf, GitError> {
+    create_dir_all(&fragments_dir())?;
+    let path = fragment_path(profile_name);
+    if path.exists() {
+        remove_file(&path)?;
+    }
+    if let Some(template) = &profile.commit_template {
+        install_template(template)?;
+    }
+
+    let file = path.to_string_lossy();
+    for (key, value) in profile.settings() {
+        let value = resolve_secret(&value)?;
+        run_mutating("git", &["config", "--file", &file, &key, &value])?;
+    }
+    Ok(path)
+}
+
+// Function to make every repository under a directory use a profile
+pub fn bind_profile(profile_name: &str, directory: &Path) -> Result<Binding, GitError> {
+    let profile = get_profile(profile_name)?;
+    let config_file = write_profile_fragment(profile_name, &profile)?;
+
+    // A trailing slash makes gitdir match every repository below the directory
+    let mut directory = fs::canonicalize(directory)?.to_string_lossy().into_owned();
+    if !directory.ends_with('/') {
+        directory.push('/');
+    }
+
+    let key = format!("includeIf.gitdir:{}.path", directory);
+    GitConfig::global().set(&key, &config_file.to_string_lossy())?;
+
+    Ok(Binding {
+        directory,
+        profile: profile_name.to_string(),
+        config_file,
+    })
+}
+
+// Function to list includeIf bindings in the global config that point at gitup profiles
+pub fn list_bindings() -> Result<Vec<Binding>, GitError> {
+    let output = run_git(&[
+        "config",
+        "--global",
+        "--null",
+        "--get-regexp",
+        r"^includeif\.gitdir:.*\.path$",
+    ])?;
+
+    let fragments = fragments_dir();
+    let mut bindings = Vec::new();
+    // With --null each entry is "key\nvalue\0", which keeps paths with spaces intact
+    for entry in String::from_utf8_lossy(&output.stdout).split('\0') {
+        let Some((key, value)) = entry.split_once('\n') else {
+            continue;
+        };
+        let config_file = PathBuf::from(value);
+        if config_file.parent() != Some(fragments.as_path()) {
+            continue;
+        }
+        let Some(profile) = config_file.file_stem().map(|s| s.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        let directory = key
+            .trim_start_matches("includeif.gitdir:")
+            .trim_end_matches(".path")
+            .to_string();
+        bindings.push(Binding {
+            directory,
+            profile,
+            config_file,
+        });
+    }
+    Ok(bindings)
+}
+
+// Function to point a renamed profile's bindings at a config file under its new name, removing
+// the old one. Returns the bindings moved
+pub fn rebind_profile(old_name: &str, new_name: &str, profile: &GitProfile) -> Result<Vec<Binding>, GitError> {
+    let bindings: Vec<Binding> = list_bindings()?
+        .into_iter()
+        .filter(|binding| binding.profile == old_name)
+        .collect();
+    let old_file = fragment_path(old_name);
+    if bindings.is_empty() && !old_file.exists() {
+        return Ok(Vec::new());
+    }
+
+    let config_file = write_profile_fragment(new_name, profile)?;
+    let mut moved = Vec::new();
+    for binding in bindings {
+        let key = format!("includeIf.gitdir:{}.path", binding.directory);
+        GitConfig::global().set(&key, &config_file.to_string_lossy())?;
+        moved.push(Binding {
+            directory: binding.directory,
+            profile: new_name.to_string(),
+            config_file: config_file.clone(),
+        });
+    }
+    if old_file.exists() {
+        remove_file(&old_file)?;
+    }
+    Ok(moved)
+}
+
+// Function to remove every binding for a profile along with its generated config file
+pub fn unbind_profile(profile_name: &str) -> Result<(), GitError> {
+    for binding in list_bindings()? {
+        if binding.profile == profile_name {
+            let key = format!("includeIf.gitdir:{}.path", binding.directory);
+            GitConfig::global().unset(&key)?;
+        }
+    }
+
+    let path = fragment_path(profile_name);
+    if path.exists() {
+        remove_file(&path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,217 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::binding::{bind_profile, list_bindings};
+use crate::commit_template::{template_location, templates_dir};
+use crate::dry_run::{create_dir_all, write_file, write_private};
+use crate::encryption::{decrypt, encrypt, passphrase};
+use crate::profile::{create_profile, get_profile, load_profiles, GitProfile};
+use crate::ssh::{public_key_path, ssh_dir};
+use crate::validate::validate_profile_name;
+use crate::{expand_home, home_dir, GitError};
+
+const BUNDLE_VERSION: u32 = 1;
+
+// Everything needed to recreate a profile on another machine, stored as a single TOML file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileBundle {
+    pub version: u32,
+    pub name: String,
+    pub profile: GitProfile,
+    // Directories the profile was bound to with `gitup profile bind`, relative to ~ where possible
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bindings: Vec<String>,
+    // Contents of a commit template file; built-in templates travel by name only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_template: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_key: Option<BundledKey>,
+}
+
+// An SSH key reference, optionally carrying the private key encrypted with the passphrase
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundledKey {
+    pub file_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+}
+
+// What importing a bundle set up
+#[derive(Debug, Clone)]
+pub struct ImportReport {
+    pub profile: String,
+    pub ssh_key: Option<PathBuf>,
+    pub bound: Vec<String>,
+    // Bound directories that don't exist on this machine
+    pub skipped: Vec<String>,
+    // Settings left out because they could run commands
+    pub blocked: Vec<String>,
+}
+
+// Function to write a profile and its bindings, commit template and SSH key reference to a bundle file
+pub fn export_profile(profile_name: &str, path: &Path, include_key: bool) -> Result<ProfileBundle, GitError> {
+    // Bundles stand alone, so inherited settings are copied in rather than referenced. The
+    // imported profile starts its own history
+    let profile = GitProfile {
+        extends: None,
+        created_at: None,
+        last_used_at: None,
+        ..get_profile(profile_name)?
+    };
+
+    let bindings = list_bindings()?
+        .into_iter()
+        .filter(|binding| binding.profile == profile_name)
+        .map(|binding| {
+            let home = format!("{}/", home_dir().display());
+            match binding.directory.strip_prefix(&home) {
+                Some(rest) => format!("~/{}", rest),
+                None => binding.directory,
+            }
+        })
+        .collect();
+
+    let commit_template = match &profile.commit_template {
+        Some(source) if template_location(source) == Path::new(source) => {
+            Some(fs::read_to_string(source)?)
+        }
+        _ => None,
+    };
+
+    let ssh_key = match &profile.ssh_key {
+        Some(key) => {
+            let public = public_key_path(key);
+            Some(BundledKey {
+                file_name: file_name(key)?,
+                public_key: public.exists().then(|| fs::read_to_string(&public)).transpose()?,
+                private_key: if include_key {
+                    Some(encrypt(&fs::read_to_string(key)?, &passphrase()?)?)
+                } else {
+                    None
+                },
+            })
+        }
+        None => None,
+    };
+
+    let bundle = ProfileBundle {
+        version: BUNDLE_VERSION,
+        name: profile_name.to_string(),
+        profile,
+        bindings,
+        commit_template,
+        ssh_key,
+    };
+    let contents = toml::to_string(&bundle).map_err(|e| GitError::Parse(e.to_string()))?;
+    write_file(path, &contents)?;
+    Ok(bundle)
+}
+
+// Function to recreate a profile from a bundle file, optionally under a different name. Unless
+// `allow_unsafe` is set, settings that would run commands (an editor, pager, credential helper
+// and the like) are left out of the profile, since a bundle usually comes from someone else
+pub fn import_profile(
+    path: &Path,
+    rename: Option<&str>,
+    force: bool,
+    allow_unsafe: bool,
+) -> Result<ImportReport, GitError> {
+    let contents = fs::read_to_string(path)?;
+    let bundle: ProfileBundle = toml::from_str(&contents)
+        .map_err(|e| GitError::Parse(format!("{}: {}", path.display(), e)))?;
+    if bundle.version > BUNDLE_VERSION {
+        return Err(GitError::Parse(format!(
+            "{} was written by a newer gitup (bundle version {})",
+            path.display(),
+            bundle.version
+        )));
+    }
+
+    let profile_name = rename.unwrap_or(&bundle.name).to_string();
+    validate_profile_name(&profile_name)?;
+    if !force && load_profiles()?.profiles.contains_key(&profile_name) {
+        return Err(GitError::InvalidInput(format!(
+            "profile '{}' already exists (use --force to overwrite or --as to rename)",
+            profile_name
+        )));
+    }
+
+    let mut profile = bundle.profile;
+    let blocked = if allow_unsafe { Vec::new() } else { profile.remove_unsafe_settings() };
+    if let Some(template) = &bundle.commit_template {
+        let dir = templates_dir();
+        create_dir_all(&dir)?;
+        let template_path = dir.join(format!("{}.txt", profile_name));
+        write_file(&template_path, template)?;
+        profile.commit_template = Some(template_path.to_string_lossy().into_owned());
+    }
+
+    let ssh_key = match &bundle.ssh_key {
+        Some(key) => Some(import_key(key)?),
+        None => None,
+    };
+    let mut blocked = blocked;
+    // A key path that didn't come with the bundle is only another way to set core.sshCommand
+    if ssh_key.is_none() && !allow_unsafe && profile.ssh_key.take().is_some() {
+        blocked.push("ssh_key".to_string());
+    }
+    if ssh_key.is_some() {
+        profile.ssh_key = ssh_key.clone();
+    }
+    create_profile(&profile_name, profile)?;
+
+    let (mut bound, mut skipped) = (Vec::new(), Vec::new());
+    for directory in bundle.bindings {
+        if expand_home(&directory).is_dir() {
+            bind_profile(&profile_name, &expand_home(&directory))?;
+            bound.push(directory);
+        } else {
+            skipped.push(directory);
+        }
+    }
+
+    Ok(ImportReport {
+        profile: profile_name,
+        ssh_key,
+        bound,
+        skipped,
+        blocked,
+    })
+}
+
+// Write a bundled key into ~/.ssh, refusing to replace a different key with the same name
+fn import_key(key: &BundledKey) -> Result<PathBuf, GitError> {
+    let dir = ssh_dir();
+    let path = dir.join(Path::new(&key.file_name).file_name().unwrap_or_default());
+    let Some(private_key) = &key.private_key else {
+        return Ok(path);
+    };
+
+    let private_key = decrypt(private_key, &passphrase()?)?;
+    if path.exists() {
+        if fs::read_to_string(&path)? == private_key {
+            return Ok(path);
+        }
+        return Err(GitError::InvalidInput(format!(
+            "{} already exists and holds a different key",
+            path.display()
+        )));
+    }
+
+    create_dir_all(&dir)?;
+    write_private(&path, &private_key)?;
+    if let Some(public_key) = &key.public_key {
+        write_file(&public_key_path(&path), public_key)?;
+    }
+    Ok(path)
+}
+
+fn file_name(path: &Path) -> Result<String, GitError> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| GitError::InvalidInput(format!("{} is not a file", path.display())))
+}
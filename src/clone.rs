@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::dry_run::DryRun;
+use crate::git::{run_with_progress, submodule_roots, Scope};
+use crate::guard::{enable_guard, is_guard_enabled};
+use crate::lock::without_lock;
+use crate::profile::{get_profile, use_profile, use_profile_in_submodules};
+use crate::ssh::{alias_url, write_host_alias};
+use crate::suggest::{normalize_remote_url, profile_for_url};
+use crate::GitError;
+
+// What `clone_repository` set up
+#[derive(Debug, Clone, Serialize)]
+pub struct ClonedRepository {
+    pub directory: PathBuf,
+    // The URL cloned from, which uses the SSH host alias when one was set up
+    pub url: String,
+    pub profile: Option<String>,
+    pub ssh_alias: Option<String>,
+    // Submodules cloned along with it, which use the profile as well
+    pub submodules: Vec<PathBuf>,
+    // Whether the identity guard was installed by this clone
+    pub guard_enabled: bool,
+}
+
+// Function to clone a repository configured for the profile its URL matches (or `profile`):
+// SSH URLs go through the profile's host alias so its key is used from the first fetch, the
+// profile is applied to the clone's .git/config, and the identity guard is installed unless
+// `guard` is false. With `recurse_submodules` the submodules are cloned too and get the profile
+// in their own config
+pub fn clone_repository(
+    url: &str,
+    directory: Option<&Path>,
+    profile: Option<&str>,
+    guard: bool,
+    recurse_submodules: bool,
+) -> Result<ClonedRepository, GitError> {
+    let profile = match profile {
+        Some(profile) => Some(profile.to_string()),
+        None => profile_for_url(url)?.map(|(profile, _)| profile),
+    };
+    let directory = directory.map_or_else(|| default_directory(url), Path::to_path_buf);
+
+    let mut clone_url = url.to_string();
+    let mut ssh_alias = None;
+    if let Some(name) = &profile {
+        let host = normalize_remote_url(url).split('/').next().unwrap_or_default().to_string();
+        let ssh_url = url.starts_with("ssh://") || !url.contains("://");
+        if get_profile(name)?.ssh_key.is_some() && ssh_url && !host.ends_with(&format!("-{}", name)) {
+            let alias = write_host_alias(name, &host)?;
+            if let Some(aliased) = alias_url(url, &host, &alias) {
+                clone_url = aliased;
+                ssh_alias = Some(alias);
+            }
+        }
+    }
+
+    let dir_arg = directory.to_string_lossy();
+    let mut args = vec!["clone", "--progress"];
+    if recurse_submodules {
+        args.push("--recurse-submodules");
+    }
+    args.extend([clone_url.as_str(), &dir_arg]);
+    // The clone's post-checkout hook may run `gitup suggest --apply`, which needs the lock
+    without_lock(|| run_with_progress("git", &args))?;
+    if let Some(name) = &profile {
+        use_profile(name, Scope::Local(directory.clone()))?;
+    }
+    // A dry run clones nothing to look into
+    let submodules = match (&profile, recurse_submodules && !DryRun::is_enabled()) {
+        (Some(name), true) => use_profile_in_submodules(name, &directory)?,
+        (None, true) => submodule_roots(&directory)?,
+        (_, false) => Vec::new(),
+    };
+    let guard_enabled = guard && !is_guard_enabled()?;
+    if guard_enabled {
+        enable_guard()?;
+    }
+
+    Ok(ClonedRepository {
+        directory,
+        url: clone_url,
+        profile,
+        ssh_alias,
+        submodules,
+        guard_enabled,
+    })
+}
+
+// The directory git would clone into: the last part of the path without .git
+fn default_directory(url: &str) -> PathBuf {
+    let trimmed = url.trim_end_matches('/');
+    let trimmed = trimmed.strip_suffix(".git").unwrap_or(trimmed);
+    let name = trimmed.rsplit(['/', ':']).next().unwrap_or(trimmed);
+    PathBuf::from(name)
+}
@@ -0,0 +1,115 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::GitConfig;
+use crate::dry_run::{create_dir_all, write_file};
+use crate::git::Scope;
+use crate::{config_dir, GitError};
+
+// Built-in commit message templates; lines starting with '#' are stripped by git
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "conventional-commits",
+        "\n\
+# <type>(<scope>): <subject>\n\
+#\n\
+# <body>\n\
+#\n\
+# <footer>\n\
+#\n\
+# type: feat, fix, docs, style, refactor, perf, test, build, ci, chore, revert\n\
+# scope: optional area of the codebase, e.g. (parser)\n\
+# subject: imperative mood, no trailing period, at most 72 characters\n\
+# footer: BREAKING CHANGE: <description>, Refs: #<issue>\n",
+    ),
+    (
+        "ticket-prefix",
+        "\n\
+# [TICKET-123] <subject>\n\
+#\n\
+# <body: what changed and why>\n\
+#\n\
+# Start the subject with the ticket id in square brackets.\n",
+    ),
+    (
+        "detailed",
+        "\n\
+# <subject: summarize the change in at most 50 characters>\n\
+#\n\
+# Why is this change needed?\n\
+#\n\
+# How does it address the issue?\n\
+#\n\
+# Are there any side effects?\n",
+    ),
+];
+
+// Function to get the names of the built-in commit templates
+pub fn builtin_templates() -> Vec<&'static str> {
+    BUILTIN_TEMPLATES.iter().map(|(name, _)| *name).collect()
+}
+
+// Function to get the directory built-in templates are installed into
+pub fn templates_dir() -> PathBuf {
+    config_dir().join("templates")
+}
+
+// Function to get the file commit.template should point at for a built-in name or a file path
+pub fn template_location(source: &str) -> PathBuf {
+    if builtin_body(source).is_some() {
+        templates_dir().join(format!("{}.txt", source))
+    } else {
+        PathBuf::from(source)
+    }
+}
+
+// Function to normalize a template source, turning file paths absolute so they work from any repository
+pub fn template_source(source: &str) -> Result<String, GitError> {
+    if builtin_body(source).is_some() {
+        return Ok(source.to_string());
+    }
+    let path = PathBuf::from(source);
+    if !path.is_file() {
+        return Err(GitError::InvalidInput(format!(
+            "'{}' is neither a template file nor a built-in template (available: {})",
+            source,
+            builtin_templates().join(", ")
+        )));
+    }
+    Ok(fs::canonicalize(path)?.to_string_lossy().into_owned())
+}
+
+// Function to make sure a template's file exists, writing built-in templates out on first use
+pub fn install_template(source: &str) -> Result<PathBuf, GitError> {
+    let source = template_source(source)?;
+    let path = template_location(&source);
+    if let Some(body) = builtin_body(&source) {
+        create_dir_all(&templates_dir())?;
+        write_file(&path, body)?;
+    }
+    Ok(path)
+}
+
+// Function to install a template and point commit.template at it
+pub fn set_commit_template(source: &str, scope: Scope) -> Result<PathBuf, GitError> {
+    let path = install_template(source)?;
+    GitConfig::new(scope).set("commit.template", &path.to_string_lossy())?;
+    Ok(path)
+}
+
+// Function to stop using a commit template
+pub fn unset_commit_template(scope: Scope) -> Result<(), GitError> {
+    GitConfig::new(scope).unset("commit.template")
+}
+
+// Function to get the globally configured commit template, if any
+pub fn current_commit_template() -> Option<String> {
+    GitConfig::global().get("commit.template")
+}
+
+fn builtin_body(name: &str) -> Option<&'static str> {
+    BUILTIN_TEMPLATES
+        .iter()
+        .find(|(template, _)| *template == name)
+        .map(|(_, body)| *body)
+}
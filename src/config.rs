@@ -0,0 +1,514 @@
+pub mod file;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use crate::config_cache::{clear_config_cache, normalize_key, scope_entries};
+use crate::dry_run::DryRun;
+use crate::git::Scope;
+#[cfg(not(feature = "libgit2"))]
+use crate::install::command_exists;
+use crate::install::elevated_command;
+#[cfg(feature = "libgit2")]
+use crate::libgit2;
+use crate::lock::lock_config;
+use crate::options::Options;
+use crate::runner::{command_runner, command_runner_replaced, CommandRunner};
+use crate::validate::{validate_config_key, validate_email, validate_name};
+use crate::GitError;
+
+use self::file::{scope_path, ConfigFile};
+
+// Environment variable choosing the backend for the CLI: auto, git, file or libgit2
+pub const CONFIG_BACKEND_ENV: &str = "GITUP_CONFIG_BACKEND";
+
+static BACKEND: Mutex<ConfigBackend> = Mutex::new(ConfigBackend::Auto);
+
+// How a GitConfig reaches its file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConfigBackend {
+    // libgit2 when built with the `libgit2` feature, otherwise git, or the file itself when git
+    // isn't installed yet. A runner set for the instance or the process always gets git calls
+    #[default]
+    Auto,
+    // Run `git config`
+    Git,
+    // Parse and edit the file directly, keeping its comments and ordering
+    File,
+    // Read and write through libgit2, running git for anything it fails at
+    #[cfg(feature = "libgit2")]
+    Libgit2,
+}
+
+impl FromStr for ConfigBackend {
+    type Err = GitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(ConfigBackend::Auto),
+            "git" => Ok(ConfigBackend::Git),
+            "file" => Ok(ConfigBackend::File),
+            #[cfg(feature = "libgit2")]
+            "libgit2" => Ok(ConfigBackend::Libgit2),
+            #[cfg(not(feature = "libgit2"))]
+            "libgit2" => Err(GitError::InvalidInput(
+                "gitup was built without the libgit2 feature".to_string(),
+            )),
+            other => Err(GitError::InvalidInput(format!(
+                "unknown config backend '{}' (expected auto, git, file or libgit2)",
+                other
+            ))),
+        }
+    }
+}
+
+// Function to choose the backend for every GitConfig that wasn't given one with `with_backend`
+pub fn set_config_backend(backend: ConfigBackend) {
+    if let Ok(mut current) = BACKEND.lock() {
+        *current = backend;
+    }
+    clear_config_cache();
+}
+
+// Function to get the backend chosen with `set_config_backend`
+pub fn config_backend() -> ConfigBackend {
+    BACKEND.lock().map(|backend| *backend).unwrap_or_default()
+}
+
+#[cfg(feature = "libgit2")]
+fn automatic_backend() -> ConfigBackend {
+    ConfigBackend::Libgit2
+}
+
+#[cfg(not(feature = "libgit2"))]
+fn automatic_backend() -> ConfigBackend {
+    if command_exists("git") {
+        ConfigBackend::Git
+    } else {
+        ConfigBackend::File
+    }
+}
+
+// Reads and writes one git configuration file (system, global, local or worktree) through a command
+// runner: its own if one was given with `with_runner`, otherwise the process-wide one from
+// `set_command_runner`. Cloning is cheap, so a configured instance can be handed around freely
+#[derive(Clone)]
+pub struct GitConfig {
+    scope: Scope,
+    runner: Option<Arc<dyn CommandRunner>>,
+    options: Options,
+    backend: Option<ConfigBackend>,
+}
+
+impl fmt::Debug for GitConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GitConfig")
+            .field("scope", &self.scope)
+            .field("options", &self.options)
+            .field("backend", &self.backend)
+            .finish_non_exhaustive()
+    }
+}
+
+impl GitConfig {
+    pub fn new(scope: Scope) -> Self {
+        GitConfig {
+            scope,
+            runner: None,
+            options: Options::default(),
+            backend: None,
+        }
+    }
+
+    // ~/.gitconfig
+    pub fn global() -> Self {
+        Self::new(Scope::Global)
+    }
+
+    // The current repository's .git/config
+    pub fn local() -> Self {
+        Self::new(Scope::local())
+    }
+
+    // The .git/config of the repository containing `dir`
+    pub fn repo(dir: impl Into<PathBuf>) -> Self {
+        Self::new(Scope::Local(dir.into()))
+    }
+
+    // The .git/config.worktree of the working tree containing `dir`
+    pub fn worktree(dir: impl Into<PathBuf>) -> Self {
+        Self::new(Scope::Worktree(dir.into()))
+    }
+
+    // /etc/gitconfig
+    pub fn system() -> Self {
+        Self::new(Scope::System)
+    }
+
+    pub fn with_runner(mut self, runner: impl CommandRunner + 'static) -> Self {
+        self.runner = Some(Arc::new(runner));
+        self
+    }
+
+    pub fn with_options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_backend(mut self, backend: ConfigBackend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    pub fn scope(&self) -> &Scope {
+        &self.scope
+    }
+
+    pub fn options(&self) -> Options {
+        self.options
+    }
+
+    // Function to get a value from this scope; unset and empty values are both None
+    pub fn get(&self, key: &str) -> Option<String> {
+        if self.uses_file() {
+            return self.load_file().ok()?.get(key).filter(|value| !value.is_empty());
+        }
+        if !self.cached() {
+            return self
+                .git(&["config", self.scope.flag(), key])
+                .ok()
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+                .filter(|value| !value.is_empty());
+        }
+        let key = normalize_key(key);
+        self.list()
+            .ok()?
+            .into_iter()
+            .rev()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value)
+            .filter(|value| !value.is_empty())
+    }
+
+    // Function to get every value of a multi-valued key, in file order
+    pub fn get_all(&self, key: &str) -> Result<Vec<String>, GitError> {
+        if self.uses_file() {
+            return Ok(self.load_file()?.get_all(key));
+        }
+        if !self.cached() {
+            let args = ["config", self.scope.flag(), "--get-all", key];
+            let output = self.git(&args)?;
+            return match output.status.code() {
+                Some(0) => Ok(String::from_utf8_lossy(&output.stdout).lines().map(String::from).collect()),
+                // git exits with 1 when the key isn't set
+                Some(1) => Ok(Vec::new()),
+                _ => Err(GitError::command_failed("git", &args, &output)),
+            };
+        }
+        let key = normalize_key(key);
+        Ok(self.list()?.into_iter().filter(|(k, _)| *k == key).map(|(_, value)| value).collect())
+    }
+
+    // Function to list every key/value pair in this scope, in file order
+    pub fn list(&self) -> Result<Vec<(String, String)>, GitError> {
+        if self.uses_file() {
+            return Ok(self.load_file()?.entries());
+        }
+        if !self.cached() {
+            return self.read_list();
+        }
+        scope_entries(&self.scope, || self.read_list())
+    }
+
+    // Reads with the real git are answered from one cached listing of the scope; a replaced
+    // runner sees each `git config` call, since that's what it answers
+    fn cached(&self) -> bool {
+        self.runner.is_none() && !command_runner_replaced()
+    }
+
+    // The backend in effect, with Auto worked out
+    fn backend(&self) -> ConfigBackend {
+        match self.backend.unwrap_or_else(config_backend) {
+            ConfigBackend::Auto if self.cached() => automatic_backend(),
+            ConfigBackend::Auto => ConfigBackend::Git,
+            backend => backend,
+        }
+    }
+
+    fn uses_file(&self) -> bool {
+        self.backend() == ConfigBackend::File
+    }
+
+    // Whether changes have to run git with administrator rights: only the system config, only
+    // with the real git, and only when this user can't write it. The file and libgit2 backends
+    // can't elevate, so these changes go through git
+    fn elevated(&self) -> bool {
+        self.scope == Scope::System
+            && self.cached()
+            && scope_path(&self.scope).is_ok_and(|path| !writable(&path))
+    }
+
+    // Make a change through libgit2, returning whether it was made (or recorded in dry-run
+    // mode); when libgit2 fails, git gets to try and report the error
+    #[cfg(feature = "libgit2")]
+    fn change_with_libgit2(&self, args: &[&str], change: impl FnOnce(&Scope) -> Result<(), GitError>) -> bool {
+        if self.backend() != ConfigBackend::Libgit2 || self.elevated() {
+            return false;
+        }
+        if self.skip(args) {
+            return true;
+        }
+        let Ok(_lock) = lock_config() else {
+            return false;
+        };
+        clear_config_cache();
+        change(&self.scope).is_ok()
+    }
+
+    fn load_file(&self) -> Result<ConfigFile, GitError> {
+        ConfigFile::load(&scope_path(&self.scope)?)
+    }
+
+    // Change the file directly, recording the change instead in dry-run mode for this instance;
+    // in global dry-run mode saving records the write
+    fn edit_file(&self, change: &str, key: &str, edit: impl FnOnce(&mut ConfigFile) -> Result<(), GitError>) -> Result<(), GitError> {
+        let path = scope_path(&self.scope)?;
+        if self.options.dry_run {
+            DryRun::record(format!("{}: {} in {}", change, key, path.display()));
+            return Ok(());
+        }
+        let _lock = lock_config()?;
+        let mut file = ConfigFile::load(&path)?;
+        edit(&mut file)?;
+        clear_config_cache();
+        file.save(&path)
+    }
+
+    fn read_list(&self) -> Result<Vec<(String, String)>, GitError> {
+        #[cfg(feature = "libgit2")]
+        if self.backend() == ConfigBackend::Libgit2 {
+            if let Ok(entries) = libgit2::list(&self.scope) {
+                return Ok(entries);
+            }
+        }
+        let args = ["config", self.scope.flag(), "--list", "--null"];
+        let output = self.git(&args)?;
+        if !output.status.success() {
+            // A missing config file is just an empty configuration
+            if output.stdout.is_empty() {
+                return Ok(Vec::new());
+            }
+            return Err(GitError::command_failed("git", &args, &output));
+        }
+
+        // With --null each entry is "key\nvalue\0"; a key without a value is an implicit true
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout
+            .split('\0')
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| match entry.split_once('\n') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None => (entry.to_string(), "true".to_string()),
+            })
+            .collect())
+    }
+
+    // Function to set a key, replacing its value; the key, and identity values, are validated first
+    pub fn set(&self, key: &str, value: &str) -> Result<(), GitError> {
+        self.validate(key, value)?;
+        if self.uses_file() && !self.elevated() {
+            return self.edit_file("set", key, |file| file.set(key, value));
+        }
+        let args = ["config", self.scope.flag(), key, value];
+        #[cfg(feature = "libgit2")]
+        if self.change_with_libgit2(&args, |scope| libgit2::set(scope, key, value)) {
+            return Ok(());
+        }
+        self.write(&args, key)
+    }
+
+    // Function to add another value to a multi-valued key, such as url.<base>.insteadOf
+    pub fn add(&self, key: &str, value: &str) -> Result<(), GitError> {
+        self.validate(key, value)?;
+        if self.uses_file() && !self.elevated() {
+            return self.edit_file("add", key, |file| {
+                file.add(key, value);
+                Ok(())
+            });
+        }
+        let args = ["config", self.scope.flag(), "--add", key, value];
+        #[cfg(feature = "libgit2")]
+        if self.change_with_libgit2(&args, |scope| libgit2::add(scope, key, value)) {
+            return Ok(());
+        }
+        self.write(&args, key)
+    }
+
+    // Function to set several keys at once, validating them all before anything is written. The
+    // first value given for a key replaces every value it had; later ones for the same key are
+    // added to it. The file is edited once rather than starting git for each key, unless git
+    // was asked for or a runner answers the calls
+    pub fn set_many<K: AsRef<str>, V: AsRef<str>>(&self, entries: &[(K, V)]) -> Result<(), GitError> {
+        for (key, value) in entries {
+            self.validate(key.as_ref(), value.as_ref())?;
+        }
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let git_requested = self.backend.unwrap_or_else(config_backend) == ConfigBackend::Git;
+        if (self.uses_file() || (self.cached() && !git_requested)) && !self.elevated() {
+            let keys: Vec<&str> = entries.iter().map(|(key, _)| key.as_ref()).collect();
+            return self.edit_file("set", &keys.join(", "), |file| {
+                let mut seen = HashSet::new();
+                for (key, value) in entries {
+                    let (key, value) = (key.as_ref(), value.as_ref());
+                    if seen.insert(normalize_key(key)) {
+                        file.replace_all(key, value);
+                    } else {
+                        file.add(key, value);
+                    }
+                }
+                Ok(())
+            });
+        }
+
+        let _lock = lock_config()?;
+        let mut seen = HashSet::new();
+        for (key, value) in entries {
+            let (key, value) = (key.as_ref(), value.as_ref());
+            let mode = if seen.insert(normalize_key(key)) { "--replace-all" } else { "--add" };
+            self.write(&["config", self.scope.flag(), mode, key, value], key)?;
+        }
+        Ok(())
+    }
+
+    // Function to remove a key; a key that isn't set is not an error
+    pub fn unset(&self, key: &str) -> Result<(), GitError> {
+        validate_config_key(key)?;
+        if self.uses_file() && !self.elevated() {
+            return self.edit_file("unset", key, |file| file.unset(key).map(|_| ()));
+        }
+        let args = ["config", self.scope.flag(), "--unset", key];
+        #[cfg(feature = "libgit2")]
+        if self.change_with_libgit2(&args, |scope| libgit2::unset(scope, key)) {
+            return Ok(());
+        }
+        if self.skip(&args) {
+            return Ok(());
+        }
+        let _lock = lock_config()?;
+        clear_config_cache();
+        let output = self.change(&args)?;
+        // git exits with 5 when the key doesn't exist
+        if output.status.success() || output.status.code() == Some(5) {
+            Ok(())
+        } else {
+            Err(GitError::command_failed("git", &args, &output))
+        }
+    }
+
+    fn validate(&self, key: &str, value: &str) -> Result<(), GitError> {
+        validate_config_key(key)?;
+        match key.to_lowercase().as_str() {
+            "user.name" => validate_name(value),
+            "user.email" => validate_email(value),
+            _ => Ok(()),
+        }
+    }
+
+    fn write(&self, args: &[&str], key: &str) -> Result<(), GitError> {
+        if self.skip(args) {
+            return Ok(());
+        }
+        let _lock = lock_config()?;
+        clear_config_cache();
+        let output = self.change(args)?;
+        match output.status.code() {
+            Some(0) => Ok(()),
+            // git exits with 1 or 2 when the section or key name is invalid or missing
+            Some(1) | Some(2) => Err(GitError::ConfigKeyInvalid(key.to_string())),
+            _ => Err(GitError::command_failed("git", args, &output)),
+        }
+    }
+
+    // Record instead of run in dry-run mode, whether set globally or for this instance
+    fn skip(&self, args: &[&str]) -> bool {
+        let (program, full_args) = self.change_command(args);
+        let args: Vec<&str> = full_args.iter().map(String::as_str).collect();
+        if DryRun::skip_command(&program, &args) {
+            return true;
+        }
+        if self.options.dry_run {
+            DryRun::record_command(&program, &args);
+            return true;
+        }
+        false
+    }
+
+    // Run git to change the config, elevated when the scope needs it
+    fn change(&self, args: &[&str]) -> Result<Output, GitError> {
+        let (program, full_args) = self.change_command(args);
+        let args: Vec<&str> = full_args.iter().map(String::as_str).collect();
+        let runner = self.runner.clone().unwrap_or_else(command_runner);
+        runner.run(&program, &args).map_err(|e| GitError::spawn_failed(&program, e))
+    }
+
+    fn change_command(&self, args: &[&str]) -> (String, Vec<String>) {
+        let full_args = self.full_args(args);
+        if let Some(path) = scope_path(&self.scope).ok().filter(|_| self.elevated()) {
+            // sudo drops GIT_CONFIG_SYSTEM, so the file is named rather than left to --system
+            let path = path.to_string_lossy().into_owned();
+            let args: Vec<&str> = full_args
+                .iter()
+                .flat_map(|arg| match arg.as_str() {
+                    "--system" => vec!["--file", path.as_str()],
+                    arg => vec![arg],
+                })
+                .collect();
+            if let Some(elevated) = elevated_command("git", &args) {
+                return elevated;
+            }
+        }
+        ("git".to_string(), full_args)
+    }
+
+    fn git(&self, args: &[&str]) -> Result<Output, GitError> {
+        let runner = self.runner.clone().unwrap_or_else(command_runner);
+        let full_args = self.full_args(args);
+        let args: Vec<&str> = full_args.iter().map(String::as_str).collect();
+        runner.run("git", &args).map_err(|e| GitError::spawn_failed("git", e))
+    }
+
+    // Repository scopes run git in the repository, which is where it finds their files
+    fn full_args(&self, args: &[&str]) -> Vec<String> {
+        let repo = self.scope.repo().map(|dir| ["-C".to_string(), dir.to_string_lossy().into_owned()]);
+        repo.into_iter().flatten().chain(args.iter().map(|arg| arg.to_string())).collect()
+    }
+}
+
+// Whether this user can replace a config file, which git does by writing <file>.lock next to
+// it and renaming that over the file
+fn writable(path: &Path) -> bool {
+    let mut lock = path.as_os_str().to_os_string();
+    lock.push(".lock");
+    match OpenOptions::new().write(true).create_new(true).open(&lock) {
+        Ok(_) => {
+            let _ = fs::remove_file(&lock);
+            true
+        }
+        Err(e) => e.kind() != ErrorKind::PermissionDenied,
+    }
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        Self::global()
+    }
+}
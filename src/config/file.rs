@@ -0,0 +1,449 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config_cache::normalize_key;
+use crate::dry_run::{create_dir_all, write_file_atomic};
+use crate::git::{global_config_path, Scope};
+use crate::GitError;
+
+// A gitconfig file read and edited without git. Lines that aren't changed are written back
+// exactly as they were, comments and blank lines included. Includes are not followed, the
+// same as `git config --file`
+#[derive(Debug, Clone, Default)]
+pub struct ConfigFile {
+    lines: Vec<Line>,
+    trailing_newline: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Line {
+    // As in the file, continuation lines included, without the final line break
+    text: String,
+    // The section the line is in, or opens: the lowercased name and the subsection
+    section: Option<(String, Option<String>)>,
+    header: bool,
+    // The normalized key and the value of an entry on this line; a bare key has no value
+    entry: Option<(String, Option<String>)>,
+}
+
+impl ConfigFile {
+    // Function to parse the text of a config file. Anything that isn't a section header or an
+    // entry is kept as it is and otherwise ignored
+    pub fn parse(text: &str) -> Self {
+        if text.is_empty() {
+            return ConfigFile::default();
+        }
+        let trailing_newline = text.ends_with('\n');
+        let mut physical = text.strip_suffix('\n').unwrap_or(text).split('\n').peekable();
+
+        let mut lines = Vec::new();
+        let mut section: Option<(String, Option<String>)> = None;
+        while let Some(first) = physical.next() {
+            let mut text = first.to_string();
+            let trimmed = first.trim_start();
+            let mut header = false;
+            let mut rest = trimmed.to_string();
+            if trimmed.starts_with('[') {
+                if let Some((name, subsection, after)) = parse_header(trimmed) {
+                    section = Some((name, subsection));
+                    header = true;
+                    rest = after.trim_start().to_string();
+                }
+            }
+
+            let mut entry = None;
+            let is_entry = rest.starts_with(|c: char| c.is_ascii_alphabetic());
+            if let (true, Some((name, subsection))) = (is_entry, &section) {
+                // A value continues on the next line after an unescaped trailing backslash
+                while ends_with_continuation(&rest) && physical.peek().is_some() {
+                    let next = physical.next().unwrap_or_default();
+                    rest.pop();
+                    rest.push_str(next);
+                    text.push('\n');
+                    text.push_str(next);
+                }
+                let key_end = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '-').unwrap_or(rest.len());
+                let (variable, after) = rest.split_at(key_end);
+                let after = after.trim_start();
+                let value = after.strip_prefix('=').map(parse_value);
+                let key = match subsection {
+                    Some(subsection) => format!("{}.{}.{}", name, subsection, variable.to_lowercase()),
+                    None => format!("{}.{}", name, variable.to_lowercase()),
+                };
+                entry = Some((key, value));
+            }
+
+            lines.push(Line {
+                text,
+                section: section.clone(),
+                header,
+                entry,
+            });
+        }
+        ConfigFile {
+            lines,
+            trailing_newline,
+        }
+    }
+
+    // Function to read a config file; a file that doesn't exist is an empty configuration
+    pub fn load(path: &Path) -> Result<Self, GitError> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Self::parse(&text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(ConfigFile::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Function to write the file back in one atomic replace, creating its directory if needed,
+    // or record the write in dry-run mode
+    pub fn save(&self, path: &Path) -> Result<(), GitError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            create_dir_all(dir)?;
+        }
+        write_file_atomic(path, &self.to_string())
+    }
+
+    // Function to get the last value of a key, the one git uses; a bare key reads as true
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.get_all(key).pop()
+    }
+
+    // Function to get every value of a multi-valued key, in file order
+    pub fn get_all(&self, key: &str) -> Vec<String> {
+        let key = normalize_key(key);
+        self.entries().into_iter().filter(|(k, _)| *k == key).map(|(_, value)| value).collect()
+    }
+
+    // Function to list every key/value pair in file order, keys in the form `git config --list`
+    // prints them
+    pub fn entries(&self) -> Vec<(String, String)> {
+        self.lines
+            .iter()
+            .filter_map(|line| line.entry.clone())
+            .map(|(key, value)| (key, value.unwrap_or_else(|| "true".to_string())))
+            .collect()
+    }
+
+    // Function to set a key, replacing its value in place, or adding it to the end of its section
+    // (or a new section) when it isn't set yet. Like git, refuses to pick one of several values
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), GitError> {
+        match self.positions(key).as_slice() {
+            [] => {
+                self.add(key, value);
+                Ok(())
+            }
+            [index] => {
+                let line = &self.lines[*index];
+                self.lines[*index] = entry_line(key, value, line.section.clone());
+                Ok(())
+            }
+            _ => Err(several_values(key)),
+        }
+    }
+
+    // Function to give a key this one value, whatever it had before: the first existing entry
+    // is replaced in place and any others are removed
+    pub fn replace_all(&mut self, key: &str, value: &str) {
+        let positions = self.positions(key);
+        let Some((&first, rest)) = positions.split_first() else {
+            return self.add(key, value);
+        };
+        for &index in rest.iter().rev() {
+            self.lines.remove(index);
+        }
+        let section = self.lines[first].section.clone();
+        self.lines[first] = entry_line(key, value, section);
+    }
+
+    // Function to add another value for a key after the last entry of its section
+    pub fn add(&mut self, key: &str, value: &str) {
+        let (section, subsection) = split_section(key);
+        let target = Some((section.to_lowercase(), subsection.map(String::from)));
+        let last = self
+            .lines
+            .iter()
+            .rposition(|line| line.section == target && (line.header || line.entry.is_some()));
+        match last {
+            Some(index) => self.lines.insert(index + 1, entry_line(key, value, target)),
+            None => {
+                let text = match subsection {
+                    Some(subsection) => format!("[{} \"{}\"]", section, subsection.replace('\\', "\\\\").replace('"', "\\\"")),
+                    None => format!("[{}]", section),
+                };
+                self.lines.push(Line {
+                    text,
+                    section: target.clone(),
+                    header: true,
+                    entry: None,
+                });
+                self.lines.push(entry_line(key, value, target));
+                self.trailing_newline = true;
+            }
+        }
+    }
+
+    // Function to remove a key, returning whether it was set. Like git, refuses to pick one of
+    // several values
+    pub fn unset(&mut self, key: &str) -> Result<bool, GitError> {
+        match self.positions(key).as_slice() {
+            [] => Ok(false),
+            [index] => {
+                self.lines.remove(*index);
+                Ok(true)
+            }
+            _ => Err(several_values(key)),
+        }
+    }
+
+    fn positions(&self, key: &str) -> Vec<usize> {
+        let key = normalize_key(key);
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.entry.as_ref().is_some_and(|(k, _)| *k == key))
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+impl fmt::Display for ConfigFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, line) in self.lines.iter().enumerate() {
+            if index > 0 {
+                f.write_str("\n")?;
+            }
+            f.write_str(&line.text)?;
+        }
+        if self.trailing_newline && !self.lines.is_empty() {
+            f.write_str("\n")?;
+        }
+        Ok(())
+    }
+}
+
+// Function to find the file git reads a scope from, without running git
+pub fn scope_path(scope: &Scope) -> Result<PathBuf, GitError> {
+    match scope {
+        Scope::System => Ok(env::var_os("GIT_CONFIG_SYSTEM").map(PathBuf::from).unwrap_or_else(|| {
+            if cfg!(windows) {
+                windows_install_dir()
+                    .unwrap_or_else(|| PathBuf::from(r"C:\Program Files\Git"))
+                    .join(r"etc\gitconfig")
+            } else {
+                PathBuf::from("/etc/gitconfig")
+            }
+        })),
+        Scope::Global => Ok(global_config_path()),
+        Scope::Local(dir) => Ok(common_dir(&repository_git_dir(dir)?).join("config")),
+        Scope::Worktree(dir) => {
+            let git_dir = repository_git_dir(dir)?;
+            let config = common_dir(&git_dir).join("config");
+            // Like `git config --worktree`, without extensions.worktreeConfig this is the local config
+            let enabled = ConfigFile::load(&config)?.get("extensions.worktreeConfig");
+            if enabled.is_some_and(|value| ["true", "yes", "on", "1"].contains(&value.to_lowercase().as_str())) {
+                Ok(git_dir.join("config.worktree"))
+            } else {
+                Ok(config)
+            }
+        }
+    }
+}
+
+// Where Git for Windows is installed, from the git.exe on PATH: <install>\cmd\git.exe, or
+// <install>\bin\git.exe or <install>\mingw64\bin\git.exe
+pub(crate) fn windows_install_dir() -> Option<PathBuf> {
+    let git = env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join("git.exe"))
+        .find(|git| git.is_file())?;
+    let dir = git.parent()?.parent()?;
+    if dir.file_name().is_some_and(|name| name.eq_ignore_ascii_case("mingw64")) {
+        dir.parent().map(Path::to_path_buf)
+    } else {
+        Some(dir.to_path_buf())
+    }
+}
+
+fn repository_git_dir(dir: &Path) -> Result<PathBuf, GitError> {
+    find_git_dir(dir).ok_or_else(|| GitError::InvalidInput(format!("{} is not in a git repository", dir.display())))
+}
+
+// The .git directory of the repository containing `dir`, following the .git file of a linked
+// worktree or submodule
+pub(crate) fn find_git_dir(dir: &Path) -> Option<PathBuf> {
+    let dir = fs::canonicalize(dir).ok()?;
+    for ancestor in dir.ancestors() {
+        let dot_git = ancestor.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git);
+        }
+        if dot_git.is_file() {
+            let contents = fs::read_to_string(&dot_git).ok()?;
+            let target = contents.trim().strip_prefix("gitdir:")?.trim();
+            return fs::canonicalize(ancestor.join(target)).ok();
+        }
+    }
+    None
+}
+
+// Where the shared config of a repository lives; a linked worktree's .git directory points to
+// the main one through its commondir file
+pub(crate) fn common_dir(git_dir: &Path) -> PathBuf {
+    fs::read_to_string(git_dir.join("commondir"))
+        .map(|common| git_dir.join(common.trim()))
+        .unwrap_or_else(|_| git_dir.to_path_buf())
+}
+
+// `[section]`, `[section "subsection"]` or the old `[section.subsection]`, and whatever
+// follows the closing bracket on the same line
+fn parse_header(line: &str) -> Option<(String, Option<String>, &str)> {
+    let inner = line.strip_prefix('[')?;
+    match inner.find(['"', ']'])? {
+        end if inner.as_bytes()[end] == b']' => {
+            let name = inner[..end].trim();
+            let rest = &inner[end + 1..];
+            match name.split_once('.') {
+                Some((section, subsection)) => Some((section.to_lowercase(), Some(subsection.to_lowercase()), rest)),
+                None => Some((name.to_lowercase(), None, rest)),
+            }
+        }
+        quote => {
+            let section = inner[..quote].trim().to_lowercase();
+            let mut subsection = String::new();
+            let mut chars = inner[quote + 1..].char_indices();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => subsection.extend(chars.next().map(|(_, c)| c)),
+                    '"' => {
+                        let rest = inner[quote + 1 + i + 1..].trim_start().strip_prefix(']')?;
+                        return Some((section, Some(subsection), rest));
+                    }
+                    c => subsection.push(c),
+                }
+            }
+            None
+        }
+    }
+}
+
+fn ends_with_continuation(text: &str) -> bool {
+    text.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1
+}
+
+// A value as git reads it: surrounding whitespace dropped, quotes removed, escapes resolved and
+// an unquoted # or ; starting a comment
+fn parse_value(raw: &str) -> String {
+    let mut value = String::new();
+    let mut space = String::new();
+    let mut quoted = false;
+    let mut chars = raw.trim_start().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' | ';' if !quoted => break,
+            c if c.is_whitespace() && !quoted => {
+                space.push(c);
+                continue;
+            }
+            _ => {}
+        }
+        value.push_str(&space);
+        space.clear();
+        match c {
+            '"' => quoted = !quoted,
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('b') => value.push('\u{8}'),
+                Some(other) => value.push(other),
+                None => {}
+            },
+            c => value.push(c),
+        }
+    }
+    value
+}
+
+fn entry_line(key: &str, value: &str, section: Option<(String, Option<String>)>) -> Line {
+    let variable = key.rsplit_once('.').map(|(_, variable)| variable).unwrap_or(key);
+    Line {
+        text: format!("\t{} = {}", variable, quote_value(value)),
+        section,
+        header: false,
+        entry: Some((normalize_key(key), Some(value.to_string()))),
+    }
+}
+
+// Quote a value when git would otherwise change it on reading
+fn quote_value(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    let needs_quotes = value.trim() != value || value.contains(['#', ';']);
+    if needs_quotes {
+        format!("\"{}\"", escaped)
+    } else {
+        escaped
+    }
+}
+
+// The section and subsection of a key as written
+fn split_section(key: &str) -> (&str, Option<&str>) {
+    let (section, rest) = key.split_once('.').unwrap_or((key, ""));
+    (section, rest.rsplit_once('.').map(|(subsection, _)| subsection))
+}
+
+fn several_values(key: &str) -> GitError {
+    GitError::InvalidInput(format!("'{}' has several values; change them with `git config --replace-all`", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "# my settings\n[user]\n\tname = Jane Doe\n\n[Core]\n\teditor = \"vim\" ; the best\n\tbare\n\
+                        [url \"git@example.com:\"]\n\tinsteadOf = https://example.com/\n\tinsteadOf = ex:\n\
+                        [alias]\n\tlg = log \\\n\t--oneline\n";
+
+    #[test]
+    fn parse_reads_entries_and_writes_the_file_back_unchanged() {
+        let file = ConfigFile::parse(TEXT);
+        assert_eq!(file.to_string(), TEXT);
+        assert_eq!(file.get("user.name").as_deref(), Some("Jane Doe"));
+        assert_eq!(file.get("core.editor").as_deref(), Some("vim"));
+        assert_eq!(file.get("CORE.BARE").as_deref(), Some("true"));
+        assert_eq!(file.get("alias.lg").as_deref(), Some("log \t--oneline"));
+        assert_eq!(
+            file.get_all("url.git@example.com:.insteadof"),
+            ["https://example.com/", "ex:"]
+        );
+        assert_eq!(ConfigFile::parse("").to_string(), "");
+        assert_eq!(ConfigFile::parse("[user]\n\tname = x").to_string(), "[user]\n\tname = x");
+    }
+
+    #[test]
+    fn changes_keep_the_rest_of_the_file_and_read_back() {
+        let mut file = ConfigFile::parse(TEXT);
+        file.set("user.name", "John").unwrap();
+        file.set("user.email", " padded # \"quoted\"\n").unwrap();
+        file.add("remote.origin.url", "git@example.com:a.git");
+        assert!(file.set("url.git@example.com:.insteadof", "x").is_err());
+        assert!(file.unset("core.bare").unwrap());
+        assert!(!file.unset("core.bare").unwrap());
+
+        let text = file.to_string();
+        assert!(text.starts_with("# my settings\n[user]\n\tname = John\n\temail = \" padded # \\\"quoted\\\"\\n\"\n"));
+        assert!(text.ends_with("[remote \"origin\"]\n\turl = git@example.com:a.git\n"));
+        let reread = ConfigFile::parse(&text);
+        assert_eq!(reread.entries(), file.entries());
+        assert_eq!(reread.get("user.email").as_deref(), Some(" padded # \"quoted\"\n"));
+        assert_eq!(reread.get("core.bare"), None);
+
+        file.replace_all("url.git@example.com:.insteadof", "gh:");
+        assert_eq!(ConfigFile::parse(&file.to_string()).get_all("url.git@example.com:.insteadOf"), ["gh:"]);
+    }
+}
@@ -0,0 +1,144 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::git::{global_config_path, ConfigEntry, Scope};
+use crate::{config_dir, GitError};
+
+// Commands like `doctor` and `whoami` read many keys; git is started once per scope or
+// repository to list everything, and each key is looked up in that. Any change gitup makes
+// through git clears the cache
+static SCOPES: Mutex<Vec<(Scope, Settings)>> = Mutex::new(Vec::new());
+static EFFECTIVE: Mutex<Vec<(PathBuf, Vec<ConfigEntry>)>> = Mutex::new(Vec::new());
+
+// Key/value pairs in file order
+type Settings = Vec<(String, String)>;
+
+static DISK_CACHE: AtomicBool = AtomicBool::new(false);
+
+const DISK_CACHE_FILE: &str = "config-cache.json";
+
+// Environment variable that turns on the disk cache when set to 1
+pub const DISK_CACHE_ENV: &str = "GITUP_CONFIG_CACHE";
+
+// The global configuration as last read, kept between runs while the file is unchanged
+#[derive(Serialize, Deserialize)]
+struct DiskCache {
+    path: PathBuf,
+    modified: u128,
+    len: u64,
+    entries: Settings,
+}
+
+// Function to forget every cached config read, after a change gitup didn't make itself
+pub fn clear_config_cache() {
+    if let Ok(mut scopes) = SCOPES.lock() {
+        scopes.clear();
+    }
+    if let Ok(mut effective) = EFFECTIVE.lock() {
+        effective.clear();
+    }
+    // A write can land within the file system's timestamp resolution and keep the size
+    if DISK_CACHE.load(Ordering::Relaxed) {
+        let _ = fs::remove_file(config_dir().join(DISK_CACHE_FILE));
+    }
+}
+
+// Function to also keep the global configuration in config-cache.json in the config directory
+// between runs, read again only once the file's modification time or size changes
+pub fn set_disk_cache(enabled: bool) {
+    DISK_CACHE.store(enabled, Ordering::Relaxed);
+}
+
+// Function to bring a config key to the form `git config --list` prints: the section and
+// variable names are case-insensitive, a subsection between them is not
+pub fn normalize_key(key: &str) -> String {
+    match (key.find('.'), key.rfind('.')) {
+        (Some(first), Some(last)) => format!(
+            "{}{}{}",
+            key[..first].to_lowercase(),
+            &key[first..last],
+            key[last..].to_lowercase()
+        ),
+        _ => key.to_lowercase(),
+    }
+}
+
+// The key/value pairs of one scope, from the cache or from `load`
+pub(crate) fn scope_entries(
+    scope: &Scope,
+    load: impl FnOnce() -> Result<Settings, GitError>,
+) -> Result<Settings, GitError> {
+    if let Some((_, entries)) = SCOPES.lock().ok().and_then(|scopes| scopes.iter().find(|(s, _)| s == scope).cloned()) {
+        return Ok(entries);
+    }
+    let entries = match scope {
+        Scope::Global if DISK_CACHE.load(Ordering::Relaxed) => global_from_disk(load)?,
+        _ => load()?,
+    };
+    if let Ok(mut scopes) = SCOPES.lock() {
+        scopes.push((scope.clone(), entries.clone()));
+    }
+    Ok(entries)
+}
+
+// Every entry git sees in a repository, from the cache or from `load`
+pub(crate) fn effective_entries(
+    repo: &Path,
+    load: impl FnOnce() -> Result<Vec<ConfigEntry>, GitError>,
+) -> Result<Vec<ConfigEntry>, GitError> {
+    let cached = EFFECTIVE
+        .lock()
+        .ok()
+        .and_then(|effective| effective.iter().find(|(dir, _)| dir == repo).map(|(_, entries)| entries.clone()));
+    if let Some(entries) = cached {
+        return Ok(entries);
+    }
+    let entries = load()?;
+    if let Ok(mut effective) = EFFECTIVE.lock() {
+        effective.push((repo.to_path_buf(), entries.clone()));
+    }
+    Ok(entries)
+}
+
+fn global_from_disk(
+    load: impl FnOnce() -> Result<Settings, GitError>,
+) -> Result<Settings, GitError> {
+    let path = global_config_path();
+    // Without a global config file there's nothing worth caching
+    let Some((modified, len)) = file_stamp(&path) else {
+        return load();
+    };
+    let cache_path = config_dir().join(DISK_CACHE_FILE);
+    let cached = fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<DiskCache>(&contents).ok());
+    if let Some(cache) = cached {
+        if cache.path == path && cache.modified == modified && cache.len == len {
+            return Ok(cache.entries);
+        }
+    }
+
+    let entries = load()?;
+    let cache = DiskCache {
+        path,
+        modified,
+        len,
+        entries,
+    };
+    // The cache only saves time, so failing to write it isn't worth reporting
+    if let Ok(contents) = serde_json::to_string(&cache) {
+        let _ = fs::create_dir_all(config_dir()).and_then(|_| fs::write(&cache_path, contents));
+    }
+    Ok(cache.entries)
+}
+
+pub(crate) fn file_stamp(path: &Path) -> Option<(u128, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+    Some((modified, metadata.len()))
+}
@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::backup::read_backup;
+use crate::config::GitConfig;
+use crate::profile::get_profile;
+use crate::GitError;
+
+// A difference between the current configuration and a target configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigChange {
+    Added { key: String, value: String },
+    Removed { key: String, value: String },
+    Changed { key: String, old: String, new: String },
+}
+
+// Function to compare two configurations, describing what changes going from `current` to `target`
+pub fn diff_config(current: &[(String, String)], target: &[(String, String)]) -> Vec<ConfigChange> {
+    let current = group(current);
+    let target = group(target);
+
+    let mut changes = Vec::new();
+    for (key, new) in &target {
+        match current.get(key) {
+            None => changes.push(ConfigChange::Added {
+                key: key.clone(),
+                value: new.join("\n"),
+            }),
+            Some(old) if old != new => changes.push(ConfigChange::Changed {
+                key: key.clone(),
+                old: old.join("\n"),
+                new: new.join("\n"),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (key, old) in &current {
+        if !target.contains_key(key) {
+            changes.push(ConfigChange::Removed {
+                key: key.clone(),
+                value: old.join("\n"),
+            });
+        }
+    }
+    changes
+}
+
+// Function to compare the global configuration against a backup file
+pub fn diff_against_backup(path: &Path) -> Result<Vec<ConfigChange>, GitError> {
+    let current = GitConfig::global().list()?;
+    Ok(diff_config(&current, &read_backup(path)?))
+}
+
+// Function to compare the global configuration against the keys a profile would set
+pub fn diff_against_profile(profile_name: &str) -> Result<Vec<ConfigChange>, GitError> {
+    let target = get_profile(profile_name)?.settings();
+    let keys: Vec<String> = target.iter().map(|(key, _)| normalize_key(key)).collect();
+
+    // A profile only touches its own keys, so everything else is out of scope
+    let current: Vec<(String, String)> = GitConfig::global().list()?
+        .into_iter()
+        .filter(|(key, _)| keys.contains(&normalize_key(key)))
+        .collect();
+    Ok(diff_config(&current, &target))
+}
+
+// Collect values per key, keeping multi-valued keys together
+fn group(entries: &[(String, String)]) -> BTreeMap<String, Vec<String>> {
+    let mut grouped: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (key, value) in entries {
+        grouped.entry(normalize_key(key)).or_default().push(value.clone());
+    }
+    grouped
+}
+
+// Section and variable names are case-insensitive; subsections are not
+fn normalize_key(key: &str) -> String {
+    match (key.find('.'), key.rfind('.')) {
+        (Some(first), Some(last)) => format!(
+            "{}{}{}",
+            key[..first].to_lowercase(),
+            &key[first..last],
+            key[last..].to_lowercase()
+        ),
+        _ => key.to_lowercase(),
+    }
+}
@@ -0,0 +1,323 @@
+use std::env;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::config::GitConfig;
+use crate::git::{get_git_version, global_config_path, run_command, MIN_GIT_VERSION};
+use crate::install::command_exists;
+use crate::ssh::list_ssh_keys;
+use crate::wsl::{is_wsl, windows_credential_manager};
+use crate::{expand_home, GitError};
+
+// Outcome of a single diagnostic check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+// A diagnostic check result with an optional hint on how to fix it
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, message: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+// Function to diagnose the Git environment; every other check needs git, so they're
+// skipped when it's missing
+pub fn run_checks() -> Vec<Check> {
+    let version = match get_git_version() {
+        Ok(version) if version < MIN_GIT_VERSION => Check::warn(
+            "git",
+            format!("git {} is older than {}, so newer features such as SSH signing are missing", version, MIN_GIT_VERSION),
+            "run `gitup upgrade`",
+        ),
+        Ok(version) => Check::pass("git", format!("git version {}", version)),
+        Err(GitError::GitNotInstalled) => {
+            return vec![Check::fail(
+                "git",
+                "Git is not installed",
+                "run `gitup install`",
+            )]
+        }
+        Err(e) => Check::warn("git", e.to_string(), "check that `git --version` works"),
+    };
+
+    let mut checks = vec![
+        version,
+        check_user_name(),
+        check_user_email(),
+        check_ssh_agent(),
+        check_ssh_keys(),
+        check_signing(),
+        check_includes(),
+        check_config_paths(),
+    ];
+    if is_wsl() {
+        checks.push(check_wsl());
+    }
+    checks
+}
+
+fn check_user_name() -> Check {
+    match GitConfig::global().get("user.name") {
+        Some(name) => Check::pass("user.name", name),
+        None => Check::fail(
+            "user.name",
+            "user.name is not set",
+            "run `gitup config --user \"Your Name\"` or `gitup profile use <profile>`",
+        ),
+    }
+}
+
+fn check_user_email() -> Check {
+    match GitConfig::global().get("user.email") {
+        Some(email) if looks_like_email(&email) => Check::pass("user.email", email),
+        Some(email) => Check::warn(
+            "user.email",
+            format!("'{}' doesn't look like an email address", email),
+            "run `gitup config --email you@example.com`",
+        ),
+        None => Check::fail(
+            "user.email",
+            "user.email is not set",
+            "run `gitup config --email you@example.com` or `gitup profile use <profile>`",
+        ),
+    }
+}
+
+fn check_ssh_agent() -> Check {
+    if env::var_os("SSH_AUTH_SOCK").is_none() {
+        return Check::warn(
+            "ssh-agent",
+            "no ssh-agent is running (SSH_AUTH_SOCK is not set)",
+            "start one with `eval \"$(ssh-agent -s)\"`",
+        );
+    }
+    // ssh-add -l exits 1 when the agent has no identities and 2 when it can't be reached
+    match run_command("ssh-add", &["-l"]).map(|output| output.status.code()) {
+        Ok(Some(0)) => Check::pass("ssh-agent", "ssh-agent is running with keys loaded"),
+        Ok(Some(1)) => Check::warn(
+            "ssh-agent",
+            "ssh-agent is running but has no keys",
+            "run `gitup ssh add <key>`",
+        ),
+        Ok(_) => Check::warn(
+            "ssh-agent",
+            "ssh-agent could not be reached",
+            "restart it with `eval \"$(ssh-agent -s)\"`",
+        ),
+        Err(_) => Check::warn(
+            "ssh-agent",
+            "ssh-add is not installed",
+            "install the OpenSSH client",
+        ),
+    }
+}
+
+fn check_ssh_keys() -> Check {
+    if let Some(command) = GitConfig::global().get("core.sshCommand") {
+        if let Some(key) = ssh_command_key(&command) {
+            if !key.exists() {
+                return Check::fail(
+                    "ssh keys",
+                    format!("core.sshCommand uses {}, which doesn't exist", key.display()),
+                    "run `gitup ssh generate -p <profile>` or fix the profile's ssh_key",
+                );
+            }
+        }
+    }
+    match list_ssh_keys() {
+        Ok(keys) if keys.is_empty() => Check::warn(
+            "ssh keys",
+            "no SSH keys found in ~/.ssh",
+            "run `gitup ssh generate -p <profile>`",
+        ),
+        Ok(keys) => Check::pass("ssh keys", format!("{} key(s) in ~/.ssh", keys.len())),
+        Err(e) => Check::warn("ssh keys", format!("could not read ~/.ssh: {}", e), "check its permissions"),
+    }
+}
+
+fn check_signing() -> Check {
+    if GitConfig::global().get("commit.gpgsign").as_deref() != Some("true") {
+        return Check::pass("signing", "commit signing is not enabled");
+    }
+    let Some(key) = GitConfig::global().get("user.signingkey") else {
+        return Check::fail(
+            "signing",
+            "commit.gpgsign is on but user.signingkey is not set",
+            "set user.signingkey or turn off commit.gpgsign",
+        );
+    };
+
+    if GitConfig::global().get("gpg.format").as_deref() == Some("ssh") {
+        // SSH signing keys may be given inline as "key::ssh-ed25519 ..." instead of a path
+        if key.starts_with("key::") || expand_home(&key).exists() {
+            Check::pass("signing", format!("commits are signed with SSH key {}", key))
+        } else {
+            Check::fail(
+                "signing",
+                format!("SSH signing key {} doesn't exist", key),
+                "point user.signingkey at an existing public key",
+            )
+        }
+    } else {
+        let program = GitConfig::global().get("gpg.program").unwrap_or_else(|| "gpg".to_string());
+        if command_exists(&program) {
+            Check::pass("signing", format!("commits are signed with GPG key {}", key))
+        } else {
+            Check::fail(
+                "signing",
+                format!("commit signing needs '{}', which is not installed", program),
+                "install GnuPG or set gpg.program",
+            )
+        }
+    }
+}
+
+fn check_includes() -> Check {
+    let entries = match GitConfig::global().list() {
+        Ok(entries) => entries,
+        Err(e) => return Check::fail("includes", e.to_string(), "check the global config file"),
+    };
+    let broken: Vec<String> = entries
+        .iter()
+        .filter(|(key, _)| {
+            let key = key.to_lowercase();
+            (key.starts_with("include.") || key.starts_with("includeif.")) && key.ends_with(".path")
+        })
+        .filter(|(_, path)| !config_relative_path(path).exists())
+        .map(|(key, path)| format!("{} -> {}", key, path))
+        .collect();
+
+    if broken.is_empty() {
+        Check::pass("includes", "every included config file exists")
+    } else {
+        Check::fail(
+            "includes",
+            format!("missing include files: {}", broken.join(", ")),
+            "run `gitup profile bind` again or remove the entry with `git config --global --unset`",
+        )
+    }
+}
+
+fn check_config_paths() -> Check {
+    let missing: Vec<String> = ["core.excludesFile", "core.hooksPath", "commit.template"]
+        .iter()
+        .filter_map(|key| GitConfig::global().get(key).map(|path| (key, path)))
+        // Relative values resolve against each repository, so only absolute ones can be checked
+        .filter(|(_, path)| {
+            let path = expand_home(path);
+            path.is_absolute() && !path.exists()
+        })
+        .map(|(key, path)| format!("{} -> {}", key, path))
+        .collect();
+
+    if missing.is_empty() {
+        Check::pass("paths", "every configured file and directory exists")
+    } else {
+        Check::warn(
+            "paths",
+            format!("missing: {}", missing.join(", ")),
+            "reinstall with `gitup ignore`, `gitup hooks` or `gitup template`, or unset the key",
+        )
+    }
+}
+
+// Inside WSL, credentials should come from the Windows side's credential manager
+fn check_wsl() -> Check {
+    let helpers = GitConfig::global().get_all("credential.helper").unwrap_or_default();
+    if helpers.iter().any(|helper| helper.contains("git-credential-manager")) {
+        Check::pass("wsl", "credentials are shared with Windows through Git Credential Manager")
+    } else if windows_credential_manager().is_some() {
+        Check::warn(
+            "wsl",
+            "credentials aren't shared with Windows, so each side signs in separately",
+            "run `gitup wsl setup`",
+        )
+    } else {
+        Check::warn(
+            "wsl",
+            "Git Credential Manager isn't installed on the Windows side",
+            "install Git for Windows, then run `gitup wsl setup`",
+        )
+    }
+}
+
+// Include paths are relative to the config file that contains them
+fn config_relative_path(path: &str) -> PathBuf {
+    let path = expand_home(path);
+    if path.is_absolute() {
+        return path;
+    }
+    match global_config_path().parent() {
+        Some(dir) => dir.join(path),
+        None => path,
+    }
+}
+
+// Pull the key path out of a core.sshCommand written by gitup (ssh -i "<path>" ...)
+fn ssh_command_key(command: &str) -> Option<PathBuf> {
+    let rest = command.split_once("-i ")?.1.trim_start();
+    let path = match rest.strip_prefix('"') {
+        // As in the shell, a backslash inside the quotes only escapes ", \, $ and `
+        Some(quoted) => {
+            let mut path = String::new();
+            let mut chars = quoted.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' if chars.peek().is_some_and(|next| matches!(next, '"' | '\\' | '$' | '`')) => {
+                        path.extend(chars.next());
+                    }
+                    c => path.push(c),
+                }
+            }
+            path
+        }
+        None => rest.split_whitespace().next()?.to_string(),
+    };
+    Some(expand_home(&path))
+}
+
+fn looks_like_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => !local.is_empty() && domain.contains('.') && !domain.ends_with('.'),
+        None => false,
+    }
+}
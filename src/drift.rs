@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::GitConfig;
+use crate::config_cache::{clear_config_cache, file_stamp, normalize_key};
+use crate::dry_run::write_file;
+use crate::git::global_config_path;
+use crate::profile::profile_settings;
+use crate::runner::interrupted;
+use crate::snapshot::take_snapshot;
+use crate::{config_dir, GitError};
+
+const APPLIED_FILE: &str = "applied.toml";
+// How often a watch checks for Ctrl-C while waiting for the next poll
+const SLEEP_STEP: Duration = Duration::from_millis(100);
+
+// What was last applied to the global config, which is what it's expected to still hold
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Applied {
+    // The profile last used globally. Its settings are looked up when checking, so secrets it
+    // refers to are never written here
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+    // The config and aliases of the last manifest applied, which may have come from stdin
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    manifest: Vec<(String, String)>,
+}
+
+// A setting that no longer has the value last applied
+#[derive(Debug, Clone, Serialize)]
+pub struct DriftedSetting {
+    pub key: String,
+    pub expected: Vec<String>,
+    // Empty when the key has been removed
+    pub actual: Vec<String>,
+}
+
+// The outcome of comparing the global config with what was last applied
+#[derive(Debug, Clone, Serialize)]
+pub struct Drift {
+    pub profile: Option<String>,
+    // Whether a manifest's settings were checked too
+    pub manifest: bool,
+    pub checked: usize,
+    pub drifted: Vec<DriftedSetting>,
+}
+
+fn applied_path() -> PathBuf {
+    config_dir().join(APPLIED_FILE)
+}
+
+fn load_applied() -> Result<Applied, GitError> {
+    let path = applied_path();
+    if !path.exists() {
+        return Ok(Applied::default());
+    }
+    toml::from_str(&fs::read_to_string(&path)?).map_err(|e| GitError::Parse(format!("{}: {}", path.display(), e)))
+}
+
+fn save_applied(applied: &Applied) -> Result<(), GitError> {
+    let contents = toml::to_string(applied).map_err(|e| GitError::Parse(e.to_string()))?;
+    write_file(&applied_path(), &contents)
+}
+
+// Function to remember that a profile was used globally, for drift checks
+pub fn record_profile(profile: &str) -> Result<(), GitError> {
+    let mut applied = load_applied()?;
+    applied.profile = Some(profile.to_string());
+    save_applied(&applied)
+}
+
+// Function to follow a profile to its new name, if it's the one last used globally
+pub fn rename_recorded_profile(old_name: &str, new_name: &str) -> Result<(), GitError> {
+    let mut applied = load_applied()?;
+    if applied.profile.as_deref() != Some(old_name) {
+        return Ok(());
+    }
+    applied.profile = Some(new_name.to_string());
+    save_applied(&applied)
+}
+
+// Function to remember the settings a manifest applied to the global config, for drift checks
+pub fn record_manifest(settings: &[(String, String)]) -> Result<(), GitError> {
+    let mut applied = load_applied()?;
+    applied.manifest = settings.to_vec();
+    save_applied(&applied)
+}
+
+// Function to compare the global config with the last profile used globally and the last
+// manifest applied. A key both set is expected to have the profile's value. Only keys they
+// set are compared; anything else may change freely
+pub fn check_drift() -> Result<Drift, GitError> {
+    let applied = load_applied()?;
+    if applied.profile.is_none() && applied.manifest.is_empty() {
+        return Err(GitError::InvalidInput(
+            "nothing has been applied to compare against; use a profile with `gitup profile use` or apply a manifest with `gitup apply` first".to_string(),
+        ));
+    }
+    let mut expected = group(&applied.manifest);
+    if let Some(profile) = &applied.profile {
+        expected.extend(group(&profile_settings(profile)?));
+    }
+
+    // A watch checks again and again; each check has to see the file as it is now
+    clear_config_cache();
+    let actual = group(&GitConfig::global().list()?);
+    let drifted: Vec<DriftedSetting> = expected
+        .iter()
+        .filter_map(|(normalized, (key, values))| {
+            let current = actual.get(normalized).map_or(&[][..], |(_, values)| values.as_slice());
+            (current != values.as_slice()).then(|| DriftedSetting {
+                key: key.clone(),
+                expected: values.clone(),
+                actual: current.to_vec(),
+            })
+        })
+        .collect();
+    Ok(Drift {
+        profile: applied.profile,
+        manifest: !applied.manifest.is_empty(),
+        checked: expected.len(),
+        drifted,
+    })
+}
+
+// Function to put drifted settings back to the values last applied
+pub fn correct_drift(drift: &Drift) -> Result<(), GitError> {
+    let settings: Vec<(&str, &str)> = drift
+        .drifted
+        .iter()
+        .flat_map(|setting| setting.expected.iter().map(|value| (setting.key.as_str(), value.as_str())))
+        .collect();
+    GitConfig::global().set_many(&settings)
+}
+
+// Function to check for drift at the start and whenever the global config file changes,
+// polling it every `interval`, until Ctrl-C. Each check is passed to `report`, along with
+// whether it was corrected. With `fix`, drift is corrected as soon as it's found, after a
+// snapshot so `gitup undo` can bring the other tool's change back. A first check that fails
+// (nothing applied yet, a deleted profile) is returned instead, since the next ones would too
+pub fn watch_drift(
+    interval: Duration,
+    fix: bool,
+    mut report: impl FnMut(Result<(Drift, bool), GitError>),
+) -> Result<(), GitError> {
+    let mut last = None;
+    loop {
+        let stamp = file_stamp(&global_config_path());
+        if last != Some(stamp) {
+            let result = check_drift().and_then(|drift| {
+                let corrected = fix && !drift.drifted.is_empty();
+                if corrected {
+                    take_snapshot("watch")?;
+                    correct_drift(&drift)?;
+                }
+                Ok((drift, corrected))
+            });
+            match result {
+                Err(e) if last.is_none() => return Err(e),
+                result => report(result),
+            }
+            // A correction changes the file; that isn't a change to report
+            last = Some(file_stamp(&global_config_path()));
+        }
+        // Ctrl-C only sets a flag, so the interval is slept in steps to notice it
+        let mut slept = Duration::ZERO;
+        while slept < interval {
+            if interrupted() {
+                return Ok(());
+            }
+            let step = SLEEP_STEP.min(interval - slept);
+            thread::sleep(step);
+            slept += step;
+        }
+    }
+}
+
+// Values per normalized key, along with the key as first written
+fn group(settings: &[(String, String)]) -> BTreeMap<String, (String, Vec<String>)> {
+    let mut grouped: BTreeMap<String, (String, Vec<String>)> = BTreeMap::new();
+    for (key, value) in settings {
+        grouped
+            .entry(normalize_key(key))
+            .or_insert_with(|| (key.clone(), Vec::new()))
+            .1
+            .push(value.clone());
+    }
+    grouped
+}
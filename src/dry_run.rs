@@ -0,0 +1,219 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::GitError;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static ACTIONS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// Execution context for previewing changes: while enabled, every command and
+// file write that would modify the system is recorded instead of performed
+pub struct DryRun;
+
+impl DryRun {
+    pub fn enable() {
+        ENABLED.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_enabled() -> bool {
+        ENABLED.load(Ordering::SeqCst)
+    }
+
+    // Record an action that would have been performed
+    pub fn record(action: String) {
+        if let Ok(mut actions) = ACTIONS.lock() {
+            actions.push(action);
+        }
+    }
+
+    // Take every action recorded so far
+    pub fn take_actions() -> Vec<String> {
+        ACTIONS
+            .lock()
+            .map(|mut actions| std::mem::take(&mut *actions))
+            .unwrap_or_default()
+    }
+
+    // In dry-run mode, record a command instead of running it and return true
+    pub fn skip_command(cmd: &str, args: &[&str]) -> bool {
+        if !Self::is_enabled() {
+            return false;
+        }
+        Self::record_command(cmd, args);
+        true
+    }
+
+    // Record a command that would have been run
+    pub fn record_command(cmd: &str, args: &[&str]) {
+        let mut line = cmd.to_string();
+        for arg in args {
+            line.push(' ');
+            line.push_str(&shell_quote(arg));
+        }
+        Self::record(format!("run: {}", line));
+    }
+}
+
+// Function to write a file, or record the write in dry-run mode
+pub fn write_file(path: &Path, contents: &str) -> Result<(), GitError> {
+    if DryRun::is_enabled() {
+        DryRun::record(format!("write: {} ({} bytes)", path.display(), contents.len()));
+        return Ok(());
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+// Function to replace a file all at once, or record the write in dry-run mode. The contents go
+// to <file>.lock first, the lock file git itself uses, which is then renamed over the file, so
+// the file is never seen half-written and git can't write it at the same time. A symlinked
+// file is replaced where it points, keeping the link
+pub fn write_file_atomic(path: &Path, contents: &str) -> Result<(), GitError> {
+    if DryRun::is_enabled() {
+        DryRun::record(format!("write: {} ({} bytes)", path.display(), contents.len()));
+        return Ok(());
+    }
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut lock_name = target.file_name().unwrap_or_default().to_os_string();
+    lock_name.push(".lock");
+    let lock = target.with_file_name(lock_name);
+
+    let mut file = match OpenOptions::new().write(true).create_new(true).open(&lock) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(GitError::Io(io::Error::new(
+                e.kind(),
+                format!(
+                    "{} exists; another program is writing {}, or left the lock behind",
+                    lock.display(),
+                    target.display()
+                ),
+            )))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let result = file
+        .write_all(contents.as_bytes())
+        .and_then(|_| file.sync_all())
+        .and_then(|_| match fs::metadata(&target) {
+            Ok(metadata) => fs::set_permissions(&lock, metadata.permissions()),
+            Err(_) => Ok(()),
+        })
+        .and_then(|_| fs::rename(&lock, &target));
+    if result.is_err() {
+        let _ = fs::remove_file(&lock);
+    }
+    Ok(result?)
+}
+
+// Function to write a script and mark it executable, or record the write in dry-run mode
+pub fn write_executable(path: &Path, contents: &str) -> Result<(), GitError> {
+    write_file(path, contents)?;
+    #[cfg(unix)]
+    if !DryRun::is_enabled() {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+// Function to write a file only the owner can read, such as a private key, or record the write
+// in dry-run mode. The contents go to a new <file>.lock that is private from the moment it's
+// created and is then renamed over the file, so the contents are never readable by others, not
+// even between writing them and changing the mode
+pub fn write_private(path: &Path, contents: &str) -> Result<(), GitError> {
+    if DryRun::is_enabled() {
+        DryRun::record(format!("write: {} ({} bytes)", path.display(), contents.len()));
+        return Ok(());
+    }
+    let mut lock_name = path.file_name().unwrap_or_default().to_os_string();
+    lock_name.push(".lock");
+    let lock = path.with_file_name(lock_name);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&lock)?;
+    let result = file
+        .write_all(contents.as_bytes())
+        .and_then(|_| file.sync_all())
+        .and_then(|_| fs::rename(&lock, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&lock);
+    }
+    Ok(result?)
+}
+
+// Function to delete a file, or record the deletion in dry-run mode
+pub fn remove_file(path: &Path) -> Result<(), GitError> {
+    if DryRun::is_enabled() {
+        DryRun::record(format!("delete: {}", path.display()));
+        return Ok(());
+    }
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+// Function to move a file or directory, or record the move in dry-run mode
+pub fn rename(from: &Path, to: &Path) -> Result<(), GitError> {
+    if DryRun::is_enabled() {
+        DryRun::record(format!("move: {} -> {}", from.display(), to.display()));
+        return Ok(());
+    }
+    fs::rename(from, to)?;
+    Ok(())
+}
+
+// Function to create a directory and its parents, or record it in dry-run mode
+pub fn create_dir_all(path: &Path) -> Result<(), GitError> {
+    if path.is_dir() {
+        return Ok(());
+    }
+    if DryRun::is_enabled() {
+        DryRun::record(format!("mkdir: {}", path.display()));
+        return Ok(());
+    }
+    fs::create_dir_all(path)?;
+    Ok(())
+}
+
+// Quote an argument so the recorded command can be pasted into a shell
+fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@,+%".contains(c));
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::PrivateDir;
+
+    #[test]
+    fn private_files_are_only_ever_readable_by_their_owner() {
+        let dir = PrivateDir::new("test").unwrap();
+        let path = dir.path().join("id_ed25519");
+        write_private(&path, "first").unwrap();
+        write_private(&path, "second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert!(!dir.path().join("id_ed25519.lock").exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+}
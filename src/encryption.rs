@@ -0,0 +1,107 @@
+use std::env;
+use std::sync::{Mutex, OnceLock};
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::secrets::get_secret;
+use crate::GitError;
+
+// First line of an encrypted file; the rest is base64 of salt, nonce and ciphertext
+const HEADER: &str = "# gitup encrypted v1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+// Environment variable checked for the passphrase before asking the user
+pub const PASSPHRASE_ENV: &str = "GITUP_PASSPHRASE";
+// Keyring entry checked after the environment variable
+pub const PASSPHRASE_SECRET: &str = "profiles-passphrase";
+
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+static PROMPT: OnceLock<fn(&str) -> Option<String>> = OnceLock::new();
+
+// Function to register how the passphrase is asked for when it isn't in the environment;
+// the library itself never prompts
+pub fn set_passphrase_prompt(prompt: fn(&str) -> Option<String>) {
+    let _ = PROMPT.set(prompt);
+}
+
+// Function to set the passphrase used for the rest of the process
+pub fn set_passphrase(passphrase: &str) {
+    if let Ok(mut cached) = PASSPHRASE.lock() {
+        *cached = Some(passphrase.to_string());
+    }
+}
+
+// Function to get the passphrase from the cache, the environment, the keyring or the registered prompt
+pub fn passphrase() -> Result<String, GitError> {
+    if let Some(cached) = PASSPHRASE.lock().ok().and_then(|cached| cached.clone()) {
+        return Ok(cached);
+    }
+    let passphrase = env::var(PASSPHRASE_ENV)
+        .ok()
+        .or_else(|| get_secret(PASSPHRASE_SECRET).ok().flatten())
+        .or_else(|| PROMPT.get().and_then(|prompt| prompt("Profiles passphrase: ")))
+        .filter(|passphrase| !passphrase.is_empty())
+        .ok_or_else(|| {
+            GitError::Encryption(format!(
+                "a passphrase is required (set {} or the '{}' keyring entry)",
+                PASSPHRASE_ENV, PASSPHRASE_SECRET
+            ))
+        })?;
+    set_passphrase(&passphrase);
+    Ok(passphrase)
+}
+
+// Function to check whether file contents were written by `encrypt`
+pub fn is_encrypted(contents: &str) -> bool {
+    contents.lines().next() == Some(HEADER)
+}
+
+// Function to encrypt text with AES-256-GCM under a key derived from the passphrase with scrypt
+pub fn encrypt(plaintext: &str, passphrase: &str) -> Result<String, GitError> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let cipher = cipher(passphrase, &salt)?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .map_err(|_| GitError::Encryption("encryption failed".to_string()))?;
+
+    let mut payload = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+    payload.extend_from_slice(&salt);
+    payload.extend_from_slice(&nonce);
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!("{}\n{}\n", HEADER, STANDARD.encode(payload)))
+}
+
+// Function to decrypt text produced by `encrypt`
+pub fn decrypt(contents: &str, passphrase: &str) -> Result<String, GitError> {
+    let encoded: String = contents.lines().skip(1).collect();
+    let payload = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| GitError::Encryption(format!("corrupt encrypted data: {}", e)))?;
+    if payload.len() < SALT_LEN + NONCE_LEN {
+        return Err(GitError::Encryption("corrupt encrypted data: too short".to_string()));
+    }
+    let (salt, rest) = payload.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let plaintext = cipher(passphrase, salt)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| GitError::Encryption("wrong passphrase or tampered data".to_string()))?;
+    String::from_utf8(plaintext)
+        .map_err(|_| GitError::Encryption("decrypted data is not valid UTF-8".to_string()))
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<Aes256Gcm, GitError> {
+    let mut key = [0u8; 32];
+    scrypt::scrypt(passphrase.as_bytes(), salt, &scrypt::Params::recommended(), &mut key)
+        .map_err(|e| GitError::Encryption(e.to_string()))?;
+    Aes256Gcm::new_from_slice(&key).map_err(|e| GitError::Encryption(e.to_string()))
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::GitConfig;
+use crate::dry_run::write_file;
+use crate::git::{repository_root, Scope};
+use crate::managed_block::upsert_block;
+use crate::GitError;
+
+// Name of the block `write_attributes` keeps in a repository's .gitattributes
+const ATTRIBUTES_BLOCK: &str = "eol";
+
+// Extensions of files that are never text, so their bytes are never converted
+const BINARY_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "ico", "pdf", "zip", "gz", "jar", "exe", "dll", "so"];
+
+// How line endings are handled, the same way for everyone on a team. Whatever the policy,
+// files are stored with LF in the repository; it only decides what the working tree gets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EolPolicy {
+    // The platform's own line endings: CRLF on Windows, LF elsewhere
+    Auto,
+    Lf,
+    Crlf,
+}
+
+impl fmt::Display for EolPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EolPolicy::Auto => f.write_str("auto"),
+            EolPolicy::Lf => f.write_str("lf"),
+            EolPolicy::Crlf => f.write_str("crlf"),
+        }
+    }
+}
+
+impl FromStr for EolPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(EolPolicy::Auto),
+            "lf" => Ok(EolPolicy::Lf),
+            "crlf" => Ok(EolPolicy::Crlf),
+            other => Err(format!("unknown line ending policy '{}' (expected auto, lf or crlf)", other)),
+        }
+    }
+}
+
+impl EolPolicy {
+    // Function to get the config this policy comes down to on this machine. core.autocrlf
+    // does the converting in repositories without a .gitattributes; core.eol takes over in
+    // repositories that turn autocrlf off and mark their text files in .gitattributes
+    pub fn settings(self) -> Vec<(String, String)> {
+        let (autocrlf, eol) = match self {
+            EolPolicy::Auto if cfg!(windows) => ("true", "native"),
+            EolPolicy::Auto => ("input", "native"),
+            EolPolicy::Lf => ("input", "lf"),
+            EolPolicy::Crlf => ("true", "crlf"),
+        };
+        vec![
+            ("core.autocrlf".to_string(), autocrlf.to_string()),
+            ("core.eol".to_string(), eol.to_string()),
+        ]
+    }
+
+    // Function to get the .gitattributes lines recommended for this policy. They travel with
+    // the repository, so they hold for clones whatever their owners' config says
+    pub fn attributes(self) -> String {
+        let mut attributes = match self {
+            EolPolicy::Auto => "* text=auto\n".to_string(),
+            EolPolicy::Lf => "* text=auto eol=lf\n".to_string(),
+            EolPolicy::Crlf => "* text=auto eol=crlf\n".to_string(),
+        };
+        // Scripts only run with the line endings of their shell, whatever the policy
+        attributes.push_str("*.sh text eol=lf\n*.bat text eol=crlf\n*.cmd text eol=crlf\n");
+        for extension in BINARY_EXTENSIONS {
+            attributes.push_str(&format!("*.{} binary\n", extension));
+        }
+        attributes
+    }
+}
+
+// Function to apply a line ending policy to the global or a repository's config
+pub fn set_eol(policy: EolPolicy, scope: Scope) -> Result<(), GitError> {
+    GitConfig::new(scope).set_many(&policy.settings())
+}
+
+// Function to write the attributes for a policy to the .gitattributes at the root of the
+// repository containing `repo`, in a block of its own so the rest of the file is kept and
+// running it again replaces the block. Returns the file written
+pub fn write_attributes(repo: &Path, policy: EolPolicy) -> Result<PathBuf, GitError> {
+    let path = repository_root(repo)?.join(".gitattributes");
+    let existing = if path.exists() { fs::read_to_string(&path)? } else { String::new() };
+    write_file(&path, &upsert_block(&existing, ATTRIBUTES_BLOCK, &policy.attributes()))?;
+    Ok(path)
+}
@@ -0,0 +1,151 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::process::Output;
+
+// A failed command's output is cut to its last lines when shown; the error keeps all of it
+const SHOWN_OUTPUT_LINES: usize = 20;
+
+// Error returned by all gitup operations
+#[derive(Debug)]
+pub enum GitError {
+    GitNotInstalled,
+    // A program gitup needs, other than git, isn't installed or not on PATH
+    CommandNotFound(String),
+    ProfileNotFound(String),
+    ConfigKeyInvalid(String),
+    PackageManagerMissing {
+        tried: Vec<String>,
+    },
+    CommandFailed {
+        cmd: String,
+        stderr: String,
+        exit_code: Option<i32>,
+    },
+    // A command ran past its timeout (see `runner::set_timeouts`) and was stopped
+    TimedOut(String),
+    // Ctrl-C stopped a command
+    Interrupted(String),
+    InvalidInput(String),
+    Parse(String),
+    Encryption(String),
+    Keyring(String),
+    Conflict(String),
+    // An event script failed, or threw to stop the operation
+    Script(String),
+    Io(io::Error),
+}
+
+impl GitError {
+    // Process exit code for this class of failure
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            GitError::GitNotInstalled => 2,
+            GitError::ProfileNotFound(_) => 3,
+            GitError::Io(_) => 4,
+            GitError::CommandNotFound(_) | GitError::CommandFailed { .. } => 5,
+            GitError::Conflict(_) => 6,
+            GitError::TimedOut(_) => 7,
+            // The shell convention for death by SIGINT
+            GitError::Interrupted(_) => 130,
+            _ => 1,
+        }
+    }
+
+    // Build a CommandFailed error from a finished process. Some tools (choco, winget) report
+    // errors on stdout, which is kept instead when stderr is empty
+    pub fn command_failed(cmd: &str, args: &[&str], output: &Output) -> Self {
+        let mut cmd = cmd.to_string();
+        for arg in args {
+            cmd.push(' ');
+            cmd.push_str(arg);
+        }
+        let mut stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if stderr.is_empty() {
+            stderr = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        }
+        GitError::CommandFailed {
+            cmd,
+            stderr,
+            exit_code: output.status.code(),
+        }
+    }
+
+    // Build an error for a command that couldn't be started
+    pub fn spawn_failed(cmd: &str, err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound if cmd == "git" => GitError::GitNotInstalled,
+            io::ErrorKind::NotFound => GitError::CommandNotFound(cmd.to_string()),
+            io::ErrorKind::TimedOut => GitError::TimedOut(err.to_string()),
+            io::ErrorKind::Interrupted => GitError::Interrupted(err.to_string()),
+            _ => GitError::Io(err),
+        }
+    }
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GitError::GitNotInstalled => write!(f, "git is not installed"),
+            GitError::CommandNotFound(cmd) => write!(f, "`{}` was not found; is it installed and on PATH?", cmd),
+            GitError::ProfileNotFound(name) => write!(f, "profile '{}' not found", name),
+            GitError::ConfigKeyInvalid(key) => write!(f, "invalid config key '{}'", key),
+            GitError::PackageManagerMissing { tried } => {
+                write!(f, "no supported package manager found (tried {})", tried.join(", "))
+            }
+            GitError::CommandFailed {
+                cmd,
+                stderr,
+                exit_code,
+            } => {
+                match exit_code {
+                    Some(code) => write!(f, "`{}` failed with exit code {}", cmd, code)?,
+                    None => write!(f, "`{}` was terminated by a signal", cmd)?,
+                }
+                let lines: Vec<&str> = stderr.lines().collect();
+                match lines.as_slice() {
+                    [] => {}
+                    [line] => write!(f, ": {}", line)?,
+                    lines => {
+                        write!(f, ":")?;
+                        let omitted = lines.len().saturating_sub(SHOWN_OUTPUT_LINES);
+                        if omitted > 0 {
+                            write!(f, "\n  ({} earlier lines omitted)", omitted)?;
+                        }
+                        for line in &lines[omitted..] {
+                            match line.trim_end() {
+                                "" => writeln!(f)?,
+                                line => write!(f, "\n  {}", line)?,
+                            }
+                        }
+                    }
+                }
+                Ok(())
+            }
+            GitError::TimedOut(msg) => write!(f, "{}", msg),
+            GitError::Interrupted(msg) => write!(f, "{}", msg),
+            GitError::InvalidInput(msg) => write!(f, "{}", msg),
+            GitError::Parse(msg) => write!(f, "parse error: {}", msg),
+            GitError::Encryption(msg) => write!(f, "encryption error: {}", msg),
+            GitError::Keyring(msg) => write!(f, "keyring error: {}", msg),
+            GitError::Script(msg) => write!(f, "script {}", msg),
+            GitError::Conflict(msg) => write!(f, "conflict: {}", msg),
+            GitError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for GitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GitError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for GitError {
+    fn from(err: io::Error) -> Self {
+        GitError::Io(err)
+    }
+}
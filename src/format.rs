@@ -0,0 +1,221 @@
+use serde_json::{Map, Value};
+
+use crate::GitError;
+
+// Turns the structured result of a command into text. Every command builds one serde_json
+// Value, so each output format is implemented once here rather than per command
+pub trait Formatter {
+    fn format(&self, value: &Value) -> Result<String, GitError>;
+}
+
+// Pretty-printed JSON
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonFormatter;
+
+impl Formatter for JsonFormatter {
+    fn format(&self, value: &Value) -> Result<String, GitError> {
+        serde_json::to_string_pretty(value).map_err(|e| GitError::Parse(e.to_string()))
+    }
+}
+
+// Block-style YAML
+#[derive(Debug, Clone, Copy, Default)]
+pub struct YamlFormatter;
+
+impl Formatter for YamlFormatter {
+    fn format(&self, value: &Value) -> Result<String, GitError> {
+        Ok(yaml_lines(value).join("\n"))
+    }
+}
+
+// TOML. It has no null, so null values are left out, and a document must be a table, so
+// anything else is put under a `value` key
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TomlFormatter;
+
+impl Formatter for TomlFormatter {
+    fn format(&self, value: &Value) -> Result<String, GitError> {
+        let value = match without_nulls(value) {
+            Value::Object(map) => Value::Object(map),
+            other => Value::Object(Map::from_iter([("value".to_string(), other)])),
+        };
+        let text = toml::to_string(&value).map_err(|e| GitError::Parse(e.to_string()))?;
+        Ok(text.trim_end().to_string())
+    }
+}
+
+// Aligned columns for lists of records, and key/value rows for a single record; nested lists
+// get a table of their own under their key
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TableFormatter;
+
+impl Formatter for TableFormatter {
+    fn format(&self, value: &Value) -> Result<String, GitError> {
+        let mut sections = Vec::new();
+        table_sections(None, value, &mut sections);
+        Ok(sections.join("\n\n"))
+    }
+}
+
+fn yaml_lines(value: &Value) -> Vec<String> {
+    match value {
+        Value::Object(map) if !map.is_empty() => map
+            .iter()
+            .flat_map(|(key, value)| {
+                let key = yaml_scalar(&Value::String(key.clone()));
+                if is_block(value) {
+                    let nested = yaml_lines(value).into_iter().map(|line| format!("  {}", line));
+                    std::iter::once(format!("{}:", key)).chain(nested).collect()
+                } else {
+                    vec![format!("{}: {}", key, yaml_scalar(value))]
+                }
+            })
+            .collect(),
+        Value::Array(items) if !items.is_empty() => items
+            .iter()
+            .flat_map(|item| {
+                if is_block(item) {
+                    // The first line of a nested block goes on the dash line
+                    yaml_lines(item)
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, line)| format!("{}{}", if i == 0 { "- " } else { "  " }, line))
+                        .collect()
+                } else {
+                    vec![format!("- {}", yaml_scalar(item))]
+                }
+            })
+            .collect(),
+        _ => vec![yaml_scalar(value)],
+    }
+}
+
+fn is_block(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+// Strings are written plain when YAML would read them back as the same string, and as JSON
+// strings (which are valid YAML) otherwise
+fn yaml_scalar(value: &Value) -> String {
+    let Value::String(s) = value else {
+        return match value {
+            Value::Object(_) => "{}".to_string(),
+            Value::Array(_) => "[]".to_string(),
+            other => other.to_string(),
+        };
+    };
+    let reserved = ["true", "false", "yes", "no", "on", "off", "null", "~"];
+    let plain = !s.is_empty()
+        && s.trim() == s
+        && !s.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@`".contains(c) || c.is_ascii_digit() || c == '.')
+        && !s.chars().any(char::is_control)
+        && !s.contains(": ")
+        && !s.contains(" #")
+        && !s.ends_with(':')
+        && !reserved.contains(&s.to_lowercase().as_str());
+    if plain {
+        s.clone()
+    } else {
+        value.to_string()
+    }
+}
+
+fn without_nulls(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key.clone(), without_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().filter(|item| !item.is_null()).map(without_nulls).collect()),
+        other => other.clone(),
+    }
+}
+
+fn table_sections(title: Option<&str>, value: &Value, sections: &mut Vec<String>) {
+    let heading = |text: String| match title {
+        Some(title) => format!("{}:\n{}", title, text),
+        None => text,
+    };
+    match value {
+        Value::Array(items) if items.iter().all(Value::is_object) && !items.is_empty() => {
+            // Columns in the order keys first appear
+            let mut columns: Vec<&String> = Vec::new();
+            for item in items.iter().filter_map(Value::as_object) {
+                for key in item.keys() {
+                    if !columns.contains(&key) {
+                        columns.push(key);
+                    }
+                }
+            }
+            let header = columns.iter().map(|column| column.to_uppercase()).collect();
+            let rows = items
+                .iter()
+                .map(|item| columns.iter().map(|column| table_cell(&item[column.as_str()])).collect())
+                .collect();
+            sections.push(heading(render_table(Some(header), rows)));
+        }
+        Value::Object(map) => {
+            // Simple fields first as key/value rows, then a section for each nested list or record
+            let (simple, nested): (Vec<_>, Vec<_>) = map.iter().partition(|(_, value)| !is_nested(value));
+            if !simple.is_empty() {
+                let rows = simple.iter().map(|(key, value)| vec![format!("{}:", key), table_cell(value)]).collect();
+                sections.push(heading(render_table(None, rows)));
+            }
+            for (key, value) in nested {
+                let key = match title {
+                    Some(title) => format!("{}.{}", title, key),
+                    None => key.clone(),
+                };
+                table_sections(Some(&key), value, sections);
+            }
+        }
+        Value::Array(items) => {
+            let rows = items.iter().map(|item| vec![table_cell(item)]).collect();
+            sections.push(heading(render_table(None, rows)));
+        }
+        other => sections.push(heading(table_cell(other))),
+    }
+}
+
+// Records and lists of records don't fit in a cell
+fn is_nested(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(items) => items.iter().any(|item| item.is_object() || item.is_array()),
+        _ => false,
+    }
+}
+
+fn table_cell(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.replace('\n', " "),
+        Value::Array(items) => items.iter().map(table_cell).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
+
+fn render_table(header: Option<Vec<String>>, rows: Vec<Vec<String>>) -> String {
+    let rows: Vec<Vec<String>> = header.into_iter().chain(rows).collect();
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| rows.iter().filter_map(|row| row.get(i)).map(|cell| cell.chars().count()).max().unwrap_or(0))
+        .collect();
+    rows.iter()
+        .map(|row| {
+            let line: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            line.join("  ").trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,407 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use semver::Version;
+use serde::Serialize;
+
+use crate::config::file::{common_dir, find_git_dir, scope_path, ConfigFile};
+use crate::config::GitConfig;
+use crate::config_cache::{clear_config_cache, effective_entries, normalize_key};
+use crate::dry_run::DryRun;
+use crate::install::command_exists;
+use crate::runner::{command_runner, command_runner_replaced, progress_reporter};
+use crate::{home_dir, GitError};
+
+// Which configuration file a setting is written to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    // /etc/gitconfig, shared by every user; writing it needs root
+    System,
+    Global,
+    // .git/config of the repository containing this directory
+    Local(PathBuf),
+    // .git/config.worktree of the working tree containing this directory; with several
+    // worktrees git only writes it once extensions.worktreeConfig is enabled
+    Worktree(PathBuf),
+}
+
+impl Scope {
+    // The repository scope for the current directory
+    pub fn local() -> Self {
+        Scope::Local(PathBuf::from("."))
+    }
+
+    // The git config flag selecting this scope
+    pub fn flag(&self) -> &'static str {
+        match self {
+            Scope::System => "--system",
+            Scope::Global => "--global",
+            Scope::Local(_) => "--local",
+            Scope::Worktree(_) => "--worktree",
+        }
+    }
+
+    // The name git uses for this scope, as in `git config --show-scope`
+    pub fn name(&self) -> &'static str {
+        self.flag().trim_start_matches('-')
+    }
+
+    // The directory git has to run in for this scope, if it depends on one
+    pub fn repo(&self) -> Option<&Path> {
+        match self {
+            Scope::Local(dir) | Scope::Worktree(dir) => Some(dir),
+            Scope::System | Scope::Global => None,
+        }
+    }
+}
+
+// Function to locate the file `git config --global` reads and writes
+pub fn global_config_path() -> PathBuf {
+    if let Some(path) = env::var_os("GIT_CONFIG_GLOBAL") {
+        return PathBuf::from(path);
+    }
+    let dotfile = home_dir().join(".gitconfig");
+    if dotfile.exists() {
+        return dotfile;
+    }
+    let xdg = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home_dir().join(".config"))
+        .join("git")
+        .join("config");
+    if xdg.exists() {
+        xdg
+    } else {
+        dotfile
+    }
+}
+
+// Function to find the top of the working tree containing `dir`
+pub fn repository_root(dir: &Path) -> Result<PathBuf, GitError> {
+    #[cfg(feature = "libgit2")]
+    if !command_runner_replaced() {
+        if let Ok((Some(work_dir), _)) = crate::libgit2::discover(dir) {
+            return Ok(work_dir);
+        }
+    }
+    Ok(PathBuf::from(rev_parse(dir, "--show-toplevel")?))
+}
+
+// Function to find the canonical .git directory of the repository containing `dir`, following
+// the .git file of a linked worktree or submodule
+pub fn repository_git_dir(dir: &Path) -> Result<PathBuf, GitError> {
+    #[cfg(feature = "libgit2")]
+    if !command_runner_replaced() {
+        if let Ok((_, git_dir)) = crate::libgit2::discover(dir) {
+            return Ok(git_dir);
+        }
+    }
+    Ok(std::fs::canonicalize(rev_parse(dir, "--absolute-git-dir")?)?)
+}
+
+// Function to check whether `dir` is in a linked worktree, one added with `git worktree add`,
+// rather than in the repository's main working tree
+pub fn is_linked_worktree(dir: &Path) -> Result<bool, GitError> {
+    // Only a linked worktree's .git directory points back to the shared one
+    Ok(repository_git_dir(dir)?.join("commondir").is_file())
+}
+
+// Function to turn on extensions.worktreeConfig in the repository containing `dir`, so each of
+// its worktrees can have settings of its own in config.worktree. As git asks, core.worktree and
+// a true core.bare first move from the shared config to the main worktree's config.worktree,
+// where only the main worktree reads them. Returns false when it was already on
+pub fn enable_worktree_config(dir: &Path) -> Result<bool, GitError> {
+    let shared = GitConfig::repo(dir);
+    if shared.get("extensions.worktreeConfig").is_some_and(|value| is_true(&value)) {
+        return Ok(false);
+    }
+    let main = common_dir(&repository_git_dir(dir)?).join("config.worktree");
+    for key in ["core.worktree", "core.bare"] {
+        if let Some(value) = shared.get(key).filter(|value| key == "core.worktree" || is_true(value)) {
+            run_mutating("git", &["config", "--file", &main.to_string_lossy(), key, &value])?;
+            shared.unset(key)?;
+        }
+    }
+    shared.set("extensions.worktreeConfig", "true")?;
+    Ok(true)
+}
+
+fn is_true(value: &str) -> bool {
+    ["true", "yes", "on", "1"].contains(&value.to_lowercase().as_str())
+}
+
+// Function to find the checked-out submodules of the repository containing `dir`, nested ones
+// included, by the top of their working trees. Submodules that were never initialised have no
+// config of their own yet and are left out
+pub fn submodule_roots(dir: &Path) -> Result<Vec<PathBuf>, GitError> {
+    let root = repository_root(dir)?;
+    let root = root.to_string_lossy();
+    let args = ["-C", &root, "submodule", "--quiet", "foreach", "--recursive", "echo \"$toplevel/$sm_path\""];
+    let output = run_checked("git", &args)?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+fn rev_parse(dir: &Path, flag: &str) -> Result<String, GitError> {
+    let output = run_git(&["-C", &dir.to_string_lossy(), "rev-parse", flag])?;
+    if !output.status.success() {
+        return Err(GitError::InvalidInput(format!("{} is not inside a git repository", dir.display())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Oldest git gitup is fully at home with: SSH commit signing needs 2.34, and
+// init.defaultBranch (2.28) and `git config --fixed-value` (2.30) come before that
+pub const MIN_GIT_VERSION: Version = Version::new(2, 34, 0);
+
+// Function to check if Git is installed
+pub fn is_git_installed() -> bool {
+    run_command("git", &["--version"]).is_ok()
+}
+
+// Function to get the installed git version. Distribution suffixes such as
+// "2.39.3 (Apple Git-146)" or "2.45.1.windows.1" are dropped
+pub fn get_git_version() -> Result<Version, GitError> {
+    let output = run_command("git", &["--version"]).map_err(|_| GitError::GitNotInstalled)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .trim()
+        .strip_prefix("git version ")
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(parse_version)
+        .ok_or_else(|| GitError::Parse(format!("unexpected `git --version` output: {}", stdout.trim())))
+}
+
+// Function to parse a git-style version such as "2.39", "2.39.5" or "2.45.1.windows.1";
+// missing minor and patch numbers count as 0
+pub fn parse_version(version: &str) -> Option<Version> {
+    let mut numbers = version.split('.').map(|part| {
+        let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+        digits.parse::<u64>().ok()
+    });
+    let major = numbers.next()??;
+    let minor = numbers.next().flatten().unwrap_or(0);
+    let patch = numbers.next().flatten().unwrap_or(0);
+    Some(Version::new(major, minor, patch))
+}
+
+// Function to get a value from the global configuration
+#[deprecated(note = "use `GitConfig::global().get(key)`")]
+pub fn get_git_config(key: &str) -> Option<String> {
+    GitConfig::global().get(key)
+}
+
+// Function to list every key/value pair in a scope, in file order
+#[deprecated(note = "use `GitConfig::new(scope).list()`")]
+pub fn list_git_config(scope: Scope) -> Result<Vec<(String, String)>, GitError> {
+    GitConfig::new(scope).list()
+}
+
+// Function to get the value Git would actually use in the current directory
+pub fn get_active_config(key: &str) -> Option<String> {
+    get_effective_config(key, Path::new("."))
+        .map(|setting| setting.value)
+        .filter(|value| !value.is_empty())
+}
+
+// A config value together with where git read it from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigValue {
+    pub value: String,
+    // "system", "global", "local", "worktree" or "command"
+    pub scope: String,
+    // File the value was read from; for includeIf entries this is the included file
+    pub origin: String,
+}
+
+// Function to get the value git uses for a key in a repository, resolving precedence the way
+// git does (system, global, local, worktree, then -c and GIT_CONFIG_* overrides, with
+// conditional includes in place and the last value of a multi-valued key winning), along with
+// where it came from
+pub fn get_effective_config(key: &str, repo: &Path) -> Option<ConfigValue> {
+    // A replaced runner answers the `git config --get` call itself
+    if command_runner_replaced() {
+        return read_effective_config(key, repo);
+    }
+    let key = normalize_key(key);
+    let entry = list_config_entries(repo).ok()?.into_iter().rev().find(|entry| entry.key == key)?;
+    Some(ConfigValue {
+        value: entry.value,
+        scope: entry.scope,
+        origin: entry.file,
+    })
+}
+
+fn read_effective_config(key: &str, repo: &Path) -> Option<ConfigValue> {
+    let dir = repo.to_string_lossy();
+    let output = run_command("git", &["-C", &dir, "config", "--show-origin", "--show-scope", "--get", key]).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut fields = stdout.trim_end_matches('\n').splitn(3, '\t');
+    let (scope, origin, value) = (fields.next()?, fields.next()?, fields.next()?);
+    Some(ConfigValue {
+        value: value.to_string(),
+        scope: scope.to_string(),
+        origin: origin.strip_prefix("file:").unwrap_or(origin).to_string(),
+    })
+}
+
+// One entry of the configuration git sees in a repository
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: String,
+    // "system", "global", "local", "worktree" or "command"
+    pub scope: String,
+    // File the entry was read from; for includeIf entries this is the included file
+    pub file: String,
+}
+
+// Function to list every configuration entry git sees in a repository, across all scopes and
+// in the order git reads them, so later entries override earlier ones with the same key. The
+// listing is cached until gitup next changes a config file
+pub fn list_config_entries(repo: &Path) -> Result<Vec<ConfigEntry>, GitError> {
+    if command_runner_replaced() {
+        return read_config_entries(repo);
+    }
+    if !command_exists("git") {
+        return effective_entries(repo, || read_config_files(repo));
+    }
+    effective_entries(repo, || read_config_entries(repo))
+}
+
+// Before git is installed the files are read directly, without following includes
+fn read_config_files(repo: &Path) -> Result<Vec<ConfigEntry>, GitError> {
+    let mut scopes = vec![Scope::System, Scope::Global];
+    if find_git_dir(repo).is_some() {
+        scopes.push(Scope::Local(repo.to_path_buf()));
+        scopes.push(Scope::Worktree(repo.to_path_buf()));
+    }
+    let mut entries = Vec::new();
+    let mut read = Vec::new();
+    for scope in scopes {
+        // Without extensions.worktreeConfig the worktree scope is the local config again
+        let path = scope_path(&scope)?;
+        if read.contains(&path) {
+            continue;
+        }
+        read.push(path.clone());
+        entries.extend(ConfigFile::load(&path)?.entries().into_iter().map(|(key, value)| ConfigEntry {
+            key,
+            value,
+            scope: scope.name().to_string(),
+            file: path.to_string_lossy().into_owned(),
+        }));
+    }
+    Ok(entries)
+}
+
+fn read_config_entries(repo: &Path) -> Result<Vec<ConfigEntry>, GitError> {
+    let dir = repo.to_string_lossy();
+    let output = run_checked("git", &["-C", &dir, "config", "--list", "--show-origin", "--show-scope", "-z"])?;
+
+    // With -z each entry is "scope\0origin\0key\nvalue\0"; a key without a value is an implicit true
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut fields = stdout.split('\0');
+    let mut entries = Vec::new();
+    while let (Some(scope), Some(origin), Some(entry)) = (fields.next(), fields.next(), fields.next()) {
+        let (key, value) = entry.split_once('\n').unwrap_or((entry, "true"));
+        entries.push(ConfigEntry {
+            key: key.to_string(),
+            value: value.to_string(),
+            scope: scope.to_string(),
+            file: origin.strip_prefix("file:").unwrap_or(origin).to_string(),
+        });
+    }
+    Ok(entries)
+}
+
+// Function to get the value git uses for a key in a directory, along with where it came from
+#[deprecated(note = "use `get_effective_config(key, dir)`")]
+pub fn get_config_origin(dir: &Path, key: &str) -> Option<ConfigValue> {
+    get_effective_config(key, dir)
+}
+
+// Function to configure Git
+#[deprecated(note = "use `GitConfig::new(scope).set(key, value)`")]
+pub fn configure_git(key: &str, value: &str, scope: Scope) -> Result<(), GitError> {
+    GitConfig::new(scope).set(key, value)
+}
+
+// Function to set several keys in one scope with a single edit of its file; see `GitConfig::set_many`
+pub fn set_git_configs<K: AsRef<str>, V: AsRef<str>>(entries: &[(K, V)], scope: Scope) -> Result<(), GitError> {
+    GitConfig::new(scope).set_many(entries)
+}
+
+// Function to remove a key from the Git configuration; a key that isn't set is not an error
+#[deprecated(note = "use `GitConfig::new(scope).unset(key)`")]
+pub fn unset_git_config(key: &str, scope: Scope) -> Result<(), GitError> {
+    GitConfig::new(scope).unset(key)
+}
+
+// Run a command and turn a non-zero exit status into an error
+pub fn run_checked(cmd: &str, args: &[&str]) -> Result<Output, GitError> {
+    let output = run_command(cmd, args).map_err(|e| GitError::spawn_failed(cmd, e))?;
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(GitError::command_failed(cmd, args, &output))
+    }
+}
+
+// Run a command that changes the system; in dry-run mode it is only recorded
+pub fn run_mutating(cmd: &str, args: &[&str]) -> Result<(), GitError> {
+    if !DryRun::skip_command(cmd, args) {
+        forget_config(cmd);
+        run_checked(cmd, args)?;
+    }
+    Ok(())
+}
+
+// Run a long command that changes the system, such as a package install, passing its output to
+// the registered progress reporter as it runs; in dry-run mode it is only recorded
+pub fn run_with_progress(cmd: &str, args: &[&str]) -> Result<(), GitError> {
+    if DryRun::skip_command(cmd, args) {
+        return Ok(());
+    }
+    forget_config(cmd);
+    let runner = command_runner();
+    let output = match progress_reporter() {
+        Some(progress) => runner.run_with_progress(cmd, args, progress),
+        None => runner.run(cmd, args),
+    }
+    .map_err(|e| GitError::spawn_failed(cmd, e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(GitError::command_failed(cmd, args, &output))
+    }
+}
+
+// Any git command that changes things may change config files too (clone, includes, ...)
+fn forget_config(cmd: &str) {
+    if cmd == "git" {
+        clear_config_cache();
+    }
+}
+
+// Run git without checking its exit status, reporting a missing binary as GitNotInstalled
+pub fn run_git(args: &[&str]) -> Result<Output, GitError> {
+    run_command("git", args).map_err(|e| GitError::spawn_failed("git", e))
+}
+
+// Run a command with data written to its stdin, keeping secrets off the command line
+pub fn run_with_input(cmd: &str, args: &[&str], input: &str) -> Result<Output, std::io::Error> {
+    command_runner().run_with_input(cmd, args, input)
+}
+
+// General function to run a command and return the output or error
+pub fn run_command(cmd: &str, args: &[&str]) -> Result<Output, std::io::Error> {
+    command_runner().run(cmd, args)
+}
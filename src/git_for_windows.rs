@@ -0,0 +1,130 @@
+use std::env;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::dry_run::{create_dir_all, remove_file, rename, DryRun};
+use crate::git::{run_checked, run_mutating};
+use crate::GitError;
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/git-for-windows/git/releases/latest";
+
+// Inno Setup switches for an unattended install that never reboots the machine
+const SILENT_ARGS: &[&str] = &["/VERYSILENT", "/NORESTART", "/NOCANCEL", "/SP-", "/SUPPRESSMSGBOXES"];
+
+// An installer from the latest Git for Windows release
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitInstaller {
+    // File name, e.g. Git-2.47.0-64-bit.exe
+    pub name: String,
+    pub url: String,
+    // Lowercase hex SHA-256 published with the release
+    pub sha256: String,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    #[serde(default)]
+    body: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+    // "sha256:<hex>", filled in by GitHub for newer uploads
+    digest: Option<String>,
+}
+
+// Function to look up the installer for this machine in the latest Git for Windows release
+pub fn latest_installer() -> Result<GitInstaller, GitError> {
+    let suffix = match env::consts::ARCH {
+        "x86_64" => "-64-bit.exe",
+        "aarch64" => "-arm64.exe",
+        arch => {
+            return Err(GitError::InvalidInput(format!(
+                "Git for Windows has no installer for {} machines",
+                arch
+            )))
+        }
+    };
+    let output = run_checked("curl", &["--silent", "--show-error", "--fail", "--location", LATEST_RELEASE_URL])?;
+    let release: Release = serde_json::from_slice(&output.stdout)
+        .map_err(|e| GitError::Parse(format!("{}: {}", LATEST_RELEASE_URL, e)))?;
+
+    let asset = release
+        .assets
+        .into_iter()
+        .find(|asset| asset.name.starts_with("Git-") && asset.name.ends_with(suffix))
+        .ok_or_else(|| GitError::Parse(format!("the latest Git for Windows release has no Git-*{} installer", suffix)))?;
+    // Older releases only list their checksums in a "file | SHA-256" table in the release notes
+    let sha256 = asset
+        .digest
+        .as_deref()
+        .and_then(|digest| digest.strip_prefix("sha256:"))
+        .map(String::from)
+        .or_else(|| published_checksum(&release.body, &asset.name))
+        .ok_or_else(|| GitError::Parse(format!("no SHA-256 checksum is published for {}", asset.name)))?;
+
+    Ok(GitInstaller {
+        name: asset.name,
+        url: asset.browser_download_url,
+        sha256: sha256.to_lowercase(),
+    })
+}
+
+// Function to download the latest Git for Windows installer into `installer_dir` (a temporary
+// directory by default), verify its checksum and run it silently. An installer already in the
+// directory is reused if its checksum matches. Returns the installer's path
+pub fn install_git_for_windows(installer_dir: Option<&Path>) -> Result<PathBuf, GitError> {
+    let installer = latest_installer()?;
+    let dir = installer_dir.map_or_else(|| env::temp_dir().join("gitup"), Path::to_path_buf);
+    create_dir_all(&dir)?;
+    let path = dir.join(&installer.name);
+
+    let reusable = path.is_file() && sha256_file(&path)? == installer.sha256;
+    if !reusable {
+        let partial = dir.join(format!("{}.part", installer.name));
+        let partial_arg = partial.to_string_lossy();
+        run_mutating(
+            "curl",
+            &["--silent", "--show-error", "--fail", "--location", "--output", &partial_arg, &installer.url],
+        )?;
+        // Nothing was downloaded in a dry run, so there is nothing to verify
+        if !DryRun::is_enabled() {
+            let actual = sha256_file(&partial)?;
+            if actual != installer.sha256 {
+                remove_file(&partial)?;
+                return Err(GitError::InvalidInput(format!(
+                    "checksum mismatch for {}: expected {}, got {}; the download was deleted",
+                    installer.name, installer.sha256, actual
+                )));
+            }
+        }
+        rename(&partial, &path)?;
+    }
+
+    run_mutating(&path.to_string_lossy(), SILENT_ARGS)?;
+    Ok(path)
+}
+
+// Function to compute the lowercase hex SHA-256 of a file
+pub fn sha256_file(path: &Path) -> Result<String, GitError> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+// Find "<name> | <sha256>" in the release notes
+fn published_checksum(body: &str, name: &str) -> Option<String> {
+    body.lines().find_map(|line| {
+        let (file, checksum) = line.split_once('|')?;
+        let checksum = checksum.trim();
+        (file.trim() == name && checksum.len() == 64 && checksum.chars().all(|c| c.is_ascii_hexdigit()))
+            .then(|| checksum.to_string())
+    })
+}
@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::GitConfig;
+use crate::dry_run::{create_dir_all, write_file};
+use crate::managed_block::{has_block, remove_block, upsert_block};
+use crate::{expand_home, home_dir, GitError};
+
+// Built-in ignore templates, grouped by OS, editor and language
+const TEMPLATES: &[(&str, &str)] = &[
+    ("macos", ".DS_Store\n.AppleDouble\n.LSOverride\n._*\n.Spotlight-V100\n.Trashes\n"),
+    ("windows", "Thumbs.db\nehthumbs.db\nDesktop.ini\n$RECYCLE.BIN/\n*.lnk\n"),
+    ("linux", "*~\n.fuse_hidden*\n.directory\n.Trash-*\n.nfs*\n"),
+    ("vscode", ".vscode/*\n!.vscode/settings.json\n!.vscode/tasks.json\n!.vscode/launch.json\n!.vscode/extensions.json\n*.code-workspace\n"),
+    ("jetbrains", ".idea/\n*.iml\nout/\n"),
+    ("vim", "[._]*.s[a-v][a-z]\n[._]*.sw[a-p]\n[._]s[a-rt-v][a-z]\n[._]ss[a-gi-z]\n[._]sw[a-p]\nSession.vim\ntags\n"),
+    ("emacs", "*~\n\\#*\\#\n.\\#*\n*.elc\nauto-save-list\n"),
+    ("rust", "target/\n**/*.rs.bk\n"),
+    ("node", "node_modules/\nnpm-debug.log*\nyarn-debug.log*\nyarn-error.log*\n.npm\n"),
+    ("python", "__pycache__/\n*.py[cod]\n.venv/\nvenv/\n.pytest_cache/\n*.egg-info/\n"),
+    ("go", "*.exe\n*.test\n*.out\nvendor/\n"),
+    ("java", "*.class\n*.jar\n*.war\nhs_err_pid*\n"),
+];
+
+// Function to get the names of the built-in templates
+pub fn available_templates() -> Vec<&'static str> {
+    TEMPLATES.iter().map(|(name, _)| *name).collect()
+}
+
+// Function to get the global excludes file, honouring an existing core.excludesFile
+pub fn excludes_file() -> PathBuf {
+    match GitConfig::global().get("core.excludesFile") {
+        Some(path) => expand_home(&path),
+        None => home_dir().join(".gitignore_global"),
+    }
+}
+
+// Function to list the templates currently installed in the global excludes file
+pub fn installed_templates() -> Result<Vec<&'static str>, GitError> {
+    let contents = read_excludes()?;
+    Ok(available_templates()
+        .into_iter()
+        .filter(|name| has_block(&contents, name))
+        .collect())
+}
+
+// Function to add templates to the global excludes file and point core.excludesFile at it
+pub fn add_templates(names: &[String]) -> Result<PathBuf, GitError> {
+    let mut contents = read_excludes()?;
+    for name in names {
+        let template = template_contents(name)?;
+        contents = upsert_block(&contents, name, template);
+    }
+    save_excludes(&contents)
+}
+
+// Function to remove templates from the global excludes file
+pub fn remove_templates(names: &[String]) -> Result<PathBuf, GitError> {
+    let mut contents = read_excludes()?;
+    for name in names {
+        template_contents(name)?;
+        if let Some(updated) = remove_block(&contents, name) {
+            contents = updated;
+        }
+    }
+    save_excludes(&contents)
+}
+
+// Function to get the patterns of a built-in template
+pub fn template_contents(name: &str) -> Result<&'static str, GitError> {
+    TEMPLATES
+        .iter()
+        .find(|(template, _)| *template == name)
+        .map(|(_, body)| *body)
+        .ok_or_else(|| {
+            GitError::InvalidInput(format!(
+                "unknown ignore template '{}' (available: {})",
+                name,
+                available_templates().join(", ")
+            ))
+        })
+}
+
+fn read_excludes() -> Result<String, GitError> {
+    let path = excludes_file();
+    if path.exists() {
+        Ok(fs::read_to_string(path)?)
+    } else {
+        Ok(String::new())
+    }
+}
+
+fn save_excludes(contents: &str) -> Result<PathBuf, GitError> {
+    let path = excludes_file();
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    write_file(&path, contents)?;
+    if GitConfig::global().get("core.excludesFile").is_none() {
+        GitConfig::global().set("core.excludesFile", &path.to_string_lossy())?;
+    }
+    Ok(path)
+}
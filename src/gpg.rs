@@ -0,0 +1,161 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::dry_run::{create_dir_all, write_file, DryRun};
+use crate::git::{run_checked, run_command};
+use crate::lock::lock_config;
+use crate::managed_block::upsert_block;
+use crate::profile::{get_profile, load_profiles, save_profiles};
+use crate::secrets::resolve_secret;
+use crate::{config_dir, home_dir, GitError};
+
+// Where Homebrew puts pinentry-mac on Apple silicon and on Intel Macs
+const PINENTRY_MAC: &[&str] = &["/opt/homebrew/bin/pinentry-mac", "/usr/local/bin/pinentry-mac"];
+
+// What `generate_gpg_key` set up
+#[derive(Debug, Clone, Serialize)]
+pub struct GpgKey {
+    pub profile: String,
+    pub fingerprint: String,
+    // The armored public key, ready to upload to a Git host
+    pub public_key: PathBuf,
+    // The pinentry gpg-agent was pointed at, if it needed one
+    pub pinentry: Option<PathBuf>,
+    pub settings: Vec<(String, String)>,
+    pub warnings: Vec<String>,
+}
+
+// Function to get GnuPG's home directory: GNUPGHOME, ~/.gnupg, or %APPDATA%\gnupg on Windows
+pub fn gnupg_dir() -> PathBuf {
+    run_command("gpgconf", &["--list-dirs", "homedir"])
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(|| home_dir().join(".gnupg"), PathBuf::from)
+}
+
+// Function to point gpg-agent at the pinentry that works on this platform, so it can ask for the
+// key's passphrase when git signs. Only macOS needs one: without pinentry-mac, gpg-agent falls back
+// to a terminal pinentry that can't prompt from an editor or a GUI client and signing fails.
+// Returns the pinentry set, and a warning for anything the user still has to do
+pub fn configure_pinentry() -> Result<(Option<PathBuf>, Vec<String>), GitError> {
+    let mut warnings = Vec::new();
+    if cfg!(target_os = "macos") {
+        let Some(pinentry) = PINENTRY_MAC.iter().map(PathBuf::from).find(|path| path.is_file()) else {
+            warnings.push(
+                "pinentry-mac is not installed, so gpg can't ask for the passphrase outside a terminal; install it with `brew install pinentry-mac` and run this again".to_string(),
+            );
+            return Ok((None, warnings));
+        };
+        let dir = gnupg_dir();
+        let path = dir.join("gpg-agent.conf");
+        let existing = fs::read_to_string(&path).unwrap_or_default();
+        let body = format!("pinentry-program {}\n", pinentry.display());
+        create_dir_all(&dir)?;
+        write_file(&path, &upsert_block(&existing, "pinentry", &body))?;
+        // The running agent only reads its config when it starts
+        if !DryRun::skip_command("gpgconf", &["--kill", "gpg-agent"]) {
+            let _ = run_command("gpgconf", &["--kill", "gpg-agent"]);
+        }
+        return Ok((Some(pinentry), warnings));
+    }
+    if cfg!(unix) && env::var_os("GPG_TTY").is_none() {
+        warnings.push(
+            "GPG_TTY is not set, so a terminal pinentry can't find the terminal; add `export GPG_TTY=$(tty)` to your shell profile".to_string(),
+        );
+    }
+    Ok((None, warnings))
+}
+
+// Function to generate a GPG signing key with a profile's name and email and have the profile sign
+// commits and tags with it. gpg-agent is set up for the platform first, since gpg asks for the new
+// key's passphrase through it, unless `passphrase` is false and the key gets none. The public key is
+// exported to gpg/<profile>.asc in the config directory for uploading to a Git host
+pub fn generate_gpg_key(profile_name: &str, expires: &str, passphrase: bool) -> Result<GpgKey, GitError> {
+    let profile = get_profile(profile_name)?;
+    let user_id = format!("{} <{}>", resolve_secret(&profile.name)?, resolve_secret(&profile.email)?);
+    // A key without a passphrase never needs pinentry
+    let (pinentry, warnings) = if passphrase { configure_pinentry()? } else { (None, Vec::new()) };
+
+    let mut args = vec!["--batch", "--status-fd", "1"];
+    if !passphrase {
+        args.extend(["--passphrase", "", "--pinentry-mode", "loopback"]);
+    }
+    args.extend(["--quick-generate-key", &user_id, "ed25519", "sign", expires]);
+    let fingerprint = if DryRun::skip_command("gpg", &args) {
+        "<fingerprint>".to_string()
+    } else {
+        let output = run_checked("gpg", &args).map_err(|e| match e {
+            GitError::CommandFailed { cmd, stderr, exit_code }
+                if passphrase && cfg!(unix) && env::var_os("GPG_TTY").is_none() =>
+            {
+                GitError::CommandFailed {
+                    cmd,
+                    stderr: format!(
+                        "{}\nIf pinentry couldn't ask for the passphrase, run `export GPG_TTY=$(tty)` first, or pass --no-passphrase",
+                        stderr.trim_end()
+                    ),
+                    exit_code,
+                }
+            }
+            e => e,
+        })?;
+        // [GNUPG:] KEY_CREATED P <fingerprint>
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.strip_prefix("[GNUPG:] KEY_CREATED "))
+            .and_then(|created| created.split_whitespace().nth(1))
+            .map(str::to_string)
+            .ok_or_else(|| GitError::Parse("gpg didn't report the key it created".to_string()))?
+    };
+
+    let dir = config_dir().join("gpg");
+    create_dir_all(&dir)?;
+    let public_key = dir.join(format!("{}.asc", profile_name));
+    let export = ["--armor", "--export", fingerprint.as_str()];
+    let armored = if DryRun::is_enabled() {
+        String::new()
+    } else {
+        String::from_utf8_lossy(&run_checked("gpg", &export)?.stdout).into_owned()
+    };
+    write_file(&public_key, &armored)?;
+
+    let settings = record_signing_key(profile_name, &fingerprint)?;
+    Ok(GpgKey {
+        profile: profile_name.to_string(),
+        fingerprint,
+        public_key,
+        pinentry,
+        settings,
+        warnings,
+    })
+}
+
+// The profile signs with the key from now on; its own settings, not inherited ones, are changed
+fn record_signing_key(profile_name: &str, fingerprint: &str) -> Result<Vec<(String, String)>, GitError> {
+    let settings: Vec<(String, String)> = [
+        ("user.signingkey", fingerprint),
+        ("gpg.format", "openpgp"),
+        ("commit.gpgsign", "true"),
+        ("tag.gpgsign", "true"),
+    ]
+    .iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect();
+    let _lock = lock_config()?;
+    let mut profiles = load_profiles()?;
+    let profile = profiles
+        .profiles
+        .get_mut(profile_name)
+        .ok_or_else(|| GitError::ProfileNotFound(profile_name.to_string()))?;
+    profile
+        .extra
+        .retain(|key, _| !settings.iter().any(|(setting, _)| setting.eq_ignore_ascii_case(key)));
+    profile.extra.extend(settings.iter().cloned());
+    save_profiles(&profiles)?;
+    Ok(settings)
+}
@@ -0,0 +1,217 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::binding::{list_bindings, Binding};
+use crate::config::GitConfig;
+use crate::git::{repository_git_dir, run_git};
+use crate::hooks::{install_hooks, installed_hooks, remove_hooks};
+use crate::profile::{get_profile, list_profiles};
+use crate::suggest::suggest_profile;
+use crate::GitError;
+
+// The curated pre-commit hook that runs `gitup guard check`
+pub const GUARD_HOOK: &str = "identity-guard";
+
+// The identity a repository's next commit would get, against the one its binding expects
+#[derive(Debug, Clone, Serialize)]
+pub struct GuardCheck {
+    // The profile bound to a directory above the repository, or else the one its remote URLs
+    // suggest, if any
+    pub profile: Option<String>,
+    // Whether `profile` comes from a directory binding rather than a remote URL
+    pub bound: bool,
+    pub expected_email: Option<String>,
+    // The author email git would use, including GIT_AUTHOR_EMAIL and local overrides
+    pub email: Option<String>,
+}
+
+impl GuardCheck {
+    // Function to check whether a commit may go ahead; repositories without a profile always pass
+    pub fn passed(&self) -> bool {
+        match (&self.expected_email, &self.email) {
+            (Some(expected), Some(email)) => expected.eq_ignore_ascii_case(email),
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+}
+
+// A past commit in a bound directory made with one of your other identities
+#[derive(Debug, Clone, Serialize)]
+pub struct IdentityLeak {
+    pub repository: PathBuf,
+    pub profile: String,
+    pub expected_email: String,
+    pub commit: String,
+    pub email: String,
+    // Author date, YYYY-MM-DD
+    pub date: String,
+    pub subject: String,
+}
+
+// The result of scanning a directory tree for identity leaks
+#[derive(Debug, Clone, Serialize)]
+pub struct IdentityScan {
+    // Repositories below a bound directory, which are the only ones checked
+    pub repositories: Vec<PathBuf>,
+    pub leaks: Vec<IdentityLeak>,
+}
+
+// Function to install the identity guard into the shared hooks directory
+pub fn enable_guard() -> Result<PathBuf, GitError> {
+    install_hooks(&[GUARD_HOOK.to_string()])
+}
+
+// Function to remove the identity guard, leaving other shared hooks in place
+pub fn disable_guard() -> Result<PathBuf, GitError> {
+    remove_hooks(&[GUARD_HOOK.to_string()])
+}
+
+// Function to check whether the identity guard is installed
+pub fn is_guard_enabled() -> Result<bool, GitError> {
+    Ok(installed_hooks()?.contains(&GUARD_HOOK))
+}
+
+// Function to compare the author email of the next commit in a repository with the email of
+// the profile bound to its directory, or else of the profile its remote URLs suggest
+pub fn check_identity(dir: &Path) -> Result<GuardCheck, GitError> {
+    let dir_arg = dir.to_string_lossy();
+    let git_dir = git_dir(&dir_arg)?;
+    let bindings = list_bindings()?;
+    let (profile, bound) = match find_binding(&bindings, &git_dir) {
+        Some(binding) => (Some(binding.profile.clone()), true),
+        None => (suggest_profile(dir)?.map(|suggestion| suggestion.profile), false),
+    };
+    let expected_email = match &profile {
+        Some(profile) => Some(get_profile(profile)?.email),
+        None => None,
+    };
+
+    Ok(GuardCheck {
+        profile,
+        bound,
+        expected_email,
+        email: author_email(&dir_arg),
+    })
+}
+
+// Function to find commits among the last `commits` on the local branches of every repository
+// under `root` that were made with another of your identities than the profile bound to the
+// repository's directory. Your identities are the emails of your profiles and the global
+// user.email; commits by anyone else are left alone, as are commits under your profile's name
+// with an address git guessed from the user and host names
+pub fn scan_identities(root: &Path, commits: usize) -> Result<IdentityScan, GitError> {
+    let bindings = list_bindings()?;
+    let profiles = list_profiles()?;
+    let mut own_emails: Vec<String> = profiles.iter().map(|(_, profile)| profile.email.to_lowercase()).collect();
+    own_emails.extend(GitConfig::global().get("user.email").map(|email| email.to_lowercase()));
+
+    let mut scan = IdentityScan {
+        repositories: Vec::new(),
+        leaks: Vec::new(),
+    };
+    for repository in find_repositories(root)? {
+        let git_dir = git_dir(&repository.to_string_lossy()).ok();
+        let Some(binding) = git_dir.and_then(|git_dir| find_binding(&bindings, &git_dir)) else {
+            continue;
+        };
+        let Some((_, profile)) = profiles.iter().find(|(name, _)| *name == binding.profile) else {
+            continue;
+        };
+
+        for (commit, name, email, date, subject) in recent_authors(&repository, commits) {
+            let email_lower = email.to_lowercase();
+            let own = own_emails.contains(&email_lower) || name == profile.name;
+            if own && email_lower != profile.email.to_lowercase() {
+                scan.leaks.push(IdentityLeak {
+                    repository: repository.clone(),
+                    profile: binding.profile.clone(),
+                    expected_email: profile.email.clone(),
+                    commit,
+                    email,
+                    date,
+                    subject,
+                });
+            }
+        }
+        scan.repositories.push(repository);
+    }
+    Ok(scan)
+}
+
+// The canonical git directory of the repository containing `dir`, which is what bindings match
+fn git_dir(dir: &str) -> Result<String, GitError> {
+    Ok(repository_git_dir(Path::new(dir))?.to_string_lossy().into_owned())
+}
+
+// Bindings end in '/' and match every repository below them; as with git's includeIf, the
+// last matching one wins
+fn find_binding<'a>(bindings: &'a [Binding], git_dir: &str) -> Option<&'a Binding> {
+    bindings.iter().rev().find(|binding| git_dir.starts_with(&binding.directory))
+}
+
+// Working trees below `root`, without descending into them or into hidden directories and symlinks
+pub(crate) fn find_repositories(root: &Path) -> Result<Vec<PathBuf>, GitError> {
+    let root = fs::canonicalize(root)?;
+    let mut repositories = Vec::new();
+    let mut pending = vec![root];
+    while let Some(dir) = pending.pop() {
+        if dir.join(".git").exists() {
+            repositories.push(dir);
+            continue;
+        }
+        // Unreadable directories are skipped rather than failing the whole scan
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if !hidden && entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                pending.push(entry.path());
+            }
+        }
+    }
+    repositories.sort();
+    Ok(repositories)
+}
+
+// (commit, author name, author email, date, subject) of recent commits on local branches;
+// empty for repositories without commits
+fn recent_authors(repository: &Path, commits: usize) -> Vec<(String, String, String, String, String)> {
+    let limit = commits.to_string();
+    let args = [
+        "-C",
+        &repository.to_string_lossy(),
+        "log",
+        "--branches",
+        "--max-count",
+        &limit,
+        "--date=short",
+        "--format=%H%x00%an%x00%ae%x00%ad%x00%s",
+    ];
+    let Ok(output) = run_git(&args) else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(5, '\0').map(String::from);
+            Some((fields.next()?, fields.next()?, fields.next()?, fields.next()?, fields.next()?))
+        })
+        .collect()
+}
+
+// `git var` resolves the author the same way `git commit` does, environment variables included
+fn author_email(dir: &str) -> Option<String> {
+    let output = run_git(&["-C", dir, "var", "GIT_AUTHOR_IDENT"]).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // "Jane Doe <jane@example.com> 1700000000 +0100"
+    let ident = String::from_utf8_lossy(&output.stdout);
+    let (_, rest) = ident.split_once('<')?;
+    let (email, _) = rest.split_once('>')?;
+    Some(email.to_string()).filter(|email| !email.is_empty())
+}
@@ -0,0 +1,106 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::dry_run::DryRun;
+use crate::git::{run_checked, run_mutating, run_with_progress};
+use crate::git_for_windows::sha256_file;
+use crate::install::{command_exists, is_root};
+use crate::temp::PrivateDir;
+use crate::GitError;
+
+// Homebrew's official install script
+pub const HOMEBREW_INSTALL_URL: &str = "https://raw.githubusercontent.com/Homebrew/install/HEAD/install.sh";
+
+// The downloaded install script, ready to be checked and run. It is kept in memory until it
+// runs, so the script that runs is the one that was checked
+#[derive(Debug, Clone)]
+pub struct HomebrewScript {
+    pub url: &'static str,
+    contents: Vec<u8>,
+    // Lowercase hex SHA-256 of the script, to show the user or compare with a pinned value
+    pub sha256: String,
+}
+
+// Function to find the brew executable, on PATH or in Homebrew's default prefix, which isn't
+// on PATH until the shell profile is updated after installing
+pub fn find_brew() -> Option<PathBuf> {
+    if command_exists("brew") {
+        return Some(PathBuf::from("brew"));
+    }
+    let brew = default_prefix().join("bin").join("brew");
+    brew.is_file().then_some(brew)
+}
+
+// Function to download Homebrew's install script and compute its checksum, without running it
+pub fn fetch_homebrew_script() -> Result<HomebrewScript, GitError> {
+    if cfg!(windows) {
+        return Err(GitError::InvalidInput("Homebrew is only available on macOS and Linux".to_string()));
+    }
+    let output = run_checked("curl", &["--silent", "--show-error", "--fail", "--location", HOMEBREW_INSTALL_URL])?;
+    Ok(HomebrewScript {
+        url: HOMEBREW_INSTALL_URL,
+        sha256: sha256_hex(&output.stdout),
+        contents: output.stdout,
+    })
+}
+
+// Function to run Homebrew's install script, refusing to if it doesn't match `pinned_sha256`.
+// Returns the path of the brew executable
+pub fn install_homebrew(script: &HomebrewScript, pinned_sha256: Option<&str>) -> Result<PathBuf, GitError> {
+    if let Some(pinned) = pinned_sha256 {
+        if !pinned.eq_ignore_ascii_case(&script.sha256) {
+            return Err(GitError::InvalidInput(format!(
+                "Homebrew's install script has changed: expected SHA-256 {}, got {}; check the new script before pinning it",
+                pinned.to_lowercase(),
+                script.sha256
+            )));
+        }
+    }
+    if is_root() {
+        return Err(GitError::InvalidInput("Homebrew refuses to be installed as root".to_string()));
+    }
+    // Without a terminal on stdin the script runs non-interactively and only uses `sudo -n`,
+    // so the password is asked for up front while it can still be typed
+    run_mutating("sudo", &["-v"])?;
+    // Written once into a directory only this user can open, and checked again just before bash
+    // reads it
+    let dir = PrivateDir::new("homebrew")?;
+    let path = dir.write("install.sh", &script.contents)?;
+    if sha256_file(&path)? != script.sha256 {
+        return Err(GitError::InvalidInput(format!(
+            "Homebrew's install script changed in {} before it could run",
+            path.display()
+        )));
+    }
+    run_with_progress("/bin/bash", &[&path.to_string_lossy()])?;
+
+    match find_brew() {
+        Some(brew) => Ok(brew),
+        // Nothing was installed in a dry run
+        None if DryRun::is_enabled() => Ok(default_prefix().join("bin").join("brew")),
+        None => Err(GitError::InvalidInput(format!(
+            "Homebrew's install script finished, but brew was not found in {}",
+            default_prefix().display()
+        ))),
+    }
+}
+
+// Function to install a package with a brew executable that may not be on PATH yet
+pub fn brew_install(brew: &Path, package: &str) -> Result<(), GitError> {
+    run_with_progress(&brew.to_string_lossy(), &["install", package])
+}
+
+fn sha256_hex(contents: &[u8]) -> String {
+    Sha256::digest(contents).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Where Homebrew installs itself by default
+fn default_prefix() -> PathBuf {
+    match (env::consts::OS, env::consts::ARCH) {
+        ("macos", "aarch64") => PathBuf::from("/opt/homebrew"),
+        ("macos", _) => PathBuf::from("/usr/local"),
+        _ => PathBuf::from("/home/linuxbrew/.linuxbrew"),
+    }
+}
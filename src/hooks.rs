@@ -0,0 +1,213 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::GitConfig;
+use crate::dry_run::{create_dir_all, remove_file, write_executable};
+use crate::managed_block::{has_block, remove_block, upsert_block};
+use crate::{expand_home, config_dir, GitError};
+
+// A curated check installed into one of git's hook scripts
+#[derive(Debug)]
+pub struct Hook {
+    pub name: &'static str,
+    pub git_hook: &'static str,
+    pub description: &'static str,
+    script: &'static str,
+}
+
+const HOOKS: &[Hook] = &[
+    Hook {
+        name: "commit-msg-lint",
+        git_hook: "commit-msg",
+        description: "Reject empty subjects and subjects longer than 72 characters",
+        script: r#"subject=$(grep -v '^#' "$1" | head -n 1)
+if [ -z "$subject" ]; then
+    echo "commit-msg: the subject line is empty" >&2
+    exit 1
+fi
+if [ "${#subject}" -gt 72 ]; then
+    echo "commit-msg: the subject line is longer than 72 characters" >&2
+    exit 1
+fi
+"#,
+    },
+    Hook {
+        name: "prevent-push-to-main",
+        git_hook: "pre-push",
+        description: "Block pushes straight to main or master",
+        script: r#"printf '%s\n' "${input-$(cat)}" | while read -r local_ref local_sha remote_ref remote_sha; do
+    case "$remote_ref" in
+        refs/heads/main|refs/heads/master)
+            echo "pre-push: pushing directly to ${remote_ref#refs/heads/} is blocked (use --no-verify to override)" >&2
+            exit 1
+            ;;
+    esac
+done || exit 1
+"#,
+    },
+    Hook {
+        name: "trailing-whitespace",
+        git_hook: "pre-commit",
+        description: "Reject commits that add trailing whitespace or conflict markers",
+        script: r#"if ! git diff --cached --check >/dev/null; then
+    echo "pre-commit: staged changes contain whitespace errors:" >&2
+    git diff --cached --check >&2
+    exit 1
+fi
+"#,
+    },
+    Hook {
+        name: "identity-guard",
+        git_hook: "pre-commit",
+        description: "Block commits whose email doesn't match the profile for the directory or remote",
+        script: r#"if command -v gitup >/dev/null 2>&1; then
+    gitup --quiet guard check || exit 1
+else
+    echo "pre-commit: gitup is not on PATH, skipping the identity guard" >&2
+fi
+"#,
+    },
+    Hook {
+        name: "profile-suggest",
+        git_hook: "post-checkout",
+        description: "Apply the profile matching the remote URL to freshly cloned repositories",
+        // A clone's checkout is the only one whose previous HEAD is all zeros
+        script: r#"case "$1" in
+    *[!0]*) ;;
+    *)
+        if command -v gitup >/dev/null 2>&1; then
+            gitup --quiet suggest --apply || true
+        fi
+        ;;
+esac
+"#,
+    },
+];
+
+// Every generated hook script starts with this; core.hooksPath replaces .git/hooks,
+// so the repository's own hook is run first to keep it working. Linked worktrees share
+// the main repository's hooks, which --git-dir doesn't point at
+const SCRIPT_HEADER: &str = r#"#!/bin/sh
+# Shared hook managed by gitup (`gitup hooks list`)
+input=$(cat)
+repo_hook="$(git rev-parse --git-common-dir)/hooks/$(basename "$0")"
+if [ -x "$repo_hook" ]; then
+    printf '%s\n' "$input" | "$repo_hook" "$@" || exit $?
+fi
+"#;
+
+// How scripts written by older versions found the repository hook, fixed up when they are read
+const OLD_REPO_HOOK: &str = r#"repo_hook="$(git rev-parse --git-dir)/hooks/$(basename "$0")""#;
+const REPO_HOOK: &str = r#"repo_hook="$(git rev-parse --git-common-dir)/hooks/$(basename "$0")""#;
+
+// Hooks in an init template are copied into .git/hooks, so there is no repository hook
+// to run first
+const TEMPLATE_HEADER: &str = r#"#!/bin/sh
+# Hook from gitup's init template (`gitup init-defaults show`)
+input=$(cat)
+"#;
+
+// Function to get the curated hooks that can be installed
+pub fn available_hooks() -> &'static [Hook] {
+    HOOKS
+}
+
+// Function to get the shared hooks directory, honouring an existing core.hooksPath
+pub fn hooks_dir() -> PathBuf {
+    match GitConfig::global().get("core.hooksPath") {
+        Some(path) => expand_home(&path),
+        None => config_dir().join("hooks"),
+    }
+}
+
+// Function to list the curated hooks currently installed in the shared hooks directory
+pub fn installed_hooks() -> Result<Vec<&'static str>, GitError> {
+    hooks_in(&hooks_dir())
+}
+
+// Function to list the curated hooks present in a hooks directory
+pub fn hooks_in(dir: &Path) -> Result<Vec<&'static str>, GitError> {
+    let mut installed = Vec::new();
+    for hook in HOOKS {
+        if has_block(&read_script(dir, hook.git_hook)?, hook.name) {
+            installed.push(hook.name);
+        }
+    }
+    Ok(installed)
+}
+
+// Function to install hooks into the shared hooks directory and point core.hooksPath at it
+pub fn install_hooks(names: &[String]) -> Result<PathBuf, GitError> {
+    let dir = hooks_dir();
+    write_hooks(&dir, names, SCRIPT_HEADER)?;
+    if GitConfig::global().get("core.hooksPath").is_none() {
+        GitConfig::global().set("core.hooksPath", &dir.to_string_lossy())?;
+    }
+    Ok(dir)
+}
+
+// Function to remove hooks, deleting hook scripts that have nothing left in them
+pub fn remove_hooks(names: &[String]) -> Result<PathBuf, GitError> {
+    let dir = hooks_dir();
+    for name in names {
+        let hook = find_hook(name)?;
+        let path = dir.join(hook.git_hook);
+        let Some(script) = remove_block(&read_script(&dir, hook.git_hook)?, hook.name) else {
+            continue;
+        };
+        if script.trim() == SCRIPT_HEADER.trim() {
+            remove_file(&path)?;
+        } else {
+            write_executable(&path, &script)?;
+        }
+    }
+    Ok(dir)
+}
+
+// Function to write hooks into the hooks/ directory of a `git init` template
+pub fn write_template_hooks(template_dir: &Path, names: &[String]) -> Result<(), GitError> {
+    write_hooks(&template_dir.join("hooks"), names, TEMPLATE_HEADER)
+}
+
+// Function to get the hook script files a curated hook can live in
+pub fn hook_files() -> Vec<&'static str> {
+    let mut files: Vec<&str> = HOOKS.iter().map(|hook| hook.git_hook).collect();
+    files.sort_unstable();
+    files.dedup();
+    files
+}
+
+// Add hooks to the scripts in a directory, starting new scripts with `header`
+fn write_hooks(dir: &Path, names: &[String], header: &str) -> Result<(), GitError> {
+    create_dir_all(dir)?;
+    for name in names {
+        let hook = find_hook(name)?;
+        let mut script = read_script(dir, hook.git_hook)?;
+        if script.is_empty() {
+            script = header.to_string();
+        }
+        let script = upsert_block(&script, hook.name, hook.script);
+        write_executable(&dir.join(hook.git_hook), &script)?;
+    }
+    Ok(())
+}
+
+fn find_hook(name: &str) -> Result<&'static Hook, GitError> {
+    HOOKS.iter().find(|hook| hook.name == name).ok_or_else(|| {
+        let names: Vec<&str> = HOOKS.iter().map(|hook| hook.name).collect();
+        GitError::InvalidInput(format!(
+            "unknown hook '{}' (available: {})",
+            name,
+            names.join(", ")
+        ))
+    })
+}
+
+fn read_script(dir: &Path, git_hook: &str) -> Result<String, GitError> {
+    let path = dir.join(git_hook);
+    if path.exists() {
+        Ok(fs::read_to_string(path)?.replace(OLD_REPO_HOOK, REPO_HOOK))
+    } else {
+        Ok(String::new())
+    }
+}
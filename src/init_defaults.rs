@@ -0,0 +1,102 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::GitConfig;
+use crate::dry_run::{create_dir_all, remove_file, write_file};
+use crate::git::run_git;
+use crate::gitignore::{available_templates, template_contents};
+use crate::hooks::{hook_files, hooks_in, write_template_hooks};
+use crate::managed_block::{has_block, upsert_block};
+use crate::{config_dir, expand_home, GitError};
+
+// What new repositories start with
+#[derive(Debug, Clone)]
+pub struct InitDefaults {
+    pub default_branch: Option<String>,
+    pub template_dir: Option<PathBuf>,
+    pub hooks: Vec<&'static str>,
+    pub ignore: Vec<&'static str>,
+}
+
+// Function to get the directory gitup generates the `git init` template in
+pub fn init_template_dir() -> PathBuf {
+    config_dir().join("init-template")
+}
+
+// Function to report the default branch and what the init template contains
+pub fn init_defaults() -> Result<InitDefaults, GitError> {
+    let template_dir = GitConfig::global().get("init.templateDir").map(|dir| expand_home(&dir));
+    let (hooks, ignore) = match &template_dir {
+        Some(dir) => {
+            let exclude = read_exclude(dir)?;
+            let ignore = available_templates()
+                .into_iter()
+                .filter(|name| has_block(&exclude, name))
+                .collect();
+            (hooks_in(&dir.join("hooks"))?, ignore)
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+    Ok(InitDefaults {
+        default_branch: GitConfig::global().get("init.defaultBranch"),
+        template_dir,
+        hooks,
+        ignore,
+    })
+}
+
+// Function to set the branch name `git init` uses for new repositories
+pub fn set_default_branch(name: &str) -> Result<(), GitError> {
+    let output = run_git(&["check-ref-format", "--branch", name])?;
+    if !output.status.success() {
+        return Err(GitError::InvalidInput(format!("'{}' is not a valid branch name", name)));
+    }
+    GitConfig::global().set("init.defaultBranch", name)
+}
+
+// Function to generate the init template with the given hooks and ignore templates and
+// point init.templateDir at it; `git init` and `git clone` copy it into every new repository.
+// The template is rewritten from scratch, so it holds exactly what was asked for
+pub fn set_init_template(hooks: &[String], ignore: &[String]) -> Result<PathBuf, GitError> {
+    let dir = init_template_dir();
+    clear_template(&dir)?;
+
+    write_template_hooks(&dir, hooks)?;
+    if !ignore.is_empty() {
+        let mut exclude = String::new();
+        for name in ignore {
+            exclude = upsert_block(&exclude, name, template_contents(name)?);
+        }
+        create_dir_all(&dir.join("info"))?;
+        write_file(&dir.join("info").join("exclude"), &exclude)?;
+    }
+    GitConfig::global().set("init.templateDir", &dir.to_string_lossy())?;
+    Ok(dir)
+}
+
+// Function to stop using gitup's init template and delete its files
+pub fn remove_init_template() -> Result<(), GitError> {
+    clear_template(&init_template_dir())?;
+    GitConfig::global().unset("init.templateDir")
+}
+
+// Delete the files gitup generates in a template directory
+fn clear_template(dir: &Path) -> Result<(), GitError> {
+    let mut files: Vec<PathBuf> = hook_files().into_iter().map(|file| dir.join("hooks").join(file)).collect();
+    files.push(dir.join("info").join("exclude"));
+    for file in files {
+        if file.exists() {
+            remove_file(&file)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_exclude(dir: &Path) -> Result<String, GitError> {
+    let path = dir.join("info").join("exclude");
+    if path.exists() {
+        Ok(fs::read_to_string(path)?)
+    } else {
+        Ok(String::new())
+    }
+}
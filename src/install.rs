@@ -0,0 +1,429 @@
+use std::env;
+use std::path::Path;
+
+use semver::Version;
+
+use crate::dry_run::DryRun;
+use crate::git::{get_git_version, run_command, run_mutating, run_with_progress};
+use crate::git_for_windows::install_git_for_windows;
+use crate::GitError;
+
+// What `install_git_with` reports when Git came from the official Windows installer
+pub const GIT_FOR_WINDOWS: &str = "the Git for Windows installer";
+
+// What `install_git_with` reports on macOS without Homebrew. `xcode-select --install` only opens
+// Apple's installer, so git is usually not there yet when it returns
+pub const COMMAND_LINE_TOOLS: &str = "the Xcode Command Line Tools";
+
+// What `upgrade_git` did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitUpgrade {
+    UpToDate(Version),
+    Upgraded {
+        package_manager: &'static str,
+        from: Version,
+        // The version afterwards, unknown in a dry run
+        to: Option<Version>,
+    },
+}
+
+// A system package manager and how to drive it non-interactively
+#[derive(Debug)]
+pub struct PackageManager {
+    pub name: &'static str,
+    // The file to run when it isn't just `name`, like scoop's batch file shim
+    program: Option<&'static str>,
+    // Needs root, which sudo provides; Windows managers elevate themselves, and brew and
+    // scoop refuse to run elevated
+    root: bool,
+    update: Option<&'static [&'static str]>,
+    install: &'static [&'static str],
+    // Upgrades an installed package to the newest version the repositories offer
+    upgrade: &'static [&'static str],
+    // Packages this manager knows under another name, e.g. winget's package ids
+    packages: &'static [(&'static str, &'static str)],
+}
+
+impl PackageManager {
+    fn program(&self) -> &'static str {
+        self.program.unwrap_or(self.name)
+    }
+
+    fn package_name<'a>(&self, package: &'a str) -> &'a str {
+        self.packages
+            .iter()
+            .find(|(name, _)| *name == package)
+            .map_or(package, |(_, renamed)| renamed)
+    }
+}
+
+// Supported package managers in detection order
+const UNIX_PACKAGE_MANAGERS: &[PackageManager] = &[
+    PackageManager {
+        name: "apt-get",
+        program: None,
+        root: true,
+        update: Some(&["update"]),
+        install: &["install", "-y"],
+        upgrade: &["install", "-y", "--only-upgrade"],
+        packages: &[],
+    },
+    PackageManager {
+        name: "dnf",
+        program: None,
+        root: true,
+        update: None,
+        install: &["install", "-y"],
+        upgrade: &["upgrade", "-y"],
+        packages: &[],
+    },
+    PackageManager {
+        name: "yum",
+        program: None,
+        root: true,
+        update: None,
+        install: &["install", "-y"],
+        upgrade: &["update", "-y"],
+        packages: &[],
+    },
+    PackageManager {
+        name: "zypper",
+        program: None,
+        root: true,
+        update: None,
+        install: &["--non-interactive", "install"],
+        upgrade: &["--non-interactive", "update"],
+        packages: &[],
+    },
+    PackageManager {
+        name: "pacman",
+        program: None,
+        root: true,
+        update: None,
+        install: &["-Sy", "--noconfirm"],
+        upgrade: &["-Sy", "--noconfirm"],
+        packages: &[],
+    },
+    PackageManager {
+        name: "apk",
+        program: None,
+        root: true,
+        update: Some(&["update"]),
+        install: &["add"],
+        upgrade: &["add", "--upgrade"],
+        packages: &[("git-delta", "delta")],
+    },
+    // Last, so a Homebrew install on Linux doesn't win over the distribution's packages
+    PackageManager {
+        name: "brew",
+        program: None,
+        root: false,
+        update: Some(&["update"]),
+        install: &["install"],
+        upgrade: &["upgrade"],
+        packages: &[],
+    },
+];
+
+const WINDOWS_PACKAGE_MANAGERS: &[PackageManager] = &[
+    PackageManager {
+        name: "choco",
+        program: None,
+        root: false,
+        update: None,
+        install: &["install", "-y"],
+        upgrade: &["upgrade", "-y"],
+        packages: &[("git-delta", "delta")],
+    },
+    PackageManager {
+        name: "winget",
+        program: None,
+        root: false,
+        update: None,
+        install: &[
+            "install",
+            "--exact",
+            "--silent",
+            "--accept-package-agreements",
+            "--accept-source-agreements",
+            "--id",
+        ],
+        upgrade: &[
+            "upgrade",
+            "--exact",
+            "--silent",
+            "--accept-package-agreements",
+            "--accept-source-agreements",
+            "--id",
+        ],
+        packages: &[("git", "Git.Git"), ("git-lfs", "GitHub.GitLFS"), ("git-delta", "dandavison.delta")],
+    },
+    PackageManager {
+        name: "scoop",
+        program: Some("scoop.cmd"),
+        root: false,
+        update: Some(&["update"]),
+        install: &["install"],
+        upgrade: &["update"],
+        packages: &[("git-delta", "delta")],
+    },
+];
+
+// Other names people use for package managers
+const ALIASES: &[(&str, &str)] = &[("apt", "apt-get"), ("homebrew", "brew"), ("chocolatey", "choco")];
+
+// Function to find the first supported package manager on this system
+pub fn detect_package_manager() -> Option<&'static PackageManager> {
+    package_managers().iter().find(|pm| command_exists(pm.name))
+}
+
+// Function to find every supported package manager on this system, in preference order
+pub fn detect_package_managers() -> Vec<&'static PackageManager> {
+    package_managers().iter().filter(|pm| command_exists(pm.name)).collect()
+}
+
+// Function to get a supported package manager by name (or a common alias such as "apt"),
+// failing if it isn't installed
+pub fn find_package_manager(name: &str) -> Result<&'static PackageManager, GitError> {
+    let name = ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, name)| name);
+    let pm = package_managers().iter().find(|pm| pm.name == name).ok_or_else(|| {
+        let names: Vec<&str> = package_managers().iter().map(|pm| pm.name).collect();
+        GitError::InvalidInput(format!(
+            "unknown package manager '{}' (supported here: {})",
+            name,
+            names.join(", ")
+        ))
+    })?;
+    if !command_exists(pm.name) {
+        return Err(GitError::InvalidInput(format!("{} is not installed", pm.name)));
+    }
+    Ok(pm)
+}
+
+// Function to install a package with the given package manager
+pub fn install_package(pm: &PackageManager, package: &str) -> Result<(), GitError> {
+    if let Some(update) = pm.update {
+        run_package_manager(pm, update)?;
+    }
+    let mut args = pm.install.to_vec();
+    args.push(pm.package_name(package));
+    run_package_manager(pm, &args)
+}
+
+// Function to upgrade an installed package with the given package manager
+pub fn upgrade_package(pm: &PackageManager, package: &str) -> Result<(), GitError> {
+    if let Some(update) = pm.update {
+        run_package_manager(pm, update)?;
+    }
+    let mut args = pm.upgrade.to_vec();
+    args.push(pm.package_name(package));
+    run_package_manager(pm, &args)
+}
+
+// Function to install a package with the named package manager, or the detected one, returning
+// the name of the one used
+pub fn install_system_package(package: &str, package_manager: Option<&str>) -> Result<&'static str, GitError> {
+    let pm = match package_manager {
+        Some(name) => find_package_manager(name)?,
+        None => require_package_manager()?,
+    };
+    install_package(pm, package)?;
+    Ok(pm.name)
+}
+
+// Function to install Git, returning the name of the package manager used
+pub fn install_git() -> Result<&'static str, GitError> {
+    install_git_with(None, None)
+}
+
+// Function to install Git, returning the name of the package manager used. `package_manager`
+// picks one by name instead of the first one detected. On Windows without a package manager, or
+// when `installer_dir` is given, the official Git for Windows installer is downloaded (or reused
+// from `installer_dir`), verified and run silently instead
+pub fn install_git_with(package_manager: Option<&str>, installer_dir: Option<&Path>) -> Result<&'static str, GitError> {
+    if let Some(name) = package_manager {
+        if installer_dir.is_some() {
+            return Err(GitError::InvalidInput(
+                "choose either a package manager or the Git for Windows installer".to_string(),
+            ));
+        }
+        if cfg!(target_os = "macos") && name == "xcode-select" {
+            return install_command_line_tools();
+        }
+        return install_system_package("git", Some(name));
+    }
+    if !cfg!(windows) {
+        if installer_dir.is_some() {
+            return Err(GitError::InvalidInput(
+                "the Git for Windows installer can only be used on Windows".to_string(),
+            ));
+        }
+        // Without Homebrew, Apple's own tools are the way to get git on a Mac
+        if cfg!(target_os = "macos") && detect_package_manager().is_none() {
+            return install_command_line_tools();
+        }
+        return install_system_package("git", None);
+    }
+    if installer_dir.is_none() {
+        if let Some(pm) = detect_package_manager() {
+            install_package(pm, "git")?;
+            return Ok(pm.name);
+        }
+    }
+    install_git_for_windows(installer_dir)?;
+    Ok(GIT_FOR_WINDOWS)
+}
+
+// Function to check whether the Xcode Command Line Tools (or Xcode), which include git, are
+// installed on macOS
+pub fn has_command_line_tools() -> bool {
+    run_command("xcode-select", &["-p"]).is_ok_and(|output| {
+        let developer_dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        output.status.success() && Path::new(&developer_dir).join("usr/bin/git").is_file()
+    })
+}
+
+// Function to start Apple's installer for the Command Line Tools unless they're already
+// installed; it runs on its own after `xcode-select --install` returns
+pub fn install_command_line_tools() -> Result<&'static str, GitError> {
+    if !has_command_line_tools() {
+        run_mutating("xcode-select", &["--install"])?;
+    }
+    Ok(COMMAND_LINE_TOOLS)
+}
+
+// Function to upgrade git when it's older than `minimum`. Fails when the package manager's
+// newest git still doesn't reach it, as on older LTS distributions
+pub fn upgrade_git(minimum: &Version) -> Result<GitUpgrade, GitError> {
+    let from = get_git_version()?;
+    if from >= *minimum {
+        return Ok(GitUpgrade::UpToDate(from));
+    }
+    let pm = require_package_manager()?;
+    upgrade_package(pm, "git")?;
+    // Nothing was upgraded in a dry run, so there is no new version to check
+    let to = if DryRun::is_enabled() {
+        None
+    } else {
+        let to = get_git_version()?;
+        if to < *minimum {
+            return Err(GitError::InvalidInput(format!(
+                "{} only offers git {}, which is older than {}; install a newer git from the git project's packages",
+                pm.name, to, minimum
+            )));
+        }
+        Some(to)
+    };
+    Ok(GitUpgrade::Upgraded {
+        package_manager: pm.name,
+        from,
+        to,
+    })
+}
+
+fn require_package_manager() -> Result<&'static PackageManager, GitError> {
+    detect_package_manager().ok_or_else(|| GitError::PackageManagerMissing {
+        tried: package_managers().iter().map(|pm| pm.name.to_string()).collect(),
+    })
+}
+
+fn package_managers() -> &'static [PackageManager] {
+    if cfg!(windows) {
+        WINDOWS_PACKAGE_MANAGERS
+    } else {
+        UNIX_PACKAGE_MANAGERS
+    }
+}
+
+fn run_package_manager(pm: &PackageManager, args: &[&str]) -> Result<(), GitError> {
+    if pm.root {
+        run_privileged(pm.program(), args)
+    } else {
+        run_with_progress(pm.program(), args)
+    }
+}
+
+// Run a command as root, going through sudo unless we already are root
+fn run_privileged(cmd: &str, args: &[&str]) -> Result<(), GitError> {
+    match elevated_command(cmd, args).filter(|_| !is_root()) {
+        Some((program, args)) => {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            run_with_progress(&program, &args)
+        }
+        None => run_with_progress(cmd, args),
+    }
+}
+
+// The command line that runs `cmd` with administrator rights: through sudo, or on Windows
+// through a UAC prompt, which passes back the exit code but not the output. None when there is
+// no way to elevate
+pub(crate) fn elevated_command(cmd: &str, args: &[&str]) -> Option<(String, Vec<String>)> {
+    if cfg!(windows) {
+        // Start-Process joins the arguments with spaces, so each is quoted for the command line
+        // first; the script then quotes the whole line as a PowerShell string
+        let line: Vec<String> = args.iter().map(|arg| windows_quote(arg)).collect();
+        let script = format!(
+            "$p = Start-Process -FilePath '{}' -ArgumentList '{}' -Verb RunAs -Wait -PassThru -WindowStyle Hidden; exit $p.ExitCode",
+            cmd.replace('\'', "''"),
+            line.join(" ").replace('\'', "''")
+        );
+        let args = ["-NoProfile", "-NonInteractive", "-Command", &script];
+        return Some(("powershell".to_string(), args.iter().map(|arg| arg.to_string()).collect()));
+    }
+    if !command_exists("sudo") {
+        return None;
+    }
+    let args = std::iter::once(cmd).chain(args.iter().copied()).map(str::to_string).collect();
+    Some(("sudo".to_string(), args))
+}
+
+// Quote an argument the way Windows programs split their command line: backslashes are only
+// special before a quote
+fn windows_quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            c => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+pub(crate) fn is_root() -> bool {
+    run_command("id", &["-u"])
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim() == "0")
+        .unwrap_or(false)
+}
+
+// Function to check whether an executable is available on PATH
+pub fn command_exists(cmd: &str) -> bool {
+    let Some(paths) = env::var_os("PATH") else {
+        return false;
+    };
+    // Windows finds programs without their extension, so look for the usual ones too
+    let extensions: &[&str] = if cfg!(windows) { &["", ".exe", ".cmd", ".bat"] } else { &[""] };
+    env::split_paths(&paths).any(|dir| {
+        extensions
+            .iter()
+            .any(|extension| dir.join(format!("{}{}", cmd, extension)).is_file())
+    })
+}
@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::Path;
+
+use crate::config::GitConfig;
+use crate::dry_run::DryRun;
+use crate::git::{repository_root, run_git, run_mutating};
+use crate::install::install_system_package;
+use crate::GitError;
+
+// Package that provides git-lfs on every supported package manager
+const LFS_PACKAGE: &str = "git-lfs";
+
+// Function to check whether the git-lfs extension is available
+pub fn is_lfs_installed() -> bool {
+    run_git(&["lfs", "version"]).is_ok_and(|output| output.status.success())
+}
+
+// Function to install git-lfs if it's missing, with the named package manager or the detected
+// one, and register its filters in the global configuration; returns the package manager used,
+// if any
+pub fn install_lfs(package_manager: Option<&str>) -> Result<Option<&'static str>, GitError> {
+    let package_manager = if is_lfs_installed() {
+        None
+    } else {
+        Some(install_system_package(LFS_PACKAGE, package_manager)?)
+    };
+    // Sets filter.lfs.* globally; the hooks are added to each repository as it's used
+    run_mutating("git", &["lfs", "install", "--skip-repo"])?;
+    Ok(package_manager)
+}
+
+// Function to track file patterns with LFS in a repository, returning the patterns that
+// weren't tracked yet; git lfs records them in the .gitattributes at the repository root
+pub fn track_patterns(repo: &Path, patterns: &[String]) -> Result<Vec<String>, GitError> {
+    // A dry run of `gitup lfs install --track` gets here without git-lfs having been installed
+    let set_up = is_lfs_installed() && GitConfig::global().get("filter.lfs.clean").is_some();
+    if !set_up && !DryRun::is_enabled() {
+        return Err(GitError::InvalidInput(
+            "git-lfs is not set up; run `gitup lfs install` first".to_string(),
+        ));
+    }
+    let root = repository_root(repo)?;
+    let tracked = tracked_patterns(&root)?;
+    let new: Vec<String> = patterns.iter().filter(|pattern| !tracked.contains(pattern)).cloned().collect();
+    if new.is_empty() {
+        return Ok(new);
+    }
+    let root_str = root.to_string_lossy().into_owned();
+    // Adds the LFS hooks (pre-push and friends) to the repository if they aren't there yet
+    run_mutating("git", &["-C", &root_str, "lfs", "update"])?;
+    let mut args = vec!["-C", root_str.as_str(), "lfs", "track", "--"];
+    args.extend(new.iter().map(String::as_str));
+    run_mutating("git", &args)?;
+    Ok(new)
+}
+
+// Function to list the patterns a repository's .gitattributes sends through LFS
+pub fn tracked_patterns(repo: &Path) -> Result<Vec<String>, GitError> {
+    let path = repository_root(repo)?.join(".gitattributes");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter(|line| line.split_whitespace().any(|attribute| attribute == "filter=lfs"))
+        .filter_map(|line| line.split_whitespace().next().map(String::from))
+        .collect())
+}
+
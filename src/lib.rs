@@ -0,0 +1,116 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub mod alias;
+pub mod audit;
+pub mod author;
+pub mod auth;
+pub mod backup;
+pub mod binding;
+pub mod bundle;
+pub mod clone;
+pub mod commit_template;
+pub mod config;
+pub mod config_cache;
+pub mod diff;
+pub mod doctor;
+pub mod drift;
+pub mod dry_run;
+pub mod encryption;
+pub mod eol;
+pub mod error;
+pub mod format;
+pub mod git;
+pub mod git_for_windows;
+pub mod gitignore;
+pub mod gpg;
+pub mod guard;
+pub mod homebrew;
+pub mod hooks;
+pub mod init_defaults;
+pub mod install;
+pub mod lfs;
+#[cfg(feature = "libgit2")]
+pub mod libgit2;
+pub mod lock;
+pub mod maintenance;
+pub mod manifest;
+mod managed_block;
+pub mod migrate;
+pub mod network;
+pub mod options;
+pub mod pager;
+pub mod plugin;
+pub mod preset;
+pub mod privacy;
+pub mod profile;
+pub mod provision;
+pub mod prompt;
+pub mod repos;
+pub mod roster;
+pub mod runner;
+pub mod scripts;
+pub mod secrets;
+pub mod signing;
+pub mod snapshot;
+pub mod ssh;
+pub mod stats;
+pub mod suggest;
+pub mod switch_history;
+pub mod sync;
+pub mod temp;
+#[cfg(test)]
+mod test_support;
+pub mod tools;
+pub mod validate;
+pub mod wsl;
+
+pub use error::GitError;
+
+static CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+// Function to get the current user's home directory
+pub fn home_dir() -> PathBuf {
+    let home = env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .unwrap_or_default();
+    PathBuf::from(home)
+}
+
+// Function to expand a leading ~/ the way git does for path-valued settings
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => home_dir().join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+// Directory to keep profiles and state in when --config-dir isn't given; plugins get it set
+pub const CONFIG_DIR_ENV: &str = "GITUP_CONFIG_DIR";
+
+// Function to keep profiles and state in a specific directory instead of the platform's
+// config directory, for tests and portable installs
+pub fn set_config_dir(path: &Path) {
+    let _ = CONFIG_DIR.set(path.to_path_buf());
+}
+
+// Function to check whether the config directory was chosen with `set_config_dir`
+pub fn config_dir_overridden() -> bool {
+    CONFIG_DIR.get().is_some()
+}
+
+// Function to get the directory where gitup keeps profiles, generated files and state:
+// $XDG_CONFIG_HOME/gitup on Linux and the platform equivalent elsewhere
+pub fn config_dir() -> PathBuf {
+    #[cfg(test)]
+    if let Some(dir) = test_support::config_dir() {
+        return dir;
+    }
+    if let Some(dir) = CONFIG_DIR.get() {
+        return dir.clone();
+    }
+    dirs::config_dir()
+        .unwrap_or_else(|| home_dir().join(".config"))
+        .join("gitup")
+}
@@ -0,0 +1,75 @@
+// Config reads and writes, and repository discovery, through libgit2 instead of a git process.
+// Only built with the `libgit2` feature; callers go back to running git when a call here fails
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use git2::{Config, ErrorCode, Repository};
+
+use crate::config::file::scope_path;
+use crate::git::Scope;
+use crate::GitError;
+
+// Function to list every key/value pair in one scope, in file order
+pub fn list(scope: &Scope) -> Result<Vec<(String, String)>, GitError> {
+    let config = open(scope)?;
+    let mut entries = Vec::new();
+    config.entries(None).map_err(error)?.for_each(|entry| {
+        if let Some(name) = entry.name() {
+            // A key without a value is an implicit true, as git lists it
+            let value = if entry.has_value() { entry.value().unwrap_or_default() } else { "true" };
+            entries.push((name.to_string(), value.to_string()));
+        }
+    }).map_err(error)?;
+    Ok(entries)
+}
+
+// Function to set a key, replacing its value; libgit2 refuses a key with several values, as git does
+pub fn set(scope: &Scope, key: &str, value: &str) -> Result<(), GitError> {
+    open(scope)?.set_str(key, value).map_err(error)
+}
+
+// Function to add another value to a multi-valued key
+pub fn add(scope: &Scope, key: &str, value: &str) -> Result<(), GitError> {
+    // Values matching the pattern are replaced; this one matches none, so the value is added
+    open(scope)?.set_multivar(key, "a^", value).map_err(error)
+}
+
+// Function to remove a key; a key that isn't set is not an error
+pub fn unset(scope: &Scope, key: &str) -> Result<(), GitError> {
+    match open(scope)?.remove(key) {
+        Err(e) if e.code() == ErrorCode::NotFound => Ok(()),
+        result => result.map_err(error),
+    }
+}
+
+// Function to find the working tree and the canonical .git directory of the repository
+// containing `dir`; a bare repository has no working tree
+pub fn discover(dir: &Path) -> Result<(Option<PathBuf>, PathBuf), GitError> {
+    let repo = Repository::discover(dir).map_err(error)?;
+    let git_dir = fs::canonicalize(repo.path())?;
+    let work_dir = repo.workdir().map(fs::canonicalize).transpose()?;
+    Ok((work_dir, git_dir))
+}
+
+// The one file behind a scope, so reads and writes go to it alone
+fn open(scope: &Scope) -> Result<Config, GitError> {
+    let path = match scope {
+        Scope::System | Scope::Global => scope_path(scope)?,
+        Scope::Local(dir) => Repository::discover(dir).map_err(error)?.commondir().join("config"),
+        Scope::Worktree(dir) => {
+            let repo = Repository::discover(dir).map_err(error)?;
+            // Like `git config --worktree`, without extensions.worktreeConfig this is the local config
+            let enabled = repo.config().and_then(|config| config.get_bool("extensions.worktreeConfig"));
+            if enabled.unwrap_or(false) {
+                repo.path().join("config.worktree")
+            } else {
+                repo.commondir().join("config")
+            }
+        }
+    };
+    Config::open(&path).map_err(error)
+}
+
+fn error(e: git2::Error) -> GitError {
+    GitError::InvalidInput(format!("libgit2: {}", e.message()))
+}
@@ -0,0 +1,122 @@
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::dry_run::DryRun;
+use crate::runner::{interrupted, progress_reporter};
+use crate::{config_dir, GitError};
+
+const LOCK_FILE: &str = "gitup.lock";
+
+// How often a waiting invocation checks whether the lock is free
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// The lock file while this process holds the lock, and how many guards hold it. A process
+// takes the lock once; nested changes (a command that writes profiles and then the git config)
+// share it. The file is None while `without_lock` has let go of it
+static HELD: Mutex<(Option<File>, usize)> = Mutex::new((None, 0));
+
+static WAIT: AtomicBool = AtomicBool::new(true);
+
+// Holds the config lock until dropped
+#[derive(Debug)]
+pub struct ConfigLock {
+    active: bool,
+}
+
+impl Drop for ConfigLock {
+    fn drop(&mut self) {
+        if !self.active {
+            return;
+        }
+        if let Ok(mut held) = HELD.lock() {
+            held.1 -= 1;
+            if held.1 == 0 {
+                // Closing the file releases the lock
+                held.0 = None;
+            }
+        }
+    }
+}
+
+// Function to choose what happens when another process holds the lock: wait for it (the
+// default) or fail straight away
+pub fn set_lock_wait(wait: bool) {
+    WAIT.store(wait, Ordering::Relaxed);
+}
+
+// Function to take the advisory lock that serializes changes to the profiles file and git
+// config across gitup processes, so concurrent invocations apply one after the other instead
+// of interleaving their writes. The lock is released when the returned guard is dropped, or
+// when the process exits. Nothing is locked in dry-run mode, which changes nothing
+pub fn lock_config() -> Result<ConfigLock, GitError> {
+    if DryRun::is_enabled() {
+        return Ok(ConfigLock { active: false });
+    }
+    let mut held = HELD.lock().map_err(|_| GitError::InvalidInput("the config lock is poisoned".to_string()))?;
+    if held.0.is_none() {
+        held.0 = Some(acquire()?);
+    }
+    held.1 += 1;
+    Ok(ConfigLock { active: true })
+}
+
+// Function to let go of the lock while running something that waits on a person, the network or
+// a child process that may run gitup itself (a prompt, `git clone` and its hooks), so other
+// invocations aren't held up by it. The lock is taken back afterwards; if that fails, later
+// changes take it one at a time instead
+pub fn without_lock<T>(f: impl FnOnce() -> T) -> T {
+    let released = HELD.lock().ok().and_then(|mut held| held.0.take());
+    let was_held = released.is_some();
+    // Closing the file releases the lock
+    drop(released);
+    let result = f();
+    if was_held {
+        if let Ok(mut held) = HELD.lock() {
+            if held.0.is_none() && held.1 > 0 {
+                held.0 = acquire().ok();
+            }
+        }
+    }
+    result
+}
+
+// Wait for (or with --no-wait, try once for) the lock file
+fn acquire() -> Result<File, GitError> {
+    fs::create_dir_all(config_dir())?;
+    let path = config_dir().join(LOCK_FILE);
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(&path)?;
+    let mut reported = false;
+    loop {
+        match file.try_lock() {
+            Ok(()) => break,
+            Err(TryLockError::WouldBlock) if WAIT.load(Ordering::Relaxed) => {
+                if interrupted() {
+                    return Err(GitError::Interrupted("stopped waiting for another gitup".to_string()));
+                }
+                if !reported {
+                    if let Some(progress) = progress_reporter() {
+                        progress.line("gitup", "waiting for another gitup to finish changing the configuration");
+                    }
+                    reported = true;
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+            Err(TryLockError::WouldBlock) => {
+                return Err(GitError::Conflict(format!(
+                    "another gitup is changing the configuration (lock held on {}); try again, or use --wait",
+                    path.display()
+                )))
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+    }
+    if reported {
+        if let Some(progress) = progress_reporter() {
+            progress.finished("gitup");
+        }
+    }
+    Ok(file)
+}
@@ -1,169 +1,4824 @@
-use std::env;
-use std::process::Command;
-use std::io::{self, Write};
-
-fn main() {
-    // Collect command-line arguments
-    let args: Vec<String> = env::args().collect();
-
-    // Initialize variables for Git name, email, and JSON flag
-    let mut git_name: Option<String> = None;
-    let mut git_email: Option<String> = None;
-    let mut output_json = false;
-
-    // Parse command-line arguments manually
-    let mut i = 1; // Start at 1 to skip the executable name
-    while i < args.len() {
-        match args[i].as_str() {
-            "-u" | "--user" => {
-                if i + 1 < args.len() {
-                    git_name = Some(args[i + 1].clone());
-                    i += 1;
+use std::cell::Cell;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+use clap_complete::env::Shells;
+use clap_complete::CompleteEnv;
+use semver::Version;
+use serde_json::{json, Value};
+
+use gitup::alias::{import_alias_pack, list_aliases, remove_alias, set_alias};
+use gitup::audit::{audit, load_policy, Severity};
+use gitup::author::{add_mailmap, fix_author, plan_author_fix};
+use gitup::auth::{credential_response, login, publish_signing_key, publish_ssh_key, Provider};
+use gitup::backup::target::BackupTarget;
+use gitup::backup::{backup_config, restore_config, BackupInfo, KeyFilter};
+use gitup::binding::bind_profile;
+use gitup::bundle::{export_profile, import_profile};
+use gitup::clone::clone_repository;
+use gitup::commit_template::{
+    builtin_templates, current_commit_template, set_commit_template, template_source,
+    unset_commit_template,
+};
+use gitup::config::{set_config_backend, GitConfig, CONFIG_BACKEND_ENV};
+use gitup::config_cache::{set_disk_cache, DISK_CACHE_ENV};
+use gitup::diff::{diff_against_backup, diff_against_profile, ConfigChange};
+use gitup::doctor::{run_checks, CheckStatus};
+use gitup::drift::{check_drift, correct_drift, watch_drift, Drift};
+use gitup::dry_run::DryRun;
+use gitup::encryption::{set_passphrase, set_passphrase_prompt, PASSPHRASE_ENV};
+use gitup::eol::{set_eol, write_attributes, EolPolicy};
+use gitup::format::{Formatter, JsonFormatter, TableFormatter, TomlFormatter, YamlFormatter};
+use gitup::gitignore::{add_templates, available_templates, installed_templates, remove_templates};
+use gitup::git::{
+    get_effective_config, is_git_installed, is_linked_worktree, list_config_entries, parse_version, ConfigValue, Scope,
+    MIN_GIT_VERSION,
+};
+use gitup::gpg::generate_gpg_key;
+use gitup::guard::{check_identity, disable_guard, enable_guard, is_guard_enabled, scan_identities, GUARD_HOOK};
+use gitup::homebrew::{brew_install, fetch_homebrew_script, find_brew, install_homebrew};
+use gitup::hooks::{available_hooks, hooks_dir, install_hooks, installed_hooks, remove_hooks};
+use gitup::init_defaults::{
+    init_defaults, remove_init_template, set_default_branch, set_init_template,
+};
+use gitup::install::{install_git, install_git_with, upgrade_git, GitUpgrade, COMMAND_LINE_TOOLS};
+use gitup::lfs::{install_lfs, track_patterns, tracked_patterns};
+use gitup::maintenance::{
+    disable_maintenance, enable_maintenance, maintenance_repositories, registered_repositories,
+};
+use gitup::manifest::{apply_manifest, load_manifest, parse_manifest, Manifest};
+use gitup::provision::provision_users;
+use gitup::lock::{lock_config, set_lock_wait, without_lock};
+use gitup::migrate::migrate_legacy_files;
+use gitup::network::{add_rewrite, proxy_config, remove_rewrite, set_proxy, unset_proxy};
+use gitup::options::Options;
+use gitup::pager::{setup_pager, Pager};
+use gitup::plugin::{find_plugin, list_plugins, run_plugin};
+use gitup::repos::{add_repositories, add_roots, known_repositories, remove_entries};
+use gitup::roster::{import_roster, read_roster, OnConflict};
+use gitup::runner::{handle_interrupts, interrupted, set_progress_reporter, set_timeouts, ProgressReporter, Timeouts};
+use gitup::preset::{load_preset, PresetAnswers};
+use gitup::privacy::{enable_privacy, noreply_email, privacy_status};
+use gitup::profile::{
+    active_profile, add_remote_patterns, copy_profile, create_profile, decrypt_profiles, delete_profile, describe_profile,
+    encrypt_profiles, get_profile, rename_profile,
+    list_profiles, profile_environment, profiles_encrypted, remove_remote_patterns, use_profile, use_profile_in_submodules,
+    whoami, GitProfile,
+    PullStrategy, Whoami, PROFILE_ENV, PROFILE_ENV_VARIABLES,
+};
+use gitup::prompt::prompt_status;
+use gitup::scripts::{run_event, Event};
+use gitup::secrets::{delete_secret, get_secret, set_secret};
+use gitup::signing::setup_ssh_signing;
+use gitup::snapshot::{list_snapshots, rollback, take_snapshot, undo};
+use gitup::ssh::{
+    add_to_agent, check_ssh_connection, generate_ssh_key, list_ssh_keys, read_public_key, rewrite_remote,
+    trust_host_keys, write_host_alias, KeyType,
+};
+use gitup::stats::{disable_stats, enable_stats, record, stats_report, StatsEvent, StatsReport};
+use gitup::suggest::suggest_profile;
+use gitup::switch_history::previous_profile;
+use gitup::sync::{sync_init, sync_pull, sync_push, sync_repo, SyncOutcome};
+use gitup::tools::{known_tools, set_tools};
+use gitup::validate::{validate_config_key, validate_email, validate_name};
+use gitup::wsl::setup_wsl;
+use gitup::{config_dir, set_config_dir, GitError, CONFIG_DIR_ENV};
+
+mod picker;
+#[cfg(feature = "tui")]
+mod tui;
+
+#[derive(Parser)]
+#[command(name = "gitup", version, about = "Check, install, and configure Git on your system")]
+struct Cli {
+    /// Print results as JSON (the same as --format json)
+    #[arg(short, long, global = true, conflicts_with = "format")]
+    json: bool,
+
+    /// Print results in a machine-readable format instead of as messages
+    #[arg(long, global = true, value_name = "FORMAT")]
+    format: Option<OutputFormat>,
+
+    /// Show which commands and file writes would happen without performing them
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Never prompt: answer yes to installs and overwrites, and keep defaults for everything else
+    #[arg(short, long, global = true)]
+    yes: bool,
+
+    /// Only print errors and the data a command was asked for
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Keep profiles and state in this directory instead of the platform config directory (also
+    /// read from GITUP_CONFIG_DIR)
+    #[arg(long, global = true, value_name = "DIR")]
+    config_dir: Option<PathBuf>,
+
+    /// Stop any external command that runs longer than this (default: 30s for git config, 10m for
+    /// other git commands, 60m for installs)
+    #[arg(long, global = true, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// When another gitup is changing the configuration, wait for it to finish (the default)
+    #[arg(long, global = true, overrides_with = "no_wait")]
+    wait: bool,
+
+    /// When another gitup is changing the configuration, fail instead of waiting
+    #[arg(long, global = true, overrides_with = "wait")]
+    no_wait: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Guided first-run setup: install Git, set your identity, create a profile and keys
+    Init,
+    /// Install Git using the system package manager, or on Windows the Git for Windows installer
+    Install {
+        /// Package manager to use instead of the first one found: apt, dnf, pacman, brew, xcode-select, choco, winget, ...
+        #[arg(long, value_name = "NAME", conflicts_with = "installer_dir")]
+        package_manager: Option<String>,
+        /// Windows: use the Git for Windows installer, downloading it to (or reusing it from) DIR
+        #[arg(long, value_name = "DIR")]
+        installer_dir: Option<PathBuf>,
+        /// Install with Homebrew, installing Homebrew itself first (after confirmation) if it's missing
+        #[arg(long, conflicts_with_all = ["package_manager", "installer_dir"])]
+        with_brew: bool,
+        /// SHA-256 Homebrew's install script must have; required to install Homebrew without a prompt
+        #[arg(long, value_name = "HASH", requires = "with_brew")]
+        brew_sha256: Option<String>,
+    },
+    /// Upgrade Git with the system package manager if it's older than a minimum version
+    Upgrade {
+        /// Minimum version, e.g. 2.40 (default: the oldest version gitup fully supports)
+        #[arg(long, value_name = "VERSION", value_parser = parse_min_version)]
+        min: Option<Version>,
+    },
+    /// Install Git LFS and track large files with it
+    Lfs {
+        #[command(subcommand)]
+        command: LfsCommands,
+    },
+    /// Keep repositories fast with git's scheduled background maintenance
+    Maintenance {
+        #[command(subcommand)]
+        command: MaintenanceCommands,
+    },
+    /// Keep a registry of your repositories and change them all at once
+    Repos {
+        #[command(subcommand)]
+        command: ReposCommands,
+    },
+    /// Set the Git user name and email, or list every config value with `gitup config list`
+    #[command(args_conflicts_with_subcommands = true)]
+    Config {
+        #[command(subcommand)]
+        command: Option<ConfigCommands>,
+        /// Git user name
+        #[arg(short, long)]
+        user: Option<String>,
+        /// Git user email
+        #[arg(short, long)]
+        email: Option<String>,
+        /// Write to the current repository's .git/config instead of the global config
+        #[arg(long)]
+        local: bool,
+    },
+    /// Show the current global Git user name and email (`gitup config list` shows everything)
+    ShowConfig,
+    /// Diagnose the Git setup and suggest fixes
+    Doctor,
+    /// Check the Git configuration against an organisation's policy file
+    Audit {
+        /// Policy file with [[rule]] entries
+        #[arg(long, value_name = "FILE")]
+        policy: PathBuf,
+    },
+    /// Compare the global Git configuration with the last profile used and manifest applied
+    Drift {
+        #[command(subcommand)]
+        command: DriftCommands,
+    },
+    /// Watch the global Git configuration and report settings other tools change from the last
+    /// profile used and manifest applied, until Ctrl-C
+    Watch {
+        /// Put drifted settings back as soon as they change
+        #[arg(long)]
+        fix: bool,
+        /// Seconds between checks of the config file
+        #[arg(long, value_name = "SECONDS", default_value_t = 2)]
+        interval: u64,
+    },
+    /// Record which profiles you use and how often the identity guard stops a commit, on this
+    /// machine only, and report on it
+    Stats {
+        #[command(subcommand)]
+        command: StatsCommands,
+    },
+    /// Bring profiles, global settings, aliases and ignore templates in line with a manifest
+    Apply {
+        /// Manifest file describing the desired state, or - to read it from stdin
+        manifest: PathBuf,
+    },
+    /// Apply a manifest to other users' accounts, as root, e.g. for a lab or classroom
+    Provision {
+        /// Accounts to set up, comma-separated
+        #[arg(long, value_name = "USERS", value_delimiter = ',', required = true)]
+        users: Vec<String>,
+        /// Manifest file describing the desired state of each account
+        #[arg(long)]
+        manifest: PathBuf,
+    },
+    /// Manage named Git identity profiles
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommands,
+    },
+    /// Generate and manage SSH keys
+    Ssh {
+        #[command(subcommand)]
+        command: SshCommands,
+    },
+    /// Generate GPG signing keys for profiles
+    Gpg {
+        #[command(subcommand)]
+        command: GpgCommands,
+    },
+    /// Set up commit and tag signing
+    Sign {
+        #[command(subcommand)]
+        command: SignCommands,
+    },
+    /// Manage global Git aliases
+    Alias {
+        #[command(subcommand)]
+        command: AliasCommands,
+    },
+    /// Manage the global gitignore (core.excludesFile) from built-in templates
+    Ignore {
+        #[command(subcommand)]
+        command: IgnoreCommands,
+    },
+    /// Install curated hooks into a shared hooks directory (core.hooksPath)
+    Hooks {
+        #[command(subcommand)]
+        command: HooksCommands,
+    },
+    /// Block commits whose email doesn't match the profile bound to the repository's directory or matching its remote
+    Guard {
+        #[command(subcommand)]
+        command: GuardCommands,
+    },
+    /// Clone a repository and set it up for the profile its URL matches
+    Clone {
+        /// Repository URL
+        url: String,
+        /// Directory to clone into (defaults to the repository name)
+        directory: Option<PathBuf>,
+        /// Use this profile instead of the one matching the URL
+        #[arg(long, add = ArgValueCandidates::new(profile_candidates))]
+        profile: Option<String>,
+        /// Don't install the identity guard
+        #[arg(long)]
+        no_guard: bool,
+        /// Clone the submodules too, and use the profile in each of them
+        #[arg(long)]
+        recurse_submodules: bool,
+    },
+    /// Suggest the profile for a repository from its remote URLs, matched against profile patterns
+    Suggest {
+        /// Repository to check
+        #[arg(long, default_value = ".")]
+        repo: PathBuf,
+        /// Apply the suggested profile to the repository's .git/config
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Find past commits in bound directories made with another of your identities
+    ScanIdentities {
+        /// Directory to search for repositories
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+        /// How many recent commits to check in each repository
+        #[arg(long, default_value_t = 100)]
+        commits: usize,
+    },
+    /// Correct the author of unpushed commits made with the wrong email
+    FixAuthor {
+        /// Repository to fix
+        #[arg(long, default_value = ".")]
+        repo: PathBuf,
+        /// Author email to replace
+        #[arg(long, value_name = "EMAIL")]
+        from: String,
+        /// Profile whose name and email the commits should have
+        #[arg(long, value_name = "PROFILE", add = ArgValueCandidates::new(profile_candidates))]
+        to_profile: String,
+        /// Add a .mailmap entry instead of rewriting commits, which also covers pushed ones
+        #[arg(long)]
+        mailmap: bool,
+    },
+    /// Keep your real email out of public commits by using the provider's noreply address
+    Privacy {
+        #[command(subcommand)]
+        command: PrivacyCommands,
+    },
+    /// Configure an HTTP(S) proxy, hosts that bypass it and URL rewrites for mirrors
+    Proxy {
+        #[command(subcommand)]
+        command: ProxyCommands,
+    },
+    /// Set up Git inside WSL to work alongside Git for Windows
+    Wsl {
+        #[command(subcommand)]
+        command: WslCommands,
+    },
+    /// Set how line endings are converted, the same way on every machine of a team
+    Eol {
+        #[command(subcommand)]
+        command: EolCommands,
+    },
+    /// Set the editor and the diff and merge tools git opens
+    Tools {
+        #[command(subcommand)]
+        command: ToolsCommands,
+    },
+    /// Make diffs easier to read with delta or diff-so-fancy
+    Pager {
+        #[command(subcommand)]
+        command: PagerCommands,
+    },
+    /// Set the default branch and the template new repositories start from
+    InitDefaults {
+        #[command(subcommand)]
+        command: InitDefaultsCommands,
+    },
+    /// Manage the commit message template (commit.template)
+    Template {
+        #[command(subcommand)]
+        command: TemplateCommands,
+    },
+    /// Set up and verify GitHub/GitLab API tokens for HTTPS access
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommands,
+    },
+    /// Store tokens and passphrases in the OS keyring
+    Secret {
+        #[command(subcommand)]
+        command: SecretCommands,
+    },
+    /// Keep profiles and gitup's config files in a private git repository
+    Sync {
+        #[command(subcommand)]
+        command: SyncCommands,
+    },
+    /// Back up the entire global Git configuration to a file
+    Backup {
+        /// File to write the backup to; TOML, or JSON when the name ends in .json
+        #[arg(default_value = "gitup_backup.toml")]
+        file: PathBuf,
+        /// Upload the backup instead: s3://bucket/path, or an http(s) URL taking PUT (WebDAV)
+        #[arg(long, value_name = "TARGET", conflicts_with = "file")]
+        to: Option<BackupTarget>,
+        /// Sign the backup with your commit signing key (user.signingkey)
+        #[arg(long)]
+        sign: bool,
+    },
+    /// Restore the global Git configuration from a backup file
+    Restore {
+        /// Backup file to restore from
+        #[arg(required_unless_present = "from")]
+        file: Option<PathBuf>,
+        /// Download the backup instead: s3://bucket/path, or an http(s) URL
+        #[arg(long, value_name = "TARGET", conflicts_with = "file")]
+        from: Option<BackupTarget>,
+        /// Only restore keys matching these patterns, e.g. user.*,alias.* ('*' matches anything)
+        #[arg(long, value_name = "PATTERNS", value_delimiter = ',')]
+        only: Vec<String>,
+        /// Don't restore keys matching these patterns, e.g. credential.*,core.hooksPath
+        #[arg(long, value_name = "PATTERNS", value_delimiter = ',')]
+        exclude: Vec<String>,
+        /// Only restore a backup signed by a trusted key
+        #[arg(long)]
+        verify: bool,
+        /// Also restore keys that run commands (core.pager, core.sshCommand, credential helpers, ...)
+        #[arg(long)]
+        allow_unsafe: bool,
+    },
+    /// Show who the next commit in a repository will be authored by, and which profile that is
+    Whoami {
+        /// Repository (or any directory) to check
+        #[arg(long, value_name = "REPO", default_value = ".")]
+        path: PathBuf,
+    },
+    /// Print the profile the current repository commits as, for embedding in a shell prompt
+    Prompt {
+        /// Directory to check
+        #[arg(long, value_name = "DIR", default_value = ".")]
+        path: PathBuf,
+    },
+    /// Print shell commands that use a profile's identity and SSH key in this shell only: eval "$(gitup env work)"
+    Env {
+        /// Profile to use (default: $GITUP_PROFILE)
+        #[arg(add = ArgValueCandidates::new(profile_candidates))]
+        profile: Option<String>,
+        /// Shell to print commands for (default: guessed from $SHELL)
+        #[arg(long)]
+        shell: Option<CompletionShell>,
+        /// Print commands that clear the variables again instead
+        #[arg(long, conflicts_with = "profile")]
+        unset: bool,
+    },
+    /// Revert the global Git configuration to before the last gitup change
+    Undo,
+    /// List the snapshots taken before each change and roll back to any of them
+    History {
+        #[command(subcommand)]
+        command: HistoryCommands,
+    },
+    /// Compare the global Git configuration against a backup file or a profile
+    #[command(group = clap::ArgGroup::new("target").required(true))]
+    Diff {
+        /// Backup file to compare against
+        #[arg(group = "target")]
+        file: Option<PathBuf>,
+        /// Profile to compare against
+        #[arg(short, long, group = "target", add = ArgValueCandidates::new(profile_candidates))]
+        profile: Option<String>,
+    },
+    /// Print a shell completion script; profile names are completed from your profiles
+    Completions {
+        /// Shell to generate the script for
+        shell: CompletionShell,
+    },
+    /// Browse profiles, the effective config, bindings and snapshots in a terminal dashboard
+    Tui,
+    /// List the plugins (gitup-<name> executables on PATH) that run as `gitup <name>`
+    Plugin {
+        #[command(subcommand)]
+        command: PluginCommands,
+    },
+    // Any other name runs the gitup-<name> plugin with the rest of the arguments
+    #[command(external_subcommand)]
+    External(Vec<String>),
+}
+
+// Formats `--format` can print results in
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Json,
+    Yaml,
+    Toml,
+    Table,
+}
+
+impl OutputFormat {
+    fn formatter(self) -> Box<dyn Formatter> {
+        match self {
+            OutputFormat::Json => Box::new(JsonFormatter),
+            OutputFormat::Yaml => Box::new(YamlFormatter),
+            OutputFormat::Toml => Box::new(TomlFormatter),
+            OutputFormat::Table => Box::new(TableFormatter),
+        }
+    }
+}
+
+// Orders `gitup profile list` can list profiles in
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ProfileSort {
+    Name,
+    Created,
+    LastUsed,
+}
+
+// Shells `gitup completions` can write a script for, and `gitup env` commands for
+#[derive(Clone, Copy, ValueEnum)]
+enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+impl CompletionShell {
+    // The shell gitup is most likely running in
+    fn detect() -> Self {
+        match std::env::var("SHELL") {
+            Ok(shell) if shell.ends_with("fish") => CompletionShell::Fish,
+            Ok(shell) if shell.ends_with("zsh") => CompletionShell::Zsh,
+            Ok(_) => CompletionShell::Bash,
+            Err(_) if cfg!(windows) => CompletionShell::Powershell,
+            Err(_) => CompletionShell::Bash,
+        }
+    }
+
+    fn export(self, name: &str, value: &str) -> String {
+        match self {
+            CompletionShell::Bash | CompletionShell::Zsh => {
+                format!("export {}='{}'", name, value.replace('\'', r"'\''"))
+            }
+            CompletionShell::Fish => {
+                format!("set -gx {} '{}'", name, value.replace('\\', r"\\").replace('\'', r"\'"))
+            }
+            CompletionShell::Powershell => format!("$env:{} = '{}'", name, value.replace('\'', "''")),
+        }
+    }
+
+    fn unset(self, name: &str) -> String {
+        match self {
+            CompletionShell::Bash | CompletionShell::Zsh => format!("unset {}", name),
+            CompletionShell::Fish => format!("set -e {}", name),
+            CompletionShell::Powershell => format!("Remove-Item Env:{} -ErrorAction SilentlyContinue", name),
+        }
+    }
+}
+
+impl Commands {
+    // Commands that change the profiles file or the git config hold the config lock while they
+    // run, reads included, so a concurrent invocation can't change anything in between. Commands
+    // that spend most of their time on the network or in child processes (a clone's hooks run
+    // gitup again) only lock each change; prompts let go of the lock while waiting for an answer
+    fn takes_lock(&self) -> bool {
+        if matches!(
+            self,
+            Commands::Init | Commands::Clone { .. } | Commands::Auth { .. } | Commands::Ssh { .. } | Commands::Gpg { .. }
+        ) {
+            return false;
+        }
+        self.snapshot_reason().is_some()
+            || matches!(
+                self,
+                Commands::Config { .. }
+                    | Commands::Restore { .. }
+                    | Commands::Undo
+                    | Commands::Sync { .. }
+                    | Commands::Template { .. }
+                    | Commands::Eol { .. }
+            )
+            || matches!(
+                self,
+                Commands::Profile { command } if !matches!(command, ProfileCommands::List { .. } | ProfileCommands::Export { .. })
+            )
+            || matches!(
+                self,
+                Commands::Repos { command } if !matches!(command, ReposCommands::List { .. })
+            )
+    }
+
+    // Commands that change the global config are snapshotted first so `gitup undo` can revert them
+    fn snapshot_reason(&self) -> Option<&'static str> {
+        match self {
+            Commands::Init => Some("init"),
+            Commands::Apply { .. } => Some("apply"),
+            Commands::History {
+                command: HistoryCommands::Rollback { .. },
+            } => Some("rollback"),
+            Commands::Config {
+                command: None,
+                local: false,
+                ..
+            } => Some("config"),
+            Commands::Config {
+                command: Some(ConfigCommands::Set { scope, .. } | ConfigCommands::Unset { scope, .. }),
+                ..
+            } if matches!(scope.scope(), None | Some(Scope::Global)) => Some("config"),
+            Commands::Profile {
+                command: ProfileCommands::Use {
+                    local: false,
+                    system: false,
+                    worktree: None,
+                    ..
+                }
+                | ProfileCommands::Last { local: false },
+            } => Some("profile-use"),
+            Commands::Profile {
+                command: ProfileCommands::Bind { .. } | ProfileCommands::Delete { .. } | ProfileCommands::Rename { .. },
+            } => Some("profile-bind"),
+            Commands::Profile {
+                command: ProfileCommands::Import { .. } | ProfileCommands::ImportBatch { .. },
+            } => Some("profile-import"),
+            Commands::Alias {
+                command: AliasCommands::List,
+            } => None,
+            Commands::Alias { .. } => Some("alias"),
+            Commands::Ignore {
+                command: IgnoreCommands::Add { .. } | IgnoreCommands::Remove { .. },
+            } => Some("ignore"),
+            Commands::Auth {
+                command: AuthCommands::Github { .. } | AuthCommands::Gitlab { .. },
+            } => Some("auth"),
+            Commands::Hooks {
+                command: HooksCommands::Install { .. } | HooksCommands::Remove { .. },
+            } => Some("hooks"),
+            Commands::Guard {
+                command: GuardCommands::Enable | GuardCommands::Disable,
+            } => Some("guard"),
+            Commands::Privacy {
+                command: PrivacyCommands::Enable { .. },
+            } => Some("privacy"),
+            Commands::Clone { no_guard: false, .. } => Some("clone"),
+            Commands::Lfs {
+                command: LfsCommands::Install { .. },
+            } => Some("lfs"),
+            Commands::Maintenance {
+                command: MaintenanceCommands::Enable { .. } | MaintenanceCommands::Disable { .. },
+            } => Some("maintenance"),
+            Commands::Proxy {
+                command: ProxyCommands::Show,
+            } => None,
+            Commands::Proxy { .. } => Some("proxy"),
+            Commands::Sign {
+                command: SignCommands::Setup { .. },
+            } => Some("sign"),
+            Commands::Wsl { .. } => Some("wsl"),
+            Commands::Eol {
+                command: EolCommands::Set { local: false, .. },
+            } => Some("eol"),
+            Commands::Tools {
+                command: ToolsCommands::Set { .. },
+            } => Some("tools"),
+            Commands::Pager { .. } => Some("pager"),
+            Commands::Drift {
+                command: DriftCommands::Check { fix: true },
+            } => Some("drift"),
+            Commands::InitDefaults {
+                command: InitDefaultsCommands::Show,
+            } => None,
+            Commands::InitDefaults { .. } => Some("init-defaults"),
+            Commands::Template {
+                command:
+                    TemplateCommands::Set { local: false, .. } | TemplateCommands::Unset { local: false },
+            } => Some("template"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// Create a profile from a name and email
+    Create {
+        /// Profile name
+        profile: String,
+        /// Git user name
+        #[arg(short, long)]
+        user: Option<String>,
+        /// Git user email
+        #[arg(short, long)]
+        email: Option<String>,
+        /// Private SSH key to use while this profile is active
+        #[arg(long)]
+        ssh_key: Option<PathBuf>,
+        /// Editor for commit messages (core.editor)
+        #[arg(long)]
+        editor: Option<String>,
+        /// Branch name for new repositories (init.defaultBranch)
+        #[arg(long)]
+        default_branch: Option<String>,
+        /// How `git pull` integrates changes (rebase or merge)
+        #[arg(long)]
+        pull: Option<PullStrategy>,
+        /// Line ending policy: auto (the platform's own), lf or crlf; see `gitup eol set`
+        #[arg(long, value_name = "POLICY")]
+        eol: Option<EolPolicy>,
+        /// Line ending conversion (core.autocrlf)
+        #[arg(long, value_parser = ["true", "false", "input"])]
+        autocrlf: Option<String>,
+        /// Commit message template (built-in name or file) overriding the global one
+        #[arg(long, value_name = "FILE|BUILTIN")]
+        commit_template: Option<String>,
+        /// Any other config key to apply with the profile, as key=value (repeatable)
+        #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        extra: Vec<(String, String)>,
+        /// Remote URL pattern of repositories that use this profile, e.g. github.com/yourcorp/* (repeatable)
+        #[arg(long = "remote", value_name = "PATTERN")]
+        remotes: Vec<String>,
+        /// Base profile to inherit every setting this one doesn't set from, the name and email included
+        #[arg(long, value_name = "PROFILE", add = ArgValueCandidates::new(profile_candidates))]
+        extends: Option<String>,
+        /// What the profile is for, shown by `profile list`
+        #[arg(long)]
+        description: Option<String>,
+        /// Tag to find the profile by with `profile list --tag` (repeatable)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+    /// Create a profile from a team template (file or URL), asking only for what it leaves out
+    New {
+        /// Profile name (defaults to the one the template suggests)
+        profile: Option<String>,
+        /// Template file or http(s) URL
+        #[arg(long = "from-template", value_name = "URL|FILE")]
+        from_template: String,
+        /// Git user name
+        #[arg(short, long)]
+        user: Option<String>,
+        /// Git user email; with a template email domain, just the part before the @ will do
+        #[arg(short, long)]
+        email: Option<String>,
+        /// Private SSH key to use while this profile is active
+        #[arg(long)]
+        ssh_key: Option<PathBuf>,
+        /// Key to sign commits with, if the template requires signing
+        #[arg(long, value_name = "KEY")]
+        signing_key: Option<String>,
+        /// Keep settings that run commands (an editor, core.pager, credential helpers, ...)
+        #[arg(long)]
+        allow_unsafe: bool,
+    },
+    /// Apply a profile to the global or repository Git configuration
+    #[command(visible_alias = "switch")]
+    Use {
+        /// Profile name, or - for the one used before the last switch; without one, pick it
+        /// from a searchable list
+        #[arg(add = ArgValueCandidates::new(profile_candidates))]
+        profile: Option<String>,
+        /// Apply to the current repository's .git/config instead of the global config
+        #[arg(long)]
+        local: bool,
+        /// Apply to the system-wide config (/etc/gitconfig) for every user of the machine,
+        /// going through sudo (UAC on Windows) when needed
+        #[arg(long, conflicts_with = "local")]
+        system: bool,
+        /// With --local, apply to the repository's checked-out submodules too, along with its
+        /// core.hooksPath
+        #[arg(long, requires = "local")]
+        recurse_submodules: bool,
+        /// Apply to one worktree of the repository only (the current one by default), in its
+        /// .git/config.worktree; turns on extensions.worktreeConfig when needed
+        #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = ".", conflicts_with_all = ["local", "system"])]
+        worktree: Option<PathBuf>,
+    },
+    /// Switch back to the profile used before the last switch, like `cd -` (same as `profile use -`)
+    Last {
+        /// Switch back in the current repository's .git/config instead of the global config
+        #[arg(long)]
+        local: bool,
+    },
+    /// List all profiles, marking the one matching the active Git identity
+    List {
+        /// Only list profiles with this tag (repeatable; profiles need every one)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Order to list the profiles in; created and last-used put the most recent first
+        #[arg(long, value_enum, default_value = "name")]
+        sort: ProfileSort,
+    },
+    /// Set a profile's description and tags
+    Describe {
+        /// Profile name
+        #[arg(add = ArgValueCandidates::new(profile_candidates))]
+        profile: String,
+        /// What the profile is for; an empty one removes it
+        #[arg(long)]
+        description: Option<String>,
+        /// Tag to add (repeatable)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Tag to remove (repeatable)
+        #[arg(long = "untag", value_name = "TAG")]
+        untags: Vec<String>,
+    },
+    /// Delete a profile and its directory bindings
+    Delete {
+        /// Profile name
+        #[arg(add = ArgValueCandidates::new(profile_candidates))]
+        profile: String,
+    },
+    /// Give a profile a new name, moving its directory bindings and the profiles extending it along
+    Rename {
+        /// Current profile name
+        #[arg(add = ArgValueCandidates::new(profile_candidates))]
+        old: String,
+        /// New profile name
+        new: String,
+    },
+    /// Create a profile as a copy of another, e.g. with a different email
+    #[command(visible_alias = "duplicate")]
+    Copy {
+        /// Profile to copy
+        #[arg(add = ArgValueCandidates::new(profile_candidates))]
+        source: String,
+        /// Name of the new profile
+        target: String,
+        /// Git user name for the copy
+        #[arg(short, long)]
+        user: Option<String>,
+        /// Git user email for the copy
+        #[arg(short, long)]
+        email: Option<String>,
+    },
+    /// Write a profile, its bindings, commit template and SSH key reference to a bundle file
+    Export {
+        /// Profile name
+        #[arg(add = ArgValueCandidates::new(profile_candidates))]
+        profile: String,
+        /// Bundle file to write (defaults to <profile>.gitup)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+        /// Also include the private SSH key, encrypted with the profiles passphrase
+        #[arg(long)]
+        include_key: bool,
+    },
+    /// Recreate a profile from a bundle file
+    Import {
+        /// Bundle file
+        file: PathBuf,
+        /// Import under a different profile name
+        #[arg(long = "as", value_name = "NAME")]
+        rename: Option<String>,
+        /// Overwrite an existing profile with the same name
+        #[arg(long)]
+        force: bool,
+        /// Keep settings that run commands (an editor, core.pager, credential helpers, ...)
+        #[arg(long)]
+        allow_unsafe: bool,
+    },
+    /// Create many profiles from a CSV or JSON roster (name, email, signing key, remote patterns)
+    ImportBatch {
+        /// Roster file: JSON when it ends in .json, otherwise CSV with a header row
+        file: PathBuf,
+        /// What to do when a profile name is taken: fail (create nothing), skip, overwrite or
+        /// rename (to <name>-2, ...)
+        #[arg(long, value_name = "ACTION", default_value_t = OnConflict::Fail)]
+        on_conflict: OnConflict,
+    },
+    /// Encrypt the profiles file with a passphrase (read from GITUP_PASSPHRASE or prompted)
+    Encrypt,
+    /// Store the profiles file as plain TOML again
+    Decrypt,
+    /// Use a profile automatically for every repository under a directory
+    Bind {
+        /// Profile name
+        #[arg(add = ArgValueCandidates::new(profile_candidates))]
+        profile: String,
+        /// Directory containing the repositories
+        directory: PathBuf,
+    },
+    /// Add remote URL patterns (e.g. github.com/yourcorp/*) that `gitup suggest` maps to a profile
+    Remote {
+        /// Profile name
+        #[arg(add = ArgValueCandidates::new(profile_candidates))]
+        profile: String,
+        /// URL patterns; '*' matches anything
+        #[arg(required = true)]
+        patterns: Vec<String>,
+        /// Remove the patterns instead
+        #[arg(long)]
+        remove: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum AliasCommands {
+    /// Create or replace an alias, e.g. `gitup alias add co checkout`
+    Add {
+        /// Alias name
+        name: String,
+        /// Git command the alias expands to
+        #[arg(required = true, num_args = 1.., trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Remove an alias
+    Remove {
+        /// Alias name
+        name: String,
+    },
+    /// List global aliases
+    List,
+    /// Install a curated alias pack (shortcuts, log-beauty)
+    Import {
+        /// Pack name
+        pack: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum IgnoreCommands {
+    /// Add templates to the global gitignore, e.g. `gitup ignore add macos vscode rust`
+    Add {
+        /// Template names
+        #[arg(required = true)]
+        templates: Vec<String>,
+    },
+    /// Remove templates from the global gitignore
+    Remove {
+        /// Template names
+        #[arg(required = true)]
+        templates: Vec<String>,
+    },
+    /// List available templates, marking the installed ones
+    List,
+}
+
+#[derive(Subcommand)]
+enum HooksCommands {
+    /// Install hooks, e.g. `gitup hooks install commit-msg-lint prevent-push-to-main`
+    Install {
+        /// Hook names
+        #[arg(required = true)]
+        hooks: Vec<String>,
+    },
+    /// Remove installed hooks
+    Remove {
+        /// Hook names
+        #[arg(required = true)]
+        hooks: Vec<String>,
+    },
+    /// List available hooks, marking the installed ones
+    List,
+}
+
+#[derive(Subcommand)]
+enum GuardCommands {
+    /// Install the identity-guard pre-commit hook into the shared hooks directory
+    Enable,
+    /// Remove the identity-guard hook
+    Disable,
+    /// Show whether the guard is installed and what it expects for a repository
+    Status {
+        /// Repository to check
+        #[arg(long, value_name = "REPO", default_value = ".")]
+        path: PathBuf,
+    },
+    /// Fail if the next commit's email doesn't match the bound profile (run by the hook)
+    Check {
+        /// Repository to check
+        #[arg(long, value_name = "REPO", default_value = ".")]
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryCommands {
+    /// List snapshots of the global config, oldest first
+    List,
+    /// Restore the global config from a snapshot, e.g. `gitup history rollback 1792162767142`
+    Rollback {
+        /// Snapshot id from `gitup history list`
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum MaintenanceCommands {
+    /// Register repositories for scheduled maintenance (git maintenance start) with gc.auto and
+    /// commit-graph settings suited to it
+    Enable {
+        /// Repository to maintain
+        #[arg(long, value_name = "REPO", default_value = ".")]
+        repo: PathBuf,
+        /// Maintain every repository under this directory instead
+        #[arg(long, value_name = "DIR", conflicts_with = "repo")]
+        all_under: Option<PathBuf>,
+    },
+    /// Stop maintaining repositories and remove the settings that came with it
+    Disable {
+        /// Repository to stop maintaining
+        #[arg(long, value_name = "REPO", default_value = ".")]
+        repo: PathBuf,
+        /// Stop maintaining every repository under this directory instead
+        #[arg(long, value_name = "DIR", conflicts_with = "repo")]
+        all_under: Option<PathBuf>,
+    },
+    /// List the repositories registered for maintenance
+    List,
+}
+
+#[derive(Subcommand)]
+enum ReposCommands {
+    /// Add repositories to the registry, e.g. `gitup repos add ~/src/api ~/src/web`
+    Add {
+        /// Repositories, or any directory inside them
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Add directories whose repositories all belong to the registry, found again on every use
+    AddRoot {
+        #[arg(required = true)]
+        dirs: Vec<PathBuf>,
+    },
+    /// Take repositories or roots out of the registry
+    Remove {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// List the repositories in the registry
+    List {
+        /// Show the name and email each one commits with, and the matching profile
+        #[arg(long)]
+        with_identity: bool,
+    },
+    /// Use a profile in several repositories, e.g. `gitup repos apply-profile work --all`
+    ApplyProfile {
+        #[arg(add = ArgValueCandidates::new(profile_candidates))]
+        profile: String,
+        /// Repositories to use it in
+        #[arg(required_unless_present = "all")]
+        repos: Vec<PathBuf>,
+        /// Use it in every repository in the registry
+        #[arg(long, conflicts_with = "repos")]
+        all: bool,
+        /// Use it in each repository's checked-out submodules too
+        #[arg(long)]
+        recurse_submodules: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum LfsCommands {
+    /// Install git-lfs with the system package manager and enable it globally (git lfs install)
+    Install {
+        /// Pattern to track in --repo as well (repeatable), e.g. --track "*.psd"
+        #[arg(long = "track", value_name = "PATTERN")]
+        track: Vec<String>,
+        /// Repository to track patterns in
+        #[arg(long, value_name = "REPO", default_value = ".")]
+        repo: PathBuf,
+        /// Package manager to install git-lfs with instead of the first one found, e.g. brew
+        #[arg(long, value_name = "NAME")]
+        package_manager: Option<String>,
+    },
+    /// Track file patterns with LFS in a repository, e.g. `gitup lfs track "*.psd" "*.zip"`
+    Track {
+        /// Patterns, quoted so the shell doesn't expand them
+        #[arg(required = true)]
+        patterns: Vec<String>,
+        /// Repository to track patterns in
+        #[arg(long, value_name = "REPO", default_value = ".")]
+        repo: PathBuf,
+    },
+    /// List the patterns a repository tracks with LFS
+    List {
+        /// Repository to look in
+        #[arg(long, value_name = "REPO", default_value = ".")]
+        repo: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProxyCommands {
+    /// Set the proxy (http.proxy) and hosts reached without it
+    Set {
+        /// Proxy for HTTP remotes, e.g. http://proxy.corp.example:3128
+        #[arg(long, value_name = "URL")]
+        http: Option<String>,
+        /// Proxy for HTTPS remotes; git uses one proxy for both, so it must match --http if both are given
+        #[arg(long, value_name = "URL")]
+        https: Option<String>,
+        /// Host to reach directly (repeatable; `*` matches one name component, as in *.corp.example)
+        #[arg(long = "no-proxy", value_name = "HOST")]
+        no_proxy: Vec<String>,
+    },
+    /// Remove the proxy and every no-proxy host
+    Unset,
+    /// Show the proxy, no-proxy hosts, URL rewrites and proxy environment variables
+    Show,
+    /// Fetch URLs starting with PREFIX from BASE instead (url.<base>.insteadOf)
+    Rewrite {
+        /// URL prefix to replace, e.g. https://github.com/
+        prefix: String,
+        /// Replacement, e.g. https://mirror.corp.example/github/
+        base: String,
+    },
+    /// Remove the rewrite for a URL prefix
+    RemoveRewrite {
+        /// URL prefix
+        prefix: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DriftCommands {
+    /// Check once for settings that changed since they were applied; exits with 1 if any did
+    Check {
+        /// Put drifted settings back
+        #[arg(long)]
+        fix: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum StatsCommands {
+    /// Start recording usage in stats.jsonl in the config directory
+    Enable,
+    /// Stop recording usage and delete what was recorded
+    Disable,
+    /// Sum up the usage recorded recently
+    Report {
+        /// How many days back to go
+        #[arg(long, value_name = "DAYS", default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+        days: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum WslCommands {
+    /// Share credentials with Windows through its Git Credential Manager, fix line ending and
+    /// file mode settings, and report settings that differ from the Windows side
+    Setup {
+        /// Repository to turn off core.fileMode in, when it is on a Windows drive
+        #[arg(long, value_name = "REPO", default_value = ".")]
+        repo: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum EolCommands {
+    /// Set core.autocrlf and core.eol for a line ending policy, e.g. `gitup eol set lf`
+    Set {
+        /// auto (the platform's own line endings), lf or crlf; defaults to the active profile's policy
+        policy: Option<EolPolicy>,
+        /// Set it in the repository's config instead of the global one
+        #[arg(long)]
+        local: bool,
+        /// Also write the recommended .gitattributes to the repository
+        #[arg(long)]
+        attributes: bool,
+        /// Repository for --local and --attributes
+        #[arg(long, value_name = "REPO", default_value = ".")]
+        repo: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ToolsCommands {
+    /// Set core.editor, diff.tool and merge.tool, e.g. `gitup tools set --editor code --mergetool meld`
+    Set {
+        /// Editor for commit messages and rebases, by tool name or command (see `gitup tools list`)
+        #[arg(long, value_name = "TOOL")]
+        editor: Option<String>,
+        /// Tool `git difftool` opens
+        #[arg(long, value_name = "TOOL")]
+        difftool: Option<String>,
+        /// Tool `git mergetool` opens
+        #[arg(long, value_name = "TOOL")]
+        mergetool: Option<String>,
+    },
+    /// List the editors and diff/merge tools gitup can set up, and which are installed
+    List,
+}
+
+#[derive(Subcommand)]
+enum PagerCommands {
+    /// Install a pager if it's missing and make git use it, e.g. `gitup pager setup delta`
+    Setup {
+        /// delta or diff-so-fancy
+        pager: Pager,
+        /// Package manager to install it with instead of the first one found, e.g. brew
+        #[arg(long, value_name = "NAME")]
+        package_manager: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum PluginCommands {
+    /// List the plugins found on PATH
+    List,
+}
+
+#[derive(Subcommand)]
+enum InitDefaultsCommands {
+    /// Show the default branch and what the init template contains
+    Show,
+    /// Set the branch name `git init` uses (init.defaultBranch), e.g. `gitup init-defaults branch main`
+    Branch {
+        /// Branch name
+        name: String,
+    },
+    /// Generate an init template (init.templateDir) with starter hooks and ignore patterns
+    Template {
+        /// Curated hook to include (repeatable; see `gitup hooks list`)
+        #[arg(long = "hook", value_name = "HOOK")]
+        hooks: Vec<String>,
+        /// Ignore template to write to .git/info/exclude (repeatable; see `gitup ignore list`)
+        #[arg(long = "ignore", value_name = "TEMPLATE")]
+        ignore: Vec<String>,
+    },
+    /// Stop using the init template and delete it
+    RemoveTemplate,
+}
+
+#[derive(Subcommand)]
+enum TemplateCommands {
+    /// Install a commit template and set commit.template, e.g. `gitup template set conventional-commits`
+    Set {
+        /// Template file, or a built-in template (conventional-commits, ticket-prefix, detailed)
+        #[arg(value_name = "FILE|BUILTIN")]
+        template: String,
+        /// Set it for the current repository only
+        #[arg(long)]
+        local: bool,
+    },
+    /// Stop using a commit template
+    Unset {
+        /// Unset it for the current repository only
+        #[arg(long)]
+        local: bool,
+    },
+    /// List built-in templates and show the one currently configured
+    List,
+}
+
+#[derive(Subcommand)]
+enum AuthCommands {
+    /// Store a GitHub token, use it for HTTPS pushes and pulls, and verify it
+    Github {
+        /// Personal access token; prompted for when omitted
+        #[arg(long)]
+        token: Option<String>,
+        /// GitHub Enterprise host
+        #[arg(long, default_value = "github.com")]
+        host: String,
+    },
+    /// Store a GitLab token, use it for HTTPS pushes and pulls, and verify it
+    Gitlab {
+        /// Personal access token; prompted for when omitted
+        #[arg(long)]
+        token: Option<String>,
+        /// Self-hosted GitLab host
+        #[arg(long, default_value = "gitlab.com")]
+        host: String,
+    },
+    /// Store a Bitbucket app password, use it for HTTPS pushes and pulls, and verify it
+    Bitbucket {
+        /// App password; prompted for when omitted
+        #[arg(long)]
+        token: Option<String>,
+        /// Bitbucket user name the app password belongs to (not the email)
+        #[arg(long)]
+        username: String,
+    },
+    /// Git credential helper entry point (called by git, not by people)
+    #[command(hide = true)]
+    Credential {
+        /// Host the helper was registered for
+        #[arg(long)]
+        host: String,
+        /// get, store or erase
+        operation: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SecretCommands {
+    /// Store a secret; the value is prompted for unless --value is given
+    Set {
+        /// Keyring entry name
+        name: String,
+        /// Secret value (visible in shell history; prefer the prompt)
+        #[arg(long)]
+        value: Option<String>,
+    },
+    /// Print a stored secret
+    Get {
+        /// Keyring entry name
+        name: String,
+    },
+    /// Delete a stored secret
+    Delete {
+        /// Keyring entry name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SyncCommands {
+    /// Set up syncing with a remote repository
+    Init {
+        /// Repository URL, e.g. git@github.com:me/gitup-state.git
+        url: String,
+    },
+    /// Commit local changes and push them
+    Push,
+    /// Merge remote changes into the local files
+    Pull {
+        /// Take the remote version of files changed on both sides
+        #[arg(long)]
+        force: bool,
+        /// Also pull changes to shared hooks and settings that run commands (core.sshCommand, credential helpers, ...)
+        #[arg(long)]
+        allow_unsafe: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PrivacyCommands {
+    /// Set the global commit email to the account's noreply address
+    Enable {
+        /// github or gitlab
+        #[arg(long, default_value = "github")]
+        provider: Provider,
+        /// Self-hosted or Enterprise host (default: github.com or gitlab.com)
+        #[arg(long)]
+        host: Option<String>,
+        /// Account name, for GitHub when no token is stored with `gitup auth github`
+        #[arg(long)]
+        username: Option<String>,
+        /// Also set user.useConfigOnly, so git refuses to commit instead of guessing an email
+        #[arg(long)]
+        use_config_only: bool,
+    },
+    /// Show whether the global commit email is a noreply address
+    Status,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// List every config value git sees here, from all scopes, in the order git reads them
+    List {
+        /// Also show the scope and file each value comes from
+        #[arg(long)]
+        show_origin: bool,
+    },
+    /// Print a config value; without a scope flag, the one git would use here and where it comes from
+    Get {
+        /// Config key, e.g. pull.rebase
+        key: String,
+        #[command(flatten)]
+        scope: ScopeArgs,
+    },
+    /// Set a config value, in the global config unless a scope flag is given
+    Set {
+        /// Config key, e.g. pull.rebase
+        key: String,
+        value: String,
+        #[command(flatten)]
+        scope: ScopeArgs,
+    },
+    /// Remove a config value, from the global config unless a scope flag is given
+    Unset {
+        /// Config key, e.g. pull.rebase
+        key: String,
+        #[command(flatten)]
+        scope: ScopeArgs,
+    },
+}
+
+#[derive(Args)]
+#[group(multiple = false)]
+struct ScopeArgs {
+    /// Use the global config (~/.gitconfig)
+    #[arg(long)]
+    global: bool,
+    /// Use the current repository's .git/config
+    #[arg(long)]
+    local: bool,
+    /// Use the current worktree's .git/config.worktree
+    #[arg(long)]
+    worktree: bool,
+    /// Use the system-wide config (/etc/gitconfig), going through sudo (UAC on Windows) to change it when needed
+    #[arg(long)]
+    system: bool,
+}
+
+impl ScopeArgs {
+    // The scope picked by a flag, if any
+    fn scope(&self) -> Option<Scope> {
+        if self.global {
+            Some(Scope::Global)
+        } else if self.local {
+            Some(Scope::local())
+        } else if self.worktree {
+            Some(Scope::Worktree(PathBuf::from(".")))
+        } else if self.system {
+            Some(Scope::System)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum SshCommands {
+    /// Generate a new SSH key and attach it to a profile
+    Generate {
+        /// Profile the key belongs to
+        #[arg(short, long, add = ArgValueCandidates::new(profile_candidates))]
+        profile: String,
+        /// Key algorithm (ed25519, ecdsa or rsa)
+        #[arg(short = 't', long = "type", default_value = "ed25519")]
+        key_type: KeyType,
+    },
+    /// List SSH keys in ~/.ssh
+    List,
+    /// Add a private key to the running ssh-agent
+    Add {
+        /// Path to the private key
+        key: PathBuf,
+    },
+    /// Write a Host alias to ~/.ssh/config that uses a profile's key (e.g. github.com-work)
+    Host {
+        /// Profile whose SSH key the alias uses
+        #[arg(short, long, add = ArgValueCandidates::new(profile_candidates))]
+        profile: String,
+        /// Real host name
+        #[arg(long, default_value = "github.com")]
+        host: String,
+        /// Also rewrite a remote of this repository to use the alias
+        #[arg(long, value_name = "REPO")]
+        rewrite: Option<PathBuf>,
+        /// Remote to rewrite
+        #[arg(long, default_value = "origin")]
+        remote: String,
+    },
+    /// Upload an SSH key to GitHub, GitLab or Bitbucket and check that the host accepts it, e.g.
+    /// `gitup ssh publish --provider github -p work`
+    Publish {
+        /// github, gitlab or bitbucket
+        #[arg(long)]
+        provider: Provider,
+        /// GitHub Enterprise or self-hosted GitLab host (defaults to the provider's public one)
+        #[arg(long)]
+        host: Option<String>,
+        /// Profile whose key to upload (defaults to the profile in use)
+        #[arg(short, long, add = ArgValueCandidates::new(profile_candidates))]
+        profile: Option<String>,
+        /// Key to upload instead of a profile's, public or private
+        #[arg(long, value_name = "KEY", conflicts_with = "profile")]
+        key: Option<PathBuf>,
+        /// Name for the key on the account (defaults to this machine's name)
+        #[arg(long)]
+        title: Option<String>,
+        /// Don't check the key with `ssh -T` afterwards
+        #[arg(long)]
+        no_check: bool,
+    },
+    /// Pin the SSH host keys of GitHub, GitLab or Bitbucket in ~/.ssh/known_hosts, so first clones
+    /// don't ask whether to trust the host, e.g. `gitup ssh trust github gitlab bitbucket`
+    Trust {
+        /// github, gitlab or bitbucket
+        #[arg(required = true)]
+        providers: Vec<Provider>,
+    },
+}
+
+#[derive(Subcommand)]
+enum GpgCommands {
+    /// Generate a GPG key with a profile's name and email and have the profile sign with it
+    Generate {
+        /// Profile the key belongs to
+        #[arg(short, long, add = ArgValueCandidates::new(profile_candidates))]
+        profile: String,
+        /// When the key expires, as gpg takes it (2y, 18m, 2030-01-01 or never)
+        #[arg(long, default_value = "2y")]
+        expires: String,
+        /// Don't protect the key with a passphrase
+        #[arg(long)]
+        no_passphrase: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SignCommands {
+    /// Upload your signing key (SSH or GPG) to GitHub or GitLab so signed commits show as
+    /// verified, e.g. `gitup sign publish --provider github`
+    Publish {
+        /// github or gitlab
+        #[arg(long)]
+        provider: Provider,
+        /// GitHub Enterprise or self-hosted GitLab host (defaults to github.com or gitlab.com)
+        #[arg(long)]
+        host: Option<String>,
+        /// Name for the key on the account (defaults to this machine's name)
+        #[arg(long)]
+        title: Option<String>,
+    },
+    /// Sign commits and tags with an SSH key and let git verify them, e.g.
+    /// `gitup sign setup --ssh --key ~/.ssh/id_ed25519.pub`
+    Setup {
+        /// Sign with an SSH key (gpg.format=ssh)
+        #[arg(long, required = true)]
+        ssh: bool,
+        /// The key, public or private
+        #[arg(long, value_name = "KEY")]
+        key: PathBuf,
+        /// Email to list the key under in the allowed signers file (defaults to user.email)
+        #[arg(long)]
+        email: Option<String>,
+        /// Sign in the current repository only; the allowed signers file is still the global one
+        #[arg(long)]
+        local: bool,
+    },
+}
+
+// Exit code used for failures that don't come from a library error
+const EXIT_FAILURE: u8 = 1;
+const EXIT_GIT_NOT_INSTALLED: u8 = 2;
+const EXIT_INTERRUPTED: u8 = 130;
+
+const COMPLETE_ENV: &str = "GITUP_COMPLETE";
+
+fn main() -> ExitCode {
+    // Completion scripts call back into gitup with this variable set to ask for candidates
+    CompleteEnv::with_factory(Cli::command).var(COMPLETE_ENV).complete();
+
+    let cli = Cli::parse();
+    let out = Printer {
+        format: cli.format.or(cli.json.then_some(OutputFormat::Json)),
+        options: Options {
+            assume_yes: cli.yes,
+            quiet: cli.quiet,
+            dry_run: cli.dry_run,
+        },
+        exit_code: Cell::new(0),
+    };
+    if cli.dry_run {
+        DryRun::enable();
+    }
+    match (&cli.config_dir, std::env::var_os(CONFIG_DIR_ENV)) {
+        (Some(dir), _) => set_config_dir(dir),
+        (None, Some(dir)) if !dir.is_empty() => set_config_dir(Path::new(&dir)),
+        _ => {}
+    }
+    if std::env::var(DISK_CACHE_ENV).is_ok_and(|value| value == "1") {
+        set_disk_cache(true);
+    }
+    if let Ok(backend) = std::env::var(CONFIG_BACKEND_ENV) {
+        match backend.parse() {
+            Ok(backend) => set_config_backend(backend),
+            Err(e) => {
+                out.error(&format!("Invalid {}", CONFIG_BACKEND_ENV), &e);
+                return ExitCode::from(out.exit_code.get());
+            }
+        }
+    }
+    if let Some(seconds) = cli.timeout {
+        set_timeouts(Timeouts::uniform(Duration::from_secs(seconds)));
+    }
+    if !out.structured() && !out.options.quiet {
+        set_progress_reporter(Arc::new(TerminalProgress {
+            redraw: io::stderr().is_terminal(),
+        }));
+    }
+    if let Err(e) = handle_interrupts() {
+        eprintln!("Ctrl-C won't stop running commands cleanly: {}", e);
+    }
+    if out.interactive() && io::stdin().is_terminal() {
+        set_passphrase_prompt(prompt_passphrase);
+    }
+    set_lock_wait(!cli.no_wait);
+    match migrate_legacy_files() {
+        Ok(Some(migration)) => {
+            if !migration.moved.is_empty() && !DryRun::is_enabled() {
+                out.info(&format!("Moved gitup's files to {}", config_dir().display()));
+            }
+            for path in &migration.kept {
+                out.info(&format!("Left {} in place; the new location already exists", path.display()));
+            }
+        }
+        Ok(None) => {}
+        Err(e) => {
+            out.error("Failed to move gitup's files to the config directory", &e);
+            return ExitCode::from(out.exit_code.get());
+        }
+    }
+
+    // Held until gitup exits, so concurrent invocations change the configuration one at a time
+    let _lock = match cli.command.as_ref().filter(|command| command.takes_lock()).map(|_| lock_config()) {
+        Some(Err(e)) => {
+            out.error("Failed to lock the configuration", &e);
+            return ExitCode::from(out.exit_code.get());
+        }
+        lock => lock,
+    };
+
+    if let Some(reason) = cli.command.as_ref().and_then(Commands::snapshot_reason) {
+        if let Err(e) = take_snapshot(reason) {
+            out.error("Failed to snapshot the Git configuration", &e);
+            return ExitCode::from(out.exit_code.get());
+        }
+    }
+
+    match cli.command {
+        None => {
+            // Check if Git is installed, then proceed with configuration
+            if ensure_git_installed(&out, true) {
+                configure_git_if_needed(&out, None, None, Scope::Global);
+            }
+        }
+        Some(Commands::Init) => run_init(&out),
+        Some(Commands::Install {
+            package_manager,
+            installer_dir,
+            with_brew,
+            brew_sha256,
+        }) => {
+            if is_git_installed() {
+                out.ok("Git is already installed.", json!({ "installed": true }));
+            } else if with_brew {
+                install_git_with_homebrew(&out, brew_sha256.as_deref());
+            } else {
+                out.info("Installing Git, which can take a few minutes...");
+                match install_git_with(package_manager.as_deref(), installer_dir.as_deref()) {
+                    Ok(COMMAND_LINE_TOOLS) if !is_git_installed() => out.ok(
+                        "Apple's installer for the Xcode Command Line Tools has been opened. \
+                         Finish it, then run gitup again to configure Git.",
+                        json!({ "installed": false, "package_manager": COMMAND_LINE_TOOLS, "pending": true }),
+                    ),
+                    Ok(pm) => out.ok(
+                        &format!("Git has been installed successfully using {}.", pm),
+                        json!({ "installed": true, "package_manager": pm }),
+                    ),
+                    Err(e) => out.error("Failed to install Git", &e),
+                }
+            }
+        }
+        Some(Commands::Upgrade { min }) => {
+            let minimum = min.unwrap_or(MIN_GIT_VERSION);
+            match upgrade_git(&minimum) {
+                Ok(GitUpgrade::UpToDate(version)) => out.ok(
+                    &format!("Git {} is already at least {}.", version, minimum),
+                    json!({ "upgraded": false, "version": version.to_string(), "minimum": minimum.to_string() }),
+                ),
+                Ok(GitUpgrade::Upgraded { package_manager, from, to }) => {
+                    let message = match &to {
+                        Some(to) => format!("Git has been upgraded from {} to {} using {}.", from, to, package_manager),
+                        None => format!("Git {} has been upgraded using {}.", from, package_manager),
+                    };
+                    out.ok(
+                        &message,
+                        json!({
+                            "upgraded": true,
+                            "package_manager": package_manager,
+                            "from": from.to_string(),
+                            "version": to.map(|to| to.to_string()),
+                            "minimum": minimum.to_string(),
+                        }),
+                    )
+                }
+                Err(e) => out.error("Failed to upgrade Git", &e),
+            }
+        }
+        Some(Commands::Lfs { command }) => run_lfs_command(&out, command),
+        Some(Commands::Maintenance { command }) => run_maintenance_command(&out, command),
+        Some(Commands::Repos { command }) => run_repos_command(&out, command),
+        Some(Commands::Config {
+            command: Some(command), ..
+        }) => run_config_command(&out, command),
+        Some(Commands::Config {
+            command: None,
+            user,
+            email,
+            local,
+        }) => {
+            let interactive = user.is_none() && email.is_none();
+            if ensure_git_installed(&out, interactive) {
+                configure_git_if_needed(&out, user, email, scope(local));
+            }
+        }
+        Some(Commands::ShowConfig) => show_config(&out),
+        Some(Commands::Doctor) => run_doctor(&out),
+        Some(Commands::Audit { policy }) => run_audit(&out, &policy),
+        Some(Commands::Drift {
+            command: DriftCommands::Check { fix },
+        }) => run_drift_check(&out, fix),
+        Some(Commands::Watch { fix, interval }) => run_watch(&out, fix, interval),
+        Some(Commands::Stats { command }) => run_stats_command(&out, command),
+        Some(Commands::Apply { manifest }) => {
+            let mut context = vec![("manifest", manifest.display().to_string())];
+            let result = run_scripts(&out, Event::PreApply, &context)
+                .and_then(|()| read_manifest(&manifest))
+                .and_then(|m| apply_manifest(&m));
+            if let Ok(changes) = &result {
+                context.push(("changes", changes.len().to_string()));
+                run_post_scripts(&out, Event::PostApply, &context);
+            }
+            match result {
+                Ok(changes) if changes.is_empty() => out.ok(
+                    "Already up to date.",
+                    json!({ "manifest": manifest, "changes": changes }),
+                ),
+                Ok(changes) => {
+                    let mut message = vec![format!("Applied {} change(s):", changes.len())];
+                    message.extend(changes.iter().map(|change| format!("  {}", change)));
+                    out.ok(&message.join("\n"), json!({ "manifest": manifest, "changes": changes }));
+                }
+                Err(e) => out.error("Failed to apply manifest", &e),
+            }
+        }
+        Some(Commands::Provision { users, manifest }) => run_provision(&out, &users, &manifest),
+        Some(Commands::Profile { command }) => run_profile_command(&out, command),
+        Some(Commands::Ssh { command }) => run_ssh_command(&out, command),
+        Some(Commands::Gpg { command }) => run_gpg_command(&out, command),
+        Some(Commands::Sign { command }) => run_sign_command(&out, command),
+        Some(Commands::Alias { command }) => run_alias_command(&out, command),
+        Some(Commands::Ignore { command }) => run_ignore_command(&out, command),
+        Some(Commands::Auth { command }) => run_auth_command(&out, command),
+        Some(Commands::Secret { command }) => run_secret_command(&out, command),
+        Some(Commands::Hooks { command }) => run_hooks_command(&out, command),
+        Some(Commands::Guard { command }) => run_guard_command(&out, command),
+        Some(Commands::Clone {
+            url,
+            directory,
+            profile,
+            no_guard,
+            recurse_submodules,
+        }) => run_clone(&out, &url, directory.as_deref(), profile.as_deref(), !no_guard, recurse_submodules),
+        Some(Commands::Suggest { repo, apply }) => run_suggest(&out, &repo, apply),
+        Some(Commands::ScanIdentities { dir, commits }) => run_scan_identities(&out, &dir, commits),
+        Some(Commands::FixAuthor {
+            repo,
+            from,
+            to_profile,
+            mailmap,
+        }) => run_fix_author(&out, &repo, &from, &to_profile, mailmap),
+        Some(Commands::Privacy { command }) => run_privacy_command(&out, command),
+        Some(Commands::Proxy { command }) => run_proxy_command(&out, command),
+        Some(Commands::Wsl { command }) => run_wsl_command(&out, command),
+        Some(Commands::Eol { command }) => run_eol_command(&out, command),
+        Some(Commands::Tools { command }) => run_tools_command(&out, command),
+        Some(Commands::Pager { command }) => run_pager_command(&out, command),
+        Some(Commands::InitDefaults { command }) => run_init_defaults_command(&out, command),
+        Some(Commands::Template { command }) => run_template_command(&out, command),
+        Some(Commands::Sync { command }) => run_sync_command(&out, command),
+        Some(Commands::Completions { shell }) => print_completions(&out, shell),
+        Some(Commands::Tui) => run_tui(&out),
+        Some(Commands::Plugin {
+            command: PluginCommands::List,
+        }) => list_plugin_commands(&out),
+        Some(Commands::External(args)) => run_external(&out, &args),
+        Some(Commands::Backup { file, to, sign }) => {
+            let target = to.unwrap_or(BackupTarget::File(file));
+            let result = backup_config(&target, sign);
+            if let Ok(count) = &result {
+                let context = [("target", target.to_string()), ("count", count.to_string())];
+                run_post_scripts(&out, Event::PostBackup, &context);
+            }
+            match result {
+                Ok(count) => out.ok(
+                    &format!("Backed up {} Git settings to {}{}", count, target, if sign { ", signed" } else { "" }),
+                    json!({ "file": target.to_string(), "count": count, "signed": sign }),
+                ),
+                Err(e) => out.error("Failed to back up Git configuration", &e),
+            }
+        }
+        Some(Commands::Restore {
+            file,
+            from,
+            verify,
+            only,
+            exclude,
+            allow_unsafe,
+        }) => {
+            // clap makes sure one of the two is given
+            let target = from.or(file.map(BackupTarget::File)).unwrap_or(BackupTarget::File(PathBuf::new()));
+            let mut context = vec![("source", target.to_string())];
+            let result = run_scripts(&out, Event::PreRestore, &context)
+                .and_then(|()| restore_config(&target, verify, &KeyFilter { only, exclude }, allow_unsafe));
+            if let Ok(report) = &result {
+                context.push(("restored", report.restored.len().to_string()));
+                run_post_scripts(&out, Event::PostRestore, &context);
+            }
+            match result {
+                Ok(report) => {
+                    let mut message = Vec::new();
+                    if let Some(signer) = &report.signed_by {
+                        message.push(format!("Good signature by {}", signer));
+                    }
+                    if let Some(origin) = describe_backup(&report.backup) {
+                        message.push(origin);
+                    }
+                    message.extend(report.restored.iter().map(|key| format!("Restored {}", key)));
+                    message.extend(report.skipped.iter().map(|key| format!("Skipped {}", key)));
+                    message.extend(report.blocked.iter().map(|key| format!("Blocked {} (it can run commands)", key)));
+                    if !report.blocked.is_empty() {
+                        message.push("Check the blocked keys and pass --allow-unsafe to restore them too.".to_string());
+                    }
+                    message.push("Run `gitup undo` to revert this restore.".to_string());
+                    out.ok(
+                        &message.join("\n"),
+                        json!({
+                            "file": target.to_string(),
+                            "restored": report.restored,
+                            "skipped": report.skipped,
+                            "blocked": report.blocked,
+                            "snapshot": report.snapshot.path,
+                            "backup": report.backup,
+                            "signed_by": report.signed_by,
+                        }),
+                    );
+                }
+                Err(e) => out.error("Failed to restore Git configuration", &e),
+            }
+        }
+        Some(Commands::Undo) => match undo() {
+            Ok(Some(snapshot)) => out.ok(
+                &format!("Reverted the last '{}' change.", snapshot.reason),
+                json!({ "reverted": snapshot.reason, "snapshot": snapshot.path }),
+            ),
+            Ok(None) => out.fail("Nothing to undo."),
+            Err(e) => out.error("Failed to undo", &e),
+        },
+        Some(Commands::Whoami { path }) => run_whoami(&out, &path),
+        Some(Commands::Prompt { path }) => run_prompt(&out, &path),
+        Some(Commands::Env { profile, shell, unset }) => {
+            run_env(&out, profile, shell.unwrap_or_else(CompletionShell::detect), unset)
+        }
+        Some(Commands::History { command }) => run_history_command(&out, command),
+        Some(Commands::Diff { file, profile }) => {
+            let changes = match (file, profile) {
+                (Some(file), _) => diff_against_backup(&file),
+                (None, Some(profile)) => diff_against_profile(&profile),
+                (None, None) => unreachable!("clap requires a diff target"),
+            };
+            match changes {
+                Ok(changes) => print_changes(&out, &changes),
+                Err(e) => out.error("Failed to compare Git configuration", &e),
+            }
+        }
+    }
+
+    out.print_planned_actions();
+    if interrupted() {
+        return ExitCode::from(EXIT_INTERRUPTED);
+    }
+    ExitCode::from(out.exit_code.get())
+}
+
+// Prints command results either as human-readable text or as JSON objects,
+// remembering the exit code of the last failure
+struct Printer {
+    // Set when results are printed as data rather than as messages
+    format: Option<OutputFormat>,
+    options: Options,
+    exit_code: Cell<u8>,
+}
+
+impl Printer {
+    // Whether the user can be asked for input; prompts would corrupt JSON output
+    fn interactive(&self) -> bool {
+        !self.structured() && self.options.interactive()
+    }
+
+    // Ask a yes/no question; --yes answers it, and without a way to ask the answer is no
+    fn confirm(&self, question: &str) -> bool {
+        if self.options.assume_yes {
+            return true;
+        }
+        self.interactive() && prompt_user(&format!("{} (y/n): ", question)).to_lowercase() == "y"
+    }
+
+    // Print a progress or status line that isn't part of a command's result
+    fn info(&self, message: &str) {
+        if !self.structured() && !self.options.quiet {
+            println!("{}", message);
+        }
+    }
+
+    // Report a successful operation; in JSON mode `data` is merged into the result object
+    fn ok(&self, message: &str, data: Value) {
+        if self.structured() {
+            let mut result = json!({ "status": "ok", "code": 0, "message": message });
+            if let (Some(result), Value::Object(data)) = (result.as_object_mut(), data) {
+                result.extend(data);
+                if DryRun::is_enabled() {
+                    result.insert("dry_run".to_string(), json!(true));
+                    result.insert("actions".to_string(), json!(DryRun::take_actions()));
+                }
+            }
+            self.print(&result);
+        } else if !message.is_empty() && !self.options.quiet {
+            if DryRun::is_enabled() {
+                println!("(dry run) {}", message);
+            } else {
+                println!("{}", message);
+            }
+        }
+    }
+
+    // List everything a dry run would have changed
+    fn print_planned_actions(&self) {
+        if !DryRun::is_enabled() {
+            return;
+        }
+        let actions = DryRun::take_actions();
+        if self.structured() {
+            if !actions.is_empty() {
+                self.print(&json!({ "dry_run": true, "actions": actions }));
+            }
+        } else if actions.is_empty() {
+            println!("Dry run: nothing would be changed.");
+        } else {
+            println!("Dry run: nothing was changed. The following actions would be performed:");
+            for action in actions {
+                println!("  {}", action);
+            }
+        }
+    }
+
+    // Report a failed operation
+    fn error(&self, context: &str, err: &GitError) {
+        self.fail_with(err.exit_code(), &format!("{}: {}", context, err));
+    }
+
+    // Report a failure that didn't come from the library
+    fn fail(&self, message: &str) {
+        self.fail_with(EXIT_FAILURE, message);
+    }
+
+    fn fail_with(&self, code: u8, message: &str) {
+        self.exit_code.set(code);
+        if self.structured() {
+            self.print(&json!({ "status": "error", "code": code, "error": message }));
+        } else {
+            eprintln!("{}", message);
+        }
+    }
+
+    // Print a value as pretty JSON
+    fn structured(&self) -> bool {
+        self.format.is_some()
+    }
+
+    fn print(&self, value: &Value) {
+        let formatter = self.format.unwrap_or(OutputFormat::Json).formatter();
+        match formatter.format(value) {
+            Ok(text) => println!("{}", text),
+            Err(e) => eprintln!("Failed to format the result: {}", e),
+        }
+    }
+}
+
+// Shows what package managers and installers print while they run. On a terminal the latest
+// line replaces the previous one, so a long install reads as a single moving status line
+struct TerminalProgress {
+    redraw: bool,
+}
+
+// Longest status line shown while redrawing, so it doesn't wrap on narrow terminals
+const PROGRESS_WIDTH: usize = 76;
+
+impl ProgressReporter for TerminalProgress {
+    fn line(&self, cmd: &str, line: &str) {
+        let line = line.trim();
+        if self.redraw {
+            let line: String = line.chars().take(PROGRESS_WIDTH).collect();
+            eprint!("\r\x1b[K{}: {}", cmd, line);
+            let _ = io::stderr().flush();
+        } else {
+            eprintln!("{}: {}", cmd, line);
+        }
+    }
+
+    fn finished(&self, _cmd: &str) {
+        if self.redraw {
+            eprint!("\r\x1b[K");
+            let _ = io::stderr().flush();
+        }
+    }
+}
+
+// Interactive first-run setup wizard
+fn run_init(out: &Printer) {
+    if out.structured() {
+        out.fail("gitup init is interactive and cannot be used with --json or --format");
+        return;
+    }
+
+    out.info("Welcome to gitup! Let's set up Git on this machine.");
+    if !ensure_git_installed(out, true) {
+        return;
+    }
+
+    // Identity, defaulting to whatever is already configured
+    let name = prompt_with_default(out, "Your Git name", GitConfig::global().get("user.name"));
+    let email = prompt_with_default(out, "Your Git email", GitConfig::global().get("user.email"));
+    if name.is_empty() || email.is_empty() {
+        out.fail("A name and email are required to use Git.");
+        return;
+    }
+    if let Err(e) = validate_name(&name).and_then(|()| validate_email(&email)) {
+        out.error("Invalid identity", &e);
+        return;
+    }
+    let config = GitConfig::global();
+    if let Err(e) = config.set("user.name", &name).and_then(|()| config.set("user.email", &email)) {
+        out.error("Failed to configure Git", &e);
+        return;
+    }
+    out.info(&format!("Git identity set to {} <{}>", name, email));
+
+    if !out.confirm("Would you like to save this identity as a profile?") {
+        out.info("Setup complete.");
+        return;
+    }
+    let profile = prompt_with_default(out, "Profile name", Some("default".to_string()));
+    let git_profile = GitProfile {
+        name,
+        email,
+        ..Default::default()
+    };
+    if let Err(e) = create_profile(&profile, git_profile) {
+        out.error("Failed to create profile", &e);
+        return;
+    }
+    out.info(&format!("Profile '{}' created.", profile));
+
+    if !out.confirm("Would you like to generate an SSH key for this profile?") {
+        out.info("Setup complete.");
+        return;
+    }
+    let key = match generate_ssh_key(&profile, KeyType::Ed25519)
+        .and_then(|key| switch_profile(out, &profile, Scope::Global).map(|_| key))
+    {
+        Ok(key) => key,
+        Err(e) => {
+            out.error("Failed to set up SSH key", &e);
+            return;
+        }
+    };
+    out.info(&format!("Generated SSH key {}", key.display()));
+    out.info(&format!("Add {}.pub to your Git hosting account.", key.display()));
+
+    if out.confirm("Would you like to sign commits with this key?") {
+        match setup_ssh_signing(&key, None, Scope::Global) {
+            Ok(setup) => out.info(&format!("Commits will be signed with {}", setup.key.display())),
+            Err(e) => out.error("Failed to configure commit signing", &e),
+        }
+    }
+
+    out.info("Setup complete.");
+}
+
+// A manifest file, or with "-" the manifest piped in, which is how `provision` hands it to
+// each account
+fn read_manifest(path: &Path) -> Result<Manifest, GitError> {
+    if path != Path::new("-") {
+        return load_manifest(path);
+    }
+    parse_manifest(&io::read_to_string(io::stdin())?, "stdin")
+}
+
+fn run_provision(out: &Printer, users: &[String], manifest: &Path) {
+    let results = match provision_users(users, manifest) {
+        Ok(results) => results,
+        Err(e) => return out.error("Failed to provision users", &e),
+    };
+    let mut lines = Vec::new();
+    let mut provisioned = Vec::new();
+    let mut failed = Vec::new();
+    for (user, result) in results {
+        match result {
+            Ok(account) => {
+                lines.push(match account.changes.len() {
+                    0 => format!("{}: already up to date", user),
+                    count => format!("{}: applied {} change(s)", user, count),
+                });
+                lines.extend(account.changes.iter().map(|change| format!("  {}", change)));
+                provisioned.push(account);
+            }
+            Err(e) => {
+                lines.push(format!("{}: failed: {}", user, e));
+                failed.push(json!({ "user": user, "error": e.to_string() }));
+            }
+        }
+    }
+    if failed.is_empty() {
+        out.ok(&lines.join("\n"), json!({ "manifest": manifest, "users": provisioned }));
+    } else if out.structured() {
+        out.exit_code.set(1);
+        out.print(&json!({ "status": "error", "code": 1, "manifest": manifest, "users": provisioned, "failed": failed }));
+    } else {
+        out.fail(&lines.join("\n"));
+    }
+}
+
+fn run_audit(out: &Printer, policy_file: &std::path::Path) {
+    let (rules, violations) = match load_policy(policy_file)
+        .and_then(|policy| audit(&policy).map(|violations| (policy.rules.len(), violations)))
+    {
+        Ok(result) => result,
+        Err(e) => return out.error("Failed to audit the Git configuration", &e),
+    };
+    let errors = violations.iter().filter(|v| v.severity == Severity::Error).count();
+    let warnings = violations.len() - errors;
+    if errors > 0 {
+        out.exit_code.set(EXIT_FAILURE);
+    }
+
+    if out.structured() {
+        out.print(&json!({
+            "status": if errors > 0 { "error" } else { "ok" },
+            "code": out.exit_code.get(),
+            "policy": policy_file,
+            "violations": violations,
+            "summary": { "rules": rules, "errors": errors, "warnings": warnings },
+        }));
+        return;
+    }
+    for violation in &violations {
+        let label = match violation.severity {
+            Severity::Error => "FAIL",
+            Severity::Warning => "WARN",
+        };
+        let actual = if violation.actual.is_empty() {
+            "unset".to_string()
+        } else {
+            violation.actual.join(", ")
+        };
+        println!("[{}] {} {} (is {})", label, violation.key, violation.expected, actual);
+        if let Some(message) = &violation.message {
+            println!("       {}", message);
+        }
+    }
+    out.info(&format!("{} rules checked: {} errors, {} warnings", rules, errors, warnings));
+}
+
+fn run_drift_check(out: &Printer, fix: bool) {
+    let drift = match check_drift() {
+        Ok(drift) => drift,
+        Err(e) => return out.error("Failed to check for drift", &e),
+    };
+    if drift.drifted.is_empty() {
+        return out.ok(
+            &format!("No drift: the {} settings from {} are as applied.", drift.checked, applied_source(&drift)),
+            json!({ "drift": drift, "fixed": false }),
+        );
+    }
+    if fix {
+        return match correct_drift(&drift) {
+            Ok(()) => {
+                let mut message = vec![format!("Put back {} drifted setting(s):", drift.drifted.len())];
+                message.extend(drift.drifted.iter().map(|setting| format!("  {} = {}", setting.key, setting.expected.join(", "))));
+                out.ok(&message.join("\n"), json!({ "drift": drift, "fixed": true }));
+            }
+            Err(e) => out.error("Failed to put back drifted settings", &e),
+        };
+    }
+
+    out.exit_code.set(EXIT_FAILURE);
+    if out.structured() {
+        return out.print(&json!({
+            "status": "error",
+            "code": out.exit_code.get(),
+            "drift": drift,
+            "fixed": false,
+        }));
+    }
+    for line in drifted_lines(&drift) {
+        println!("{}", line);
+    }
+    out.info(&format!(
+        "{} of {} settings from {} drifted; run `gitup drift check --fix` to put them back.",
+        drift.drifted.len(),
+        drift.checked,
+        applied_source(&drift)
+    ));
+}
+
+fn run_watch(out: &Printer, fix: bool, interval: u64) {
+    if !out.structured() {
+        out.info("Watching the global Git configuration for drift; press Ctrl-C to stop.");
+    }
+    let result = watch_drift(Duration::from_secs(interval.max(1)), fix, |result| {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        let (drift, fixed) = match result {
+            Ok(checked) => checked,
+            Err(e) => return eprintln!("[{}] Failed to check for drift: {}", format_timestamp(now), e),
+        };
+        if out.structured() {
+            return out.print(&json!({ "time": format_timestamp(now), "drift": drift, "fixed": fixed }));
+        }
+        if drift.drifted.is_empty() {
+            return out.info(&format!("[{}] No drift from {}", format_timestamp(now), applied_source(&drift)));
+        }
+        println!("[{}] {} setting(s) drifted from {}:", format_timestamp(now), drift.drifted.len(), applied_source(&drift));
+        for line in drifted_lines(&drift) {
+            println!("  {}", line);
+        }
+        if fixed {
+            println!("  Put them back; `gitup undo` restores the changed values.");
+        }
+    });
+    if let Err(e) = result {
+        out.error("Failed to watch for drift", &e);
+    }
+}
+
+fn run_stats_command(out: &Printer, command: StatsCommands) {
+    match command {
+        StatsCommands::Enable => match enable_stats() {
+            Ok(path) => out.ok(
+                &format!("Recording usage in {}; it never leaves this machine.", path.display()),
+                json!({ "enabled": true, "file": path }),
+            ),
+            Err(e) => out.error("Failed to enable usage statistics", &e),
+        },
+        StatsCommands::Disable => match disable_stats() {
+            Ok(()) => out.ok(
+                "Stopped recording usage and deleted what was recorded.",
+                json!({ "enabled": false }),
+            ),
+            Err(e) => out.error("Failed to disable usage statistics", &e),
+        },
+        StatsCommands::Report { days } => match stats_report(days) {
+            Ok(report) if out.structured() => out.print(&json!({ "days": days, "report": report })),
+            Ok(report) => print_stats_report(out, &report, days),
+            Err(e) => out.error("Failed to report usage", &e),
+        },
+    }
+}
+
+fn print_stats_report(out: &Printer, report: &StatsReport, days: u64) {
+    let period = format!("the last {} day{}", days, if days == 1 { "" } else { "s" });
+    if report.switches == 0 && report.commits == 0 {
+        return out.info(&format!("Nothing was recorded in {}.", period));
+    }
+    println!("In {}:", period);
+    println!("  {} profile switch(es)", report.switches);
+    println!(
+        "  {} commit(s) checked by the identity guard, {} with a profile mismatch",
+        report.commits, report.mismatches
+    );
+    if !report.profiles.is_empty() {
+        println!();
+        println!("{:<16} {:>8} {:>8} {:>10}  LAST USED", "PROFILE", "SWITCHES", "COMMITS", "MISMATCHES");
+        for (profile, usage) in &report.profiles {
+            let last_used = usage.last_used.map(format_timestamp).unwrap_or_else(|| "-".to_string());
+            println!(
+                "{:<16} {:>8} {:>8} {:>10}  {}",
+                profile, usage.switches, usage.commits, usage.mismatches, last_used
+            );
+            for repo in &usage.repositories {
+                println!("  applied to {}", repo.display());
+            }
+        }
+    }
+    if !report.mismatched_repositories.is_empty() {
+        println!();
+        println!("Commits stopped for a profile mismatch:");
+        for (repo, count) in &report.mismatched_repositories {
+            println!("  {:>4}  {}", count, repo.display());
+        }
+    }
+    if report.mismatches > 0 {
+        out.info(&format!(
+            "\nYou committed {} time(s) in {} with a profile mismatch warning; `gitup guard status --path REPO` shows what the repositories above expect.",
+            report.mismatches, period
+        ));
+    }
+}
+
+// What the drift was checked against, e.g. "profile 'work' and the last manifest"
+fn applied_source(drift: &Drift) -> String {
+    match (&drift.profile, drift.manifest) {
+        (Some(profile), true) => format!("profile '{}' and the last manifest", profile),
+        (Some(profile), false) => format!("profile '{}'", profile),
+        (None, _) => "the last manifest".to_string(),
+    }
+}
+
+fn drifted_lines(drift: &Drift) -> Vec<String> {
+    drift
+        .drifted
+        .iter()
+        .map(|setting| {
+            let actual = if setting.actual.is_empty() { "unset".to_string() } else { setting.actual.join(", ") };
+            format!("[DRIFT] {} is {}, expected {}", setting.key, actual, setting.expected.join(", "))
+        })
+        .collect()
+}
+
+fn run_doctor(out: &Printer) {
+    let checks = run_checks();
+    let count = |status| checks.iter().filter(|c| c.status == status).count();
+    let (passed, warnings, failed) = (
+        count(CheckStatus::Pass),
+        count(CheckStatus::Warn),
+        count(CheckStatus::Fail),
+    );
+    if failed > 0 {
+        out.exit_code.set(EXIT_FAILURE);
+    }
+
+    if out.structured() {
+        out.print(&json!({
+            "status": if failed > 0 { "error" } else { "ok" },
+            "code": out.exit_code.get(),
+            "checks": checks,
+            "summary": { "passed": passed, "warnings": warnings, "failed": failed },
+        }));
+        return;
+    }
+    for check in &checks {
+        if out.options.quiet && check.status == CheckStatus::Pass {
+            continue;
+        }
+        let label = match check.status {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+        };
+        println!("[{}] {}: {}", label, check.name, check.message);
+        if let Some(hint) = &check.hint {
+            println!("       hint: {}", hint);
+        }
+    }
+    out.info(&format!("\n{} passed, {} warnings, {} failed", passed, warnings, failed));
+}
+
+// Run the scripts for an event. What they print goes to stderr, as git does with its hooks'
+// output. A failing script is returned, so one for a pre- event stops the operation
+fn run_scripts(out: &Printer, event: Event, context: &[(&str, String)]) -> Result<(), GitError> {
+    let quiet = out.options.quiet;
+    run_event(event, context, &mut |_, line| {
+        if !quiet {
+            eprintln!("{}", line);
+        }
+    })
+    .map(|_| ())
+}
+
+// Run the scripts for a post- event. The operation is done by then, so a failing script only
+// gets a warning
+fn run_post_scripts(out: &Printer, event: Event, context: &[(&str, String)]) {
+    if let Err(e) = run_scripts(out, event, context) {
+        eprintln!("Warning: {}", e);
+    }
+}
+
+// Switch to a profile with the profile switch scripts around it. They get the profile, the
+// one active before and the scope, and once switched, the name and email it set
+fn switch_profile(out: &Printer, profile: &str, scope: Scope) -> Result<GitProfile, GitError> {
+    let previous = active_profile().ok().flatten().unwrap_or_default();
+    let mut context = vec![
+        ("profile", profile.to_string()),
+        ("previous", previous),
+        ("scope", scope.name().to_string()),
+    ];
+    run_scripts(out, Event::PreProfileSwitch, &context)?;
+    let settings = use_profile(profile, scope)?;
+    context.extend([("name", settings.name.clone()), ("email", settings.email.clone())]);
+    run_post_scripts(out, Event::PostProfileSwitch, &context);
+    Ok(settings)
+}
+
+fn run_profile_command(out: &Printer, command: ProfileCommands) {
+    match command {
+        ProfileCommands::Create {
+            profile,
+            user,
+            email,
+            ssh_key,
+            editor,
+            default_branch,
+            pull,
+            eol,
+            autocrlf,
+            commit_template,
+            extra,
+            remotes,
+            extends,
+            description,
+            tags,
+        } => {
+            // A profile that extends another only needs what it changes
+            let (user, email) = match extends {
+                Some(_) => (user.or_else(|| Some(String::new())), email.or_else(|| Some(String::new()))),
+                None => (user, email),
+            };
+            let (Some(name), Some(email)) = (
+                user.or_else(|| prompt_if_interactive(out, "Enter your Git name: ")),
+                email.or_else(|| prompt_if_interactive(out, "Enter your Git email: ")),
+            ) else {
+                out.fail("Both --user and --email are required with --json, --format or --yes");
+                return;
+            };
+            let commit_template = match commit_template.as_deref().map(template_source).transpose() {
+                Ok(template) => template,
+                Err(e) => return out.error("Failed to create profile", &e),
+            };
+            let git_profile = GitProfile {
+                extends,
+                name,
+                email,
+                ssh_key,
+                editor,
+                default_branch,
+                pull,
+                eol,
+                autocrlf,
+                commit_template,
+                extra: extra.into_iter().collect(),
+                remotes,
+                description,
+                tags,
+                created_at: None,
+                last_used_at: None,
+            };
+            match create_profile(&profile, git_profile.clone()) {
+                Ok(()) => out.ok(
+                    &format!("Profile '{}' created.", profile),
+                    json!({ "profile": profile, "settings": git_profile }),
+                ),
+                Err(e) => out.error("Failed to create profile", &e),
+            }
+        }
+        ProfileCommands::New {
+            profile,
+            from_template,
+            user,
+            email,
+            ssh_key,
+            signing_key,
+            allow_unsafe,
+        } => {
+            // The keys are passed along as given; the name and email may still be asked for
+            let keys = PresetAnswers {
+                ssh_key,
+                signing_key,
+                ..Default::default()
+            };
+            new_profile_from_preset(out, profile, &from_template, user, email, keys, allow_unsafe)
+        }
+        ProfileCommands::Use {
+            profile,
+            local,
+            system,
+            recurse_submodules,
+            worktree,
+        } => {
+            let scope = match worktree {
+                Some(dir) => Scope::Worktree(dir),
+                None if system => Scope::System,
+                None => scope(local),
+            };
+            let profile = match profile.as_deref() {
+                Some("-") => match previous_profile(&scope) {
+                    Ok(previous) => previous,
+                    Err(e) => return out.error("Failed to find the previous profile", &e),
+                },
+                Some(profile) => profile.to_string(),
+                None if !out.interactive() || !io::stdin().is_terminal() || !io::stdout().is_terminal() => {
+                    return out.fail("Name the profile to use; it can only be picked from a list in a terminal");
+                }
+                None => match without_lock(picker::pick_profile) {
+                    Ok(Some(profile)) => profile,
+                    Ok(None) => return out.fail("No profile was picked."),
+                    Err(e) => return out.error("Failed to pick a profile", &e),
+                },
+            };
+            // The shared config of a linked worktree is every other worktree's config too
+            let shared = local && is_linked_worktree(Path::new(".")).unwrap_or(false);
+            let p = match switch_profile(out, &profile, scope.clone()) {
+                Ok(p) => p,
+                Err(e) => return out.error("Failed to use profile", &e),
+            };
+            let mut message = format!("Switched to profile '{}' ({} <{}>)", profile, p.name, p.email);
+            match &scope {
+                Scope::Worktree(dir) if dir == Path::new(".") => message.push_str(" in this worktree"),
+                Scope::Worktree(dir) => message.push_str(&format!(" in the worktree at {}", dir.display())),
+                _ => {}
+            }
+            if shared {
+                message.push_str(
+                    "; this is a linked worktree, so the repository's other worktrees use it too (`--worktree` applies it here only)",
+                );
+            }
+            let mut data = json!({ "profile": profile, "scope": scope.name(), "settings": p });
+            if recurse_submodules {
+                match use_profile_in_submodules(&profile, Path::new(".")) {
+                    Ok(found) => {
+                        message.push_str(&format!(" here and in {}", submodules(found.len())));
+                        data["submodules"] = json!(found);
+                    }
+                    Err(e) => return out.error("Failed to use the profile in the submodules", &e),
+                }
+            }
+            out.ok(&message, data);
+        }
+        ProfileCommands::Last { local } => run_profile_command(
+            out,
+            ProfileCommands::Use {
+                profile: Some("-".to_string()),
+                local,
+                system: false,
+                recurse_submodules: false,
+                worktree: None,
+            },
+        ),
+        ProfileCommands::List { tags, sort } => match list_profiles() {
+            Ok(mut profiles) => {
+                let active = active_profile().ok().flatten();
+                profiles.retain(|(_, p)| tags.iter().all(|tag| p.tags.contains(tag)));
+                // Profiles never created or used by this version of gitup go last
+                match sort {
+                    ProfileSort::Name => {}
+                    ProfileSort::Created => profiles.sort_by_key(|(_, p)| std::cmp::Reverse(p.created_at)),
+                    ProfileSort::LastUsed => profiles.sort_by_key(|(_, p)| std::cmp::Reverse(p.last_used_at)),
+                }
+                if out.structured() {
+                    let profiles: Vec<Value> = profiles
+                        .iter()
+                        .map(|(profile, p)| {
+                            let mut entry = json!({
+                                "profile": profile,
+                                "active": active.as_ref() == Some(profile),
+                            });
+                            if let (Some(entry), Ok(Value::Object(fields))) =
+                                (entry.as_object_mut(), serde_json::to_value(p))
+                            {
+                                entry.extend(fields);
+                            }
+                            entry
+                        })
+                        .collect();
+                    out.print(&json!({ "profiles": profiles }));
+                } else {
+                    for (profile, p) in &profiles {
+                        let marker = if active.as_ref() == Some(profile) { "*" } else { " " };
+                        let base = p.extends.as_ref().map(|base| format!(" (extends {})", base)).unwrap_or_default();
+                        let tags = if p.tags.is_empty() { String::new() } else { format!(" [{}]", p.tags.join(", ")) };
+                        let time = match sort {
+                            ProfileSort::Name => None,
+                            ProfileSort::Created => Some(("created", p.created_at)),
+                            ProfileSort::LastUsed => Some(("last used", p.last_used_at)),
+                        };
+                        let time = match time {
+                            Some((what, Some(timestamp))) => format!(", {} {}", what, format_timestamp(timestamp)),
+                            Some((what, None)) => format!(", {} unknown", what),
+                            None => String::new(),
+                        };
+                        println!("{} {}: {} <{}>{}{}{}", marker, profile, p.name, p.email, base, tags, time);
+                        if let Some(description) = &p.description {
+                            println!("      {}", description);
+                        }
+                    }
+                    if profiles.is_empty() && !tags.is_empty() {
+                        println!("No profile has the tag{} {}.", if tags.len() == 1 { "" } else { "s" }, tags.join(", "));
+                    }
+                }
+            }
+            Err(e) => out.error("Failed to load profiles", &e),
+        },
+        ProfileCommands::Describe {
+            profile,
+            description,
+            tags,
+            untags,
+        } => match describe_profile(&profile, description.as_deref(), &tags, &untags) {
+            Ok(updated) => {
+                let mut message = format!("Profile '{}'", profile);
+                match &updated.description {
+                    Some(description) => message.push_str(&format!(": {}", description)),
+                    None => message.push_str(" has no description"),
+                }
+                if !updated.tags.is_empty() {
+                    message.push_str(&format!(" [{}]", updated.tags.join(", ")));
+                }
+                out.ok(
+                    &message,
+                    json!({ "profile": profile, "description": updated.description, "tags": updated.tags }),
+                );
+            }
+            Err(e) => out.error("Failed to describe profile", &e),
+        },
+        ProfileCommands::Delete { profile } => match delete_profile(&profile) {
+            Ok(_) => out.ok(
+                &format!("Profile '{}' deleted.", profile),
+                json!({ "profile": profile }),
+            ),
+            Err(e) => out.error("Failed to delete profile", &e),
+        },
+        ProfileCommands::Rename { old, new } => match rename_profile(&old, &new) {
+            Ok(moved) => {
+                let mut message = format!("Profile '{}' renamed to '{}'.", old, new);
+                if !moved.is_empty() {
+                    let count = moved.len();
+                    message.push_str(&format!(
+                        " Moved {} directory binding{}.",
+                        count,
+                        if count == 1 { "" } else { "s" }
+                    ));
+                }
+                let bindings: Vec<&str> = moved.iter().map(|binding| binding.directory.as_str()).collect();
+                out.ok(&message, json!({ "profile": new, "previous": old, "bindings": bindings }));
+            }
+            Err(e) => out.error("Failed to rename profile", &e),
+        },
+        ProfileCommands::Copy {
+            source,
+            target,
+            user,
+            email,
+        } => match copy_profile(&source, &target, user.as_deref(), email.as_deref()) {
+            Ok(profile) => out.ok(
+                &format!("Profile '{}' created as a copy of '{}'.", target, source),
+                json!({ "profile": target, "source": source, "settings": profile }),
+            ),
+            Err(e) => out.error("Failed to copy profile", &e),
+        },
+        ProfileCommands::Export {
+            profile,
+            out: file,
+            include_key,
+        } => {
+            // The key is encrypted with the profiles passphrase, which is only known already
+            // when the profiles file itself is encrypted
+            if include_key && !matches!(profiles_encrypted(), Ok(true)) && !read_new_passphrase(out) {
+                return;
+            }
+            let file = file.unwrap_or_else(|| PathBuf::from(format!("{}.gitup", profile)));
+            match export_profile(&profile, &file, include_key) {
+                Ok(bundle) => out.ok(
+                    &format!("Exported profile '{}' to {}", profile, file.display()),
+                    json!({
+                        "profile": profile,
+                        "file": file,
+                        "bindings": bundle.bindings,
+                        "includes_key": bundle.ssh_key.is_some_and(|key| key.private_key.is_some()),
+                    }),
+                ),
+                Err(e) => out.error("Failed to export profile", &e),
+            }
+        }
+        ProfileCommands::Import {
+            file,
+            rename,
+            force,
+            allow_unsafe,
+        } => {
+            match import_profile(&file, rename.as_deref(), out.options.allow_overwrite(force), allow_unsafe) {
+                Ok(report) => {
+                    let mut message = vec![format!("Imported profile '{}'", report.profile)];
+                    if let Some(key) = &report.ssh_key {
+                        message.push(format!("SSH key: {}", key.display()));
+                    }
+                    for directory in &report.bound {
+                        message.push(format!("Bound to {}", directory));
+                    }
+                    for directory in &report.skipped {
+                        message.push(format!("Skipped binding to {} (directory doesn't exist)", directory));
+                    }
+                    if !report.blocked.is_empty() {
+                        message.push(unsafe_settings_note(&report.blocked));
+                    }
+                    out.ok(
+                        &message.join("\n"),
+                        json!({
+                            "profile": report.profile,
+                            "ssh_key": report.ssh_key,
+                            "bound": report.bound,
+                            "skipped": report.skipped,
+                            "blocked": report.blocked,
+                        }),
+                    );
+                }
+                Err(e) => out.error("Failed to import profile", &e),
+            }
+        }
+        ProfileCommands::ImportBatch { file, on_conflict } => {
+            let report = match read_roster(&file).and_then(|entries| import_roster(&entries, on_conflict)) {
+                Ok(report) => report,
+                Err(e) => return out.error("Failed to import the roster", &e),
+            };
+            let mut message = Vec::new();
+            if !report.created.is_empty() || report.overwritten.is_empty() {
+                let count = report.created.len();
+                message.push(format!("Created {} profile{}", count, if count == 1 { "" } else { "s" }));
+            }
+            for (wanted, renamed) in &report.renamed {
+                message.push(format!("'{}' was taken, so it was created as '{}'", wanted, renamed));
+            }
+            if !report.overwritten.is_empty() {
+                message.push(format!("Overwrote {}", report.overwritten.join(", ")));
+            }
+            if !report.skipped.is_empty() {
+                message.push(format!("Skipped {}, which already exist", report.skipped.join(", ")));
+            }
+            out.ok(&message.join("\n"), json!(report));
+        }
+        ProfileCommands::Encrypt => {
+            if !read_new_passphrase(out) {
+                return;
+            }
+            match encrypt_profiles() {
+                Ok(()) => out.ok("Profiles encrypted.", json!({ "encrypted": true })),
+                Err(e) => out.error("Failed to encrypt profiles", &e),
+            }
+        }
+        ProfileCommands::Decrypt => match decrypt_profiles() {
+            Ok(()) => out.ok("Profiles decrypted.", json!({ "encrypted": false })),
+            Err(e) => out.error("Failed to decrypt profiles", &e),
+        },
+        ProfileCommands::Bind { profile, directory } => match bind_profile(&profile, &directory) {
+            Ok(binding) => out.ok(
+                &format!(
+                    "Repositories under {} will now use profile '{}'",
+                    binding.directory, binding.profile
+                ),
+                json!({
+                    "profile": binding.profile,
+                    "directory": binding.directory,
+                    "config_file": binding.config_file,
+                }),
+            ),
+            Err(e) => out.error("Failed to bind profile", &e),
+        },
+        ProfileCommands::Remote {
+            profile,
+            patterns,
+            remove,
+        } => {
+            let result = if remove {
+                remove_remote_patterns(&profile, &patterns)
+            } else {
+                add_remote_patterns(&profile, &patterns)
+            };
+            match result {
+                Ok(updated) if updated.remotes.is_empty() => out.ok(
+                    &format!("Profile '{}' has no remote patterns.", profile),
+                    json!({ "profile": profile, "remotes": updated.remotes }),
+                ),
+                Ok(updated) => out.ok(
+                    &format!("Profile '{}' is suggested for {}", profile, updated.remotes.join(", ")),
+                    json!({ "profile": profile, "remotes": updated.remotes }),
+                ),
+                Err(e) => out.error("Failed to update remote patterns", &e),
+            }
+        }
+    }
+}
+
+fn run_gpg_command(out: &Printer, command: GpgCommands) {
+    match command {
+        GpgCommands::Generate {
+            profile,
+            expires,
+            no_passphrase,
+        } => match generate_gpg_key(&profile, &expires, !no_passphrase) {
+            Ok(key) => {
+                for warning in &key.warnings {
+                    out.info(&format!("Warning: {}", warning));
+                }
+                let mut message = vec![format!("Generated GPG key {} for profile '{}'", key.fingerprint, profile)];
+                if let Some(pinentry) = &key.pinentry {
+                    message.push(format!("gpg-agent now asks for the passphrase with {}", pinentry.display()));
+                }
+                message.push(format!("The profile signs commits and tags with it; `gitup profile use {}` applies that.", profile));
+                message.push(format!("Upload the public key {} to your Git host as a GPG key.", key.public_key.display()));
+                out.ok(&message.join("\n"), json!({ "gpg": key }));
+            }
+            Err(e) => out.error("Failed to generate the GPG key", &e),
+        },
+    }
+}
+
+fn run_sign_command(out: &Printer, command: SignCommands) {
+    match command {
+        SignCommands::Publish { provider, host, title } => {
+            let host = host.unwrap_or_else(|| provider.default_host().to_string());
+            match publish_signing_key(provider, &host, title.as_deref()) {
+                Ok((key, published)) => {
+                    let kind = if key.format == "ssh" { "SSH" } else { "GPG" };
+                    let message = if published.added {
+                        format!(
+                            "Uploaded your {} signing key {} to {} as {}; commits signed with it under a verified email now show as verified.",
+                            kind, key.key, host, published.username
+                        )
+                    } else {
+                        format!(
+                            "Your {} signing key {} is already on {} for {}.",
+                            kind, key.key, host, published.username
+                        )
+                    };
+                    out.ok(
+                        &message,
+                        json!({
+                            "provider": provider.to_string(),
+                            "host": host,
+                            "username": published.username,
+                            "format": key.format,
+                            "key": key.key,
+                            "fingerprint": key.fingerprint,
+                            "id": published.id,
+                            "added": published.added,
+                        }),
+                    );
+                }
+                Err(e) => out.error("Failed to publish the signing key", &e),
+            }
+        }
+        SignCommands::Setup {
+            ssh: _,
+            key,
+            email,
+            local,
+        } => match setup_ssh_signing(&key, email.as_deref(), scope(local)) {
+            Ok(setup) => {
+                let mut message = vec![format!(
+                    "Commits and tags{} will be signed with {}",
+                    if local { " in this repository" } else { "" },
+                    setup.key.display()
+                )];
+                message.extend(setup.settings.iter().map(|(key, value)| format!("  {}={}", key, value)));
+                message.push(match setup.added {
+                    true => format!("Added the key for {} to {}", setup.email, setup.allowed_signers.display()),
+                    false => format!("The key is already allowed for {} in {}", setup.email, setup.allowed_signers.display()),
+                });
+                message.push("`git log --show-signature` now verifies your signatures.".to_string());
+                out.ok(&message.join("\n"), json!({ "signing": setup }));
+            }
+            Err(e) => out.error("Failed to set up signing", &e),
+        },
+    }
+}
+
+fn run_ssh_command(out: &Printer, command: SshCommands) {
+    match command {
+        SshCommands::Generate { profile, key_type } => match generate_ssh_key(&profile, key_type) {
+            Ok(path) => out.ok(
+                &format!(
+                    "Generated {} key {}\nPublic key: {}.pub",
+                    key_type,
+                    path.display(),
+                    path.display()
+                ),
+                json!({ "profile": profile, "type": key_type.as_str(), "key": path }),
+            ),
+            Err(e) => out.error("Failed to generate SSH key", &e),
+        },
+        SshCommands::List => match list_ssh_keys() {
+            Ok(keys) => {
+                if out.structured() {
+                    out.print(&json!({ "keys": keys }));
+                } else {
+                    for key in keys {
+                        println!("{}", key.display());
+                    }
+                }
+            }
+            Err(e) => out.error("Failed to list SSH keys", &e),
+        },
+        SshCommands::Add { key } => match add_to_agent(&key) {
+            Ok(()) => out.ok(
+                &format!("Added {} to ssh-agent", key.display()),
+                json!({ "key": key }),
+            ),
+            Err(e) => out.error("Failed to add key to ssh-agent", &e),
+        },
+        SshCommands::Host {
+            profile,
+            host,
+            rewrite,
+            remote,
+        } => {
+            let alias = match write_host_alias(&profile, &host) {
+                Ok(alias) => alias,
+                Err(e) => return out.error("Failed to write SSH host alias", &e),
+            };
+            let Some(repo) = rewrite else {
+                return out.ok(
+                    &format!("Added Host {} to ~/.ssh/config; use git@{}:owner/repo.git", alias, alias),
+                    json!({ "profile": profile, "host": host, "alias": alias }),
+                );
+            };
+            match rewrite_remote(&repo, &remote, &host, &alias) {
+                Ok(url) => out.ok(
+                    &format!("Added Host {} to ~/.ssh/config and set {} to {}", alias, remote, url),
+                    json!({ "profile": profile, "host": host, "alias": alias, "remote": remote, "url": url }),
+                ),
+                Err(e) => out.error("Failed to rewrite remote", &e),
+            }
+        }
+        SshCommands::Publish {
+            provider,
+            host,
+            profile,
+            key,
+            title,
+            no_check,
+        } => run_ssh_publish(out, provider, host, profile, key, title.as_deref(), no_check),
+        SshCommands::Trust { providers } => {
+            let mut lines = Vec::new();
+            let mut trusted = Vec::new();
+            let mut failed = Vec::new();
+            for provider in providers {
+                match trust_host_keys(provider) {
+                    Ok(host) => {
+                        let count = host.keys.len();
+                        let noun = if count == 1 { "key" } else { "keys" };
+                        lines.push(format!("Pinned {} host {} for {}", count, noun, host.host));
+                        lines.extend(host.keys.iter().map(|key| format!("  {}", key)));
+                        lines.extend(host.skipped.iter().map(|key| {
+                            format!("  skipped {}: not a fingerprint gitup knows", key)
+                        }));
+                        trusted.push(json!({
+                            "host": host.host,
+                            "keys": host.keys,
+                            "skipped": host.skipped,
+                            "known_hosts": host.known_hosts,
+                        }));
+                    }
+                    Err(e) => {
+                        lines.push(format!("{}: failed: {}", provider.default_host(), e));
+                        failed.push(json!({ "host": provider.default_host(), "error": e.to_string() }));
+                    }
+                }
+            }
+            if failed.is_empty() {
+                out.ok(&lines.join("\n"), json!({ "hosts": trusted }));
+            } else if out.structured() {
+                out.exit_code.set(EXIT_FAILURE);
+                out.print(&json!({ "status": "error", "code": EXIT_FAILURE, "hosts": trusted, "failed": failed }));
+            } else {
+                out.fail(&lines.join("\n"));
+            }
+        }
+    }
+}
+
+fn run_ssh_publish(
+    out: &Printer,
+    provider: Provider,
+    host: Option<String>,
+    profile: Option<String>,
+    key: Option<PathBuf>,
+    title: Option<&str>,
+    no_check: bool,
+) {
+    let host = host.unwrap_or_else(|| provider.default_host().to_string());
+    let key = match key {
+        Some(key) => key,
+        None => {
+            let profile = match profile.map(Ok).unwrap_or_else(|| {
+                active_profile()?.ok_or_else(|| {
+                    GitError::InvalidInput("no profile is in use; pass --profile or --key".to_string())
+                })
+            }) {
+                Ok(profile) => profile,
+                Err(e) => return out.error("Failed to find the key to publish", &e),
+            };
+            match get_profile(&profile).map(|p| p.ssh_key) {
+                Ok(Some(key)) => key,
+                Ok(None) => {
+                    return out.fail(&format!(
+                        "Profile '{}' has no SSH key; create one with `gitup ssh generate -p {}`",
+                        profile, profile
+                    ))
+                }
+                Err(e) => return out.error("Failed to find the key to publish", &e),
+            }
+        }
+    };
+    let published = match read_public_key(&key)
+        .and_then(|public_key| publish_ssh_key(provider, &host, &public_key, title))
+    {
+        Ok(published) => published,
+        Err(e) => return out.error("Failed to publish the SSH key", &e),
+    };
+    let mut lines = vec![if published.added {
+        format!("Uploaded {} to {} for {}.", key.display(), host, published.username)
+    } else {
+        format!("{} is already on {} for {}.", key.display(), host, published.username)
+    }];
+    let mut data = json!({
+        "provider": provider.to_string(),
+        "host": host,
+        "username": published.username,
+        "key": key,
+        "id": published.id,
+        "added": published.added,
+    });
+
+    // A dry run didn't upload anything for ssh to find
+    if no_check || DryRun::is_enabled() {
+        return out.ok(&lines.join("\n"), data);
+    }
+    let private_key = if key.extension().is_some_and(|ext| ext == "pub") {
+        key.with_extension("")
+    } else {
+        key.clone()
+    };
+    let connection = match check_ssh_connection(&host, Some(&private_key)) {
+        Ok(connection) => connection,
+        Err(e) => return out.error("Uploaded the key, but couldn't run ssh to check it", &e),
+    };
+    data["connection"] = json!({
+        "authenticated": connection.authenticated,
+        "username": connection.username,
+        "message": connection.message,
+    });
+    if connection.authenticated {
+        lines.push(match &connection.username {
+            Some(username) => format!("ssh -T git@{} authenticated as {}.", host, username),
+            None => format!("ssh -T git@{} authenticated.", host),
+        });
+        out.ok(&lines.join("\n"), data);
+    } else if out.structured() {
+        out.exit_code.set(EXIT_FAILURE);
+        data["status"] = json!("error");
+        data["code"] = json!(EXIT_FAILURE);
+        data["error"] = json!(format!("ssh -T git@{} failed: {}", host, connection.message));
+        out.print(&data);
+    } else {
+        lines.push(format!("ssh -T git@{} failed: {}", host, connection.message));
+        out.fail(&lines.join("\n"));
+    }
+}
+
+fn run_alias_command(out: &Printer, command: AliasCommands) {
+    match command {
+        AliasCommands::Add { name, command } => {
+            let command = command.join(" ");
+            match set_alias(&name, &command) {
+                Ok(()) => out.ok(
+                    &format!("Alias '{}' set to '{}'", name, command),
+                    json!({ "alias": name, "command": command }),
+                ),
+                Err(e) => out.error("Failed to set alias", &e),
+            }
+        }
+        AliasCommands::Remove { name } => match remove_alias(&name) {
+            Ok(()) => out.ok(&format!("Alias '{}' removed.", name), json!({ "alias": name })),
+            Err(e) => out.error("Failed to remove alias", &e),
+        },
+        AliasCommands::List => match list_aliases() {
+            Ok(aliases) => {
+                if out.structured() {
+                    let aliases: Vec<Value> = aliases
+                        .iter()
+                        .map(|(name, command)| json!({ "alias": name, "command": command }))
+                        .collect();
+                    out.print(&json!({ "aliases": aliases }));
+                } else {
+                    for (name, command) in aliases {
+                        println!("{} = {}", name, command);
+                    }
+                }
+            }
+            Err(e) => out.error("Failed to list aliases", &e),
+        },
+        AliasCommands::Import { pack } => match import_alias_pack(&pack) {
+            Ok(aliases) => {
+                let names: Vec<&str> = aliases.iter().map(|(name, _)| name.as_str()).collect();
+                out.ok(
+                    &format!("Imported {} aliases from '{}': {}", names.len(), pack, names.join(", ")),
+                    json!({ "pack": pack, "aliases": names }),
+                );
+            }
+            Err(e) => out.error("Failed to import alias pack", &e),
+        },
+    }
+}
+
+fn print_changes(out: &Printer, changes: &[ConfigChange]) {
+    if out.structured() {
+        let changes: Vec<Value> = changes
+            .iter()
+            .map(|change| match change {
+                ConfigChange::Added { key, value } => {
+                    json!({ "change": "added", "key": key, "value": value })
+                }
+                ConfigChange::Removed { key, value } => {
+                    json!({ "change": "removed", "key": key, "value": value })
+                }
+                ConfigChange::Changed { key, old, new } => {
+                    json!({ "change": "changed", "key": key, "old": old, "new": new })
+                }
+            })
+            .collect();
+        out.print(&json!({ "changes": changes }));
+        return;
+    }
+
+    if changes.is_empty() {
+        println!("No differences.");
+    }
+    for change in changes {
+        match change {
+            ConfigChange::Added { key, value } => println!("+ {} = {}", key, value),
+            ConfigChange::Removed { key, value } => println!("- {} = {}", key, value),
+            ConfigChange::Changed { key, old, new } => println!("~ {}: {} -> {}", key, old, new),
+        }
+    }
+}
+
+// Parse a KEY=VALUE command-line argument
+fn parse_key_value(arg: &str) -> Result<(String, String), String> {
+    arg.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", arg))
+}
+
+fn run_ignore_command(out: &Printer, command: IgnoreCommands) {
+    match command {
+        IgnoreCommands::Add { templates } => match add_templates(&templates) {
+            Ok(path) => out.ok(
+                &format!("Added {} to {}", templates.join(", "), path.display()),
+                json!({ "templates": templates, "file": path }),
+            ),
+            Err(e) => out.error("Failed to add ignore templates", &e),
+        },
+        IgnoreCommands::Remove { templates } => match remove_templates(&templates) {
+            Ok(path) => out.ok(
+                &format!("Removed {} from {}", templates.join(", "), path.display()),
+                json!({ "templates": templates, "file": path }),
+            ),
+            Err(e) => out.error("Failed to remove ignore templates", &e),
+        },
+        IgnoreCommands::List => match installed_templates() {
+            Ok(installed) => {
+                if out.structured() {
+                    out.print(&json!({
+                        "available": available_templates(),
+                        "installed": installed,
+                    }));
+                } else {
+                    for template in available_templates() {
+                        let marker = if installed.contains(&template) { "*" } else { " " };
+                        println!("{} {}", marker, template);
+                    }
+                }
+            }
+            Err(e) => out.error("Failed to read the global gitignore", &e),
+        },
+    }
+}
+
+fn run_auth_command(out: &Printer, command: AuthCommands) {
+    let (provider, token, host) = match command {
+        AuthCommands::Github { token, host } => (Provider::GitHub, token, host),
+        AuthCommands::Gitlab { token, host } => (Provider::GitLab, token, host),
+        AuthCommands::Bitbucket { token, username } => {
+            let host = Provider::Bitbucket.default_host().to_string();
+            let Some(token) = token.or_else(|| prompt_passphrase(&format!("{} app password: ", host))) else {
+                return out.fail("An app password is required; pass --token when not running in a terminal");
+            };
+            (Provider::Bitbucket, Some(format!("{}:{}", username, token)), host)
+        }
+        AuthCommands::Credential { host, operation } => {
+            // git sends the request on stdin; it has to be drained even though the host is known
+            let _ = io::copy(&mut io::stdin(), &mut io::sink());
+            match credential_response(&host, &operation) {
+                Ok(response) => print!("{}", response),
+                Err(e) => out.error("gitup credential helper", &e),
+            }
+            return;
+        }
+    };
+
+    let Some(token) = token.or_else(|| prompt_passphrase(&format!("{} token: ", host))) else {
+        return out.fail("A token is required; pass --token when not running in a terminal");
+    };
+    match login(provider, &host, &token) {
+        Ok(info) => {
+            let scopes = if info.scopes.is_empty() {
+                "no scopes reported".to_string()
+            } else {
+                format!("scopes: {}", info.scopes.join(", "))
+            };
+            out.ok(
+                &format!(
+                    "Authenticated to {} as {} ({}); git will use the token for https://{}",
+                    info.host, info.username, scopes, info.host
+                ),
+                json!({
+                    "provider": info.provider.to_string(),
+                    "host": info.host,
+                    "username": info.username,
+                    "scopes": info.scopes,
+                }),
+            );
+        }
+        Err(e) => out.error(&format!("Failed to set up {} authentication", provider), &e),
+    }
+}
+
+fn run_sync_command(out: &Printer, command: SyncCommands) {
+    let result = match command {
+        SyncCommands::Init { url } => match sync_init(&url) {
+            Ok(()) => {
+                return out.ok(
+                    &format!("Syncing gitup state with {}; run `gitup sync pull` to fetch it.", url),
+                    json!({ "url": url, "repository": sync_repo() }),
+                )
+            }
+            Err(e) => return out.error("Failed to set up sync", &e),
+        },
+        SyncCommands::Push => sync_push(),
+        SyncCommands::Pull { force, allow_unsafe } => sync_pull(force, allow_unsafe),
+    };
+    match result {
+        Ok(SyncOutcome::UpToDate) => out.ok("Already up to date.", json!({ "files": [] })),
+        Ok(SyncOutcome::Pushed { files }) => out.ok(
+            &sync_summary("Pushed", &files),
+            json!({ "pushed": true, "files": files }),
+        ),
+        Ok(SyncOutcome::Pulled { files }) => {
+            let mut message = vec![sync_summary("Pulled", &files)];
+            message.extend(files.iter().map(|file| format!("  {}", file)));
+            out.ok(&message.join("\n"), json!({ "pulled": true, "files": files }));
+        }
+        Err(e) => out.error("Sync failed", &e),
+    }
+}
+
+// Print the script that hooks gitup into the shell's completion system; the script asks
+// the gitup binary itself for candidates, so it never goes stale
+fn print_completions(out: &Printer, shell: CompletionShell) {
+    let name = shell.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default();
+    let shells = Shells::builtins();
+    let Some(completer) = shells.completer(&name) else {
+        return out.fail(&format!("Completions aren't supported for {}", name));
+    };
+    let exe = std::env::current_exe()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "gitup".to_string());
+    let mut script = Vec::new();
+    if let Err(e) = completer.write_registration(COMPLETE_ENV, "gitup", "gitup", &exe, &mut script) {
+        return out.error("Failed to write completion script", &e.into());
+    }
+    if out.structured() {
+        out.print(&json!({ "shell": name, "script": String::from_utf8_lossy(&script) }));
+    } else {
+        print!("{}", String::from_utf8_lossy(&script));
+    }
+}
+
+fn run_tui(out: &Printer) {
+    if out.structured() || !io::stdout().is_terminal() || !io::stdin().is_terminal() {
+        return out.fail("gitup tui needs a terminal; use the other commands in scripts");
+    }
+    #[cfg(feature = "tui")]
+    if let Err(e) = tui::run() {
+        out.error("The dashboard failed", &e);
+    }
+    #[cfg(not(feature = "tui"))]
+    out.fail("This gitup was built without the dashboard; reinstall with `cargo install gitup --features tui`");
+}
+
+// Profile names offered when completing a profile argument
+fn profile_candidates() -> Vec<CompletionCandidate> {
+    list_profiles()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(profile, p)| CompletionCandidate::new(profile).help(Some(format!("{} <{}>", p.name, p.email).into())))
+        .collect()
+}
+
+// A merge can move history without changing any file
+fn sync_summary(action: &str, files: &[String]) -> String {
+    if files.is_empty() {
+        format!("{}; no files changed.", action)
+    } else {
+        format!("{} {} changed file(s).", action, files.len())
+    }
+}
+
+fn run_secret_command(out: &Printer, command: SecretCommands) {
+    match command {
+        SecretCommands::Set { name, value } => {
+            let Some(value) = value.or_else(|| prompt_passphrase("Secret value: ")) else {
+                return out.fail("A value is required; pass --value when not running in a terminal");
+            };
+            match set_secret(&name, &value) {
+                Ok(()) => out.ok(
+                    &format!("Stored secret '{}'; reference it in profiles as keyring:{}", name, name),
+                    json!({ "secret": name }),
+                ),
+                Err(e) => out.error("Failed to store secret", &e),
+            }
+        }
+        SecretCommands::Get { name } => match get_secret(&name) {
+            Ok(Some(value)) => {
+                if out.structured() {
+                    out.print(&json!({ "secret": name, "value": value }));
+                } else {
+                    println!("{}", value);
+                }
+            }
+            Ok(None) => out.fail(&format!("No secret named '{}'", name)),
+            Err(e) => out.error("Failed to read secret", &e),
+        },
+        SecretCommands::Delete { name } => match delete_secret(&name) {
+            Ok(true) => out.ok(&format!("Secret '{}' deleted.", name), json!({ "secret": name })),
+            Ok(false) => out.fail(&format!("No secret named '{}'", name)),
+            Err(e) => out.error("Failed to delete secret", &e),
+        },
+    }
+}
+
+fn run_hooks_command(out: &Printer, command: HooksCommands) {
+    match command {
+        HooksCommands::Install { hooks } => match install_hooks(&hooks) {
+            Ok(dir) => out.ok(
+                &format!("Installed {} in {}", hooks.join(", "), dir.display()),
+                json!({ "hooks": hooks, "directory": dir }),
+            ),
+            Err(e) => out.error("Failed to install hooks", &e),
+        },
+        HooksCommands::Remove { hooks } => match remove_hooks(&hooks) {
+            Ok(dir) => out.ok(
+                &format!("Removed {} from {}", hooks.join(", "), dir.display()),
+                json!({ "hooks": hooks, "directory": dir }),
+            ),
+            Err(e) => out.error("Failed to remove hooks", &e),
+        },
+        HooksCommands::List => match installed_hooks() {
+            Ok(installed) => {
+                if out.structured() {
+                    let hooks: Vec<Value> = available_hooks()
+                        .iter()
+                        .map(|hook| {
+                            json!({
+                                "hook": hook.name,
+                                "git_hook": hook.git_hook,
+                                "description": hook.description,
+                                "installed": installed.contains(&hook.name),
+                            })
+                        })
+                        .collect();
+                    out.print(&json!({ "directory": hooks_dir(), "hooks": hooks }));
+                } else {
+                    for hook in available_hooks() {
+                        let marker = if installed.contains(&hook.name) { "*" } else { " " };
+                        println!("{} {} ({}): {}", marker, hook.name, hook.git_hook, hook.description);
+                    }
+                }
+            }
+            Err(e) => out.error("Failed to read installed hooks", &e),
+        },
+    }
+}
+
+// Install git with brew, bootstrapping Homebrew from its install script when it's missing. The
+// script is shown by checksum and run only after confirmation, or when it matches a pinned hash
+fn install_git_with_homebrew(out: &Printer, pinned_sha256: Option<&str>) {
+    if find_brew().is_some() {
+        match install_git_with(Some("brew"), None) {
+            Ok(pm) => out.ok(
+                &format!("Git has been installed successfully using {}.", pm),
+                json!({ "installed": true, "package_manager": pm, "homebrew_installed": false }),
+            ),
+            Err(e) => out.error("Failed to install Git", &e),
+        }
+        return;
+    }
+
+    let script = match fetch_homebrew_script() {
+        Ok(script) => script,
+        Err(e) => return out.error("Failed to download Homebrew's install script", &e),
+    };
+    match pinned_sha256 {
+        Some(_) => {
+            if !out.confirm(&format!("Homebrew is not installed. Run its install script from {}?", script.url)) {
+                return out.fail("Homebrew is needed to install Git with --with-brew.");
+            }
+        }
+        // --yes alone doesn't vouch for a script nobody has looked at
+        None => {
+            if !out.interactive() {
+                return out.fail(&format!(
+                    "Homebrew is not installed. Its install script ({}) has SHA-256 {}; \
+                     check it and pass --brew-sha256 {} to install Homebrew without a prompt.",
+                    script.url, script.sha256, script.sha256
+                ));
+            }
+            println!("Homebrew is not installed. Its install script is {}", script.url);
+            println!("SHA-256: {}", script.sha256);
+            if prompt_user("Run it to install Homebrew? (y/n): ").to_lowercase() != "y" {
+                return out.fail("Homebrew is needed to install Git with --with-brew.");
+            }
+        }
+    }
+
+    let brew = match install_homebrew(&script, pinned_sha256) {
+        Ok(brew) => brew,
+        Err(e) => return out.error("Failed to install Homebrew", &e),
+    };
+    match brew_install(&brew, "git") {
+        Ok(()) => out.ok(
+            &match brew.parent().filter(|_| brew.is_absolute()) {
+                Some(bin) => format!(
+                    "Homebrew and Git have been installed. Add {} to your PATH (see `brew shellenv`).",
+                    bin.display()
+                ),
+                None => "Homebrew and Git have been installed.".to_string(),
+            },
+            json!({ "installed": true, "package_manager": "brew", "homebrew_installed": true, "brew": brew }),
+        ),
+        Err(e) => out.error("Failed to install Git with Homebrew", &e),
+    }
+}
+
+fn run_clone(out: &Printer, url: &str, directory: Option<&Path>, profile: Option<&str>, guard: bool, recurse_submodules: bool) {
+    match clone_repository(url, directory, profile, guard, recurse_submodules) {
+        Ok(cloned) => {
+            let mut message = format!("Cloned {} into {}", url, cloned.directory.display());
+            match &cloned.profile {
+                Some(profile) => message.push_str(&format!(" using profile '{}'", profile)),
+                None => message.push_str(
+                    "; no profile matches its URL, so the global identity applies (add one with `gitup profile remote`)",
+                ),
+            }
+            if cloned.profile.is_some() && !cloned.submodules.is_empty() {
+                message.push_str(&format!(" here and in {}", submodules(cloned.submodules.len())));
+            }
+            if let Some(alias) = &cloned.ssh_alias {
+                message.push_str(&format!(", through SSH host alias {}", alias));
+            }
+            if cloned.guard_enabled {
+                message.push_str(", and enabled the identity guard");
+            }
+            message.push('.');
+            out.ok(&message, json!({ "clone": cloned }));
+        }
+        Err(e) => out.error("Failed to clone the repository", &e),
+    }
+}
+
+fn run_suggest(out: &Printer, repo: &Path, apply: bool) {
+    let suggestion = match suggest_profile(repo) {
+        Ok(Some(suggestion)) => suggestion,
+        Ok(None) => {
+            return out.ok(
+                "No profile matches this repository's remotes; add patterns with `gitup profile remote`.",
+                json!({ "profile": null }),
+            )
+        }
+        Err(e) => return out.error("Failed to suggest a profile", &e),
+    };
+    let reason = match &suggestion.pattern {
+        Some(pattern) => format!("{} matches {}", suggestion.url, pattern),
+        None => format!("{} uses its SSH host alias", suggestion.url),
+    };
+
+    if !apply {
+        return out.ok(
+            &format!("Suggested profile: {} ({} {})", suggestion.profile, suggestion.remote, reason),
+            json!({ "profile": suggestion.profile, "suggestion": suggestion, "applied": false }),
+        );
+    }
+    match switch_profile(out, &suggestion.profile, Scope::Local(repo.to_path_buf())) {
+        Ok(profile) => out.ok(
+            &format!(
+                "Applied profile '{}' to this repository as {} <{}> ({} {})",
+                suggestion.profile, profile.name, profile.email, suggestion.remote, reason
+            ),
+            json!({ "profile": suggestion.profile, "suggestion": suggestion, "applied": true }),
+        ),
+        Err(e) => out.error("Failed to apply the suggested profile", &e),
+    }
+}
+
+fn run_scan_identities(out: &Printer, dir: &Path, commits: usize) {
+    let scan = match scan_identities(dir, commits) {
+        Ok(scan) => scan,
+        Err(e) => return out.error("Failed to scan for identity leaks", &e),
+    };
+    if !scan.leaks.is_empty() {
+        out.exit_code.set(EXIT_FAILURE);
+    }
+
+    if out.structured() {
+        out.print(&json!({
+            "status": if scan.leaks.is_empty() { "ok" } else { "error" },
+            "code": out.exit_code.get(),
+            "repositories": scan.repositories,
+            "leaks": scan.leaks,
+        }));
+        return;
+    }
+    for leak in &scan.leaks {
+        println!(
+            "{}: {} {} by {} (profile '{}' uses {}): {}",
+            leak.repository.display(),
+            &leak.commit[..leak.commit.len().min(10)],
+            leak.date,
+            leak.email,
+            leak.profile,
+            leak.expected_email,
+            leak.subject
+        );
+    }
+    if scan.repositories.is_empty() {
+        out.info("No repositories below a directory bound with `gitup profile bind` were found.");
+    } else {
+        out.info(&format!(
+            "{} repositories checked: {} commits made with the wrong identity",
+            scan.repositories.len(),
+            scan.leaks.len()
+        ));
+    }
+}
+
+fn run_fix_author(out: &Printer, repo: &Path, from: &str, to_profile: &str, mailmap: bool) {
+    let profile = match get_profile(to_profile) {
+        Ok(profile) => profile,
+        Err(e) => return out.error("Failed to fix the author", &e),
+    };
+    let to = format!("{} <{}>", profile.name, profile.email);
+
+    if mailmap {
+        return match add_mailmap(repo, from, &profile.name, &profile.email) {
+            Ok(path) => out.ok(
+                &format!("{} now shows {} as {}; commit {} to share it.", path.display(), from, to, path.display()),
+                json!({ "mailmap": path, "from": from, "to": to }),
+            ),
+            Err(e) => out.error("Failed to update .mailmap", &e),
+        };
+    }
+
+    let plan = match plan_author_fix(repo, from) {
+        Ok(plan) => plan,
+        Err(e) => return out.error("Failed to fix the author", &e),
+    };
+    if plan.commits.is_empty() {
+        return out.ok(
+            &format!("No unpushed commits on {} are authored by {}.", plan.branch, from),
+            json!({ "branch": plan.branch, "commits": plan.commits }),
+        );
+    }
+
+    if !out.structured() {
+        println!("Unpushed commits on {} authored by {}:", plan.branch, from);
+        for commit in &plan.commits {
+            println!("  {} {}", &commit.commit[..commit.commit.len().min(10)], commit.subject);
+        }
+    }
+    // Rewriting history is the one thing here that can't be undone by `gitup undo`
+    if !DryRun::is_enabled() {
+        let question = format!(
+            "Rewrite {} commits as {}? Every unpushed commit on {} gets a new hash",
+            plan.commits.len(),
+            to,
+            plan.branch
+        );
+        if !out.confirm(&question) {
+            return out.fail("Nothing was changed; pass --yes to rewrite without asking.");
+        }
+    }
+
+    match fix_author(&plan, &profile.name, &profile.email) {
+        Ok(backup) => out.ok(
+            &format!(
+                "Rewrote {} commits as {}. The old branch is saved as {}; `git reset --hard {}` goes back to it.",
+                plan.commits.len(),
+                to,
+                backup,
+                backup
+            ),
+            json!({ "branch": plan.branch, "commits": plan.commits, "to": to, "backup": backup }),
+        ),
+        Err(e) => out.error("Failed to rewrite the commits; the branch was left as it was", &e),
+    }
+}
+
+fn run_privacy_command(out: &Printer, command: PrivacyCommands) {
+    match command {
+        PrivacyCommands::Enable {
+            provider,
+            host,
+            username,
+            use_config_only,
+        } => {
+            let host = host.unwrap_or_else(|| provider.default_host().to_string());
+            let noreply = match noreply_email(provider, &host, username.as_deref()) {
+                Ok(noreply) => noreply,
+                Err(e) => return out.error("Failed to find the noreply address", &e),
+            };
+            if !noreply.id_based {
+                out.info("No token is stored, so the address is based on the user name and stops matching if the account is renamed.");
+            }
+            match enable_privacy(&noreply.email, use_config_only) {
+                Ok(()) => out.ok(
+                    &format!("Commits will use {}.", noreply.email),
+                    json!({ "email": noreply.email, "id_based": noreply.id_based, "use_config_only": use_config_only }),
+                ),
+                Err(e) => out.error("Failed to set the noreply address", &e),
+            }
+        }
+        PrivacyCommands::Status => {
+            let status = privacy_status();
+            if out.structured() {
+                return out.print(&json!(status));
+            }
+            println!("Email:           {}", status.email.as_deref().unwrap_or("(not set)"));
+            println!("Noreply:         {}", if status.noreply { "yes" } else { "no" });
+            println!("useConfigOnly:   {}", if status.use_config_only { "yes" } else { "no" });
+        }
+    }
+}
+
+fn run_guard_command(out: &Printer, command: GuardCommands) {
+    match command {
+        GuardCommands::Enable => match enable_guard() {
+            Ok(dir) => out.ok(
+                &format!("Enabled the identity guard in {}", dir.display()),
+                json!({ "hook": GUARD_HOOK, "directory": dir }),
+            ),
+            Err(e) => out.error("Failed to enable the identity guard", &e),
+        },
+        GuardCommands::Disable => match disable_guard() {
+            Ok(dir) => out.ok(
+                &format!("Disabled the identity guard in {}", dir.display()),
+                json!({ "hook": GUARD_HOOK, "directory": dir }),
+            ),
+            Err(e) => out.error("Failed to disable the identity guard", &e),
+        },
+        GuardCommands::Status { path } => {
+            let enabled = match is_guard_enabled() {
+                Ok(enabled) => enabled,
+                Err(e) => return out.error("Failed to read installed hooks", &e),
+            };
+            match check_identity(&path) {
+                Ok(check) => {
+                    if out.structured() {
+                        out.print(&json!({ "enabled": enabled, "passed": check.passed(), "check": check }));
+                        return;
+                    }
+                    println!("Guard:    {}", if enabled { "enabled" } else { "disabled" });
+                    match (&check.profile, &check.expected_email) {
+                        (Some(profile), Some(expected)) => println!("Expects:  {} <{}>", profile, expected),
+                        _ => println!("Expects:  nothing, no profile is bound to this directory or matches its remotes"),
+                    }
+                    println!("Email:    {}", check.email.as_deref().unwrap_or("(not set)"));
+                }
+                Err(e) => out.error("Failed to check the identity", &e),
+            }
+        }
+        GuardCommands::Check { path } => match check_identity(&path).inspect(|check| {
+            record(StatsEvent::Commit {
+                repo: path.clone(),
+                profile: check.profile.clone(),
+                matched: check.passed(),
+            })
+        }) {
+            Ok(check) if check.passed() => {
+                let message = match &check.profile {
+                    Some(profile) => format!("The commit email matches profile '{}'.", profile),
+                    None => "No profile is bound to this repository's directory or matches its remotes.".to_string(),
+                };
+                out.ok(&message, json!({ "check": check }))
+            }
+            Ok(check) => {
+                let profile = check.profile.as_deref().unwrap_or_default();
+                let expected = check.expected_email.as_deref().unwrap_or_default();
+                let actual = match &check.email {
+                    Some(email) => format!("would be committed as {}", email),
+                    None => "has no email set".to_string(),
+                };
+                let belongs = if check.bound {
+                    "is in a directory bound to"
                 } else {
-                    eprintln!("Error: --user requires a value");
-                    return;
+                    "has a remote matching"
+                };
+                out.fail(&format!(
+                    "This repository {} profile '{}' <{}>, but it {}.\n\
+                     Switch with `gitup profile use {} --local`, or commit with --no-verify to skip the check.",
+                    belongs, profile, expected, actual, profile
+                ));
+            }
+            Err(e) => out.error("Failed to check the identity", &e),
+        },
+    }
+}
+
+fn parse_min_version(value: &str) -> Result<Version, String> {
+    parse_version(value).ok_or_else(|| format!("'{}' is not a version like 2.40 or 2.40.1", value))
+}
+
+fn run_whoami(out: &Printer, path: &Path) {
+    match whoami(path) {
+        Ok(identity) => {
+            if out.structured() {
+                out.print(&json!(identity));
+                return;
+            }
+            let describe = |setting: &Option<ConfigValue>| match setting {
+                Some(setting) => format!("{} ({}, {})", setting.value, setting.scope, setting.origin),
+                None => "(not set)".to_string(),
+            };
+            println!("Name:        {}", describe(&identity.name));
+            println!("Email:       {}", describe(&identity.email));
+            if identity.sign_commits || identity.signing_key.is_some() {
+                let signed = if identity.sign_commits { "signed" } else { "not signed" };
+                println!("Signing key: {}, commits are {}", describe(&identity.signing_key), signed);
+            }
+            match (&identity.bound_profile, &identity.profile) {
+                (Some(bound), _) => println!("Profile:     {} (bound to this directory)", bound),
+                (None, Some(profile)) => println!("Profile:     {}", profile),
+                (None, None) => println!("Profile:     none matches this identity"),
+            }
+        }
+        Err(e) => out.error("Failed to resolve the identity", &e),
+    }
+}
+
+fn run_prompt(out: &Printer, path: &Path) {
+    match prompt_status(path) {
+        Ok(status) if out.structured() => out.print(&json!(status)),
+        // Nothing at all outside a repository, so the prompt stays clean
+        Ok(None) => {}
+        Ok(Some(status)) => {
+            let current = status.profile.clone().or(status.email.clone()).unwrap_or_else(|| "?".to_string());
+            match &status.expected {
+                Some(expected) if status.mismatch() => println!("{}!={}", current, expected),
+                _ => println!("{}", current),
+            }
+        }
+        Err(e) => out.error("Failed to resolve the profile", &e),
+    }
+}
+
+fn run_env(out: &Printer, profile: Option<String>, shell: CompletionShell, unset: bool) {
+    if unset {
+        if out.structured() {
+            out.print(&json!({ "unset": PROFILE_ENV_VARIABLES }));
+        } else {
+            for name in PROFILE_ENV_VARIABLES {
+                println!("{}", shell.unset(name));
+            }
+        }
+        return;
+    }
+    let Some(profile) = profile.or_else(|| std::env::var(PROFILE_ENV).ok().filter(|profile| !profile.is_empty())) else {
+        return out.fail(&format!("Name the profile to use, or set {}", PROFILE_ENV));
+    };
+    match profile_environment(&profile) {
+        Ok(variables) => {
+            if out.structured() {
+                let variables: serde_json::Map<String, Value> =
+                    variables.into_iter().map(|(name, value)| (name, json!(value))).collect();
+                out.print(&json!({ "profile": profile, "variables": variables }));
+                return;
+            }
+            // A profile without an SSH key mustn't keep the key from an earlier `gitup env`
+            for name in PROFILE_ENV_VARIABLES {
+                if !variables.iter().any(|(set, _)| set == name) {
+                    println!("{}", shell.unset(name));
                 }
             }
-            "-e" | "--email" => {
-                if i + 1 < args.len() {
-                    git_email = Some(args[i + 1].clone());
-                    i += 1;
+            for (name, value) in &variables {
+                println!("{}", shell.export(name, value));
+            }
+        }
+        Err(e) => out.error("Failed to read the profile", &e),
+    }
+}
+
+fn run_history_command(out: &Printer, command: HistoryCommands) {
+    match command {
+        HistoryCommands::List => match list_snapshots() {
+            Ok(snapshots) => {
+                if out.structured() {
+                    let snapshots: Vec<Value> = snapshots
+                        .iter()
+                        .map(|snapshot| {
+                            json!({
+                                "id": snapshot.id,
+                                "timestamp": snapshot.timestamp,
+                                "reason": snapshot.reason,
+                                "path": snapshot.path,
+                            })
+                        })
+                        .collect();
+                    out.print(&json!({ "snapshots": snapshots }));
+                } else if snapshots.is_empty() {
+                    println!("No snapshots yet.");
                 } else {
-                    eprintln!("Error: --email requires a value");
-                    return;
+                    for snapshot in snapshots {
+                        println!(
+                            "{}  {}  before {}",
+                            snapshot.id,
+                            format_timestamp(snapshot.timestamp),
+                            snapshot.reason
+                        );
+                    }
                 }
             }
-            "-j" | "--json" => {
-                output_json = true;
+            Err(e) => out.error("Failed to list snapshots", &e),
+        },
+        HistoryCommands::Rollback { id } => match rollback(&id) {
+            Ok(snapshot) => out.ok(
+                &format!(
+                    "Rolled back to before the '{}' change of {}. Run `gitup undo` to revert this rollback.",
+                    snapshot.reason,
+                    format_timestamp(snapshot.timestamp)
+                ),
+                json!({ "id": snapshot.id, "reason": snapshot.reason, "snapshot": snapshot.path }),
+            ),
+            Err(e) => out.error("Failed to roll back", &e),
+        },
+    }
+}
+
+// Seconds since the epoch as "YYYY-MM-DD HH:MM:SS UTC"
+// Where and when a backup was taken, as far as it says; backups in the older formats don't
+fn describe_backup(backup: &BackupInfo) -> Option<String> {
+    let mut parts = Vec::new();
+    if let Some(created) = backup.created {
+        parts.push(format!("taken {}", format_timestamp(created)));
+    }
+    if let Some(hostname) = &backup.hostname {
+        parts.push(format!("on {}", hostname));
+    }
+    if let Some(git_version) = &backup.git_version {
+        parts.push(format!("with git {}", git_version));
+    }
+    if let Some(source) = &backup.source {
+        parts.push(format!("from {}", source));
+    }
+    if parts.is_empty() && backup.scope.is_none() {
+        return None;
+    }
+    let scope = backup.scope.as_deref().unwrap_or("global");
+    Some(format!("Restoring a backup of the {} config {}", scope, parts.join(", ")).trim_end().to_string())
+}
+
+fn format_timestamp(timestamp: u64) -> String {
+    let (days, seconds) = (timestamp / 86_400, timestamp % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+fn run_maintenance_command(out: &Printer, command: MaintenanceCommands) {
+    match command {
+        MaintenanceCommands::Enable { repo, all_under } => {
+            let setup = match maintenance_repositories(&repo, all_under.as_deref()).and_then(|repos| enable_maintenance(&repos)) {
+                Ok(setup) => setup,
+                Err(e) => return out.error("Failed to enable maintenance", &e),
+            };
+            if setup.repositories.is_empty() {
+                return out.fail("No repositories found.");
             }
-            _ => {
-                eprintln!("Unknown argument: {}", args[i]);
-                return;
+            let mut message = vec![format!("Enabled maintenance in {}:", repositories(setup.repositories.len()))];
+            message.extend(setup.repositories.iter().map(|repo| format!("  {}", repo.display())));
+            message.push("with:".to_string());
+            message.extend(setup.settings.iter().map(|(key, value)| format!("  {} = {}", key, value)));
+            message.extend(setup.warnings.iter().map(|warning| format!("Warning: {}", warning)));
+            let settings: serde_json::Map<String, Value> =
+                setup.settings.into_iter().map(|(key, value)| (key, Value::String(value))).collect();
+            out.ok(
+                &message.join("\n"),
+                json!({ "repositories": setup.repositories, "settings": settings, "warnings": setup.warnings }),
+            )
+        }
+        MaintenanceCommands::Disable { repo, all_under } => {
+            match maintenance_repositories(&repo, all_under.as_deref()).and_then(|repos| disable_maintenance(&repos)) {
+                Ok(disabled) if disabled.is_empty() => out.ok(
+                    "None of these repositories were registered for maintenance.",
+                    json!({ "repositories": disabled }),
+                ),
+                Ok(disabled) => {
+                    let mut message = vec![format!("Disabled maintenance in {}:", repositories(disabled.len()))];
+                    message.extend(disabled.iter().map(|repo| format!("  {}", repo.display())));
+                    out.ok(&message.join("\n"), json!({ "repositories": disabled }))
+                }
+                Err(e) => out.error("Failed to disable maintenance", &e),
             }
         }
-        i += 1;
+        MaintenanceCommands::List => match registered_repositories() {
+            Ok(repositories) if out.structured() => out.print(&json!({ "repositories": repositories })),
+            Ok(repositories) if repositories.is_empty() => {
+                println!("No repositories are registered; add one with `gitup maintenance enable`.")
+            }
+            Ok(repositories) => {
+                for repo in repositories {
+                    println!("{}", repo.display());
+                }
+            }
+            Err(e) => out.error("Failed to list maintained repositories", &e),
+        },
     }
+}
 
-    // Check if Git is installed
-    if !is_git_installed() {
-        let error_message = "git is not installed.";
-        if output_json {
-            println!(
-                "{{\"error\": \"{}\", \"action\": \"install git please\"}}",
-                error_message
-            );
-            return;
-        } else {
-            eprintln!("{}", error_message);
-            if git_name.is_some() || git_email.is_some() {
-                eprintln!("Please install Git manually and run this tool again with the provided arguments.");
-                return;
+fn run_repos_command(out: &Printer, command: ReposCommands) {
+    match command {
+        ReposCommands::Add { paths } => match add_repositories(&paths) {
+            Ok(added) => out.ok(
+                &format!("Added {} to the registry.", repositories(added.len())),
+                json!({ "added": added }),
+            ),
+            Err(e) => out.error("Failed to add repositories", &e),
+        },
+        ReposCommands::AddRoot { dirs } => match add_roots(&dirs).and_then(|added| Ok((added, known_repositories()?))) {
+            Ok((added, known)) => out.ok(
+                &format!(
+                    "Added {} root(s); the registry now has {}.",
+                    added.len(),
+                    repositories(known.len())
+                ),
+                json!({ "added": added, "repositories": known.len() }),
+            ),
+            Err(e) => out.error("Failed to add roots", &e),
+        },
+        ReposCommands::Remove { paths } => match remove_entries(&paths) {
+            Ok(removed) if removed.is_empty() => out.fail("None of these are in the registry."),
+            Ok(removed) => {
+                let mut message = vec![format!("Removed {} entr{}:", removed.len(), if removed.len() == 1 { "y" } else { "ies" })];
+                message.extend(removed.iter().map(|path| format!("  {}", path.display())));
+                out.ok(&message.join("\n"), json!({ "removed": removed }))
+            }
+            Err(e) => out.error("Failed to remove from the registry", &e),
+        },
+        ReposCommands::List { with_identity } => list_repositories(out, with_identity),
+        ReposCommands::ApplyProfile {
+            profile,
+            repos,
+            all,
+            recurse_submodules,
+        } => {
+            if let Err(e) = get_profile(&profile) {
+                return out.error("Failed to apply the profile", &e);
+            }
+            let repos = if all {
+                match known_repositories() {
+                    Ok(known) if known.is_empty() => {
+                        let e = GitError::InvalidInput(
+                            "the registry has no repositories; add some with `gitup repos add` or `gitup repos add-root`".to_string(),
+                        );
+                        return out.error("Failed to apply the profile", &e);
+                    }
+                    Ok(known) => known.into_iter().map(|repo| repo.path).collect(),
+                    Err(e) => return out.error("Failed to read the registry", &e),
+                }
             } else {
-                if prompt_user("Would you like to install Git? (y/n): ").to_lowercase() == "y" {
-                    if install_git() {
-                        println!("Git has been installed successfully.");
-                        // Continue to configuration directly after installation
-                    } else {
-                        eprintln!("Failed to install Git. Please install it manually and run this tool again.");
-                        return;
+                repos
+            };
+            let mut applied = Vec::new();
+            let mut found = Vec::new();
+            let mut failed = Vec::new();
+            for repo in repos {
+                let result = switch_profile(out, &profile, Scope::Local(repo.clone())).and_then(|_| match recurse_submodules {
+                    true => use_profile_in_submodules(&profile, &repo),
+                    false => Ok(Vec::new()),
+                });
+                match result {
+                    Ok(submodules) => {
+                        applied.push(repo);
+                        found.extend(submodules);
                     }
+                    Err(e) => failed.push((repo, e)),
+                }
+            }
+            let mut message = Vec::new();
+            if !applied.is_empty() || failed.is_empty() {
+                message.push(format!("Applied profile '{}' to {}:", profile, repositories(applied.len())));
+                message.extend(applied.iter().map(|repo| format!("  {}", repo.display())));
+                if recurse_submodules {
+                    message.push(format!("and {} inside them", submodules(found.len())));
+                }
+            }
+            if !failed.is_empty() {
+                message.push(format!("Failed in {}:", repositories(failed.len())));
+                message.extend(failed.iter().map(|(repo, e)| format!("  {}: {}", repo.display(), e)));
+            }
+            let failures: Vec<Value> = failed
+                .iter()
+                .map(|(repo, e)| json!({ "repository": repo, "error": e.to_string() }))
+                .collect();
+            let data = json!({ "profile": profile, "applied": applied, "submodules": found, "failed": failures });
+            match failed.first() {
+                None => out.ok(&message.join("\n"), data),
+                Some((_, e)) if out.structured() => {
+                    out.exit_code.set(e.exit_code());
+                    out.print(&json!({ "status": "error", "code": e.exit_code(), "result": data }));
+                }
+                Some((_, e)) => {
+                    out.exit_code.set(e.exit_code());
+                    eprintln!("{}", message.join("\n"));
+                }
+            }
+        }
+    }
+}
+
+fn list_repositories(out: &Printer, with_identity: bool) {
+    let known = match known_repositories() {
+        Ok(known) => known,
+        Err(e) => return out.error("Failed to read the registry", &e),
+    };
+    let identities: Vec<Option<Whoami>> = known
+        .iter()
+        .map(|repo| if with_identity { whoami(&repo.path).ok() } else { None })
+        .collect();
+    if out.structured() {
+        let repositories: Vec<Value> = known
+            .iter()
+            .zip(&identities)
+            .map(|(repo, identity)| {
+                let mut entry = json!({ "path": repo.path, "root": repo.root });
+                if let (Some(entry), Some(identity)) = (entry.as_object_mut(), identity) {
+                    entry.insert("identity".to_string(), json!(identity));
+                }
+                entry
+            })
+            .collect();
+        return out.print(&json!({ "repositories": repositories }));
+    }
+    if known.is_empty() {
+        println!("The registry is empty; add repositories with `gitup repos add` or a directory of them with `gitup repos add-root`.");
+        return;
+    }
+    for (repo, identity) in known.iter().zip(&identities) {
+        let Some(identity) = identity.as_ref().filter(|_| with_identity) else {
+            println!("{}", repo.path.display());
+            continue;
+        };
+        let value = |setting: &Option<ConfigValue>| setting.as_ref().map_or("?".to_string(), |setting| setting.value.clone());
+        let profile = match (&identity.bound_profile, &identity.profile) {
+            (Some(bound), _) => format!("{} (bound)", bound),
+            (None, Some(profile)) => profile.clone(),
+            (None, None) => "no profile".to_string(),
+        };
+        println!(
+            "{}  {} <{}>  {}",
+            repo.path.display(),
+            value(&identity.name),
+            value(&identity.email),
+            profile
+        );
+    }
+}
+
+fn repositories(count: usize) -> String {
+    format!("{} {}", count, if count == 1 { "repository" } else { "repositories" })
+}
+
+fn submodules(count: usize) -> String {
+    format!("{} {}", count, if count == 1 { "submodule" } else { "submodules" })
+}
+
+fn run_lfs_command(out: &Printer, command: LfsCommands) {
+    match command {
+        LfsCommands::Install {
+            track,
+            repo,
+            package_manager,
+        } => {
+            let package_manager = match install_lfs(package_manager.as_deref()) {
+                Ok(package_manager) => package_manager,
+                Err(e) => return out.error("Failed to install Git LFS", &e),
+            };
+            let tracked = if track.is_empty() {
+                Vec::new()
+            } else {
+                match track_patterns(&repo, &track) {
+                    Ok(tracked) => tracked,
+                    Err(e) => return out.error("Git LFS is installed, but tracking failed", &e),
+                }
+            };
+            let mut message = match package_manager {
+                Some(pm) => format!("Git LFS has been installed using {} and enabled.", pm),
+                None => "Git LFS is enabled.".to_string(),
+            };
+            if !tracked.is_empty() {
+                message.push_str(&format!(" Tracking {} in {}", tracked.join(", "), repo.display()));
+            }
+            out.ok(
+                &message,
+                json!({ "installed": true, "package_manager": package_manager, "tracked": tracked }),
+            )
+        }
+        LfsCommands::Track { patterns, repo } => match track_patterns(&repo, &patterns) {
+            Ok(tracked) if tracked.is_empty() => out.ok("Already tracked.", json!({ "tracked": tracked })),
+            Ok(tracked) => out.ok(
+                &format!("Tracking {} with LFS; commit .gitattributes to share it", tracked.join(", ")),
+                json!({ "tracked": tracked }),
+            ),
+            Err(e) => out.error("Failed to track patterns", &e),
+        },
+        LfsCommands::List { repo } => match tracked_patterns(&repo) {
+            Ok(patterns) => {
+                if out.structured() {
+                    out.print(&json!({ "patterns": patterns }));
+                } else if patterns.is_empty() {
+                    println!("No patterns are tracked with LFS.");
                 } else {
-                    eprintln!("Please install Git manually and run this tool again.");
+                    for pattern in patterns {
+                        println!("{}", pattern);
+                    }
+                }
+            }
+            Err(e) => out.error("Failed to read tracked patterns", &e),
+        },
+    }
+}
+
+fn run_proxy_command(out: &Printer, command: ProxyCommands) {
+    match command {
+        ProxyCommands::Set { http, https, no_proxy } => {
+            if http.is_none() && https.is_none() && no_proxy.is_empty() {
+                return out.fail("Nothing to set; pass --http, --https and/or --no-proxy");
+            }
+            match set_proxy(http.as_deref(), https.as_deref(), &no_proxy) {
+                Ok(()) => {
+                    let proxy = http.or(https);
+                    let mut message = match &proxy {
+                        Some(proxy) => format!("Git now uses the proxy {}", proxy),
+                        None => "Proxy exceptions added".to_string(),
+                    };
+                    if !no_proxy.is_empty() {
+                        message.push_str(&format!(" (direct: {})", no_proxy.join(", ")));
+                    }
+                    out.ok(&message, json!({ "proxy": proxy, "no_proxy": no_proxy }))
+                }
+                Err(e) => out.error("Failed to set the proxy", &e),
+            }
+        }
+        ProxyCommands::Unset => match unset_proxy() {
+            Ok(()) => out.ok("Removed the proxy settings.", json!({ "removed": true })),
+            Err(e) => out.error("Failed to remove the proxy settings", &e),
+        },
+        ProxyCommands::Show => match proxy_config() {
+            Ok(config) => {
+                if out.structured() {
+                    let environment: serde_json::Map<String, Value> = config
+                        .environment
+                        .iter()
+                        .map(|(name, value)| (name.clone(), json!(value)))
+                        .collect();
+                    out.print(&json!({
+                        "proxy": config.proxy,
+                        "no_proxy": config.no_proxy,
+                        "rewrites": config.rewrites,
+                        "environment": environment,
+                    }));
                     return;
                 }
+                println!("Proxy: {}", config.proxy.as_deref().unwrap_or("(none)"));
+                println!("No proxy: {}", list_or_none(&config.no_proxy));
+                if config.rewrites.is_empty() {
+                    println!("Rewrites: none");
+                } else {
+                    println!("Rewrites:");
+                    for rewrite in &config.rewrites {
+                        println!("  {} -> {}", rewrite.prefix, rewrite.base);
+                    }
+                }
+                if !config.environment.is_empty() {
+                    let note = if config.proxy.is_some() { " (overridden by http.proxy)" } else { "" };
+                    println!("Environment{}:", note);
+                    for (name, value) in &config.environment {
+                        println!("  {}={}", name, value);
+                    }
+                }
+            }
+            Err(e) => out.error("Failed to read the proxy settings", &e),
+        },
+        ProxyCommands::Rewrite { prefix, base } => match add_rewrite(&prefix, &base) {
+            Ok(()) => out.ok(
+                &format!("URLs starting with {} now go to {}", prefix, base),
+                json!({ "prefix": prefix, "base": base }),
+            ),
+            Err(e) => out.error("Failed to add the rewrite", &e),
+        },
+        ProxyCommands::RemoveRewrite { prefix } => match remove_rewrite(&prefix) {
+            Ok(()) => out.ok(&format!("Removed the rewrite for {}", prefix), json!({ "prefix": prefix })),
+            Err(e) => out.error("Failed to remove the rewrite", &e),
+        },
+    }
+}
+
+fn run_wsl_command(out: &Printer, command: WslCommands) {
+    match command {
+        WslCommands::Setup { repo } => match setup_wsl(&repo) {
+            Ok(setup) => {
+                let mut message = if setup.changes.is_empty() {
+                    vec!["Git is already set up for WSL.".to_string()]
+                } else {
+                    let mut message = vec![format!("Made {} change(s):", setup.changes.len())];
+                    message.extend(setup.changes.iter().map(|change| format!("  {}", change)));
+                    message
+                };
+                message.extend(setup.warnings.iter().map(|warning| format!("Warning: {}", warning)));
+                out.ok(&message.join("\n"), json!({ "changes": setup.changes, "warnings": setup.warnings }))
+            }
+            Err(e) => out.error("Failed to set up Git for WSL", &e),
+        },
+    }
+}
+
+fn run_eol_command(out: &Printer, command: EolCommands) {
+    match command {
+        EolCommands::Set {
+            policy,
+            local,
+            attributes,
+            repo,
+        } => {
+            let policy = match policy {
+                Some(policy) => policy,
+                None => match active_profile().and_then(|profile| profile.map(|name| get_profile(&name)).transpose()) {
+                    Ok(Some(GitProfile { eol: Some(policy), .. })) => policy,
+                    Ok(_) => return out.fail("Give a policy (auto, lf or crlf); the active profile doesn't set one"),
+                    Err(e) => return out.error("Failed to read the active profile", &e),
+                },
+            };
+            let scope = if local { Scope::Local(repo.clone()) } else { Scope::Global };
+            if let Err(e) = set_eol(policy, scope) {
+                return out.error("Failed to set the line ending policy", &e);
+            }
+            let settings = policy.settings();
+            let mut message = format!(
+                "Line endings are now {} ({})",
+                policy,
+                settings.iter().map(|(key, value)| format!("{}={}", key, value)).collect::<Vec<_>>().join(", ")
+            );
+            let written = if attributes {
+                match write_attributes(&repo, policy) {
+                    Ok(path) => {
+                        message.push_str(&format!("; wrote {}, commit it to share the policy", path.display()));
+                        Some(path)
+                    }
+                    Err(e) => return out.error("The policy is set, but writing .gitattributes failed", &e),
+                }
+            } else {
+                None
+            };
+            let settings: serde_json::Map<String, Value> =
+                settings.into_iter().map(|(key, value)| (key, Value::String(value))).collect();
+            out.ok(&message, json!({ "policy": policy, "settings": settings, "attributes": written }))
+        }
+    }
+}
+
+fn run_tools_command(out: &Printer, command: ToolsCommands) {
+    match command {
+        ToolsCommands::Set {
+            editor,
+            difftool,
+            mergetool,
+        } => {
+            if editor.is_none() && difftool.is_none() && mergetool.is_none() {
+                return out.fail("Nothing to set; pass --editor, --difftool and/or --mergetool");
+            }
+            match set_tools(editor.as_deref(), difftool.as_deref(), mergetool.as_deref()) {
+                Ok(settings) => {
+                    let mut message = vec!["Tools set:".to_string()];
+                    message.extend(settings.iter().map(|(key, value)| format!("  {} = {}", key, value)));
+                    let settings: serde_json::Map<String, Value> =
+                        settings.into_iter().map(|(key, value)| (key, Value::String(value))).collect();
+                    out.ok(&message.join("\n"), json!({ "settings": settings }))
+                }
+                Err(e) => out.error("Failed to set tools", &e),
+            }
+        }
+        ToolsCommands::List => {
+            let tools = known_tools();
+            if out.structured() {
+                return out.print(&json!({ "tools": tools }));
+            }
+            for tool in tools {
+                let roles: Vec<&str> = [(tool.editor, "editor"), (tool.difftool, "difftool"), (tool.mergetool, "mergetool")]
+                    .into_iter()
+                    .filter_map(|(supported, role)| supported.then_some(role))
+                    .collect();
+                let installed = match tool.installed {
+                    Some(program) => format!("installed ({})", program),
+                    None => "not installed".to_string(),
+                };
+                println!("{:<10} {:<28} {}", tool.name, roles.join(", "), installed);
             }
         }
     }
+}
 
-    // Proceed with configuration (either after checking that Git is installed or after installation)
-    configure_git_if_needed(git_name, git_email, output_json);
+fn run_pager_command(out: &Printer, command: PagerCommands) {
+    match command {
+        PagerCommands::Setup { pager, package_manager } => match setup_pager(pager, package_manager.as_deref()) {
+            Ok(setup) => {
+                let mut message = vec![match setup.installed_with {
+                    Some(pm) => format!("{} has been installed using {} and set as git's pager:", pager, pm),
+                    None => format!("{} is now git's pager:", pager),
+                }];
+                message.extend(setup.settings.iter().map(|(key, value)| format!("  {} = {}", key, value)));
+                let settings: serde_json::Map<String, Value> =
+                    setup.settings.into_iter().map(|(key, value)| (key, Value::String(value))).collect();
+                out.ok(
+                    &message.join("\n"),
+                    json!({
+                        "pager": pager.to_string(),
+                        "installed": setup.installed_with.is_some(),
+                        "package_manager": setup.installed_with,
+                        "settings": settings,
+                    }),
+                )
+            }
+            Err(e) => out.error(&format!("Failed to set up {}", pager), &e),
+        },
+    }
 }
 
-fn configure_git_if_needed(git_name: Option<String>, git_email: Option<String>, output_json: bool) {
-    // Check existing Git configuration
-    let current_name = get_git_config("user.name");
-    let current_email = get_git_config("user.email");
+fn list_plugin_commands(out: &Printer) {
+    // A plugin named like a built-in command never runs
+    let command = Cli::command();
+    let plugins: Vec<Value> = list_plugins()
+        .into_iter()
+        .map(|plugin| {
+            let shadowed = command.find_subcommand(&plugin.name).is_some();
+            json!({ "name": plugin.name, "path": plugin.path, "shadowed": shadowed })
+        })
+        .collect();
+    if out.structured() {
+        return out.print(&json!({ "plugins": plugins }));
+    }
+    if plugins.is_empty() {
+        println!("No plugins found; gitup runs gitup-<name> executables on your PATH as `gitup <name>`.");
+    }
+    for plugin in plugins {
+        let shadowed = if plugin["shadowed"] == true { " (hidden by the built-in command)" } else { "" };
+        println!("{:<16} {}{}", plugin["name"].as_str().unwrap_or_default(), plugin["path"].as_str().unwrap_or_default(), shadowed);
+    }
+}
 
-    if output_json {
-        let json_output = format!(
-            r#"{{
-    "git_name": "{}",
-    "git_email": "{}"
-}}"#,
-            current_name.unwrap_or_default(),
-            current_email.unwrap_or_default()
-        );
-        println!("{}", json_output);
+// Levenshtein distance between two command names
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            current.push((previous[j] + usize::from(ca != *cb)).min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// Run `gitup <name> args...` as the gitup-<name> plugin, exiting the way it did
+fn run_external(out: &Printer, args: &[String]) {
+    let Some((name, args)) = args.split_first() else {
         return;
+    };
+    let Some(plugin) = find_plugin(name) else {
+        // Unknown names come here instead of clap, which would have suggested a command
+        let command = Cli::command();
+        let suggestion = command
+            .get_subcommands()
+            .map(|subcommand| subcommand.get_name())
+            .filter(|candidate| edit_distance(name, candidate) <= 2)
+            .min_by_key(|candidate| edit_distance(name, candidate));
+        return out.fail(&match suggestion {
+            Some(candidate) => format!("'{}' is not a gitup command; did you mean '{}'?", name, candidate),
+            None => format!(
+                "'{}' is not a gitup command, and no gitup-{} plugin is on PATH; see `gitup --help` and `gitup plugin list`",
+                name, name
+            ),
+        });
+    };
+    let format = out.format.and_then(|format| format.to_possible_value()).map(|value| value.get_name().to_string());
+    match run_plugin(&plugin, args, &out.options, format.as_deref()) {
+        // A plugin killed by a signal has no exit code
+        Ok(status) => out.exit_code.set(status.code().map_or(EXIT_FAILURE, |code| code.clamp(0, 255) as u8)),
+        Err(e) => out.error(&format!("Failed to run {}", plugin.path.display()), &e),
+    }
+}
+
+fn run_init_defaults_command(out: &Printer, command: InitDefaultsCommands) {
+    match command {
+        InitDefaultsCommands::Show => match init_defaults() {
+            Ok(defaults) => {
+                if out.structured() {
+                    out.print(&json!({
+                        "default_branch": defaults.default_branch,
+                        "template_dir": defaults.template_dir,
+                        "hooks": defaults.hooks,
+                        "ignore": defaults.ignore,
+                    }));
+                    return;
+                }
+                println!(
+                    "Default branch: {}",
+                    defaults.default_branch.as_deref().unwrap_or("(git's default)")
+                );
+                match &defaults.template_dir {
+                    Some(dir) => {
+                        println!("Init template: {}", dir.display());
+                        println!("  hooks: {}", list_or_none(&defaults.hooks));
+                        println!("  ignore: {}", list_or_none(&defaults.ignore));
+                    }
+                    None => println!("Init template: (none)"),
+                }
+            }
+            Err(e) => out.error("Failed to read init defaults", &e),
+        },
+        InitDefaultsCommands::Branch { name } => match set_default_branch(&name) {
+            Ok(()) => out.ok(
+                &format!("New repositories will start on '{}'.", name),
+                json!({ "default_branch": name }),
+            ),
+            Err(e) => out.error("Failed to set the default branch", &e),
+        },
+        InitDefaultsCommands::Template { hooks, ignore } => {
+            if hooks.is_empty() && ignore.is_empty() {
+                return out.fail("Nothing to put in the template; pass --hook and/or --ignore");
+            }
+            match set_init_template(&hooks, &ignore) {
+                Ok(dir) => out.ok(
+                    &format!(
+                        "New repositories will start from {} (hooks: {}; ignore: {})",
+                        dir.display(),
+                        list_or_none(&hooks),
+                        list_or_none(&ignore)
+                    ),
+                    json!({ "template_dir": dir, "hooks": hooks, "ignore": ignore }),
+                ),
+                Err(e) => out.error("Failed to write the init template", &e),
+            }
+        }
+        InitDefaultsCommands::RemoveTemplate => match remove_init_template() {
+            Ok(()) => out.ok("Removed the init template.", json!({ "removed": true })),
+            Err(e) => out.error("Failed to remove the init template", &e),
+        },
+    }
+}
+
+fn list_or_none<S: AsRef<str>>(items: &[S]) -> String {
+    if items.is_empty() {
+        "none".to_string()
+    } else {
+        items.iter().map(AsRef::as_ref).collect::<Vec<_>>().join(", ")
+    }
+}
+
+fn run_template_command(out: &Printer, command: TemplateCommands) {
+    match command {
+        TemplateCommands::Set { template, local } => {
+            match set_commit_template(&template, scope(local)) {
+                Ok(path) => out.ok(
+                    &format!("Commit template set to {}", path.display()),
+                    json!({ "template": template, "file": path }),
+                ),
+                Err(e) => out.error("Failed to set commit template", &e),
+            }
+        }
+        TemplateCommands::Unset { local } => match unset_commit_template(scope(local)) {
+            Ok(()) => out.ok("Commit template unset.", json!({})),
+            Err(e) => out.error("Failed to unset commit template", &e),
+        },
+        TemplateCommands::List => {
+            let current = current_commit_template();
+            if out.structured() {
+                out.print(&json!({ "builtin": builtin_templates(), "current": current }));
+            } else {
+                for template in builtin_templates() {
+                    println!("{}", template);
+                }
+                match current {
+                    Some(path) => println!("\nCurrent commit.template: {}", path),
+                    None => println!("\nNo commit.template is set."),
+                }
+            }
+        }
+    }
+}
+
+fn scope(local: bool) -> Scope {
+    if local {
+        Scope::local()
+    } else {
+        Scope::Global
+    }
+}
+
+// Check that Git is installed, offering to install it when running interactively
+fn ensure_git_installed(out: &Printer, interactive: bool) -> bool {
+    if is_git_installed() {
+        return true;
+    }
+
+    out.exit_code.set(EXIT_GIT_NOT_INSTALLED);
+    if out.structured() {
+        out.print(&json!({
+            "status": "error",
+            "code": EXIT_GIT_NOT_INSTALLED,
+            "error": "git is not installed.",
+            "action": "install git please",
+        }));
+        return false;
+    }
+
+    eprintln!("git is not installed.");
+    if !interactive && !out.options.assume_yes {
+        eprintln!("Please install Git manually and run this tool again with the provided arguments.");
+        return false;
+    }
+
+    if !out.confirm("Would you like to install Git?") {
+        eprintln!("Please install Git manually and run this tool again.");
+        return false;
+    }
+
+    match install_git() {
+        Ok(COMMAND_LINE_TOOLS) if !is_git_installed() => {
+            out.info(
+                "Apple's installer for the Xcode Command Line Tools has been opened. \
+                 Finish it, then run gitup init again.",
+            );
+            false
+        }
+        Ok(pm) => {
+            out.info(&format!("Git has been installed successfully using {}.", pm));
+            out.exit_code.set(0);
+            true
+        }
+        Err(e) => {
+            out.error("Failed to install Git. Please install it manually and run this tool again", &e);
+            false
+        }
+    }
+}
+
+fn run_config_command(out: &Printer, command: ConfigCommands) {
+    match command {
+        ConfigCommands::List { show_origin } => match list_config_entries(Path::new(".")) {
+            Ok(entries) => {
+                if out.structured() {
+                    out.print(&json!({ "entries": entries }));
+                } else {
+                    for entry in entries {
+                        if show_origin {
+                            println!("{}\t{}\t{}={}", entry.scope, entry.file, entry.key, entry.value);
+                        } else {
+                            println!("{}={}", entry.key, entry.value);
+                        }
+                    }
+                }
+            }
+            Err(e) => out.error("Failed to list the Git configuration", &e),
+        },
+        ConfigCommands::Get { key, scope } => {
+            if let Err(e) = validate_config_key(&key) {
+                return out.error("Failed to get config value", &e);
+            }
+            let value = match scope.scope() {
+                Some(scope) => GitConfig::new(scope.clone())
+                    .get(&key)
+                    .map(|value| (value, scope.name().to_string(), None)),
+                None => get_effective_config(&key, Path::new("."))
+                    .map(|setting| (setting.value, setting.scope, Some(setting.origin))),
+            };
+            match value {
+                Some((value, scope, origin)) if out.structured() => {
+                    out.print(&json!({ "key": key, "value": value, "scope": scope, "origin": origin }))
+                }
+                Some((value, _, _)) => println!("{}", value),
+                None => out.fail(&format!("{} is not set", key)),
+            }
+        }
+        ConfigCommands::Set { key, value, scope } => {
+            let scope = scope.scope().unwrap_or(Scope::Global);
+            match GitConfig::new(scope.clone()).set(&key, &value) {
+                Ok(()) => out.ok(
+                    &format!("Set {} = {} in the {} config.", key, value, scope.name()),
+                    json!({ "key": key, "value": value }),
+                ),
+                Err(e) => out.error("Failed to set config value", &e),
+            }
+        }
+        ConfigCommands::Unset { key, scope } => {
+            let scope = scope.scope().unwrap_or(Scope::Global);
+            match GitConfig::new(scope.clone()).unset(&key) {
+                Ok(()) => out.ok(
+                    &format!("Unset {} in the {} config.", key, scope.name()),
+                    json!({ "key": key }),
+                ),
+                Err(e) => out.error("Failed to unset config value", &e),
+            }
+        }
+    }
+}
+
+fn show_config(out: &Printer) {
+    let current_name = GitConfig::global().get("user.name");
+    let current_email = GitConfig::global().get("user.email");
+
+    if out.structured() {
+        out.print(&json!({
+            "git_name": current_name.unwrap_or_default(),
+            "git_email": current_email.unwrap_or_default(),
+        }));
     } else {
-        if let (Some(name), Some(email)) = (current_name.clone(), current_email.clone()) {
+        println!("Name: {}", current_name.unwrap_or_default());
+        println!("Email: {}", current_email.unwrap_or_default());
+    }
+}
+
+fn configure_git_if_needed(
+    out: &Printer,
+    git_name: Option<String>,
+    git_email: Option<String>,
+    scope: Scope,
+) {
+    // Check existing global Git configuration
+    let current_name = GitConfig::global().get("user.name");
+    let current_email = GitConfig::global().get("user.email");
+
+    if let (Scope::Global, Some(name), Some(email)) = (&scope, current_name, current_email) {
+        if git_name.is_none() && git_email.is_none() {
+            // Without a way to ask for new values there is nothing to reconfigure with
+            if !out.interactive() {
+                out.ok(
+                    &format!("Git is already configured as {} <{}>.", name, email),
+                    json!({ "git_name": name, "git_email": email }),
+                );
+                return;
+            }
+
             println!("Git is already configured with the following settings:");
             println!("Name: {}", name);
             println!("Email: {}", email);
 
-            if git_name.is_none() && git_email.is_none() {
-                if prompt_user("Would you like to reconfigure Git? (y/n): ").to_lowercase() != "y" {
-                    println!("Keeping existing Git configuration.");
-                    return;
-                }
+            if !out.confirm("Would you like to reconfigure Git?") {
+                println!("Keeping existing Git configuration.");
+                return;
             }
         }
     }
 
     // Prompt for missing Git name or email
-    let git_name = git_name.or_else(|| Some(prompt_user("Enter your Git name: "))).unwrap();
-    let git_email = git_email.or_else(|| Some(prompt_user("Enter your Git email: "))).unwrap();
+    let (Some(git_name), Some(git_email)) = (
+        git_name.or_else(|| prompt_if_interactive(out, "Enter your Git name: ")),
+        git_email.or_else(|| prompt_if_interactive(out, "Enter your Git email: ")),
+    ) else {
+        out.fail("Both --user and --email are required with --json, --format or --yes");
+        return;
+    };
+
+    // Check both values first so a bad email doesn't leave a new name behind
+    if let Err(e) = validate_name(&git_name).and_then(|()| validate_email(&git_email)) {
+        out.error("Invalid identity", &e);
+        return;
+    }
 
     // Configure Git with the provided name and email
-    configure_git("user.name", &git_name);
-    configure_git("user.email", &git_email);
+    let config = GitConfig::new(scope);
+    let result = config
+        .set("user.name", &git_name)
+        .and_then(|()| config.set("user.email", &git_email));
+    match result {
+        Ok(()) => out.ok(
+            &format!(
+                "Git user.name set to '{}'\nGit user.email set to '{}'",
+                git_name, git_email
+            ),
+            json!({ "git_name": git_name, "git_email": git_email }),
+        ),
+        Err(e) => out.error("Failed to configure Git", &e),
+    }
 }
 
-// Function to check if Git is installed
-fn is_git_installed() -> bool {
-    run_command("git", &["--version"]).is_ok()
-}
+// Prompt for a value unless the user can't be asked
+fn new_profile_from_preset(
+    out: &Printer,
+    profile: Option<String>,
+    source: &str,
+    user: Option<String>,
+    email: Option<String>,
+    keys: PresetAnswers,
+    allow_unsafe: bool,
+) {
+    let preset = match load_preset(source) {
+        Ok(preset) => preset,
+        Err(e) => return out.error("Failed to load the template", &e),
+    };
+    if let Some(description) = &preset.description {
+        out.info(description);
+    }
 
-// Function to get the current Git configuration
-fn get_git_config(key: &str) -> Option<String> {
-    run_command("git", &["config", "--global", key])
-        .ok()
-        .and_then(|output| {
-            let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if value.is_empty() {
-                None
-            } else {
-                Some(value)
-            }
-        })
-}
+    let Some(profile) = profile
+        .or_else(|| preset.profile.clone())
+        .or_else(|| prompt_if_interactive(out, "Profile name: ").filter(|name| !name.is_empty()))
+    else {
+        return out.fail("The template doesn't name the profile; pass a profile name");
+    };
+    let name = user.or_else(|| prompt_if_interactive(out, "Enter your Git name: "));
+    // With an email domain only the user part is asked for, but a full address is accepted too
+    let email = match email {
+        Some(email) if email.contains('@') => Some(email),
+        Some(user) => preset.email_for(&user).or(Some(user)),
+        None => match &preset.email_domain {
+            Some(domain) => prompt_if_interactive(out, &format!("Email user (…@{}): ", domain))
+                .and_then(|user| preset.email_for(&user)),
+            None => prompt_if_interactive(out, "Enter your Git email: "),
+        },
+    };
+    let (Some(name), Some(email)) = (name, email) else {
+        return out.fail("Both --user and --email are required with --json, --format or --yes");
+    };
 
-// Function to prompt user for input
-fn prompt_user(prompt: &str) -> String {
-    print!("{}", prompt);
-    io::stdout().flush().unwrap(); // Make sure the prompt is printed before reading input
+    let mut answers = PresetAnswers { name, email, ..keys };
+    if preset.needs_signing_key(&answers) {
+        answers.signing_key = prompt_if_interactive(out, "This template requires signed commits. Signing key: ")
+            .filter(|key| !key.is_empty());
+    }
 
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).expect("Failed to read line");
+    let mut git_profile = match preset.to_profile(answers) {
+        Ok(git_profile) => git_profile,
+        Err(e) => return out.error("Failed to create profile", &e),
+    };
+    // Templates are handed around teams and fetched from URLs
+    let blocked = if allow_unsafe { Vec::new() } else { git_profile.remove_unsafe_settings() };
+    let mut message = format!("Profile '{}' created; run `gitup profile use {}` to switch to it.", profile, profile);
+    if !blocked.is_empty() {
+        message.push('\n');
+        message.push_str(&unsafe_settings_note(&blocked));
+    }
+    match create_profile(&profile, git_profile.clone()) {
+        Ok(()) => out.ok(
+            &message,
+            json!({ "profile": profile, "settings": git_profile, "blocked": blocked }),
+        ),
+        Err(e) => out.error("Failed to create profile", &e),
+    }
+}
 
-    input.trim().to_string() // Remove any trailing newline or spaces
+// Tell which settings of an imported profile were left out for running commands
+fn unsafe_settings_note(blocked: &[String]) -> String {
+    format!(
+        "Left out {}, which can run commands; check them and pass --allow-unsafe to keep them.",
+        blocked.join(", ")
+    )
 }
 
-// Function to install Git
-fn install_git() -> bool {
-    run_command("sudo", &["apt-get", "update"]).is_ok() && run_command("sudo", &["apt-get", "install", "-y", "git"]).is_ok()
+fn prompt_if_interactive(out: &Printer, prompt: &str) -> Option<String> {
+    if out.interactive() {
+        Some(prompt_user(prompt))
+    } else {
+        None
+    }
 }
 
-// Function to configure Git
-fn configure_git(key: &str, value: &str) {
-    if run_command("git", &["config", "--global", key, value]).is_ok() {
-        println!("Git {} set to '{}'", key, value);
+// Prompt for a value, keeping the default when the answer is empty or the user can't be asked
+fn prompt_with_default(out: &Printer, prompt: &str, default: Option<String>) -> String {
+    if !out.interactive() {
+        return default.unwrap_or_default();
+    }
+    let answer = match &default {
+        Some(default) => prompt_user(&format!("{} [{}]: ", prompt, default)),
+        None => prompt_user(&format!("{}: ", prompt)),
+    };
+    if answer.is_empty() {
+        default.unwrap_or_default()
     } else {
-        eprintln!("Failed to set Git {}: {}", key, value);
+        answer
+    }
+}
+
+// Function to ask for a new passphrase twice, so a typo can't lock data away;
+// GITUP_PASSPHRASE takes precedence
+fn read_new_passphrase(out: &Printer) -> bool {
+    if std::env::var_os(PASSPHRASE_ENV).is_some() {
+        return true;
+    }
+    let Some(passphrase) = prompt_passphrase("New passphrase: ") else {
+        out.fail(&format!("A passphrase is required; set {}", PASSPHRASE_ENV));
+        return false;
+    };
+    if prompt_passphrase("Repeat passphrase: ").as_ref() != Some(&passphrase) {
+        out.fail("Passphrases don't match.");
+        return false;
+    }
+    set_passphrase(&passphrase);
+    true
+}
+
+// Function to read a passphrase without echoing it, if there's a terminal to ask on
+fn prompt_passphrase(prompt: &str) -> Option<String> {
+    if !io::stdin().is_terminal() {
+        return None;
     }
+    without_lock(|| rpassword::prompt_password(prompt).ok())
 }
 
-// General function to run a command and return the output or error
-fn run_command(cmd: &str, args: &[&str]) -> Result<std::process::Output, std::io::Error> {
-    Command::new(cmd).args(args).output()
+// Function to prompt the user for input
+fn prompt_user(prompt: &str) -> String {
+    print!("{}", prompt);
+    io::stdout().flush().unwrap(); // Make sure the prompt is printed before reading input
+
+    let mut input = String::new();
+    without_lock(|| io::stdin().read_line(&mut input)).expect("Failed to read line");
+
+    input.trim().to_string() // Remove any trailing newline or spaces
 }
@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::GitConfig;
+use crate::git::{repository_root, run_mutating};
+use crate::guard::find_repositories;
+use crate::GitError;
+
+// What Scalar, git's own tool for large repositories, sets alongside scheduled maintenance
+const SETTINGS: &[(&str, &str)] = &[
+    // The scheduled tasks repack in the background, so commands never stop for gc
+    ("gc.auto", "0"),
+    ("core.commitGraph", "true"),
+    ("gc.writeCommitGraph", "true"),
+    // The hourly commit-graph task keeps it up to date, which fetches would otherwise do
+    ("fetch.writeCommitGraph", "false"),
+];
+
+// What `enable_maintenance` did
+#[derive(Debug, Clone)]
+pub struct MaintenanceSetup {
+    pub repositories: Vec<PathBuf>,
+    pub settings: Vec<(String, String)>,
+    // Set when the repositories are registered but no scheduler could be set up to run the tasks
+    pub warnings: Vec<String>,
+}
+
+// Function to get the repositories a maintenance command is about: every working tree under
+// `all_under`, or else the one containing `repo`
+pub fn maintenance_repositories(repo: &Path, all_under: Option<&Path>) -> Result<Vec<PathBuf>, GitError> {
+    let repositories = match all_under {
+        Some(dir) => find_repositories(dir)?,
+        None => vec![repository_root(repo)?],
+    };
+    // git registers repositories by their real path
+    Ok(repositories
+        .into_iter()
+        .map(|repository| fs::canonicalize(&repository).unwrap_or(repository))
+        .collect())
+}
+
+// Function to register repositories for git's background maintenance (prefetching, commit-graph
+// and loose object upkeep, incremental repacks) with the recommended settings, and have the
+// system scheduler run it (cron, systemd timers, launchd or the Windows task scheduler)
+pub fn enable_maintenance(repositories: &[PathBuf]) -> Result<MaintenanceSetup, GitError> {
+    let settings: Vec<(String, String)> = SETTINGS
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    for repository in repositories {
+        GitConfig::repo(repository).set_many(&settings)?;
+        run_mutating("git", &["-C", &repository.to_string_lossy(), "maintenance", "register"])?;
+    }
+
+    let mut warnings = Vec::new();
+    // The schedule runs every registered repository, so it only needs starting once
+    if let Some(repository) = repositories.first() {
+        if let Err(e) = run_mutating("git", &["-C", &repository.to_string_lossy(), "maintenance", "start"]) {
+            warnings.push(format!(
+                "no scheduler could run maintenance ({}); have your own scheduler run `git maintenance run --schedule=hourly` and `--schedule=daily`",
+                e
+            ));
+        }
+    }
+    Ok(MaintenanceSetup {
+        repositories: repositories.to_vec(),
+        settings,
+        warnings,
+    })
+}
+
+// Function to take repositories out of background maintenance, removing the settings that came
+// with it. Returns the ones that were registered
+pub fn disable_maintenance(repositories: &[PathBuf]) -> Result<Vec<PathBuf>, GitError> {
+    let registered = registered_repositories()?;
+    let mut disabled = Vec::new();
+    for repository in repositories {
+        // git refuses to unregister a repository that isn't registered
+        if registered.contains(repository) {
+            run_mutating("git", &["-C", &repository.to_string_lossy(), "maintenance", "unregister"])?;
+            disabled.push(repository.clone());
+        }
+        let config = GitConfig::repo(repository);
+        for (key, _) in SETTINGS {
+            config.unset(key)?;
+        }
+    }
+    Ok(disabled)
+}
+
+// Function to list the repositories registered for background maintenance
+pub fn registered_repositories() -> Result<Vec<PathBuf>, GitError> {
+    Ok(GitConfig::global()
+        .get_all("maintenance.repo")?
+        .into_iter()
+        .map(PathBuf::from)
+        .collect())
+}
@@ -0,0 +1,57 @@
+// Helpers for gitup-owned sections inside user files (~/.ssh/config, global ignore, ...),
+// delimited by "# BEGIN gitup <name>" and "# END gitup <name>" marker lines
+
+fn begin_marker(name: &str) -> String {
+    format!("# BEGIN gitup {}", name)
+}
+
+fn end_marker(name: &str) -> String {
+    format!("# END gitup {}", name)
+}
+
+// Function to insert or replace the named block, wrapping `body` in markers
+pub(crate) fn upsert_block(existing: &str, name: &str, body: &str) -> String {
+    let mut block = format!("{}\n{}", begin_marker(name), body);
+    if !block.ends_with('\n') {
+        block.push('\n');
+    }
+    block.push_str(&end_marker(name));
+    block.push('\n');
+
+    match find_block(existing, name) {
+        Some((start, stop)) => format!("{}{}{}", &existing[..start], block, &existing[stop..]),
+        None if existing.is_empty() => block,
+        None if existing.ends_with('\n') => format!("{}\n{}", existing, block),
+        None => format!("{}\n\n{}", existing, block),
+    }
+}
+
+// Function to remove the named block, returning None if it isn't present
+pub(crate) fn remove_block(existing: &str, name: &str) -> Option<String> {
+    let (mut start, stop) = find_block(existing, name)?;
+    // Drop the blank separator line upsert_block put in front of the block
+    if existing[..start].ends_with("\n\n") {
+        start -= 1;
+    }
+    Some(format!("{}{}", &existing[..start], &existing[stop..]))
+}
+
+// Function to check whether the named block is present
+pub(crate) fn has_block(existing: &str, name: &str) -> bool {
+    find_block(existing, name).is_some()
+}
+
+// Byte range of a block including its trailing newline
+fn find_block(existing: &str, name: &str) -> Option<(usize, usize)> {
+    let begin = begin_marker(name);
+    let end = end_marker(name);
+    let start = existing
+        .match_indices(&begin)
+        .map(|(i, _)| i)
+        .find(|&i| existing[i + begin.len()..].starts_with('\n'))?;
+    let mut stop = start + existing[start..].find(&end)? + end.len();
+    if existing[stop..].starts_with('\n') {
+        stop += 1;
+    }
+    Some((start, stop))
+}
@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::alias::set_alias;
+use crate::config::GitConfig;
+use crate::drift::record_manifest;
+use crate::gitignore::{add_templates, installed_templates};
+use crate::profile::{create_profile, load_profiles, GitProfile};
+use crate::{expand_home, GitError};
+
+// The state a machine should be in, read from a TOML file. Everything listed is created
+// or updated; anything not listed is left alone
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    // Directory for shared hooks (core.hooksPath)
+    pub hooks_path: Option<String>,
+    // Built-in gitignore templates for the global excludes file
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    // Global config keys; nested tables are joined with dots, so both
+    // `"pull.rebase" = true` and `pull.rebase = true` work
+    #[serde(default)]
+    pub config: toml::Table,
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, GitProfile>,
+}
+
+// Function to read a manifest file
+pub fn load_manifest(path: &Path) -> Result<Manifest, GitError> {
+    let contents = fs::read_to_string(path)?;
+    parse_manifest(&contents, &path.display().to_string())
+}
+
+// Function to read a manifest from its TOML text; `source` names it in errors
+pub fn parse_manifest(contents: &str, source: &str) -> Result<Manifest, GitError> {
+    toml::from_str(contents).map_err(|e| GitError::Parse(format!("{}: {}", source, e)))
+}
+
+// Function to bring the machine in line with a manifest, returning a description of each
+// change; applying the same manifest again changes nothing
+pub fn apply_manifest(manifest: &Manifest) -> Result<Vec<String>, GitError> {
+    let mut changes = Vec::new();
+
+    let settings = manifest_settings(manifest)?;
+    for (key, value) in &settings {
+        let current = GitConfig::global().get(key);
+        if current.as_deref() == Some(value.as_str()) {
+            continue;
+        }
+        if let Some(name) = key.strip_prefix("alias.") {
+            set_alias(name, value)?;
+        } else {
+            GitConfig::global().set(key, value)?;
+        }
+        changes.push(match current {
+            Some(current) => format!("changed {}: {} -> {}", key, current, value),
+            None => format!("set {} = {}", key, value),
+        });
+    }
+
+    let installed = installed_templates()?;
+    let missing: Vec<String> = manifest
+        .ignore
+        .iter()
+        .filter(|name| !installed.contains(&name.as_str()))
+        .cloned()
+        .collect();
+    if !missing.is_empty() {
+        add_templates(&missing)?;
+        changes.extend(missing.iter().map(|name| format!("added ignore template {}", name)));
+    }
+
+    let existing = load_profiles()?.profiles;
+    // Base profiles are created before the profiles that extend them
+    let depth = |name: &String| {
+        let mut depth = 0;
+        let mut next = manifest.profiles.get(name).and_then(|p| p.extends.as_ref());
+        while let Some(base) = next.filter(|_| depth < manifest.profiles.len()) {
+            depth += 1;
+            next = manifest.profiles.get(base).and_then(|p| p.extends.as_ref());
+        }
+        depth
+    };
+    let mut profiles: Vec<_> = manifest.profiles.iter().collect();
+    profiles.sort_by_key(|(name, _)| depth(name));
+    for (name, profile) in profiles {
+        match existing.get(name) {
+            Some(current) if current == profile => continue,
+            Some(_) => changes.push(format!("updated profile {}", name)),
+            None => changes.push(format!("created profile {}", name)),
+        }
+        create_profile(name, profile.clone())?;
+    }
+    record_manifest(&settings)?;
+    Ok(changes)
+}
+
+// Function to get the global config a manifest sets: its config, aliases and hooks path
+pub fn manifest_settings(manifest: &Manifest) -> Result<Vec<(String, String)>, GitError> {
+    let mut settings = Vec::new();
+    flatten_config("", &manifest.config, &mut settings)?;
+    for (name, command) in &manifest.aliases {
+        settings.push((format!("alias.{}", name), command.clone()));
+    }
+    if let Some(path) = &manifest.hooks_path {
+        settings.push(("core.hooksPath".to_string(), expand_home(path).to_string_lossy().into_owned()));
+    }
+    Ok(settings)
+}
+
+// Turn nested config tables into dotted key/value pairs
+fn flatten_config(prefix: &str, table: &toml::Table, settings: &mut Vec<(String, String)>) -> Result<(), GitError> {
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        let value = match value {
+            toml::Value::Table(table) => {
+                flatten_config(&key, table, settings)?;
+                continue;
+            }
+            toml::Value::String(value) => value.clone(),
+            toml::Value::Boolean(value) => value.to_string(),
+            toml::Value::Integer(value) => value.to_string(),
+            _ => {
+                return Err(GitError::InvalidInput(format!(
+                    "config value for '{}' must be a string, boolean or integer",
+                    key
+                )))
+            }
+        };
+        settings.push((key, value));
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::GitConfig;
+use crate::dry_run::{create_dir_all, remove_file, rename, write_file};
+use crate::lock::lock_config;
+use crate::profile::{load_profiles, profiles_path, save_profiles};
+use crate::{config_dir, config_dir_overridden, home_dir, GitError};
+
+// Where gitup kept its files before moving to the platform config directory
+const LEGACY_PROFILES_FILE: &str = ".git_profiles.toml";
+const LEGACY_STATE_DIR: &str = ".gitup";
+// The legacy files an earlier migration left behind, one path per line, so the migration isn't
+// redone on every run while they are still there
+const KEPT_FILE: &str = "legacy-kept";
+
+// What a migration moved
+#[derive(Debug, Clone, Default)]
+pub struct Migration {
+    pub moved: Vec<PathBuf>,
+    // Legacy files left behind because the new location already had one
+    pub kept: Vec<PathBuf>,
+}
+
+// Function to move the legacy ~/.git_profiles.toml and ~/.gitup into the config directory,
+// pointing git config, profile fragments and profiles at the new paths; returns None when
+// there is nothing to migrate or the config directory was overridden
+pub fn migrate_legacy_files() -> Result<Option<Migration>, GitError> {
+    if config_dir_overridden() {
+        return Ok(None);
+    }
+    let legacy_profiles = home_dir().join(LEGACY_PROFILES_FILE);
+    let legacy_dir = home_dir().join(LEGACY_STATE_DIR);
+    let mut remaining: Vec<PathBuf> = Vec::new();
+    if legacy_profiles.exists() {
+        remaining.push(legacy_profiles.clone());
+    }
+    if legacy_dir.is_dir() {
+        for entry in fs::read_dir(&legacy_dir)? {
+            remaining.push(entry?.path());
+        }
+    }
+    let dir = config_dir();
+    let kept_file = dir.join(KEPT_FILE);
+    let kept_before: Vec<PathBuf> = fs::read_to_string(&kept_file)
+        .unwrap_or_default()
+        .lines()
+        .map(PathBuf::from)
+        .collect();
+    if remaining.iter().all(|path| kept_before.contains(path)) {
+        return Ok(None);
+    }
+
+    create_dir_all(&dir)?;
+    let _lock = lock_config()?;
+    let mut migration = Migration::default();
+    move_entry(&legacy_profiles, &profiles_path(), &mut migration)?;
+    if legacy_dir.is_dir() {
+        for entry in fs::read_dir(&legacy_dir)? {
+            let path = entry?.path();
+            let target = dir.join(path.file_name().unwrap_or_default());
+            move_entry(&path, &target, &mut migration)?;
+        }
+        if migration.kept.iter().all(|path| !path.starts_with(&legacy_dir)) {
+            let _ = fs::remove_dir(&legacy_dir);
+        }
+    }
+
+    // Git config and generated fragments name files by absolute path
+    let old_prefix = format!("{}/", legacy_dir.display());
+    let new_prefix = format!("{}/", dir.display());
+    for (key, value) in GitConfig::global().list()? {
+        if let Some(rest) = value.strip_prefix(&old_prefix) {
+            GitConfig::global().set(&key, &format!("{}{}", new_prefix, rest))?;
+        }
+    }
+    if dir.is_dir() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "gitconfig") {
+                let contents = fs::read_to_string(&path)?;
+                if contents.contains(&old_prefix) {
+                    write_file(&path, &contents.replace(&old_prefix, &new_prefix))?;
+                }
+            }
+        }
+    }
+
+    let mut profiles = load_profiles()?;
+    let mut changed = false;
+    for profile in profiles.profiles.values_mut() {
+        if let Some(rest) = profile.commit_template.as_ref().and_then(|t| t.strip_prefix(&old_prefix)) {
+            profile.commit_template = Some(format!("{}{}", new_prefix, rest));
+            changed = true;
+        }
+    }
+    if changed {
+        save_profiles(&profiles)?;
+    }
+    if !migration.kept.is_empty() {
+        let kept: Vec<String> = migration.kept.iter().map(|path| path.display().to_string()).collect();
+        write_file(&kept_file, &(kept.join("\n") + "\n"))?;
+    } else if kept_file.exists() {
+        remove_file(&kept_file)?;
+    }
+    Ok(Some(migration))
+}
+
+// Move one legacy file or directory unless something already lives at the target
+fn move_entry(from: &Path, to: &Path, migration: &mut Migration) -> Result<(), GitError> {
+    if !from.exists() {
+        return Ok(());
+    }
+    if to.exists() {
+        migration.kept.push(from.to_path_buf());
+        return Ok(());
+    }
+    if rename(from, to).is_err() {
+        // Renaming fails across filesystems; plain files can still be copied
+        if !from.is_file() {
+            return Err(GitError::InvalidInput(format!(
+                "couldn't move {} to {}",
+                from.display(),
+                to.display()
+            )));
+        }
+        write_file(to, &fs::read_to_string(from)?)?;
+        remove_file(from)?;
+    }
+    migration.moved.push(to.to_path_buf());
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use std::env;
+
+use serde::Serialize;
+
+use crate::config::GitConfig;
+use crate::git::run_mutating;
+use crate::GitError;
+
+// Environment variables curl, and so git, reads when http.proxy isn't set
+const PROXY_VARIABLES: &[&str] = &[
+    "http_proxy",
+    "HTTP_PROXY",
+    "https_proxy",
+    "HTTPS_PROXY",
+    "all_proxy",
+    "ALL_PROXY",
+    "no_proxy",
+    "NO_PROXY",
+];
+
+// Schemes a no-proxy exception is written for; git matches the scheme of http.<url>.proxy exactly
+const SCHEMES: &[&str] = &["https", "http"];
+
+// A url.<base>.insteadOf entry: remote URLs starting with `prefix` are fetched from `base` instead
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UrlRewrite {
+    pub prefix: String,
+    pub base: String,
+}
+
+// The proxy settings git uses for HTTP(S) remotes
+#[derive(Debug, Clone, Default)]
+pub struct ProxyConfig {
+    // http.proxy, used for every HTTP and HTTPS remote
+    pub proxy: Option<String>,
+    // Hosts reached directly, from http.<url>.proxy entries set to ""
+    pub no_proxy: Vec<String>,
+    pub rewrites: Vec<UrlRewrite>,
+    // Proxy variables set in the environment; git only falls back to them without http.proxy
+    pub environment: Vec<(String, String)>,
+}
+
+// Function to point git at a proxy and exempt hosts from it. Git has a single proxy setting for
+// HTTP and HTTPS remotes, so different URLs for the two are rejected
+pub fn set_proxy(http: Option<&str>, https: Option<&str>, no_proxy: &[String]) -> Result<(), GitError> {
+    let proxy = match (http, https) {
+        (Some(http), Some(https)) if http != https => {
+            return Err(GitError::InvalidInput(
+                "git uses one proxy (http.proxy) for both HTTP and HTTPS remotes; give the same URL for both"
+                    .to_string(),
+            ))
+        }
+        (http, https) => http.or(https),
+    };
+    if let Some(proxy) = proxy {
+        validate_proxy(proxy)?;
+    }
+    for host in no_proxy {
+        validate_host(host)?;
+    }
+
+    if let Some(proxy) = proxy {
+        GitConfig::global().set("http.proxy", proxy)?;
+    }
+    for host in no_proxy {
+        for scheme in SCHEMES {
+            // An empty proxy for a URL turns proxying off for it
+            GitConfig::global().set(&format!("http.{}://{}.proxy", scheme, host), "")?;
+        }
+    }
+    Ok(())
+}
+
+// Function to remove the proxy and every no-proxy exception from the global configuration
+pub fn unset_proxy() -> Result<(), GitError> {
+    GitConfig::global().unset("http.proxy")?;
+    for key in exception_keys()? {
+        GitConfig::global().unset(&key)?;
+    }
+    Ok(())
+}
+
+// Function to report the proxy, its exceptions and URL rewrites
+pub fn proxy_config() -> Result<ProxyConfig, GitError> {
+    let mut no_proxy = Vec::new();
+    for key in exception_keys()? {
+        let host = key
+            .trim_start_matches("http.")
+            .trim_end_matches(".proxy")
+            .split_once("://")
+            .map(|(_, host)| host.to_string())
+            .unwrap_or_default();
+        if !no_proxy.contains(&host) {
+            no_proxy.push(host);
+        }
+    }
+    let environment = PROXY_VARIABLES
+        .iter()
+        .filter_map(|name| env::var(name).ok().map(|value| (name.to_string(), value)))
+        .collect();
+    Ok(ProxyConfig {
+        proxy: GitConfig::global().get("http.proxy"),
+        no_proxy,
+        rewrites: list_rewrites()?,
+        environment,
+    })
+}
+
+// Function to make git fetch remote URLs starting with `prefix` from `base` instead, e.g. an
+// internal mirror of github.com
+pub fn add_rewrite(prefix: &str, base: &str) -> Result<(), GitError> {
+    validate_url(prefix)?;
+    validate_url(base)?;
+    let rewrites = list_rewrites()?;
+    if let Some(existing) = rewrites.iter().find(|rewrite| rewrite.prefix == prefix) {
+        if existing.base == base {
+            return Ok(());
+        }
+        remove_rewrite(prefix)?;
+    }
+    // A base can stand in for several prefixes, so the value is added rather than replaced
+    GitConfig::global().add(&format!("url.{}.insteadOf", base), prefix)
+}
+
+// Function to remove the rewrite for a URL prefix
+pub fn remove_rewrite(prefix: &str) -> Result<(), GitError> {
+    let rewrite = list_rewrites()?
+        .into_iter()
+        .find(|rewrite| rewrite.prefix == prefix)
+        .ok_or_else(|| GitError::InvalidInput(format!("no rewrite for '{}'", prefix)))?;
+    let key = format!("url.{}.insteadOf", rewrite.base);
+    run_mutating("git", &["config", "--global", "--fixed-value", "--unset", &key, prefix])
+}
+
+// Function to list the global url.<base>.insteadOf rewrites
+pub fn list_rewrites() -> Result<Vec<UrlRewrite>, GitError> {
+    Ok(GitConfig::global().list()?
+        .into_iter()
+        .filter_map(|(key, prefix)| {
+            let base = key.strip_prefix("url.")?.strip_suffix(".insteadof")?;
+            Some(UrlRewrite {
+                prefix,
+                base: base.to_string(),
+            })
+        })
+        .collect())
+}
+
+// Keys of the http.<url>.proxy entries that are set to "", i.e. the no-proxy exceptions
+fn exception_keys() -> Result<Vec<String>, GitError> {
+    Ok(GitConfig::global().list()?
+        .into_iter()
+        .filter(|(key, value)| {
+            value.is_empty() && key.starts_with("http.") && key.ends_with(".proxy") && key != "http.proxy"
+        })
+        .map(|(key, _)| key)
+        .collect())
+}
+
+// git accepts [protocol://][user[:password]@]host[:port] for http.proxy
+fn validate_proxy(proxy: &str) -> Result<(), GitError> {
+    let host = proxy.split_once("://").map_or(proxy, |(_, rest)| rest);
+    if host.is_empty() || proxy.contains(char::is_whitespace) {
+        return Err(GitError::InvalidInput(format!("'{}' is not a valid proxy URL", proxy.escape_debug())));
+    }
+    Ok(())
+}
+
+// A no-proxy host may use '*' for one name component, as git's URL matching does
+fn validate_host(host: &str) -> Result<(), GitError> {
+    if host.is_empty() || host.contains(|c: char| c.is_whitespace() || c == '/' || c == '@') {
+        return Err(GitError::InvalidInput(format!(
+            "'{}' is not a host name (use e.g. git.corp.example or *.corp.example)",
+            host.escape_debug()
+        )));
+    }
+    Ok(())
+}
+
+fn validate_url(url: &str) -> Result<(), GitError> {
+    if url.is_empty() || url.contains(char::is_whitespace) {
+        return Err(GitError::InvalidInput(format!("'{}' is not a valid URL", url.escape_debug())));
+    }
+    Ok(())
+}
@@ -0,0 +1,24 @@
+// How a front end wants gitup to behave when nobody may be watching, such as when
+// provisioning a CI machine; the library reports results and leaves printing and
+// prompting to the caller, which consults these settings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Options {
+    // Answer yes to confirmations: install missing software and overwrite existing data
+    pub assume_yes: bool,
+    // Only report errors
+    pub quiet: bool,
+    // Record changes instead of making them, like the global DryRun mode but for one caller
+    pub dry_run: bool,
+}
+
+impl Options {
+    // Function to check whether the user may be asked questions at all
+    pub fn interactive(&self) -> bool {
+        !self.assume_yes
+    }
+
+    // Function to decide whether an overwrite goes ahead, given the command's own --force flag
+    pub fn allow_overwrite(&self, force: bool) -> bool {
+        force || self.assume_yes
+    }
+}
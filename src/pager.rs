@@ -0,0 +1,122 @@
+use std::fmt;
+use std::str::FromStr;
+
+use semver::Version;
+
+use crate::config::GitConfig;
+use crate::git::get_git_version;
+use crate::install::{command_exists, install_system_package};
+use crate::GitError;
+
+// The first git with merge.conflictStyle=zdiff3, which delta shows best
+const ZDIFF3_VERSION: Version = Version::new(2, 35, 0);
+
+// A pager that makes git's diffs easier to read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pager {
+    // https://github.com/dandavison/delta
+    Delta,
+    // https://github.com/so-fancy/diff-so-fancy
+    DiffSoFancy,
+}
+
+impl fmt::Display for Pager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pager::Delta => f.write_str("delta"),
+            Pager::DiffSoFancy => f.write_str("diff-so-fancy"),
+        }
+    }
+}
+
+impl FromStr for Pager {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "delta" | "git-delta" => Ok(Pager::Delta),
+            "diff-so-fancy" => Ok(Pager::DiffSoFancy),
+            other => Err(format!("unknown pager '{}' (expected delta or diff-so-fancy)", other)),
+        }
+    }
+}
+
+impl Pager {
+    // The command it is run as
+    fn program(self) -> &'static str {
+        match self {
+            Pager::Delta => "delta",
+            Pager::DiffSoFancy => "diff-so-fancy",
+        }
+    }
+
+    // Its package; most package managers call delta git-delta, since another delta exists
+    fn package(self) -> &'static str {
+        match self {
+            Pager::Delta => "git-delta",
+            Pager::DiffSoFancy => "diff-so-fancy",
+        }
+    }
+
+    // The settings each project recommends in its README
+    fn settings(self) -> Vec<(&'static str, &'static str)> {
+        match self {
+            Pager::Delta => {
+                let conflict_style = match get_git_version() {
+                    Ok(version) if version < ZDIFF3_VERSION => "diff3",
+                    _ => "zdiff3",
+                };
+                vec![
+                    ("core.pager", "delta"),
+                    // `git add -p` needs the colored diff with the lines untouched
+                    ("interactive.diffFilter", "delta --color-only"),
+                    // n and N move between files in a diff
+                    ("delta.navigate", "true"),
+                    ("merge.conflictStyle", conflict_style),
+                    ("diff.colorMoved", "default"),
+                ]
+            }
+            Pager::DiffSoFancy => vec![
+                ("core.pager", "diff-so-fancy | less --tabs=4 -RF"),
+                ("interactive.diffFilter", "diff-so-fancy --patch"),
+                ("color.ui", "true"),
+                ("color.diff-highlight.oldNormal", "red bold"),
+                ("color.diff-highlight.oldHighlight", "red bold 52"),
+                ("color.diff-highlight.newNormal", "green bold"),
+                ("color.diff-highlight.newHighlight", "green bold 22"),
+                ("color.diff.meta", "11"),
+                ("color.diff.frag", "magenta bold"),
+                ("color.diff.func", "146 bold"),
+                ("color.diff.commit", "yellow bold"),
+                ("color.diff.old", "red bold"),
+                ("color.diff.new", "green bold"),
+                ("color.diff.whitespace", "red reverse"),
+            ],
+        }
+    }
+}
+
+// What `setup_pager` did
+#[derive(Debug, Clone)]
+pub struct PagerSetup {
+    // The package manager it was installed with; None when it was already installed
+    pub installed_with: Option<&'static str>,
+    pub settings: Vec<(String, String)>,
+}
+
+// Function to install a pager if it's missing, with the named package manager or the detected
+// one, and make git use it for diffs, logs and `git add -p` with the recommended settings
+pub fn setup_pager(pager: Pager, package_manager: Option<&str>) -> Result<PagerSetup, GitError> {
+    let installed_with = if command_exists(pager.program()) {
+        None
+    } else {
+        Some(install_system_package(pager.package(), package_manager)?)
+    };
+    let settings: Vec<(String, String)> = pager
+        .settings()
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    GitConfig::global().set_many(&settings)?;
+    Ok(PagerSetup { installed_with, settings })
+}
@@ -0,0 +1,229 @@
+// The picker `gitup profile use` opens when no profile is named: type to narrow the profiles down
+// by fuzzy search, then pick one. With the `tui` feature it filters as you type; without it the
+// profiles are listed and the search is asked for
+use std::cmp::Reverse;
+use std::path::Path;
+
+use gitup::profile::{active_profile, list_profiles, GitProfile};
+use gitup::GitError;
+
+// A profile as the picker shows it
+struct Entry {
+    profile: String,
+    identity: String,
+    signing: String,
+    // Tags and description, searched along with the name
+    about: String,
+    active: bool,
+}
+
+impl Entry {
+    fn new(profile: String, p: &GitProfile, active: Option<&String>) -> Entry {
+        Entry {
+            active: active == Some(&profile),
+            identity: format!("{} <{}>", p.name, p.email),
+            signing: signing_summary(p),
+            about: p.tags.iter().chain(&p.description).cloned().collect::<Vec<_>>().join(" "),
+            profile,
+        }
+    }
+
+    // What the search is matched against
+    fn haystack(&self) -> String {
+        format!("{} {} {}", self.profile, self.identity, self.about)
+    }
+}
+
+// Function to ask which profile to use, returning None when the user backs out
+pub fn pick_profile() -> Result<Option<String>, GitError> {
+    let active = active_profile()?;
+    let entries: Vec<Entry> = list_profiles()?
+        .into_iter()
+        .map(|(profile, p)| Entry::new(profile, &p, active.as_ref()))
+        .collect();
+    if entries.is_empty() {
+        return Err(GitError::InvalidInput(
+            "there are no profiles to pick from; create one with `gitup profile create`".to_string(),
+        ));
+    }
+    pick(&entries)
+}
+
+// The entries matching a search, best match first; all of them, in order, for an empty search
+fn matches(entries: &[Entry], query: &str) -> Vec<usize> {
+    let mut scored: Vec<(i64, usize)> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(i, entry)| fuzzy_score(query, &entry.haystack()).map(|score| (score, i)))
+        .collect();
+    // The sort is stable, so equally good matches keep their order
+    scored.sort_by_key(|&(score, _)| Reverse(score));
+    scored.into_iter().map(|(_, i)| i).collect()
+}
+
+// How well `text` matches `query` when the query's characters appear in it in order, ignoring
+// case and spaces in the query; None when they don't. Runs of consecutive characters and matches
+// at the start of a word score higher, and gaps lower
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut last: Option<usize> = None;
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + text[position..].iter().position(|&c| c == wanted)?;
+        score += 1;
+        match last {
+            Some(last) if found == last + 1 => score += 8,
+            Some(last) => score -= (found - last - 1).min(8) as i64,
+            None => score -= found.min(8) as i64,
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 4;
+        }
+        last = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+// How the profile signs commits, briefly: the format and which key
+fn signing_summary(p: &GitProfile) -> String {
+    let get = |key: &str| {
+        p.extra
+            .iter()
+            .find(|(setting, _)| setting.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    };
+    let Some(key) = get("user.signingkey") else {
+        return "unsigned".to_string();
+    };
+    let format = get("gpg.format").unwrap_or("openpgp");
+    let key = match format {
+        // A key file, or the public key itself after key::
+        "ssh" if key.starts_with("key::") => "inline key".to_string(),
+        "ssh" => Path::new(key)
+            .file_name()
+            .map_or_else(|| key.to_string(), |name| name.to_string_lossy().into_owned()),
+        // The long key ID is what gpg shows
+        _ if key.len() > 16 && key.chars().all(|c| c.is_ascii_hexdigit()) => key[key.len() - 16..].to_string(),
+        _ => key.to_string(),
+    };
+    let signs = get("commit.gpgsign").is_some_and(|value| value.eq_ignore_ascii_case("true"));
+    format!("{} {}{}", format, key, if signs { "" } else { " (commits unsigned)" })
+}
+
+#[cfg(feature = "tui")]
+fn pick(entries: &[Entry]) -> Result<Option<String>, GitError> {
+    use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+    use ratatui::layout::{Constraint, Layout};
+    use ratatui::style::{Modifier, Style, Stylize};
+    use ratatui::text::{Line, Span};
+    use ratatui::widgets::{List, ListItem, ListState, Paragraph};
+    use ratatui::{TerminalOptions, Viewport};
+
+    // The picker is drawn below the prompt rather than taking over the screen
+    let height = entries.len().min(10) as u16 + 2;
+    let mut terminal = ratatui::try_init_with_options(TerminalOptions {
+        viewport: Viewport::Inline(height),
+    })?;
+    let mut query = String::new();
+    let mut list = ListState::default().with_selected(Some(0));
+    let result = loop {
+        let shown = matches(entries, &query);
+        list.select(if shown.is_empty() {
+            None
+        } else {
+            Some(list.selected().unwrap_or_default().min(shown.len() - 1))
+        });
+        let drawn = terminal.draw(|frame| {
+            let [input, rows, help] =
+                Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+            frame.render_widget(Line::from(vec!["Profile: ".bold(), Span::raw(&query)]), input);
+            frame.set_cursor_position((input.x + 9 + query.chars().count() as u16, input.y));
+            let items: Vec<ListItem> = shown
+                .iter()
+                .map(|&i| {
+                    let entry = &entries[i];
+                    let marker = if entry.active { "*" } else { " " };
+                    ListItem::new(Line::from(vec![
+                        Span::raw(format!("{} {}  ", marker, entry.profile)).bold(),
+                        Span::raw(format!("{}  ", entry.identity)),
+                        Span::raw(entry.signing.as_str()).dim(),
+                    ]))
+                })
+                .collect();
+            let list_widget = List::new(items).highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+            frame.render_stateful_widget(list_widget, rows, &mut list);
+            frame.render_widget(
+                Paragraph::new(format!(
+                    "{}/{} profiles; type to search, arrows select, Enter uses, Esc cancels",
+                    shown.len(),
+                    entries.len()
+                ))
+                .dim(),
+                help,
+            );
+        });
+        if let Err(e) = drawn {
+            break Err(e.into());
+        }
+        let key = match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+            Ok(_) => continue,
+            Err(e) => break Err(e.into()),
+        };
+        match key.code {
+            KeyCode::Esc => break Ok(None),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break Ok(None),
+            KeyCode::Enter => {
+                if let Some(&i) = list.selected().and_then(|selected| shown.get(selected)) {
+                    break Ok(Some(entries[i].profile.clone()));
+                }
+            }
+            KeyCode::Down | KeyCode::Tab => list.select_next(),
+            KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => list.select_next(),
+            KeyCode::Up | KeyCode::BackTab => list.select_previous(),
+            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => list.select_previous(),
+            KeyCode::Backspace => {
+                query.pop();
+                list.select(Some(0));
+            }
+            KeyCode::Char(c) => {
+                query.push(c);
+                list.select(Some(0));
+            }
+            _ => {}
+        }
+    };
+    // Leave the terminal as it was, with the picker gone
+    let _ = terminal.clear();
+    ratatui::restore();
+    result
+}
+
+#[cfg(not(feature = "tui"))]
+fn pick(entries: &[Entry]) -> Result<Option<String>, GitError> {
+    let mut shown: Vec<usize> = (0..entries.len()).collect();
+    loop {
+        for (n, &i) in shown.iter().enumerate() {
+            let entry = &entries[i];
+            let marker = if entry.active { "*" } else { " " };
+            println!("{:>3}. {} {}  {}  {}", n + 1, marker, entry.profile, entry.identity, entry.signing);
+        }
+        let answer = crate::prompt_user("Profile (number, or text to search; empty to cancel): ");
+        if answer.is_empty() {
+            return Ok(None);
+        }
+        if let Some(&i) = answer.parse::<usize>().ok().and_then(|n| shown.get(n.wrapping_sub(1))) {
+            return Ok(Some(entries[i].profile.clone()));
+        }
+        let found = matches(entries, &answer);
+        match found.as_slice() {
+            [] => println!("No profile matches '{}'.", answer),
+            // An exact name wins even when the search matches others too
+            _ if found.iter().any(|&i| entries[i].profile == answer) => return Ok(Some(answer)),
+            [i] => return Ok(Some(entries[*i].profile.clone())),
+            _ => shown = found,
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+use serde::Serialize;
+
+use crate::options::Options;
+use crate::{config_dir, GitError, CONFIG_DIR_ENV};
+
+// Executables named gitup-<name> on PATH run as `gitup <name>`, the way git runs git-<name>
+pub const PLUGIN_PREFIX: &str = "gitup-";
+
+// What a plugin is told about the gitup that started it. GITUP_BIN is there to call back
+// into, e.g. `"$GITUP_BIN" --json profile list`; CONFIG_DIR_ENV carries the directory
+// profiles and state are kept in, which that call picks up as well
+pub const PLUGIN_BIN_ENV: &str = "GITUP_BIN";
+pub const PLUGIN_VERSION_ENV: &str = "GITUP_VERSION";
+// The --format asked for (json, yaml, toml or table); unset for plain messages
+pub const PLUGIN_FORMAT_ENV: &str = "GITUP_FORMAT";
+// "1" for --dry-run, --yes and --quiet
+pub const PLUGIN_DRY_RUN_ENV: &str = "GITUP_DRY_RUN";
+pub const PLUGIN_YES_ENV: &str = "GITUP_YES";
+pub const PLUGIN_QUIET_ENV: &str = "GITUP_QUIET";
+
+// A gitup-<name> executable found on PATH
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Plugin {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+// Function to find every plugin on PATH. As with running a command, the first directory
+// with a given name wins
+pub fn list_plugins() -> Vec<Plugin> {
+    let Some(paths) = env::var_os("PATH") else {
+        return Vec::new();
+    };
+    let mut seen = HashSet::new();
+    let mut plugins = Vec::new();
+    for dir in env::split_paths(&paths) {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut found: Vec<Plugin> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.path();
+                let name = plugin_name(&path)?;
+                is_executable(&path).then_some(Plugin { name, path })
+            })
+            .collect();
+        found.sort_by(|a, b| a.name.cmp(&b.name));
+        plugins.extend(found.into_iter().filter(|plugin| seen.insert(plugin.name.clone())));
+    }
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    plugins
+}
+
+// Function to find the plugin that runs as `gitup <name>`
+pub fn find_plugin(name: &str) -> Option<Plugin> {
+    list_plugins().into_iter().find(|plugin| plugin.name == name)
+}
+
+// Function to run a plugin with its arguments and gitup's settings in its environment,
+// returning how it exited. Plugins get the terminal, so they run directly rather than through
+// the command runner, which captures output
+pub fn run_plugin(plugin: &Plugin, args: &[String], options: &Options, format: Option<&str>) -> Result<ExitStatus, GitError> {
+    let mut command = Command::new(&plugin.path);
+    command
+        .args(args)
+        .env(PLUGIN_BIN_ENV, env::current_exe()?)
+        .env(PLUGIN_VERSION_ENV, env!("CARGO_PKG_VERSION"))
+        .env(CONFIG_DIR_ENV, config_dir());
+    for (variable, value) in [
+        (PLUGIN_FORMAT_ENV, format),
+        (PLUGIN_DRY_RUN_ENV, options.dry_run.then_some("1")),
+        (PLUGIN_YES_ENV, options.assume_yes.then_some("1")),
+        (PLUGIN_QUIET_ENV, options.quiet.then_some("1")),
+    ] {
+        match value {
+            Some(value) => command.env(variable, value),
+            // Not inherited from a gitup that started this one
+            None => command.env_remove(variable),
+        };
+    }
+    command
+        .status()
+        .map_err(|e| GitError::spawn_failed(&plugin.path.to_string_lossy(), e))
+}
+
+// The name a plugin file runs as, without the prefix and, on Windows, the extension
+fn plugin_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    let name = file_name.strip_prefix(PLUGIN_PREFIX)?;
+    let name = if cfg!(windows) {
+        let (stem, extension) = name.rsplit_once('.')?;
+        ["exe", "cmd", "bat"].contains(&extension.to_lowercase().as_str()).then_some(stem)?
+    } else {
+        name
+    };
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::commit_template::template_source;
+use crate::eol::EolPolicy;
+use crate::git::run_checked;
+use crate::profile::{GitProfile, PullStrategy};
+use crate::ssh::public_key_path;
+use crate::validate::validate_email;
+use crate::GitError;
+
+// Values git accepts for gpg.format
+const SIGNING_FORMATS: &[&str] = &["ssh", "openpgp", "x509"];
+
+// Defaults a team hands to new members as a TOML file or URL; `gitup profile new
+// --from-template` fills in the personal parts (name, email user, keys) and creates a profile
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfilePreset {
+    // Suggested profile name, e.g. "acme"
+    pub profile: Option<String>,
+    pub description: Option<String>,
+    // Emails must be in this domain; only the part before the @ is asked for
+    pub email_domain: Option<String>,
+    pub editor: Option<String>,
+    pub default_branch: Option<String>,
+    pub pull: Option<PullStrategy>,
+    // Line ending policy every member's profile gets
+    pub eol: Option<EolPolicy>,
+    pub autocrlf: Option<String>,
+    // Built-in commit template name
+    pub commit_template: Option<String>,
+    #[serde(default)]
+    pub signing: SigningPolicy,
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    // Any other git config keys
+    #[serde(default)]
+    pub config: BTreeMap<String, String>,
+    // Remote URL patterns of the team's repositories, e.g. github.com/acme/*
+    #[serde(default)]
+    pub remotes: Vec<String>,
+    // Tags every member's profile gets
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+// Whether commits and tags must be signed, and with what kind of key
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SigningPolicy {
+    #[serde(default)]
+    pub required: bool,
+    // gpg.format: "ssh", "openpgp" or "x509"
+    pub format: Option<String>,
+}
+
+// The personal details a preset leaves out
+#[derive(Debug, Clone, Default)]
+pub struct PresetAnswers {
+    pub name: String,
+    pub email: String,
+    pub ssh_key: Option<PathBuf>,
+    // Key used for signing; with SSH signing it defaults to the profile's SSH key
+    pub signing_key: Option<String>,
+}
+
+impl ProfilePreset {
+    // Function to build the email address for a user name in the preset's domain
+    pub fn email_for(&self, user: &str) -> Option<String> {
+        self.email_domain.as_ref().map(|domain| format!("{}@{}", user, domain))
+    }
+
+    // Function to check whether a signing key still has to be asked for
+    pub fn needs_signing_key(&self, answers: &PresetAnswers) -> bool {
+        self.signing.required && answers.signing_key.is_none() && !(self.ssh_signing() && answers.ssh_key.is_some())
+    }
+
+    // Function to combine the preset with the personal details into a profile, enforcing the
+    // email domain and signing policy
+    pub fn to_profile(&self, answers: PresetAnswers) -> Result<GitProfile, GitError> {
+        validate_email(&answers.email)?;
+        if let Some(domain) = &self.email_domain {
+            let in_domain = answers
+                .email
+                .rsplit_once('@')
+                .is_some_and(|(_, email_domain)| email_domain.eq_ignore_ascii_case(domain));
+            if !in_domain {
+                return Err(GitError::InvalidInput(format!(
+                    "this template requires an email address at {}",
+                    domain
+                )));
+            }
+        }
+
+        let mut extra = self.config.clone();
+        for (name, command) in &self.aliases {
+            extra.insert(format!("alias.{}", name), command.clone());
+        }
+        if self.signing.required {
+            let signing_key = match (&answers.signing_key, &answers.ssh_key) {
+                (Some(key), _) => key.clone(),
+                (None, Some(ssh_key)) if self.ssh_signing() => public_key_path(ssh_key).to_string_lossy().into_owned(),
+                _ => {
+                    return Err(GitError::InvalidInput(
+                        "this template requires signed commits; give a signing key".to_string(),
+                    ))
+                }
+            };
+            extra.insert("user.signingkey".to_string(), signing_key);
+            extra.insert("commit.gpgsign".to_string(), "true".to_string());
+            extra.insert("tag.gpgsign".to_string(), "true".to_string());
+        }
+        if let Some(format) = &self.signing.format {
+            extra.insert("gpg.format".to_string(), format.clone());
+        }
+
+        Ok(GitProfile {
+            extends: None,
+            name: answers.name,
+            email: answers.email,
+            ssh_key: answers.ssh_key,
+            editor: self.editor.clone(),
+            default_branch: self.default_branch.clone(),
+            pull: self.pull,
+            eol: self.eol,
+            autocrlf: self.autocrlf.clone(),
+            commit_template: self.commit_template.as_deref().map(template_source).transpose()?,
+            extra,
+            remotes: self.remotes.clone(),
+            description: self.description.clone(),
+            tags: self.tags.clone(),
+            created_at: None,
+            last_used_at: None,
+        })
+    }
+
+    fn ssh_signing(&self) -> bool {
+        self.signing.format.as_deref() == Some("ssh")
+    }
+}
+
+// Function to read a preset from a file or an http(s) URL
+pub fn load_preset(source: &str) -> Result<ProfilePreset, GitError> {
+    let contents = if source.starts_with("https://") || source.starts_with("http://") {
+        let output = run_checked("curl", &["--silent", "--show-error", "--fail", "--location", source])?;
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    } else {
+        fs::read_to_string(source)?
+    };
+    let preset: ProfilePreset =
+        toml::from_str(&contents).map_err(|e| GitError::Parse(format!("{}: {}", source, e)))?;
+    if let Some(format) = &preset.signing.format {
+        if !SIGNING_FORMATS.contains(&format.as_str()) {
+            return Err(GitError::Parse(format!(
+                "{}: unknown signing format '{}' (expected {})",
+                source,
+                format,
+                SIGNING_FORMATS.join(", ")
+            )));
+        }
+    }
+    Ok(preset)
+}
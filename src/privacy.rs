@@ -0,0 +1,85 @@
+use serde::Serialize;
+
+use crate::auth::{secret_name, verify_token, Provider};
+use crate::config::GitConfig;
+use crate::secrets::get_secret;
+use crate::GitError;
+
+// A provider address that hides the real email in public commits but still links them to the account
+#[derive(Debug, Clone, Serialize)]
+pub struct NoreplyEmail {
+    pub email: String,
+    // The ID-based form keeps working after the account is renamed; the older name-only form
+    // (GitHub only) is used when no token is stored to look up the ID
+    pub id_based: bool,
+}
+
+// The identity settings that decide whether a real address can end up in a commit
+#[derive(Debug, Clone, Serialize)]
+pub struct PrivacyStatus {
+    pub email: Option<String>,
+    pub noreply: bool,
+    pub use_config_only: bool,
+}
+
+// Function to work out the noreply address of an account, using the token stored with
+// `gitup auth` to look up its ID, or `username` when there is none
+pub fn noreply_email(provider: Provider, host: &str, username: Option<&str>) -> Result<NoreplyEmail, GitError> {
+    if provider == Provider::Bitbucket {
+        return Err(GitError::InvalidInput("Bitbucket has no noreply addresses".to_string()));
+    }
+    // Both services, self-hosted ones included, use users.noreply.<host>
+    let domain = format!("users.noreply.{}", host);
+    // Without a usable keyring (e.g. a headless Linux box) a given user name is still enough
+    let token = get_secret(&secret_name(host)).or_else(|e| if username.is_some() { Ok(None) } else { Err(e) })?;
+    if let Some(token) = token {
+        let info = verify_token(provider, host, &token)?;
+        let id = info
+            .id
+            .ok_or_else(|| GitError::Parse(format!("{} did not report an account ID", host)))?;
+        let email = match provider {
+            Provider::GitHub => format!("{}+{}@{}", id, info.username, domain),
+            _ => format!("{}-{}@{}", id, info.username, domain),
+        };
+        return Ok(NoreplyEmail { email, id_based: true });
+    }
+
+    match (provider, username) {
+        (Provider::GitHub, Some(username)) => Ok(NoreplyEmail {
+            email: format!("{}@{}", username, domain),
+            id_based: false,
+        }),
+        (Provider::GitHub, None) => Err(GitError::InvalidInput(format!(
+            "no token is stored for {}; run `gitup auth github` or give a user name",
+            host
+        ))),
+        _ => Err(GitError::InvalidInput(format!(
+            "GitLab noreply addresses include the account ID; run `gitup auth gitlab --host {}` first",
+            host
+        ))),
+    }
+}
+
+// Function to make a noreply address the global commit email. With `use_config_only`, git
+// also stops guessing an address from the user and host names when none is configured
+pub fn enable_privacy(email: &str, use_config_only: bool) -> Result<(), GitError> {
+    let config = GitConfig::global();
+    config.set("user.email", email)?;
+    if use_config_only {
+        config.set("user.useConfigOnly", "true")?;
+    }
+    Ok(())
+}
+
+// Function to check whether the global commit email is a noreply address
+pub fn privacy_status() -> PrivacyStatus {
+    let config = GitConfig::global();
+    let email = config.get("user.email");
+    PrivacyStatus {
+        noreply: email.as_deref().is_some_and(|email| email.contains("@users.noreply.")),
+        email,
+        use_config_only: config
+            .get("user.useConfigOnly")
+            .is_some_and(|value| matches!(value.to_lowercase().as_str(), "true" | "yes" | "on" | "1")),
+    }
+}
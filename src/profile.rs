@@ -0,0 +1,815 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::binding::{fragment_path, rebind_profile, unbind_profile, write_profile_fragment, Binding};
+use crate::commit_template::{install_template, template_location};
+use crate::config::GitConfig;
+use crate::drift::{record_profile, rename_recorded_profile};
+use crate::dry_run::{create_dir_all, write_file};
+use crate::encryption::{decrypt, encrypt, is_encrypted, passphrase};
+use crate::eol::EolPolicy;
+use crate::secrets::{is_secret_reference, resolve_secret};
+use crate::stats::{record, StatsEvent};
+use crate::switch_history::{record_switch, rename_in_history};
+use crate::validate::{is_unsafe_setting, validate_config_key, validate_email, validate_name, validate_profile_name};
+use crate::lock::lock_config;
+use crate::git::{
+    enable_worktree_config, get_active_config, get_effective_config, repository_root, set_git_configs, submodule_roots,
+    ConfigValue, Scope,
+};
+use crate::{config_dir, expand_home, GitError};
+
+const PROFILES_FILE: &str = "profiles.toml";
+
+// Version of the profiles file format this gitup writes
+pub const PROFILES_VERSION: u32 = 1;
+
+// Each entry upgrades a parsed profiles file from the version at its index to the next one, so
+// a file of any older version loads by running the entries from its version on
+type Migration = fn(&mut toml::Table) -> Result<(), GitError>;
+const MIGRATIONS: &[Migration] = &[
+    // 0 -> 1: files from before the version field have the same layout
+    |_| Ok(()),
+];
+
+// Environment variable naming the profile for `gitup env`
+pub const PROFILE_ENV: &str = "GITUP_PROFILE";
+
+// Every variable `profile_environment` can set, so a shell can clear them again
+pub const PROFILE_ENV_VARIABLES: &[&str] = &[
+    "GIT_AUTHOR_NAME",
+    "GIT_AUTHOR_EMAIL",
+    "GIT_COMMITTER_NAME",
+    "GIT_COMMITTER_EMAIL",
+    "GIT_SSH_COMMAND",
+];
+
+// A named Git identity and working style. Unknown fields are an error rather than being
+// dropped the next time the profiles are saved
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GitProfile {
+    // A base profile to inherit settings from; anything set here overrides the base
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    // Empty when inherited from the base profile
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub email: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_key: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub editor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_branch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull: Option<PullStrategy>,
+    // Line ending policy; autocrlf, when set as well, overrides the core.autocrlf it picks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eol: Option<EolPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autocrlf: Option<String>,
+    // Built-in template name or absolute path, overriding the global commit.template
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_template: Option<String>,
+    // Any other git config keys to apply with the profile; "keyring:<name>" values are
+    // read from the OS keyring when applied
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, String>,
+    // Remote URL patterns such as github.com/yourcorp/* for repositories that should use this
+    // profile wherever they are checked out (see `gitup suggest`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remotes: Vec<String>,
+    // What the profile is for, shown by `gitup profile list`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    // Labels to find profiles by, e.g. `gitup profile list --tag bots`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    // Seconds since the Unix epoch, kept up to date by gitup. Like the description and tags,
+    // these belong to the profile itself and are never inherited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<u64>,
+}
+
+impl GitProfile {
+    // Every git config key/value pair this profile sets
+    pub fn settings(&self) -> Vec<(String, String)> {
+        let mut settings = vec![
+            ("user.name".to_string(), self.name.clone()),
+            ("user.email".to_string(), self.email.clone()),
+        ];
+        if let Some(key) = &self.ssh_key {
+            settings.push(("core.sshCommand".to_string(), ssh_command(key)));
+        }
+        if let Some(editor) = &self.editor {
+            settings.push(("core.editor".to_string(), editor.clone()));
+        }
+        if let Some(branch) = &self.default_branch {
+            settings.push(("init.defaultBranch".to_string(), branch.clone()));
+        }
+        if let Some(pull) = &self.pull {
+            let rebase = *pull == PullStrategy::Rebase;
+            settings.push(("pull.rebase".to_string(), rebase.to_string()));
+        }
+        if let Some(eol) = self.eol {
+            settings.extend(eol.settings());
+        }
+        if let Some(autocrlf) = &self.autocrlf {
+            settings.push(("core.autocrlf".to_string(), autocrlf.clone()));
+        }
+        if let Some(template) = &self.commit_template {
+            let path = template_location(template);
+            settings.push(("commit.template".to_string(), path.to_string_lossy().into_owned()));
+        }
+        for (key, value) in &self.extra {
+            settings.push((key.clone(), value.clone()));
+        }
+        settings
+    }
+
+    // Drop the settings that would let whoever wrote the profile run commands here (see
+    // `is_unsafe_setting`), returning their keys
+    pub fn remove_unsafe_settings(&mut self) -> Vec<String> {
+        let mut removed = Vec::new();
+        if self.editor.as_deref().is_some_and(|editor| is_unsafe_setting("core.editor", editor)) {
+            self.editor = None;
+            removed.push("core.editor".to_string());
+        }
+        self.extra.retain(|key, value| {
+            let unsafe_setting = is_unsafe_setting(key, value);
+            if unsafe_setting {
+                removed.push(key.clone());
+            }
+            !unsafe_setting
+        });
+        removed
+    }
+
+    // Fill in what this profile leaves unset from its base. Remote patterns are not inherited,
+    // since they say which repositories belong to this particular profile
+    fn inherit(mut self, base: GitProfile) -> GitProfile {
+        if self.name.is_empty() {
+            self.name = base.name;
+        }
+        if self.email.is_empty() {
+            self.email = base.email;
+        }
+        self.ssh_key = self.ssh_key.or(base.ssh_key);
+        self.editor = self.editor.or(base.editor);
+        self.default_branch = self.default_branch.or(base.default_branch);
+        self.pull = self.pull.or(base.pull);
+        self.eol = self.eol.or(base.eol);
+        self.autocrlf = self.autocrlf.or(base.autocrlf);
+        self.commit_template = self.commit_template.or(base.commit_template);
+        let mut extra = base.extra;
+        extra.append(&mut self.extra);
+        self.extra = extra;
+        self
+    }
+}
+
+// How `git pull` integrates upstream changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PullStrategy {
+    Rebase,
+    Merge,
+}
+
+impl fmt::Display for PullStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PullStrategy::Rebase => f.write_str("rebase"),
+            PullStrategy::Merge => f.write_str("merge"),
+        }
+    }
+}
+
+impl FromStr for PullStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rebase" => Ok(PullStrategy::Rebase),
+            "merge" => Ok(PullStrategy::Merge),
+            other => Err(format!("unknown pull strategy '{}' (expected rebase or merge)", other)),
+        }
+    }
+}
+
+// Contents of the profiles file, keyed by profile name
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profiles {
+    // Format version; files written before it existed have none and count as 0
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, GitProfile>,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Profiles {
+            version: PROFILES_VERSION,
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+// Function to get the path of the profiles file in the config directory
+pub fn profiles_path() -> PathBuf {
+    config_dir().join(PROFILES_FILE)
+}
+
+// Function to load all profiles, returning an empty set if the file doesn't exist yet
+pub fn load_profiles() -> Result<Profiles, GitError> {
+    let path = profiles_path();
+    if !path.exists() {
+        return Ok(Profiles::default());
+    }
+    let mut contents = fs::read_to_string(&path)?;
+    if is_encrypted(&contents) {
+        contents = decrypt(&contents, &passphrase()?)?;
+    }
+    parse_profiles(&contents).map_err(|e| match e {
+        GitError::Parse(message) => GitError::Parse(format!("{}: {}", path.display(), message.trim_end())),
+        e => e,
+    })
+}
+
+// Function to read the contents of a profiles file, upgrading an older format to the current
+// one. A file from a newer gitup, or with settings this one doesn't know, is refused rather
+// than loaded with those settings missing
+pub fn parse_profiles(contents: &str) -> Result<Profiles, GitError> {
+    let mut table: toml::Table = toml::from_str(contents).map_err(|e| GitError::Parse(e.to_string()))?;
+    let version = match table.get("version") {
+        None => 0,
+        Some(toml::Value::Integer(version)) => u32::try_from(*version)
+            .map_err(|_| GitError::Parse(format!("version {} is not a valid version", version)))?,
+        Some(other) => return Err(GitError::Parse(format!("version must be a number, not {}", other))),
+    };
+    if version > PROFILES_VERSION {
+        return Err(GitError::Parse(format!(
+            "the profiles are in format version {}, written by a newer gitup (this one reads up to {}); upgrade gitup",
+            version, PROFILES_VERSION
+        )));
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(&mut table)?;
+    }
+    table.insert("version".to_string(), toml::Value::Integer(PROFILES_VERSION.into()));
+    toml::Value::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| GitError::Parse(e.to_string()))
+}
+
+// Function to write all profiles back to disk, keeping the file encrypted if it was
+pub fn save_profiles(profiles: &Profiles) -> Result<(), GitError> {
+    write_profiles(profiles, profiles_encrypted()?)
+}
+
+// Function to check whether the profiles file is encrypted
+pub fn profiles_encrypted() -> Result<bool, GitError> {
+    let path = profiles_path();
+    Ok(path.exists() && is_encrypted(&fs::read_to_string(path)?))
+}
+
+// Function to encrypt the profiles file with the passphrase
+pub fn encrypt_profiles() -> Result<(), GitError> {
+    let _lock = lock_config()?;
+    if profiles_encrypted()? {
+        return Err(GitError::InvalidInput("profiles are already encrypted".to_string()));
+    }
+    write_profiles(&load_profiles()?, true)
+}
+
+// Function to store the profiles file as plain TOML again
+pub fn decrypt_profiles() -> Result<(), GitError> {
+    let _lock = lock_config()?;
+    if !profiles_encrypted()? {
+        return Err(GitError::InvalidInput("profiles are not encrypted".to_string()));
+    }
+    write_profiles(&load_profiles()?, false)
+}
+
+fn write_profiles(profiles: &Profiles, encrypted: bool) -> Result<(), GitError> {
+    let _lock = lock_config()?;
+    let mut contents = toml::to_string(profiles).map_err(|e| GitError::Parse(e.to_string()))?;
+    if encrypted {
+        contents = encrypt(&contents, &passphrase()?)?;
+    }
+    create_dir_all(&config_dir())?;
+    write_file(&profiles_path(), &contents)
+}
+
+// Function to create (or overwrite) a profile. With `extends` set, the name and email may be
+// left empty to inherit them from the base profile
+pub fn create_profile(profile_name: &str, mut profile: GitProfile) -> Result<(), GitError> {
+    validate_profile_name(profile_name)?;
+    let inherited = |value: &str| profile.extends.is_some() && value.is_empty();
+    // Keyring references are checked once they resolve, when the profile is applied
+    if !is_secret_reference(&profile.name) && !inherited(&profile.name) {
+        validate_name(&profile.name)?;
+    }
+    if !is_secret_reference(&profile.email) && !inherited(&profile.email) {
+        validate_email(&profile.email)?;
+    }
+    for key in profile.extra.keys() {
+        validate_config_key(key)?;
+    }
+    for pattern in &profile.remotes {
+        validate_remote_pattern(pattern)?;
+    }
+    for tag in &profile.tags {
+        validate_tag(tag)?;
+    }
+    // Held from reading the profiles to writing them, so a concurrent change isn't lost
+    let _lock = lock_config()?;
+    let mut profiles = load_profiles()?;
+    // Overwriting a profile keeps its history
+    let existing = profiles.profiles.get(profile_name);
+    profile.created_at = profile
+        .created_at
+        .or_else(|| existing.and_then(|p| p.created_at))
+        .or_else(|| Some(now()));
+    profile.last_used_at = profile.last_used_at.or_else(|| existing.and_then(|p| p.last_used_at));
+    profiles.profiles.insert(profile_name.to_string(), profile);
+
+    // Catches a missing base or a cycle, and a name or email that nothing in the chain sets
+    let resolved = resolve_profile(&profiles.profiles, profile_name)?;
+    if resolved.name.is_empty() || resolved.email.is_empty() {
+        return Err(GitError::InvalidInput(format!(
+            "profile '{}' needs a name and email, either its own or from a profile it extends",
+            profile_name
+        )));
+    }
+
+    // Keep the config files used by directory bindings in sync with the profile, and with the
+    // profiles that inherit from it
+    for name in profiles.profiles.keys() {
+        let affected = name == profile_name
+            || base_profiles(&profiles.profiles, name).is_ok_and(|bases| bases.iter().any(|base| base == profile_name));
+        if affected && fragment_path(name).exists() {
+            write_profile_fragment(name, &resolve_profile(&profiles.profiles, name)?)?;
+        }
+    }
+
+    save_profiles(&profiles)
+}
+
+// Function to list all profiles sorted by name, with inherited settings filled in
+pub fn list_profiles() -> Result<Vec<(String, GitProfile)>, GitError> {
+    let profiles = load_profiles()?.profiles;
+    profiles
+        .keys()
+        .map(|name| Ok((name.clone(), resolve_profile(&profiles, name)?)))
+        .collect()
+}
+
+// Function to get the profiles a profile inherits from through `extends`, nearest first
+pub fn base_profiles(profiles: &BTreeMap<String, GitProfile>, profile_name: &str) -> Result<Vec<String>, GitError> {
+    let mut chain = vec![profile_name.to_string()];
+    let mut next = profiles
+        .get(profile_name)
+        .ok_or_else(|| GitError::ProfileNotFound(profile_name.to_string()))?
+        .extends
+        .clone();
+    while let Some(base) = next {
+        if chain.contains(&base) {
+            chain.push(base);
+            return Err(GitError::InvalidInput(format!(
+                "profiles extend each other in a cycle: {}",
+                chain.join(" -> ")
+            )));
+        }
+        let Some(base_profile) = profiles.get(&base) else {
+            return Err(GitError::InvalidInput(format!(
+                "profile '{}' extends '{}', which doesn't exist",
+                chain.last().map(String::as_str).unwrap_or_default(),
+                base
+            )));
+        };
+        next = base_profile.extends.clone();
+        chain.push(base);
+    }
+    Ok(chain.split_off(1))
+}
+
+// Function to work out a profile's settings, filling in what it leaves unset from the
+// profiles it extends
+pub fn resolve_profile(profiles: &BTreeMap<String, GitProfile>, profile_name: &str) -> Result<GitProfile, GitError> {
+    let bases = base_profiles(profiles, profile_name)?;
+    let profile = profiles[profile_name].clone();
+    Ok(bases.iter().fold(profile, |profile, base| profile.inherit(profiles[base].clone())))
+}
+
+// Function to find the profile matching the identity Git currently uses, if any
+pub fn active_profile() -> Result<Option<String>, GitError> {
+    let name = get_active_config("user.name");
+    let email = get_active_config("user.email");
+    Ok(list_profiles()?
+        .into_iter()
+        .find(|(_, p)| Some(&p.name) == name.as_ref() && Some(&p.email) == email.as_ref())
+        .map(|(profile_name, _)| profile_name))
+}
+
+// The identity git would use for the next commit in a directory
+#[derive(Debug, Clone, Serialize)]
+pub struct Whoami {
+    pub name: Option<ConfigValue>,
+    pub email: Option<ConfigValue>,
+    pub signing_key: Option<ConfigValue>,
+    pub sign_commits: bool,
+    // The profile with this name and email, if any
+    pub profile: Option<String>,
+    // Set when the identity comes from a profile bound to the directory with `gitup profile bind`
+    pub bound_profile: Option<String>,
+}
+
+// Function to work out who the next commit in a directory would be authored by, and which
+// profile that identity belongs to
+pub fn whoami(dir: &Path) -> Result<Whoami, GitError> {
+    if !dir.is_dir() {
+        return Err(GitError::InvalidInput(format!("{} is not a directory", dir.display())));
+    }
+    let name = get_effective_config("user.name", dir);
+    let email = get_effective_config("user.email", dir);
+    let sign_commits = get_effective_config("commit.gpgsign", dir)
+        .is_some_and(|value| matches!(value.value.to_lowercase().as_str(), "true" | "yes" | "on" | "1"));
+
+    let profiles = list_profiles()?;
+    let current_name = name.as_ref().map(|setting| setting.value.as_str());
+    let current_email = email.as_ref().map(|setting| setting.value.as_str());
+    let profile = profiles
+        .iter()
+        .find(|(_, p)| current_name == Some(p.name.as_str()) && current_email == Some(p.email.as_str()))
+        .map(|(profile_name, _)| profile_name.clone());
+    let bound_profile = profiles.iter().find_map(|(profile_name, _)| {
+        let fragment = fragment_path(profile_name).to_string_lossy().into_owned();
+        [&name, &email]
+            .iter()
+            .any(|setting| setting.as_ref().is_some_and(|setting| setting.origin == fragment))
+            .then(|| profile_name.clone())
+    });
+
+    Ok(Whoami {
+        name,
+        email,
+        signing_key: get_effective_config("user.signingkey", dir),
+        sign_commits,
+        profile,
+        bound_profile,
+    })
+}
+
+// Function to delete a profile and any directory bindings that use it
+pub fn delete_profile(profile_name: &str) -> Result<GitProfile, GitError> {
+    let _lock = lock_config()?;
+    let mut profiles = load_profiles()?;
+    let profile = profiles
+        .profiles
+        .remove(profile_name)
+        .ok_or_else(|| GitError::ProfileNotFound(profile_name.to_string()))?;
+    let extended_by: Vec<&str> = profiles
+        .profiles
+        .iter()
+        .filter(|(_, p)| p.extends.as_deref() == Some(profile_name))
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if !extended_by.is_empty() {
+        return Err(GitError::InvalidInput(format!(
+            "'{}' is the base of {}; delete those profiles or change what they extend first",
+            profile_name,
+            extended_by.join(", ")
+        )));
+    }
+
+    unbind_profile(profile_name)?;
+    save_profiles(&profiles)?;
+    Ok(profile)
+}
+
+// Function to give a profile a new name, carrying along the profiles that extend it, its
+// directory bindings, and the records drift checks and `profile use -` go by. Returns the
+// bindings moved
+pub fn rename_profile(old_name: &str, new_name: &str) -> Result<Vec<Binding>, GitError> {
+    validate_profile_name(new_name)?;
+    let _lock = lock_config()?;
+    let mut profiles = load_profiles()?;
+    if profiles.profiles.contains_key(new_name) {
+        return Err(GitError::Conflict(format!("profile '{}' already exists", new_name)));
+    }
+    let profile = profiles
+        .profiles
+        .remove(old_name)
+        .ok_or_else(|| GitError::ProfileNotFound(old_name.to_string()))?;
+    profiles.profiles.insert(new_name.to_string(), profile);
+    for profile in profiles.profiles.values_mut() {
+        if profile.extends.as_deref() == Some(old_name) {
+            profile.extends = Some(new_name.to_string());
+        }
+    }
+
+    let resolved = resolve_profile(&profiles.profiles, new_name)?;
+    save_profiles(&profiles)?;
+    let moved = rebind_profile(old_name, new_name, &resolved)?;
+    rename_recorded_profile(old_name, new_name)?;
+    rename_in_history(old_name, new_name)?;
+    Ok(moved)
+}
+
+// Function to create a profile as a copy of another, optionally with a different name and
+// email. The copy extends the same base and starts its own history; directory bindings and
+// remote URL patterns stay with the original, since a directory or remote can only have one
+// profile
+pub fn copy_profile(
+    source: &str,
+    target: &str,
+    name: Option<&str>,
+    email: Option<&str>,
+) -> Result<GitProfile, GitError> {
+    let _lock = lock_config()?;
+    if load_profiles()?.profiles.contains_key(target) {
+        return Err(GitError::Conflict(format!("profile '{}' already exists", target)));
+    }
+    let mut profile = stored_profile(source)?;
+    if let Some(name) = name {
+        profile.name = name.to_string();
+    }
+    if let Some(email) = email {
+        profile.email = email.to_string();
+    }
+    profile.remotes.clear();
+    profile.created_at = None;
+    profile.last_used_at = None;
+    create_profile(target, profile.clone())?;
+    Ok(profile)
+}
+
+// Function to add remote URL patterns to a profile, returning the updated profile
+pub fn add_remote_patterns(profile_name: &str, patterns: &[String]) -> Result<GitProfile, GitError> {
+    let _lock = lock_config()?;
+    let mut profile = stored_profile(profile_name)?;
+    for pattern in patterns {
+        if !profile.remotes.contains(pattern) {
+            profile.remotes.push(pattern.clone());
+        }
+    }
+    create_profile(profile_name, profile.clone())?;
+    Ok(profile)
+}
+
+// Function to remove remote URL patterns from a profile, returning the updated profile
+pub fn remove_remote_patterns(profile_name: &str, patterns: &[String]) -> Result<GitProfile, GitError> {
+    let _lock = lock_config()?;
+    let mut profile = stored_profile(profile_name)?;
+    profile.remotes.retain(|pattern| !patterns.contains(pattern));
+    create_profile(profile_name, profile.clone())?;
+    Ok(profile)
+}
+
+// Function to change a profile's description and tags, returning the updated profile. An empty
+// description removes it
+pub fn describe_profile(
+    profile_name: &str,
+    description: Option<&str>,
+    add_tags: &[String],
+    remove_tags: &[String],
+) -> Result<GitProfile, GitError> {
+    let _lock = lock_config()?;
+    let mut profile = stored_profile(profile_name)?;
+    if let Some(description) = description {
+        profile.description = Some(description.trim().to_string()).filter(|description| !description.is_empty());
+    }
+    profile.tags.retain(|tag| !remove_tags.contains(tag));
+    for tag in add_tags {
+        if !profile.tags.contains(tag) {
+            profile.tags.push(tag.clone());
+        }
+    }
+    create_profile(profile_name, profile.clone())?;
+    Ok(profile)
+}
+
+// Function to look up a single profile by name, with inherited settings filled in
+pub fn get_profile(profile_name: &str) -> Result<GitProfile, GitError> {
+    resolve_profile(&load_profiles()?.profiles, profile_name)
+}
+
+// A profile as written in the profiles file, for changes that must not copy inherited settings
+fn stored_profile(profile_name: &str) -> Result<GitProfile, GitError> {
+    load_profiles()?
+        .profiles
+        .remove(profile_name)
+        .ok_or_else(|| GitError::ProfileNotFound(profile_name.to_string()))
+}
+
+// Function to apply a profile to the global or current repository's Git configuration
+pub fn use_profile(profile_name: &str, scope: Scope) -> Result<GitProfile, GitError> {
+    let profile = get_profile(profile_name)?;
+    let previous = profile_in_scope(&scope);
+    // Without it, git writes a worktree's settings to the config all of them share
+    if let Scope::Worktree(dir) = &scope {
+        enable_worktree_config(dir)?;
+    }
+    if let Some(template) = &profile.commit_template {
+        install_template(template)?;
+    }
+
+    set_git_configs(&profile_settings(profile_name)?, scope.clone())?;
+
+    // Switch the SSH key along with the identity, clearing any key left by another profile
+    if profile.ssh_key.is_none() {
+        GitConfig::new(scope.clone()).unset("core.sshCommand")?;
+    }
+    if scope == Scope::Global {
+        record_profile(profile_name)?;
+    }
+    record_switch(profile_name, previous.as_deref(), &scope);
+    record_use(profile_name);
+    record(StatsEvent::Switch {
+        profile: profile_name.to_string(),
+        scope: scope.name().to_string(),
+        repo: scope.repo().map(Path::to_path_buf),
+    });
+    Ok(profile)
+}
+
+// Only `profile list --sort last-used` goes by it, so failing to save it doesn't fail the switch
+fn record_use(profile_name: &str) {
+    let _ = lock_config().and_then(|_lock| {
+        let mut profiles = load_profiles()?;
+        if let Some(profile) = profiles.profiles.get_mut(profile_name) {
+            profile.last_used_at = Some(now());
+            save_profiles(&profiles)?;
+        }
+        Ok(())
+    });
+}
+
+// The profile whose name and email are set in a config, if any
+fn profile_in_scope(scope: &Scope) -> Option<String> {
+    let config = GitConfig::new(scope.clone());
+    let (name, email) = (config.get("user.name"), config.get("user.email"));
+    list_profiles()
+        .ok()?
+        .into_iter()
+        .find(|(_, p)| Some(&p.name) == name.as_ref() && Some(&p.email) == email.as_ref())
+        .map(|(profile_name, _)| profile_name)
+}
+
+// Function to apply a profile to the local config of every checked-out submodule of the
+// repository containing `repo`, nested ones included. Each submodule has a config of its own, so
+// without this they commit with the global identity. A core.hooksPath from the repository's own
+// config is carried over too, made absolute so it still points at the repository's hooks.
+// Returns the submodules
+pub fn use_profile_in_submodules(profile_name: &str, repo: &Path) -> Result<Vec<PathBuf>, GitError> {
+    let profile = get_profile(profile_name)?;
+    let mut settings = profile_settings(profile_name)?;
+    let root = repository_root(repo)?;
+    let hooks_set = settings.iter().any(|(key, _)| key.eq_ignore_ascii_case("core.hooksPath"));
+    if let Some(hooks) = GitConfig::repo(&root).get("core.hooksPath").filter(|_| !hooks_set) {
+        settings.push(("core.hooksPath".to_string(), root.join(expand_home(&hooks)).to_string_lossy().into_owned()));
+    }
+    let submodules = submodule_roots(&root)?;
+    for submodule in &submodules {
+        set_git_configs(&settings, Scope::Local(submodule.clone()))?;
+        if profile.ssh_key.is_none() {
+            GitConfig::repo(submodule).unset("core.sshCommand")?;
+        }
+    }
+    Ok(submodules)
+}
+
+// Function to get the config a profile sets, with the secrets it refers to filled in
+pub fn profile_settings(profile_name: &str) -> Result<Vec<(String, String)>, GitError> {
+    get_profile(profile_name)?
+        .settings()
+        .into_iter()
+        .map(|(key, value)| Ok((key, resolve_secret(&value)?)))
+        .collect()
+}
+
+// Function to get the environment variables that make git commit as a profile, and use its SSH
+// key, in a single shell session without touching any config file. They take precedence over
+// user.name, user.email and core.sshCommand wherever they are set
+pub fn profile_environment(profile_name: &str) -> Result<Vec<(String, String)>, GitError> {
+    let profile = get_profile(profile_name)?;
+    let name = resolve_secret(&profile.name)?;
+    let email = resolve_secret(&profile.email)?;
+    let mut variables = vec![
+        ("GIT_AUTHOR_NAME".to_string(), name.clone()),
+        ("GIT_AUTHOR_EMAIL".to_string(), email.clone()),
+        ("GIT_COMMITTER_NAME".to_string(), name),
+        ("GIT_COMMITTER_EMAIL".to_string(), email),
+    ];
+    if let Some(key) = &profile.ssh_key {
+        variables.push(("GIT_SSH_COMMAND".to_string(), ssh_command(key)));
+    }
+    Ok(variables)
+}
+
+// Tags are single words, so they can be listed and matched unambiguously
+fn validate_tag(tag: &str) -> Result<(), GitError> {
+    if tag.is_empty() || tag.contains(|c: char| c.is_whitespace() || c == ',') {
+        return Err(GitError::InvalidInput(format!(
+            "'{}' is not a tag (use a single word without commas)",
+            tag.escape_debug()
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_remote_pattern(pattern: &str) -> Result<(), GitError> {
+    if pattern.is_empty() || pattern.contains(char::is_whitespace) {
+        return Err(GitError::InvalidInput(format!(
+            "'{}' is not a remote URL pattern (use e.g. github.com/yourcorp/*)",
+            pattern.escape_debug()
+        )));
+    }
+    Ok(())
+}
+
+// Build the core.sshCommand value that forces git to use a specific key. git runs it through the
+// shell, so the characters that keep their meaning inside double quotes are escaped
+pub(crate) fn ssh_command(key: &Path) -> String {
+    let mut quoted = String::new();
+    for c in key.display().to_string().chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    format!("ssh -i \"{}\" -o IdentitiesOnly=yes", quoted)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Sandbox;
+
+    #[test]
+    fn use_profile_sets_the_profile_in_the_repository() {
+        let sandbox = Sandbox::new();
+        let repo = sandbox.dir.join("repo");
+        let profile = GitProfile {
+            name: "Jane Doe".to_string(),
+            email: "jane@example.com".to_string(),
+            extra: BTreeMap::from([("commit.gpgsign".to_string(), "true".to_string())]),
+            ..Default::default()
+        };
+        create_profile("work", profile).unwrap();
+        use_profile("work", Scope::Local(repo.clone())).unwrap();
+
+        // Reads of the repository's config are recorded too; only the changes matter here
+        let prefix = format!("git -C {} config --local --", repo.display());
+        let changes: Vec<String> = sandbox
+            .runner
+            .lines()
+            .iter()
+            .filter_map(|line| line.strip_prefix(&prefix).map(String::from))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                "replace-all user.name Jane Doe",
+                "replace-all user.email jane@example.com",
+                "replace-all commit.gpgsign true",
+                // No SSH key, so one left by another profile is cleared
+                "unset core.sshCommand",
+            ]
+        );
+        assert!(sandbox.dir.join("config").join("profiles.toml").exists());
+    }
+
+    #[test]
+    fn ssh_command_escapes_the_key_path() {
+        assert_eq!(
+            ssh_command(Path::new("/home/jane/.ssh/id_ed25519")),
+            "ssh -i \"/home/jane/.ssh/id_ed25519\" -o IdentitiesOnly=yes"
+        );
+        assert_eq!(
+            ssh_command(Path::new("/tmp/a\"$(touch x)`id`\\b")),
+            "ssh -i \"/tmp/a\\\"\\$(touch x)\\`id\\`\\\\b\" -o IdentitiesOnly=yes"
+        );
+    }
+}
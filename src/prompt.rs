@@ -0,0 +1,136 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::binding::list_bindings;
+use crate::config::file::{common_dir, find_git_dir, ConfigFile};
+use crate::config::GitConfig;
+use crate::git::global_config_path;
+use crate::profile::{list_profiles, profiles_path};
+use crate::secrets::resolve_secret;
+use crate::{config_dir, expand_home, GitError};
+
+const PROMPT_CACHE_FILE: &str = "prompt-cache.json";
+
+// The identity a repository commits with, as shown by `gitup prompt`
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptStatus {
+    // The profile with the email git would commit with; None when no profile has it
+    pub profile: Option<String>,
+    pub email: Option<String>,
+    // The profile bound to a directory containing the repository
+    pub expected: Option<String>,
+}
+
+impl PromptStatus {
+    // Whether the repository commits as something other than the profile bound to it
+    pub fn mismatch(&self) -> bool {
+        self.expected.is_some() && self.expected != self.profile
+    }
+}
+
+// What the prompt needs from the global config and the profiles, so it can be answered
+// without running git. Rebuilt whenever either file changes
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PromptCache {
+    global_config_modified: Option<u128>,
+    profiles_modified: Option<u128>,
+    global_email: Option<String>,
+    // Bound directories, with a trailing slash, and their profiles, in config file order
+    bindings: Vec<(String, String)>,
+    // Profile names and emails
+    profiles: Vec<(String, String)>,
+}
+
+// Function to work out which profile the repository containing `dir` commits as, reading
+// config files directly and the cached bindings so it is fast enough to run on every shell
+// prompt. Returns None outside a repository
+pub fn prompt_status(dir: &Path) -> Result<Option<PromptStatus>, GitError> {
+    let Some(git_dir) = find_git_dir(dir) else {
+        return Ok(None);
+    };
+    let cache = prompt_cache()?;
+
+    // As with git's includeIf, the last matching binding wins
+    let git_dir = git_dir.to_string_lossy();
+    let expected = cache
+        .bindings
+        .iter()
+        .rev()
+        .find(|(directory, _)| git_dir.starts_with(directory.as_str()))
+        .map(|(_, profile)| profile.clone());
+    let email_of = |name: &str| cache.profiles.iter().find(|(profile, _)| profile == name).map(|(_, email)| email.clone());
+
+    // Most specific first: the environment (`gitup env`), the repository, a binding, the global config
+    let email = env::var("GIT_AUTHOR_EMAIL")
+        .ok()
+        .filter(|email| !email.is_empty())
+        .or_else(|| repository_email(Path::new(git_dir.as_ref())))
+        .or_else(|| expected.as_deref().and_then(email_of))
+        .or(cache.global_email.clone());
+
+    // Several profiles can share an email; the bound one is the most likely meant
+    let profile = match (&email, &expected) {
+        (Some(email), Some(expected)) if email_of(expected).as_ref() == Some(email) => Some(expected.clone()),
+        (Some(email), _) => cache.profiles.iter().find(|(_, e)| e == email).map(|(profile, _)| profile.clone()),
+        (None, _) => None,
+    };
+    Ok(Some(PromptStatus {
+        profile,
+        email,
+        expected,
+    }))
+}
+
+fn prompt_cache() -> Result<PromptCache, GitError> {
+    let global_config_modified = modified(&global_config_path());
+    let profiles_modified = modified(&profiles_path());
+    let path = config_dir().join(PROMPT_CACHE_FILE);
+    let cached = fs::read_to_string(&path)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<PromptCache>(&contents).ok());
+    if let Some(cache) = cached {
+        if cache.global_config_modified == global_config_modified && cache.profiles_modified == profiles_modified {
+            return Ok(cache);
+        }
+    }
+
+    let cache = PromptCache {
+        global_config_modified,
+        profiles_modified,
+        global_email: GitConfig::global().get("user.email"),
+        bindings: list_bindings()?
+            .into_iter()
+            .map(|binding| (expand_home(&binding.directory).to_string_lossy().into_owned(), binding.profile))
+            .collect(),
+        profiles: list_profiles()?
+            .into_iter()
+            .map(|(name, profile)| {
+                let email = resolve_secret(&profile.email).unwrap_or(profile.email);
+                (name, email)
+            })
+            .collect(),
+    };
+    // The cache only saves time, so failing to write it isn't worth reporting
+    if let Ok(contents) = serde_json::to_string(&cache) {
+        let _ = fs::create_dir_all(config_dir()).and_then(|_| fs::write(&path, contents));
+    }
+    Ok(cache)
+}
+
+fn modified(path: &Path) -> Option<u128> {
+    let time = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?;
+    Some(time.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+// user.email from a repository's own config files; a linked worktree shares the main
+// repository's config but has its own config.worktree
+fn repository_email(git_dir: &Path) -> Option<String> {
+    [git_dir.join("config.worktree"), common_dir(git_dir).join("config")]
+        .iter()
+        .filter_map(|path| ConfigFile::load(path).ok())
+        .find_map(|file| file.get("user.email").filter(|email| !email.is_empty()))
+}
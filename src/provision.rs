@@ -0,0 +1,143 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::dry_run::DryRun;
+use crate::git::{run_command, run_with_input};
+use crate::install::{command_exists, is_root};
+use crate::manifest::parse_manifest;
+use crate::GitError;
+
+// One account brought in line with a manifest by `provision_users`
+#[derive(Debug, Clone, Serialize)]
+pub struct ProvisionedUser {
+    pub user: String,
+    pub home: PathBuf,
+    pub changes: Vec<String>,
+}
+
+// How provisioning one account went
+pub type UserOutcome = (String, Result<ProvisionedUser, GitError>);
+
+// Function to apply a manifest to other users' accounts, as root. Each user gets their own
+// `gitup apply` run as that user, with HOME and the rest of the environment of their account,
+// so every file it writes (.gitconfig, profiles, the global ignore file) lands in their home
+// and belongs to them. The manifest is checked once up front; after that a failure for one
+// user doesn't stop the others, and each user's outcome is returned in order
+pub fn provision_users(users: &[String], manifest: &Path) -> Result<Vec<UserOutcome>, GitError> {
+    if cfg!(windows) {
+        return Err(GitError::InvalidInput(
+            "provisioning other accounts is only supported on Linux and macOS".to_string(),
+        ));
+    }
+    if !is_root() {
+        return Err(GitError::InvalidInput(
+            "provisioning other accounts needs root; run it with sudo".to_string(),
+        ));
+    }
+    let contents = fs::read_to_string(manifest)?;
+    parse_manifest(&contents, &manifest.display().to_string())?;
+    let gitup = env::current_exe()?;
+
+    Ok(users
+        .iter()
+        .map(|user| (user.clone(), provision_user(user, &gitup, &contents)))
+        .collect())
+}
+
+fn provision_user(user: &str, gitup: &Path, manifest: &str) -> Result<ProvisionedUser, GitError> {
+    validate_user_name(user)?;
+    let home = home_of(user)?;
+    if !home.is_dir() {
+        return Err(GitError::InvalidInput(format!("{}'s home directory {} doesn't exist", user, home.display())));
+    }
+
+    // A clean environment, as a login would give them; only PATH is kept, so git is found. It
+    // runs in their home, since git fails in a directory they can't read, such as root's
+    let home_variable = format!("HOME={}", home.display());
+    let path_variable = format!("PATH={}", env::var("PATH").unwrap_or_default());
+    let (user_variable, logname_variable) = (format!("USER={}", user), format!("LOGNAME={}", user));
+    let gitup = gitup.to_string_lossy();
+    let mut command = vec!["env", "-i", &home_variable, &path_variable, &user_variable, &logname_variable];
+    command.extend(["/bin/sh", "-c", "cd \"$HOME\" && exec \"$0\" \"$@\""]);
+    command.extend([gitup.as_ref(), "--format", "json", "--yes"]);
+    if DryRun::is_enabled() {
+        command.push("--dry-run");
+    }
+    command.extend(["apply", "-"]);
+
+    let (program, args) = as_user(user, &command)?;
+    let output = run_with_input(program, &args, manifest).map_err(|e| GitError::spawn_failed(program, e))?;
+    let result: Option<Value> = serde_json::from_slice(&output.stdout).ok();
+    if !output.status.success() {
+        return Err(match result.as_ref().and_then(|result| result["error"].as_str()) {
+            Some(error) => GitError::InvalidInput(error.to_string()),
+            None => GitError::command_failed(program, &args, &output),
+        });
+    }
+    let changes = result
+        .as_ref()
+        .and_then(|result| result["changes"].as_array())
+        .map(|changes| changes.iter().filter_map(|change| change.as_str().map(str::to_string)).collect())
+        .unwrap_or_default();
+    Ok(ProvisionedUser {
+        user: user.to_string(),
+        home,
+        changes,
+    })
+}
+
+// The command line running `command` as `user`; root can do that without a password
+fn as_user<'a>(user: &'a str, command: &[&'a str]) -> Result<(&'static str, Vec<&'a str>), GitError> {
+    if command_exists("runuser") {
+        Ok(("runuser", [&["-u", user, "--"], command].concat()))
+    } else if command_exists("sudo") {
+        Ok(("sudo", [&["-u", user, "--"], command].concat()))
+    } else {
+        Err(GitError::CommandNotFound("runuser or sudo".to_string()))
+    }
+}
+
+// Account names as useradd accepts them, which also keeps them from being read as options
+fn validate_user_name(user: &str) -> Result<(), GitError> {
+    let valid = user.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+        && user.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(GitError::InvalidInput(format!("'{}' is not a user name", user.escape_debug())))
+    }
+}
+
+// A user's home directory from the account database: getent covers LDAP and other NSS
+// sources, /etc/passwd is the fallback where getent doesn't exist. macOS keeps accounts in
+// Directory Services, which only dscl reads; its /etc/passwd lists system accounts alone
+fn home_of(user: &str) -> Result<PathBuf, GitError> {
+    if cfg!(target_os = "macos") {
+        let output = run_command("dscl", &[".", "-read", &format!("/Users/{}", user), "NFSHomeDirectory"])?;
+        // NFSHomeDirectory: /Users/name
+        return String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .strip_prefix("NFSHomeDirectory:")
+            .map(str::trim)
+            .filter(|home| output.status.success() && !home.is_empty())
+            .map(PathBuf::from)
+            .ok_or_else(|| GitError::InvalidInput(format!("no user named '{}'", user)));
+    }
+    let entry = match run_command("getent", &["passwd", user]) {
+        Ok(output) if output.status.success() => Some(String::from_utf8_lossy(&output.stdout).into_owned()),
+        _ => fs::read_to_string("/etc/passwd")
+            .ok()
+            .and_then(|passwd| passwd.lines().find(|line| line.split(':').next() == Some(user)).map(str::to_string)),
+    };
+    // name:password:uid:gid:gecos:home:shell
+    entry
+        .as_deref()
+        .and_then(|entry| entry.trim().split(':').nth(5))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .ok_or_else(|| GitError::InvalidInput(format!("no user named '{}'", user)))
+}
@@ -0,0 +1,142 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::dry_run::write_file;
+use crate::git::repository_root;
+use crate::guard::find_repositories;
+use crate::lock::lock_config;
+use crate::{config_dir, GitError};
+
+const REGISTRY_FILE: &str = "repos.toml";
+
+// The repositories gitup knows about: ones added by hand, and every repository under the roots,
+// found again each time the registry is read so new clones show up by themselves
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Registry {
+    #[serde(default)]
+    pub roots: Vec<PathBuf>,
+    #[serde(default)]
+    pub repos: Vec<PathBuf>,
+}
+
+// A repository in the registry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KnownRepository {
+    pub path: PathBuf,
+    // The root it was found under; None for repositories added by hand
+    pub root: Option<PathBuf>,
+}
+
+// Function to get the file the registry is kept in
+pub fn registry_path() -> PathBuf {
+    config_dir().join(REGISTRY_FILE)
+}
+
+// Function to read the registry
+pub fn load_registry() -> Result<Registry, GitError> {
+    let path = registry_path();
+    if !path.exists() {
+        return Ok(Registry::default());
+    }
+    toml::from_str(&fs::read_to_string(&path)?).map_err(|e| GitError::Parse(format!("{}: {}", path.display(), e)))
+}
+
+fn save_registry(registry: &Registry) -> Result<(), GitError> {
+    let contents = toml::to_string(registry).map_err(|e| GitError::Parse(e.to_string()))?;
+    fs::create_dir_all(config_dir())?;
+    write_file(&registry_path(), &contents)
+}
+
+// Function to add repositories to the registry by any directory inside them, returning the
+// repositories that weren't in it yet
+pub fn add_repositories(paths: &[PathBuf]) -> Result<Vec<PathBuf>, GitError> {
+    let _lock = lock_config()?;
+    let mut registry = load_registry()?;
+    let mut added = Vec::new();
+    for path in paths {
+        let repo = canonical(&repository_root(path)?);
+        if !registry.repos.contains(&repo) {
+            registry.repos.push(repo.clone());
+            added.push(repo);
+        }
+    }
+    registry.repos.sort();
+    save_registry(&registry)?;
+    Ok(added)
+}
+
+// Function to add directories whose repositories are all part of the registry, returning the
+// roots that weren't in it yet
+pub fn add_roots(dirs: &[PathBuf]) -> Result<Vec<PathBuf>, GitError> {
+    let _lock = lock_config()?;
+    let mut registry = load_registry()?;
+    let mut added = Vec::new();
+    for dir in dirs {
+        if !dir.is_dir() {
+            return Err(GitError::InvalidInput(format!("{} is not a directory", dir.display())));
+        }
+        let root = canonical(dir);
+        if !registry.roots.contains(&root) {
+            registry.roots.push(root.clone());
+            added.push(root);
+        }
+    }
+    registry.roots.sort();
+    save_registry(&registry)?;
+    Ok(added)
+}
+
+// Function to take repositories or roots out of the registry, returning the entries removed.
+// Paths no longer on disk can be removed too. A repository found under a root stays in the
+// registry until its root is removed
+pub fn remove_entries(paths: &[PathBuf]) -> Result<Vec<PathBuf>, GitError> {
+    let _lock = lock_config()?;
+    let mut registry = load_registry()?;
+    let paths: Vec<PathBuf> = paths.iter().map(|path| canonical(path)).collect();
+    let mut removed = Vec::new();
+    for entries in [&mut registry.repos, &mut registry.roots] {
+        entries.retain(|entry| {
+            let keep = !paths.contains(entry);
+            if !keep {
+                removed.push(entry.clone());
+            }
+            keep
+        });
+    }
+    save_registry(&registry)?;
+    Ok(removed)
+}
+
+// Function to list the repositories in the registry, sorted by path. Repositories added by
+// hand that have since been deleted or moved are left out
+pub fn known_repositories() -> Result<Vec<KnownRepository>, GitError> {
+    let registry = load_registry()?;
+    let mut known: Vec<KnownRepository> = registry
+        .repos
+        .iter()
+        .filter(|path| path.is_dir())
+        .map(|path| KnownRepository {
+            path: path.clone(),
+            root: None,
+        })
+        .collect();
+    for root in registry.roots.iter().filter(|root| root.is_dir()) {
+        for path in find_repositories(root)? {
+            if !known.iter().any(|repo| repo.path == path) {
+                known.push(KnownRepository {
+                    path,
+                    root: Some(root.clone()),
+                });
+            }
+        }
+    }
+    known.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(known)
+}
+
+// Paths are kept absolute and resolved, so the same repository is never listed twice
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,367 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::expand_home;
+use crate::profile::{
+    base_profiles, create_profile, load_profiles, resolve_profile, validate_remote_pattern, GitProfile,
+};
+use crate::validate::{validate_email, validate_name, validate_profile_name};
+use crate::GitError;
+
+// A profile to create, as listed in a roster file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RosterEntry {
+    // Defaults to the email's local part
+    #[serde(default)]
+    pub profile: Option<String>,
+    // Either may be left empty to inherit it from the `extends` profile
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub email: String,
+    // A GPG key ID or fingerprint, or an SSH public key file, to sign commits and tags with
+    #[serde(default, alias = "key", alias = "key_id")]
+    pub signing_key: Option<String>,
+    #[serde(default)]
+    pub ssh_key: Option<PathBuf>,
+    // Remote URL patterns, as for `gitup profile remote add`
+    #[serde(default, alias = "urls", alias = "patterns")]
+    pub remotes: Vec<String>,
+    #[serde(default)]
+    pub extends: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+// What to do with an entry whose profile name is already taken, by an existing profile or an
+// earlier entry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnConflict {
+    // Create nothing
+    #[default]
+    Fail,
+    Skip,
+    Overwrite,
+    // Create it as <name>-2, <name>-3, ...
+    Rename,
+}
+
+impl fmt::Display for OnConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OnConflict::Fail => "fail",
+            OnConflict::Skip => "skip",
+            OnConflict::Overwrite => "overwrite",
+            OnConflict::Rename => "rename",
+        })
+    }
+}
+
+impl FromStr for OnConflict {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fail" => Ok(OnConflict::Fail),
+            "skip" => Ok(OnConflict::Skip),
+            "overwrite" => Ok(OnConflict::Overwrite),
+            "rename" => Ok(OnConflict::Rename),
+            other => Err(format!(
+                "unknown conflict handling '{}' (expected fail, skip, overwrite or rename)",
+                other
+            )),
+        }
+    }
+}
+
+// What `import_roster` did, by profile name
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RosterReport {
+    pub created: Vec<String>,
+    pub overwritten: Vec<String>,
+    // The name asked for and the one used instead
+    pub renamed: Vec<(String, String)>,
+    pub skipped: Vec<String>,
+}
+
+// Function to read a roster: a JSON array of entries (or an object with a "profiles" array) for
+// .json files, and CSV with a header row for anything else. CSV lists several remote patterns
+// in one field separated by spaces or semicolons
+pub fn read_roster(path: &Path) -> Result<Vec<RosterEntry>, GitError> {
+    let contents = fs::read_to_string(path)?;
+    let parse_error = |e: String| GitError::Parse(format!("{}: {}", path.display(), e));
+    let is_json = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+    if !is_json {
+        return parse_csv(&contents).map_err(parse_error);
+    }
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Roster {
+        List(Vec<RosterEntry>),
+        Object { profiles: Vec<RosterEntry> },
+    }
+    match serde_json::from_str(&contents).map_err(|e| parse_error(e.to_string()))? {
+        Roster::List(entries) | Roster::Object { profiles: entries } => Ok(entries),
+    }
+}
+
+// Function to create a profile for every roster entry. All entries are checked before any
+// profile is created, so a bad row, a base profile that exists nowhere or (with
+// OnConflict::Fail) a taken name leaves the profiles as they were
+pub fn import_roster(entries: &[RosterEntry], on_conflict: OnConflict) -> Result<RosterReport, GitError> {
+    let mut profiles = load_profiles()?.profiles;
+    let existing: BTreeSet<String> = profiles.keys().cloned().collect();
+    let mut taken = existing.clone();
+    let mut planned = Vec::new();
+    let mut report = RosterReport::default();
+    for (row, entry) in entries.iter().enumerate() {
+        let wanted = entry_profile_name(entry).ok_or_else(|| {
+            GitError::InvalidInput(format!("entry {}: no profile name, and none can be made from the email", row + 1))
+        })?;
+        let profile = validate_profile_name(&wanted)
+            .and_then(|()| entry_profile(entry))
+            .map_err(|e| GitError::InvalidInput(format!("entry {} ({}): {}", row + 1, wanted, e)))?;
+        let profile_name = if !taken.contains(&wanted) {
+            wanted
+        } else {
+            match on_conflict {
+                OnConflict::Fail => {
+                    return Err(GitError::Conflict(format!(
+                        "profile '{}' already exists (choose what to do with --on-conflict skip, overwrite or rename)",
+                        wanted
+                    )))
+                }
+                OnConflict::Skip => {
+                    report.skipped.push(wanted);
+                    continue;
+                }
+                // Only existing profiles are overwritten; two entries with one name are a mistake
+                OnConflict::Overwrite if planned.iter().any(|(name, _)| *name == wanted) => {
+                    return Err(GitError::InvalidInput(format!("profile '{}' is listed more than once", wanted)))
+                }
+                OnConflict::Overwrite => {
+                    report.overwritten.push(wanted.clone());
+                    wanted
+                }
+                OnConflict::Rename => {
+                    let renamed = (2..)
+                        .map(|n| format!("{}-{}", wanted, n))
+                        .find(|name| !taken.contains(name))
+                        .unwrap_or_default();
+                    report.renamed.push((wanted, renamed.clone()));
+                    renamed
+                }
+            }
+        };
+        taken.insert(profile_name.clone());
+        planned.push((profile_name, profile));
+    }
+
+    // Check every `extends` against the profiles there will be, and create bases before the
+    // profiles built on them, whatever order the rows came in
+    for (profile_name, profile) in &planned {
+        profiles.insert(profile_name.clone(), profile.clone());
+    }
+    let mut depths = BTreeMap::new();
+    for (profile_name, _) in &planned {
+        depths.insert(profile_name.clone(), base_profiles(&profiles, profile_name)?.len());
+        let resolved = resolve_profile(&profiles, profile_name)?;
+        if resolved.name.is_empty() || resolved.email.is_empty() {
+            return Err(GitError::InvalidInput(format!(
+                "profile '{}' needs a name and email, either its own or from a profile it extends",
+                profile_name
+            )));
+        }
+    }
+    planned.sort_by_key(|(profile_name, _)| depths[profile_name]);
+
+    for (profile_name, profile) in planned {
+        create_profile(&profile_name, profile)?;
+        if !existing.contains(&profile_name) {
+            report.created.push(profile_name);
+        }
+    }
+    Ok(report)
+}
+
+fn entry_profile_name(entry: &RosterEntry) -> Option<String> {
+    if let Some(profile) = entry.profile.as_deref().map(str::trim).filter(|profile| !profile.is_empty()) {
+        return Some(profile.to_string());
+    }
+    let (local, _) = entry.email.split_once('@')?;
+    let name: String = local
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    let name = name.trim_matches('-');
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+// The profile an entry describes, checked as `create_profile` would so problems show up before
+// anything is written
+fn entry_profile(entry: &RosterEntry) -> Result<GitProfile, GitError> {
+    let inherited = |value: &str| entry.extends.is_some() && value.is_empty();
+    if !inherited(&entry.name) {
+        validate_name(&entry.name)?;
+    }
+    if !inherited(&entry.email) {
+        validate_email(&entry.email)?;
+    }
+    for pattern in &entry.remotes {
+        validate_remote_pattern(pattern)?;
+    }
+    let mut profile = GitProfile {
+        extends: entry.extends.clone(),
+        name: entry.name.clone(),
+        email: entry.email.clone(),
+        ssh_key: entry.ssh_key.as_deref().map(|key| expand_home(&key.to_string_lossy())),
+        remotes: entry.remotes.clone(),
+        description: entry.description.clone().filter(|description| !description.is_empty()),
+        tags: entry.tags.clone(),
+        ..Default::default()
+    };
+    if let Some(key) = entry.signing_key.as_deref().filter(|key| !key.is_empty()) {
+        // A path is an SSH key; anything else is a GPG key ID or fingerprint
+        let (key, format) = if key.contains(['/', '\\']) || key.ends_with(".pub") {
+            (expand_home(key).to_string_lossy().into_owned(), "ssh")
+        } else {
+            (key.to_string(), "openpgp")
+        };
+        profile.extra.extend([
+            ("user.signingkey".to_string(), key),
+            ("gpg.format".to_string(), format.to_string()),
+            ("commit.gpgsign".to_string(), "true".to_string()),
+            ("tag.gpgsign".to_string(), "true".to_string()),
+        ]);
+    }
+    Ok(profile)
+}
+
+// Spreadsheets export headers in all sorts of ways: "Signing key", "signing_key", "KEY ID"
+fn parse_csv(contents: &str) -> Result<Vec<RosterEntry>, String> {
+    let mut rows = csv_rows(contents)?.into_iter();
+    let Some(header) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let columns: Vec<String> = header
+        .iter()
+        .map(|column| column.trim().to_lowercase().replace([' ', '-'], "_"))
+        .collect();
+    let mut entries = Vec::new();
+    for (line, row) in rows.enumerate() {
+        let mut entry = RosterEntry::default();
+        for (column, value) in columns.iter().zip(row) {
+            let value = value.trim().to_string();
+            match column.as_str() {
+                "profile" => entry.profile = Some(value),
+                "name" | "user" => entry.name = value,
+                "email" => entry.email = value,
+                "signing_key" | "key" | "key_id" => entry.signing_key = Some(value),
+                "ssh_key" if value.is_empty() => {}
+                "ssh_key" => entry.ssh_key = Some(PathBuf::from(value)),
+                "remotes" | "urls" | "patterns" => {
+                    entry.remotes = value
+                        .split([';', ' ', '\t'])
+                        .filter(|pattern| !pattern.is_empty())
+                        .map(str::to_string)
+                        .collect();
+                }
+                "extends" => entry.extends = Some(value).filter(|base| !base.is_empty()),
+                "description" => entry.description = Some(value),
+                "tags" => {
+                    entry.tags = value
+                        .split([';', ',', ' ', '\t'])
+                        .filter(|tag| !tag.is_empty())
+                        .map(str::to_string)
+                        .collect();
+                }
+                other => return Err(format!("unknown column '{}' (line {})", other, line + 2)),
+            }
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+// RFC 4180 CSV: commas between fields, quotes around fields holding commas, quotes or line
+// breaks, and "" for a quote inside quotes. Blank lines are skipped
+fn csv_rows(contents: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = contents.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                if row.iter().any(|field| !field.trim().is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                }
+                row.clear();
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("a quoted field is never closed".to_string());
+    }
+    row.push(field);
+    if row.iter().any(|field| !field.trim().is_empty()) {
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Sandbox;
+
+    fn entry(profile: &str, email: &str, extends: Option<&str>) -> RosterEntry {
+        RosterEntry {
+            profile: Some(profile.to_string()),
+            name: if extends.is_some() { String::new() } else { "Jane Doe".to_string() },
+            email: email.to_string(),
+            extends: extends.map(str::to_string),
+            ..RosterEntry::default()
+        }
+    }
+
+    #[test]
+    fn import_checks_every_base_first_and_creates_bases_before_the_profiles_on_them() {
+        let _sandbox = Sandbox::new();
+        let missing = [entry("work", "jane@example.com", None), entry("oss", "", Some("nowhere"))];
+        assert!(import_roster(&missing, OnConflict::Fail).is_err());
+        assert!(load_profiles().unwrap().profiles.is_empty());
+
+        let entries = [
+            entry("oss", "", Some("work")),
+            entry("client", "jane@client.example", Some("oss")),
+            entry("work", "jane@example.com", None),
+        ];
+        let report = import_roster(&entries, OnConflict::Fail).unwrap();
+        assert_eq!(report.created, ["work", "oss", "client"]);
+        let profiles = load_profiles().unwrap().profiles;
+        assert_eq!(resolve_profile(&profiles, "client").unwrap().name, "Jane Doe");
+    }
+}
@@ -0,0 +1,476 @@
+use std::io::{self, Read, Write};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use signal_hook::consts::SIGINT;
+use signal_hook::flag;
+
+use crate::config_cache::clear_config_cache;
+
+// How often a running command is checked for a timeout or Ctrl-C; checks start out more
+// frequent, so quick commands like git config aren't slowed down
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// How long a command gets to exit after SIGTERM before it is killed outright
+const KILL_GRACE: Duration = Duration::from_secs(3);
+
+// How long external commands may run before they are stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    // git config, which only reads and writes local files
+    pub git_config: Duration,
+    // Every other git command, including clones and pushes over the network
+    pub git: Duration,
+    // Package managers, installers and downloads, which may also wait on another install's lock
+    pub install: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            git_config: Duration::from_secs(30),
+            git: Duration::from_secs(10 * 60),
+            install: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl Timeouts {
+    // The same limit for every command
+    pub fn uniform(timeout: Duration) -> Self {
+        Timeouts {
+            git_config: timeout,
+            git: timeout,
+            install: timeout,
+        }
+    }
+
+    // Function to get the limit for a command line
+    pub fn for_command(&self, cmd: &str, args: &[&str]) -> Duration {
+        if cmd != "git" {
+            return self.install;
+        }
+        match git_subcommand(args) {
+            Some("config") => self.git_config,
+            _ => self.git,
+        }
+    }
+}
+
+// Runs the external commands gitup needs: git, package managers, ssh tools. Every
+// invocation in the library goes through the runner registered with `set_command_runner`,
+// so tests and embedding programs can keep gitup away from the real system
+pub trait CommandRunner: Send + Sync {
+    fn run(&self, cmd: &str, args: &[&str]) -> io::Result<Output>;
+
+    // Run with data on stdin, which keeps secrets off the command line; runners that don't
+    // care about the input can rely on this default
+    fn run_with_input(&self, cmd: &str, args: &[&str], _input: &str) -> io::Result<Output> {
+        self.run(cmd, args)
+    }
+
+    // Run a long command, such as a package install, passing its output to `progress` as it
+    // is printed; runners that can't stream can rely on this default
+    fn run_with_progress(&self, cmd: &str, args: &[&str], _progress: Arc<dyn ProgressReporter>) -> io::Result<Output> {
+        self.run(cmd, args)
+    }
+}
+
+// Receives the output of long commands (package managers, installers) line by line while they
+// run, so a front end can show that something is happening
+pub trait ProgressReporter: Send + Sync {
+    fn line(&self, cmd: &str, line: &str);
+
+    // Called once the command has exited
+    fn finished(&self, _cmd: &str) {}
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemRunner;
+
+// Runs commands for real, stopping them when they exceed their timeout or Ctrl-C is pressed
+impl CommandRunner for SystemRunner {
+    fn run(&self, cmd: &str, args: &[&str]) -> io::Result<Output> {
+        let child = spawn(cmd, args, Stdio::null())?;
+        wait(child, cmd, args, None)
+    }
+
+    fn run_with_input(&self, cmd: &str, args: &[&str], input: &str) -> io::Result<Output> {
+        let mut child = spawn(cmd, args, Stdio::piped())?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input.as_bytes())?;
+        }
+        wait(child, cmd, args, None)
+    }
+
+    fn run_with_progress(&self, cmd: &str, args: &[&str], progress: Arc<dyn ProgressReporter>) -> io::Result<Output> {
+        let child = spawn(cmd, args, Stdio::null())?;
+        let output = wait(child, cmd, args, Some(Arc::clone(&progress)));
+        progress.finished(cmd);
+        output
+    }
+}
+
+impl<F> CommandRunner for F
+where
+    F: Fn(&str, &[&str]) -> io::Result<Output> + Send + Sync,
+{
+    fn run(&self, cmd: &str, args: &[&str]) -> io::Result<Output> {
+        self(cmd, args)
+    }
+}
+
+// A command gitup ran, as recorded by `RecordingRunner`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedCommand {
+    pub cmd: String,
+    pub args: Vec<String>,
+    pub input: Option<String>,
+}
+
+impl RecordedCommand {
+    // The command as a single line, e.g. "git config --global user.name Jane"
+    pub fn line(&self) -> String {
+        let mut line = self.cmd.clone();
+        for arg in &self.args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+        line
+    }
+}
+
+// A fake runner that records every command instead of running it. Commands succeed with
+// empty output unless a canned response was registered for a prefix of their command line
+#[derive(Debug, Default)]
+pub struct RecordingRunner {
+    commands: Mutex<Vec<RecordedCommand>>,
+    responses: Mutex<Vec<(String, i32, String)>>,
+}
+
+impl RecordingRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Function to answer commands whose line starts with `prefix` (e.g. "git config --global
+    // user.name") with an exit code and stdout; the most recent matching response wins
+    pub fn respond(&self, prefix: &str, exit_code: i32, stdout: &str) {
+        if let Ok(mut responses) = self.responses.lock() {
+            responses.push((prefix.to_string(), exit_code, stdout.to_string()));
+        }
+    }
+
+    // Function to get the commands run so far, in order
+    pub fn commands(&self) -> Vec<RecordedCommand> {
+        self.commands.lock().map(|commands| commands.clone()).unwrap_or_default()
+    }
+
+    // Function to get the commands run so far as single lines
+    pub fn lines(&self) -> Vec<String> {
+        self.commands().iter().map(RecordedCommand::line).collect()
+    }
+
+    fn record(&self, cmd: &str, args: &[&str], input: Option<&str>) -> io::Result<Output> {
+        let command = RecordedCommand {
+            cmd: cmd.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            input: input.map(String::from),
+        };
+        let line = command.line();
+        if let Ok(mut commands) = self.commands.lock() {
+            commands.push(command);
+        }
+        let (exit_code, stdout) = self
+            .responses
+            .lock()
+            .ok()
+            .and_then(|responses| {
+                responses
+                    .iter()
+                    .rev()
+                    .find(|(prefix, _, _)| line.starts_with(prefix.as_str()))
+                    .map(|(_, exit_code, stdout)| (*exit_code, stdout.clone()))
+            })
+            .unwrap_or((0, String::new()));
+        Ok(Output {
+            status: exit_status(exit_code),
+            stdout: stdout.into_bytes(),
+            stderr: Vec::new(),
+        })
+    }
+}
+
+impl CommandRunner for RecordingRunner {
+    fn run(&self, cmd: &str, args: &[&str]) -> io::Result<Output> {
+        self.record(cmd, args, None)
+    }
+
+    fn run_with_input(&self, cmd: &str, args: &[&str], input: &str) -> io::Result<Output> {
+        self.record(cmd, args, Some(input))
+    }
+}
+
+static RUNNER: RwLock<Option<Arc<dyn CommandRunner>>> = RwLock::new(None);
+
+// Function to route every command the library runs through `runner` for the rest of the
+// process, or until it is replaced
+pub fn set_command_runner(runner: Arc<dyn CommandRunner>) {
+    if let Ok(mut current) = RUNNER.write() {
+        *current = Some(runner);
+    }
+    clear_config_cache();
+}
+
+// Function to go back to running commands for real
+pub fn reset_command_runner() {
+    if let Ok(mut current) = RUNNER.write() {
+        *current = None;
+    }
+    clear_config_cache();
+}
+
+// Function to check whether commands go through a runner registered with `set_command_runner`
+pub fn command_runner_replaced() -> bool {
+    #[cfg(test)]
+    if crate::test_support::command_runner().is_some() {
+        return true;
+    }
+    RUNNER.read().is_ok_and(|current| current.is_some())
+}
+
+// Function to get the runner commands currently go through
+pub fn command_runner() -> Arc<dyn CommandRunner> {
+    #[cfg(test)]
+    if let Some(runner) = crate::test_support::command_runner() {
+        return runner;
+    }
+    RUNNER
+        .read()
+        .ok()
+        .and_then(|current| current.clone())
+        .unwrap_or_else(|| Arc::new(SystemRunner))
+}
+
+static PROGRESS: RwLock<Option<Arc<dyn ProgressReporter>>> = RwLock::new(None);
+
+// Function to show the output of long commands through `reporter` while they run
+pub fn set_progress_reporter(reporter: Arc<dyn ProgressReporter>) {
+    if let Ok(mut current) = PROGRESS.write() {
+        *current = Some(reporter);
+    }
+}
+
+// Function to get the registered progress reporter, if any
+pub fn progress_reporter() -> Option<Arc<dyn ProgressReporter>> {
+    PROGRESS.read().ok().and_then(|current| current.clone())
+}
+
+static TIMEOUTS: RwLock<Option<Timeouts>> = RwLock::new(None);
+
+// Function to change how long `SystemRunner` lets commands run
+pub fn set_timeouts(timeouts: Timeouts) {
+    if let Ok(mut current) = TIMEOUTS.write() {
+        *current = Some(timeouts);
+    }
+}
+
+// Function to get the timeouts commands currently run with
+pub fn timeouts() -> Timeouts {
+    TIMEOUTS.read().ok().and_then(|current| *current).unwrap_or_default()
+}
+
+// Set by Ctrl-C while a command runs; cleared only by exiting
+static INTERRUPTED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+// Whether no command is running, in which case Ctrl-C exits straight away as usual
+static IDLE: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+// Function to make Ctrl-C stop the running command instead of leaving it behind: the command is
+// terminated, it and every later command fail as interrupted, and a second Ctrl-C exits at once
+pub fn handle_interrupts() -> io::Result<()> {
+    let interrupted = INTERRUPTED.get_or_init(|| Arc::new(AtomicBool::new(false)));
+    let idle = IDLE.get_or_init(|| Arc::new(AtomicBool::new(true)));
+    // Actions run in registration order, so the exits are checked before the flag is set
+    flag::register_conditional_default(SIGINT, Arc::clone(interrupted))?;
+    flag::register_conditional_default(SIGINT, Arc::clone(idle))?;
+    flag::register(SIGINT, Arc::clone(interrupted))?;
+    Ok(())
+}
+
+// Function to check whether Ctrl-C stopped a command
+pub fn interrupted() -> bool {
+    INTERRUPTED.get().is_some_and(|flag| flag.load(Ordering::SeqCst))
+}
+
+fn spawn(cmd: &str, args: &[&str], stdin: Stdio) -> io::Result<Child> {
+    if interrupted() {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, format!("`{}` was not run after Ctrl-C", line(cmd, args))));
+    }
+    Command::new(cmd)
+        .args(args)
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+}
+
+// Collect a child's output, stopping it on timeout or Ctrl-C
+fn wait(mut child: Child, cmd: &str, args: &[&str], progress: Option<Arc<dyn ProgressReporter>>) -> io::Result<Output> {
+    // Drain both pipes while waiting, or a chatty command blocks on a full pipe
+    let stdout = child.stdout.take().map(|pipe| drain(pipe, cmd, progress.clone()));
+    let stderr = child.stderr.take().map(|pipe| drain(pipe, cmd, progress.clone()));
+    let collect = |reader: Option<thread::JoinHandle<Vec<u8>>>| reader.and_then(|r| r.join().ok()).unwrap_or_default();
+
+    let timeout = timeouts().for_command(cmd, args);
+    let started = Instant::now();
+    let mut interval = Duration::from_millis(1);
+    if let Some(idle) = IDLE.get() {
+        idle.store(false, Ordering::SeqCst);
+    }
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) => {}
+            Err(e) => break Err(e),
+        }
+        if interrupted() {
+            stop(&mut child);
+            break Err(io::Error::new(io::ErrorKind::Interrupted, format!("`{}` was interrupted", line(cmd, args))));
+        }
+        if started.elapsed() >= timeout {
+            stop(&mut child);
+            break Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("`{}` was stopped after {}s without finishing", line(cmd, args), timeout.as_secs()),
+            ));
+        }
+        thread::sleep(interval);
+        interval = (interval * 2).min(POLL_INTERVAL);
+    };
+    if let Some(idle) = IDLE.get() {
+        idle.store(true, Ordering::SeqCst);
+    }
+
+    let status = status?;
+    Ok(Output {
+        status,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    })
+}
+
+// Read a pipe to the end, reporting each line as it arrives. Progress bars redraw themselves
+// with \r, so that ends a line too
+fn drain(
+    mut pipe: impl Read + Send + 'static,
+    cmd: &str,
+    progress: Option<Arc<dyn ProgressReporter>>,
+) -> thread::JoinHandle<Vec<u8>> {
+    let cmd = cmd.to_string();
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let mut chunk = [0; 4096];
+        let mut reported = 0;
+        loop {
+            let read = match pipe.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+            buffer.extend_from_slice(&chunk[..read]);
+            let Some(progress) = &progress else {
+                continue;
+            };
+            while let Some(end) = buffer[reported..].iter().position(|&byte| byte == b'\n' || byte == b'\r') {
+                let line = String::from_utf8_lossy(&buffer[reported..reported + end]);
+                if !line.trim().is_empty() {
+                    progress.line(&cmd, line.trim_end());
+                }
+                reported += end + 1;
+            }
+        }
+        buffer
+    })
+}
+
+// Ask the command to exit first, so sudo can pass it on to what it started, then kill it
+fn stop(child: &mut Child) {
+    if cfg!(unix) {
+        let _ = Command::new("kill").args(["-TERM", &child.id().to_string()]).output();
+        let asked = Instant::now();
+        while asked.elapsed() < KILL_GRACE {
+            if let Ok(Some(_)) = child.try_wait() {
+                return;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+// The subcommand of a git command line, skipping options such as -C <dir>
+fn git_subcommand<'a>(args: &[&'a str]) -> Option<&'a str> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "-C" | "-c" | "--git-dir" | "--work-tree" => {
+                args.next();
+            }
+            arg if arg.starts_with('-') => {}
+            arg => return Some(arg),
+        }
+    }
+    None
+}
+
+fn line(cmd: &str, args: &[&str]) -> String {
+    let mut line = cmd.to_string();
+    for arg in args {
+        line.push(' ');
+        line.push_str(arg);
+    }
+    line
+}
+
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    // The raw value is a wait status, which keeps the exit code in the second byte
+    ExitStatus::from_raw((code & 0xff) << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+    ExitStatus::from_raw(code as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_runner_records_commands_and_answers_with_the_latest_matching_response() {
+        let runner = RecordingRunner::new();
+        runner.respond("git config", 1, "");
+        runner.respond("git config --global user.name", 0, "Jane Doe\n");
+
+        let output = runner.run("git", &["config", "--global", "user.name"]).unwrap();
+        assert_eq!((output.status.code(), output.stdout.as_slice()), (Some(0), "Jane Doe\n".as_bytes()));
+        let output = runner.run("git", &["config", "--global", "user.email"]).unwrap();
+        assert_eq!((output.status.code(), output.stdout.as_slice()), (Some(1), "".as_bytes()));
+        let output = runner.run_with_input("ssh-keygen", &["-Y", "sign"], "payload").unwrap();
+        assert!(output.status.success());
+
+        assert_eq!(
+            runner.lines(),
+            ["git config --global user.name", "git config --global user.email", "ssh-keygen -Y sign"]
+        );
+        assert_eq!(runner.commands()[2].input.as_deref(), Some("payload"));
+    }
+}
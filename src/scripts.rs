@@ -0,0 +1,147 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::dry_run::DryRun;
+use crate::{config_dir, GitError};
+
+// Points in gitup's commands where user scripts run. A script for a pre- event can stop the
+// operation by throwing; scripts for post- events run once it has succeeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    PreProfileSwitch,
+    PostProfileSwitch,
+    PreRestore,
+    PostRestore,
+    PreApply,
+    PostApply,
+    PostBackup,
+}
+
+const EVENTS: &[(Event, &str)] = &[
+    (Event::PreProfileSwitch, "pre-profile-switch"),
+    (Event::PostProfileSwitch, "post-profile-switch"),
+    (Event::PreRestore, "pre-restore"),
+    (Event::PostRestore, "post-restore"),
+    (Event::PreApply, "pre-apply"),
+    (Event::PostApply, "post-apply"),
+    (Event::PostBackup, "post-backup"),
+];
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = EVENTS.iter().find(|(event, _)| event == self).map_or("", |(_, name)| name);
+        f.write_str(name)
+    }
+}
+
+// Function to get the directory event scripts are kept in, next to gitup's shared git hooks
+pub fn scripts_dir() -> PathBuf {
+    config_dir().join("hooks")
+}
+
+// Function to list the scripts for an event in the order they run: <event>.rhai, and any
+// number of <event>.<name>.rhai, sorted by file name
+pub fn event_scripts(event: Event) -> Result<Vec<PathBuf>, GitError> {
+    let dir = scripts_dir();
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let event = event.to_string();
+    let mut scripts: Vec<PathBuf> = fs::read_dir(&dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            name.strip_suffix(".rhai")
+                .is_some_and(|stem| stem == event || stem.strip_prefix(&event).is_some_and(|rest| rest.starts_with('.')))
+        })
+        .filter(|path| path.is_file())
+        .collect();
+    scripts.sort();
+    Ok(scripts)
+}
+
+// Function to run the scripts for an event. Each script sees the event name as `event` and
+// what it is about as the `ctx` map (the profile switched to, the restored file, ...), and
+// what it prints is passed to `output` after it finishes. The first script that fails stops
+// the rest. In dry-run mode the scripts are only recorded. Returns how many scripts ran
+pub fn run_event(event: Event, context: &[(&str, String)], output: &mut dyn FnMut(&Path, &str)) -> Result<usize, GitError> {
+    let scripts = event_scripts(event)?;
+    for script in &scripts {
+        if DryRun::is_enabled() {
+            DryRun::record(format!("script: {} ({})", script.display(), event));
+            continue;
+        }
+        run_script(script, event, context, output)?;
+    }
+    Ok(scripts.len())
+}
+
+#[cfg(feature = "scripts")]
+fn run_script(
+    script: &Path,
+    event: Event,
+    context: &[(&str, String)],
+    output: &mut dyn FnMut(&Path, &str),
+) -> Result<(), GitError> {
+    use std::cell::RefCell;
+    use std::env;
+    use std::rc::Rc;
+
+    use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope};
+
+    use crate::config::GitConfig;
+    use crate::git::run_command;
+
+    let mut engine = Engine::new();
+    let printed = Rc::new(RefCell::new(Vec::new()));
+    let print = Rc::clone(&printed);
+    engine.on_print(move |line| print.borrow_mut().push(line.to_string()));
+    let debug = Rc::clone(&printed);
+    engine.on_debug(move |line, _, _| debug.borrow_mut().push(line.to_string()));
+
+    // run("ssh-add", ["-D"]) runs a command and returns #{ code, stdout, stderr }
+    fn run(program: &str, args: Array) -> Result<Map, Box<EvalAltResult>> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let result = run_command(program, &args).map_err(|e| format!("couldn't run {}: {}", program, e))?;
+        let mut map = Map::new();
+        map.insert("code".into(), Dynamic::from(result.status.code().unwrap_or(-1) as i64));
+        map.insert("stdout".into(), String::from_utf8_lossy(&result.stdout).into_owned().into());
+        map.insert("stderr".into(), String::from_utf8_lossy(&result.stderr).into_owned().into());
+        Ok(map)
+    }
+    engine.register_fn("run", run);
+    engine.register_fn("run", |program: &str| run(program, Array::new()));
+    // env("HOME") and git_config("user.email") return () when unset
+    engine.register_fn("env", |name: &str| env::var(name).map_or(Dynamic::UNIT, Dynamic::from));
+    engine.register_fn("git_config", |key: &str| GitConfig::global().get(key).map_or(Dynamic::UNIT, Dynamic::from));
+
+    let mut scope = Scope::new();
+    scope.push_constant("event", event.to_string());
+    let ctx: Map = context.iter().map(|(key, value)| ((*key).into(), Dynamic::from(value.clone()))).collect();
+    scope.push_constant("ctx", ctx);
+
+    let result = engine.run_file_with_scope(&mut scope, script.to_path_buf());
+    for line in printed.borrow().iter() {
+        output(script, line);
+    }
+    result.map_err(|e| {
+        // A thrown value is the script's own message
+        let message = match *e {
+            EvalAltResult::ErrorRuntime(value, _) => value.to_string(),
+            e => e.to_string(),
+        };
+        GitError::Script(format!("{} ({}): {}", script.display(), event, message))
+    })
+}
+
+#[cfg(not(feature = "scripts"))]
+fn run_script(script: &Path, event: Event, _: &[(&str, String)], _: &mut dyn FnMut(&Path, &str)) -> Result<(), GitError> {
+    Err(GitError::Script(format!(
+        "{} ({}) wasn't run: this gitup was built without script support; reinstall with `cargo install gitup --features scripts`",
+        script.display(),
+        event
+    )))
+}
@@ -0,0 +1,67 @@
+use keyring::Entry;
+
+use crate::dry_run::DryRun;
+use crate::GitError;
+
+// Keyring service every gitup secret is stored under
+const SERVICE: &str = "gitup";
+
+// Profile values of the form "keyring:<name>" are looked up in the keyring when applied
+const REFERENCE_PREFIX: &str = "keyring:";
+
+// Function to store a secret in the OS keyring (Keychain, Secret Service, Credential Manager)
+pub fn set_secret(name: &str, value: &str) -> Result<(), GitError> {
+    if DryRun::is_enabled() {
+        DryRun::record(format!("keyring: set {}/{}", SERVICE, name));
+        return Ok(());
+    }
+    entry(name)?.set_password(value).map_err(keyring_error)
+}
+
+// Function to read a secret from the OS keyring, returning None if it doesn't exist
+pub fn get_secret(name: &str) -> Result<Option<String>, GitError> {
+    match entry(name)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(keyring_error(e)),
+    }
+}
+
+// Function to delete a secret, returning false if it didn't exist
+pub fn delete_secret(name: &str) -> Result<bool, GitError> {
+    if DryRun::is_enabled() {
+        DryRun::record(format!("keyring: delete {}/{}", SERVICE, name));
+        return Ok(true);
+    }
+    match entry(name)?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(keyring_error(e)),
+    }
+}
+
+// Function to check whether a profile value refers to a keyring entry
+pub fn is_secret_reference(value: &str) -> bool {
+    value.starts_with(REFERENCE_PREFIX)
+}
+
+// Function to replace a "keyring:<name>" reference with the secret it names
+pub fn resolve_secret(value: &str) -> Result<String, GitError> {
+    let Some(name) = value.strip_prefix(REFERENCE_PREFIX) else {
+        return Ok(value.to_string());
+    };
+    get_secret(name)?.ok_or_else(|| {
+        GitError::InvalidInput(format!(
+            "keyring entry '{}' not found; add it with `gitup secret set {}`",
+            name, name
+        ))
+    })
+}
+
+fn entry(name: &str) -> Result<Entry, GitError> {
+    Entry::new(SERVICE, name).map_err(keyring_error)
+}
+
+fn keyring_error(err: keyring::Error) -> GitError {
+    GitError::Keyring(err.to_string())
+}
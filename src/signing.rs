@@ -0,0 +1,411 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::GitConfig;
+use crate::dry_run::{create_dir_all, write_file};
+use crate::expand_home;
+use crate::git::{get_active_config, run_with_input, Scope};
+use crate::ssh::{public_key_path, read_public_key, ssh_dir};
+use crate::temp::PrivateDir;
+use crate::validate::validate_email;
+use crate::GitError;
+
+// The allowed signers option gitup writes: git's own signatures, and the ones on gitup backups
+const NAMESPACES: &str = "namespaces=\"git,gitup-backup\"";
+const GIT_ONLY: &str = "namespaces=\"git\"";
+
+// A detached signature made with the key git signs commits with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Signature {
+    // "ssh" or "openpgp", as in gpg.format
+    pub format: String,
+    // The user.signingkey it was made with
+    pub key: String,
+    // The armored signature
+    pub data: String,
+}
+
+// The public half of the key git signs commits with, as it is uploaded to a Git host
+#[derive(Debug, Clone, Serialize)]
+pub struct SigningKey {
+    // "ssh" or "openpgp", as in gpg.format
+    pub format: String,
+    // The user.signingkey it comes from
+    pub key: String,
+    // An SSH public key line, or an armored GPG public key
+    pub public_key: String,
+    // The fingerprint of a GPG key's primary key
+    pub fingerprint: Option<String>,
+}
+
+// Function to get the public half of the user's commit signing key (user.signingkey, an SSH key
+// when gpg.format is ssh and a GPG key otherwise)
+pub fn signing_key() -> Result<SigningKey, GitError> {
+    let config = GitConfig::global();
+    let key = config.get("user.signingkey").ok_or_else(|| {
+        GitError::InvalidInput(
+            "no signing key is set up; use `gitup sign setup --ssh` or `gitup gpg generate` first".to_string(),
+        )
+    })?;
+    let format = signing_format(&config)?;
+    if format == "ssh" {
+        return Ok(SigningKey {
+            public_key: own_public_key(&config)?,
+            format,
+            key,
+            fingerprint: None,
+        });
+    }
+
+    let program = config.get("gpg.program").unwrap_or_else(|| "gpg".to_string());
+    let armored = run(&program, &["--armor", "--export", &key], "")?;
+    if armored.trim().is_empty() {
+        return Err(GitError::InvalidInput(format!("{} has no public key {}", program, key)));
+    }
+    // fpr:::::::::<fingerprint>: follows the primary key's pub line
+    let listing = run(&program, &["--with-colons", "--fingerprint", &key], "")?;
+    let fingerprint = listing
+        .lines()
+        .find(|line| line.starts_with("fpr:"))
+        .and_then(|line| line.split(':').nth(9))
+        .map(str::to_string);
+    Ok(SigningKey {
+        format,
+        key,
+        public_key: armored,
+        fingerprint,
+    })
+}
+
+// What `setup_ssh_signing` configured
+#[derive(Debug, Clone, Serialize)]
+pub struct SshSigningSetup {
+    // The public key commits are signed with
+    pub key: PathBuf,
+    pub email: String,
+    pub allowed_signers: PathBuf,
+    // Whether the key had to be added to the allowed signers file
+    pub added: bool,
+    pub settings: Vec<(String, String)>,
+}
+
+// Function to sign commits and tags with an SSH key (a public key, or the private key next to
+// it) and make git able to verify them: the key is listed for `email` (or user.email) in the
+// allowed signers file, gpg.ssh.allowedSignersFile if set and ~/.ssh/allowed_signers otherwise,
+// so `git log --show-signature` shows them as good. The settings go to `scope`; the allowed
+// signers file is always the global one
+pub fn setup_ssh_signing(key: &Path, email: Option<&str>, scope: Scope) -> Result<SshSigningSetup, GitError> {
+    let key = expand_home(&key.to_string_lossy());
+    let public_key = read_public_key(&key)?;
+    let key = if key.extension().is_some_and(|extension| extension == "pub") {
+        key
+    } else {
+        public_key_path(&key)
+    };
+    // git signs from whatever directory it runs in
+    let key = fs::canonicalize(&key)?;
+
+    let email = match email {
+        Some(email) => email.to_string(),
+        None => get_active_config("user.email").ok_or_else(|| {
+            GitError::InvalidInput("the allowed signers file lists keys by email; set user.email or pass --email".to_string())
+        })?,
+    };
+    validate_email(&email)?;
+
+    let global = GitConfig::global();
+    let allowed_signers = global
+        .get("gpg.ssh.allowedSignersFile")
+        .map_or_else(|| ssh_dir().join("allowed_signers"), |path| expand_home(&path));
+    let contents = fs::read_to_string(&allowed_signers).unwrap_or_default();
+    // <principals> [options] <type> <base64 key>; the key may already be there under this email
+    let is_entry = |line: &str| {
+        let line = line.trim();
+        !line.starts_with('#')
+            && line.contains(&public_key)
+            && line.split_whitespace().next().is_some_and(|principals| principals.split(',').any(|p| p == email))
+    };
+    let added = !contents.lines().any(is_entry);
+    // Entries written before backups were signed only accept git signatures
+    let upgraded = contents.lines().any(|line| is_entry(line) && line.contains(GIT_ONLY));
+    if added || upgraded {
+        let mut contents: String = contents
+            .split_inclusive('\n')
+            .map(|line| if is_entry(line) { line.replace(GIT_ONLY, NAMESPACES) } else { line.to_string() })
+            .collect();
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        // Only git signatures and gitup's own backup signatures are accepted for the entry, so the
+        // key can't vouch for anything else
+        if added {
+            contents.push_str(&format!("{} {} {}\n", email, NAMESPACES, public_key));
+        }
+        if let Some(dir) = allowed_signers.parent() {
+            create_dir_all(dir)?;
+        }
+        write_file(&allowed_signers, &contents)?;
+    }
+
+    if global.get("gpg.ssh.allowedSignersFile").is_none() {
+        global.set("gpg.ssh.allowedSignersFile", &allowed_signers.to_string_lossy())?;
+    }
+    let settings: Vec<(String, String)> = [
+        ("gpg.format", "ssh".to_string()),
+        ("user.signingkey", key.to_string_lossy().into_owned()),
+        ("commit.gpgsign", "true".to_string()),
+        ("tag.gpgsign", "true".to_string()),
+    ]
+    .into_iter()
+    .map(|(setting, value)| (setting.to_string(), value))
+    .collect();
+    GitConfig::new(scope).set_many(&settings)?;
+    Ok(SshSigningSetup {
+        key,
+        email,
+        allowed_signers,
+        added,
+        settings,
+    })
+}
+
+// Function to sign `payload` with the user's commit signing key (user.signingkey, with
+// gpg.format saying whether it is an SSH or a GPG key). SSH signatures are made for
+// `namespace`, so one can't be passed off as a signature over something else
+pub fn sign(payload: &str, namespace: &str) -> Result<Signature, GitError> {
+    let config = GitConfig::global();
+    let key = config.get("user.signingkey").ok_or_else(|| {
+        GitError::InvalidInput(
+            "signing needs a key; set user.signingkey (and gpg.format to ssh for an SSH key)".to_string(),
+        )
+    })?;
+    let format = signing_format(&config)?;
+
+    let data = if format == "ssh" {
+        let program = config.get("gpg.ssh.program").unwrap_or_else(|| "ssh-keygen".to_string());
+        // An inline "key::ssh-ed25519 ..." key has to be in a file for ssh-keygen
+        let inline = key.strip_prefix("key::").map(|public_key| TempFile::new("key.pub", public_key)).transpose()?;
+        let key_file = inline.as_ref().map_or_else(|| expand_home(&key), |file| file.path.clone());
+        let key_file = key_file.to_string_lossy();
+        run(&program, &["-Y", "sign", "-f", &key_file, "-n", namespace], payload)?
+    } else {
+        let program = config.get("gpg.program").unwrap_or_else(|| "gpg".to_string());
+        run(&program, &["--armor", "--detach-sign", "--local-user", &key], payload)?
+    };
+    Ok(Signature { format, key, data })
+}
+
+// Function to check that `signature` is a valid signature over `payload` by a trusted key,
+// returning who made it. SSH signatures are checked against gpg.ssh.allowedSignersFile, or
+// when that isn't set, against the user's own signing key; GPG signatures against the keyring
+pub fn verify(payload: &str, namespace: &str, signature: &Signature) -> Result<String, GitError> {
+    let config = GitConfig::global();
+    let signature_file = TempFile::new("sig", &signature.data)?;
+    let signature_path = signature_file.path.to_string_lossy().into_owned();
+
+    match signature.format.as_str() {
+        "ssh" => {
+            let program = config.get("gpg.ssh.program").unwrap_or_else(|| "ssh-keygen".to_string());
+            // Any principal with the right key will do; it's the key that is trusted
+            let (allowed_signers, own_key) = match config.get("gpg.ssh.allowedSignersFile") {
+                Some(path) => (expand_home(&path), None),
+                None => {
+                    let file = TempFile::new("allowed_signers", &format!("* {}", own_public_key(&config)?))?;
+                    (file.path.clone(), Some(file))
+                }
+            };
+            let principal = principal(&program, &signature_path, &allowed_signers, payload).map_err(|e| {
+                if own_key.is_some() {
+                    GitError::InvalidInput("the signature was made with a key other than your signing key".to_string())
+                } else {
+                    e
+                }
+            })?;
+            let allowed_signers = allowed_signers.to_string_lossy();
+            let args = ["-Y", "verify", "-f", &allowed_signers, "-I", &principal, "-n", namespace, "-s", &signature_path];
+            check(&program, &args, payload, signature)?;
+            Ok(if principal == "*" { "your signing key".to_string() } else { principal })
+        }
+        "openpgp" => {
+            let program = config.get("gpg.program").unwrap_or_else(|| "gpg".to_string());
+            let output = check(&program, &["--status-fd=1", "--verify", &signature_path, "-"], payload, signature)?;
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            trusted_gpg_signer(&stdout, config.get("user.signingkey").as_deref())
+                .map_err(|e| GitError::InvalidInput(format!("{}: {}", program, e)))
+        }
+        other => Err(GitError::InvalidInput(format!("unsupported signature format '{}'", other))),
+    }
+}
+
+// Who made a GPG signature, from gpg's --status-fd output. Any key in the keyring gives a good
+// signature, so it also has to be the user's own signing key (user.signingkey, matched against
+// the signing key's or primary key's fingerprint) or a key the keyring trusts fully
+fn trusted_gpg_signer(status: &str, own_key: Option<&str>) -> Result<String, String> {
+    let fields = |keyword: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("[GNUPG:] ")?.strip_prefix(keyword)?.strip_prefix(' '))
+    };
+    if let Some(expired) = fields("EXPKEYSIG") {
+        return Err(format!("the signing key {} has expired", expired.split(' ').next().unwrap_or(expired)));
+    }
+    if let Some(revoked) = fields("REVKEYSIG") {
+        return Err(format!("the signing key {} was revoked", revoked.split(' ').next().unwrap_or(revoked)));
+    }
+    // GOODSIG <long key id> <user id>
+    let good = fields("GOODSIG").ok_or("no good signature was reported")?;
+    let (key_id, user) = good.split_once(' ').unwrap_or((good, good));
+    // VALIDSIG <fingerprint> <date> <timestamp> <expiry> <version> <reserved> <algorithms...> <primary fingerprint>
+    let valid: Vec<&str> = fields("VALIDSIG").ok_or("the signature wasn't reported valid")?.split(' ').collect();
+    let own_key = own_key
+        .map(|key| key.trim().trim_end_matches('!').trim_start_matches("0x").replace(' ', "").to_uppercase())
+        .filter(|key| key.len() >= 16 && key.chars().all(|c| c.is_ascii_hexdigit()));
+    let by_own_key = own_key.is_some_and(|own| {
+        [valid.first(), valid.get(9)]
+            .into_iter()
+            .flatten()
+            .any(|fingerprint| fingerprint.to_uppercase().ends_with(&own))
+    });
+    let trusted = status
+        .lines()
+        .any(|line| line.starts_with("[GNUPG:] TRUST_FULLY") || line.starts_with("[GNUPG:] TRUST_ULTIMATE"));
+    if !by_own_key && !trusted {
+        return Err(format!(
+            "the signature by {} ({}) is good, but the key is neither your signing key nor fully trusted in your keyring",
+            user, key_id
+        ));
+    }
+    Ok(user.to_string())
+}
+
+fn signing_format(config: &GitConfig) -> Result<String, GitError> {
+    match config.get("gpg.format").as_deref() {
+        None | Some("openpgp") => Ok("openpgp".to_string()),
+        Some("ssh") => Ok("ssh".to_string()),
+        Some(other) => Err(GitError::InvalidInput(format!(
+            "signing with gpg.format={} is not supported; use an SSH or GPG key",
+            other
+        ))),
+    }
+}
+
+// The user's SSH signing key as a public key line, for an allowed signers entry
+fn own_public_key(config: &GitConfig) -> Result<String, GitError> {
+    let no_key = || {
+        GitError::InvalidInput(
+            "SSH signatures are checked against gpg.ssh.allowedSignersFile or your own signing key, and neither is set"
+                .to_string(),
+        )
+    };
+    if config.get("gpg.format").as_deref() != Some("ssh") {
+        return Err(no_key());
+    }
+    let key = config.get("user.signingkey").ok_or_else(no_key)?;
+    if let Some(public_key) = key.strip_prefix("key::") {
+        return Ok(public_key.to_string());
+    }
+    // user.signingkey may name the private key; its public half sits next to it
+    let path = expand_home(&key);
+    let public = if path.extension().is_some_and(|extension| extension == "pub") {
+        path
+    } else {
+        PathBuf::from(format!("{}.pub", path.display()))
+    };
+    Ok(fs::read_to_string(&public)?.trim().to_string())
+}
+
+// The principal the allowed signers file lists for the signature's key; "*" matches our own key
+fn principal(program: &str, signature: &str, allowed_signers: &Path, payload: &str) -> Result<String, GitError> {
+    let allowed = allowed_signers.to_string_lossy();
+    let output = run_with_input(program, &["-Y", "find-principals", "-f", &allowed, "-s", signature], payload)
+        .map_err(|e| GitError::spawn_failed(program, e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match stdout.lines().next() {
+        // An entry may list several principals; any of them identifies the key
+        Some(principals) if output.status.success() => {
+            Ok(principals.split(',').next().unwrap_or(principals).trim().to_string())
+        }
+        _ => Err(GitError::InvalidInput(format!(
+            "the signature was made with a key that isn't in {}",
+            allowed_signers.display()
+        ))),
+    }
+}
+
+fn run(program: &str, args: &[&str], input: &str) -> Result<String, GitError> {
+    let output = run_with_input(program, args, input).map_err(|e| GitError::spawn_failed(program, e))?;
+    if !output.status.success() {
+        return Err(GitError::command_failed(program, args, &output));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn check(program: &str, args: &[&str], input: &str, signature: &Signature) -> Result<process::Output, GitError> {
+    let output = run_with_input(program, args, input).map_err(|e| GitError::spawn_failed(program, e))?;
+    if output.status.success() {
+        return Ok(output);
+    }
+    // The verdict is the last line; gpg first describes the signature
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(GitError::InvalidInput(format!(
+        "the signature by {} doesn't match: {}",
+        signature.key,
+        stderr.trim().lines().last().unwrap_or_default()
+    )))
+}
+
+// A file the signing tools read, in a private directory of its own so nobody can swap it
+// before they read it; removed when dropped
+struct TempFile {
+    path: PathBuf,
+    _dir: PrivateDir,
+}
+
+impl TempFile {
+    fn new(name: &str, contents: &str) -> Result<Self, GitError> {
+        let dir = PrivateDir::new("signing")?;
+        let path = dir.write(name, contents.as_bytes())?;
+        Ok(TempFile { path, _dir: dir })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FINGERPRINT: &str = "DD14653099EA24B1CA5F17C8913C22D6DD82C523";
+
+    fn status(extra: &str) -> String {
+        format!(
+            "[GNUPG:] NEWSIG\n[GNUPG:] GOODSIG 913C22D6DD82C523 Jane <jane@example.com>\n\
+             [GNUPG:] VALIDSIG {0} 2026-10-16 1792173006 0 4 0 22 10 00 {0}\n{1}",
+            FINGERPRINT, extra
+        )
+    }
+
+    #[test]
+    fn gpg_signatures_must_be_by_the_signing_key_or_a_trusted_one() {
+        let jane = Ok("Jane <jane@example.com>".to_string());
+        assert_eq!(trusted_gpg_signer(&status(""), Some(FINGERPRINT)), jane);
+        assert_eq!(trusted_gpg_signer(&status(""), Some("0x913c22d6dd82c523!")), jane);
+        assert_eq!(trusted_gpg_signer(&status("[GNUPG:] TRUST_FULLY 0 pgp\n"), None), jane);
+        assert_eq!(trusted_gpg_signer(&status("[GNUPG:] TRUST_ULTIMATE 0 pgp\n"), Some("jane@example.com")), jane);
+
+        assert!(trusted_gpg_signer(&status(""), None).is_err());
+        assert!(trusted_gpg_signer(&status("[GNUPG:] TRUST_UNDEFINED 0 pgp\n"), Some("2790D3507448DE9B")).is_err());
+        assert!(trusted_gpg_signer(&status("[GNUPG:] TRUST_MARGINAL 0 pgp\n"), Some("jane@example.com")).is_err());
+    }
+
+    #[test]
+    fn expired_revoked_and_bad_gpg_signatures_are_rejected() {
+        let expired = "[GNUPG:] EXPKEYSIG 913C22D6DD82C523 Jane\n[GNUPG:] VALIDSIG X\n[GNUPG:] TRUST_ULTIMATE 0 pgp\n";
+        assert!(trusted_gpg_signer(expired, Some(FINGERPRINT)).unwrap_err().contains("expired"));
+        let revoked = "[GNUPG:] REVKEYSIG 913C22D6DD82C523 Jane\n[GNUPG:] TRUST_ULTIMATE 0 pgp\n";
+        assert!(trusted_gpg_signer(revoked, Some(FINGERPRINT)).unwrap_err().contains("revoked"));
+        assert!(trusted_gpg_signer("[GNUPG:] BADSIG 913C22D6DD82C523 Jane\n", Some(FINGERPRINT)).is_err());
+        assert!(trusted_gpg_signer("[GNUPG:] GOODSIG 913C22D6DD82C523 Jane\n", Some(FINGERPRINT)).is_err());
+    }
+}
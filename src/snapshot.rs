@@ -0,0 +1,136 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::dry_run::{create_dir_all, remove_file, write_file};
+use crate::git::global_config_path;
+use crate::{config_dir, GitError};
+
+// Snapshots kept before the oldest are pruned
+const MAX_SNAPSHOTS: usize = 100;
+
+// A saved copy of the global config file taken before a mutating operation
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub id: String,
+    pub timestamp: u64,
+    pub reason: String,
+    pub path: PathBuf,
+}
+
+// Function to get the directory holding config snapshots
+pub fn snapshots_dir() -> PathBuf {
+    config_dir().join("snapshots")
+}
+
+// Function to save a copy of the current global config file
+pub fn take_snapshot(reason: &str) -> Result<Snapshot, GitError> {
+    let config = global_config_path();
+    let contents = if config.exists() {
+        fs::read_to_string(&config)?
+    } else {
+        String::new()
+    };
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    let dir = snapshots_dir();
+    create_dir_all(&dir)?;
+
+    // Millisecond ids sort chronologically; bump on the rare collision
+    let mut id = timestamp;
+    while dir.join(file_name(id, reason)).exists() {
+        id += 1;
+    }
+    let path = dir.join(file_name(id, reason));
+    write_file(&path, &contents)?;
+
+    let snapshots = list_snapshots()?;
+    for old in &snapshots[..snapshots.len().saturating_sub(MAX_SNAPSHOTS)] {
+        remove_file(&old.path)?;
+    }
+
+    Ok(Snapshot {
+        id: id.to_string(),
+        timestamp: id / 1000,
+        reason: reason.to_string(),
+        path,
+    })
+}
+
+// Function to list snapshots from oldest to newest
+pub fn list_snapshots() -> Result<Vec<Snapshot>, GitError> {
+    let dir = snapshots_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let Some(stem) = path.file_stem().map(|s| s.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        let Some((id, reason)) = stem.split_once('-') else {
+            continue;
+        };
+        let Ok(millis) = id.parse::<u64>() else {
+            continue;
+        };
+        snapshots.push(Snapshot {
+            id: id.to_string(),
+            timestamp: millis / 1000,
+            reason: reason.to_string(),
+            path,
+        });
+    }
+    snapshots.sort_by_key(|s| s.id.parse::<u64>().unwrap_or_default());
+    Ok(snapshots)
+}
+
+// Function to put the global config back the way it was before the last mutating operation
+pub fn undo() -> Result<Option<Snapshot>, GitError> {
+    let config = global_config_path();
+    let current = if config.exists() {
+        fs::read_to_string(&config)?
+    } else {
+        String::new()
+    };
+
+    // Skip snapshots of operations that didn't end up changing anything
+    for snapshot in list_snapshots()?.into_iter().rev() {
+        let contents = fs::read_to_string(&snapshot.path)?;
+        if contents != current {
+            write_file(&config, &contents)?;
+            remove_file(&snapshot.path)?;
+            return Ok(Some(snapshot));
+        }
+        remove_file(&snapshot.path)?;
+    }
+    Ok(None)
+}
+
+// Function to find a snapshot by the id shown in `gitup history list`
+pub fn find_snapshot(id: &str) -> Result<Snapshot, GitError> {
+    list_snapshots()?
+        .into_iter()
+        .find(|snapshot| snapshot.id == id)
+        .ok_or_else(|| {
+            GitError::InvalidInput(format!("no snapshot with id '{}' (see `gitup history list`)", id))
+        })
+}
+
+// Function to put the global config back the way it was in a snapshot. Unlike `undo`, the
+// snapshots are all kept, so a rollback can itself be rolled back
+pub fn rollback(id: &str) -> Result<Snapshot, GitError> {
+    let snapshot = find_snapshot(id)?;
+    let contents = fs::read_to_string(&snapshot.path)?;
+    write_file(&global_config_path(), &contents)?;
+    Ok(snapshot)
+}
+
+fn file_name(id: u64, reason: &str) -> String {
+    format!("{}-{}.gitconfig", id, reason)
+}
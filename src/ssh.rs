@@ -0,0 +1,405 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::auth::Provider;
+use crate::dry_run::{create_dir_all, write_file};
+use crate::git::{run_checked, run_command, run_mutating};
+use crate::managed_block::upsert_block;
+use crate::lock::lock_config;
+use crate::profile::{get_profile, load_profiles, save_profiles};
+use crate::{home_dir, GitError};
+
+// SSH key algorithms supported by ssh-keygen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    Ed25519,
+    Ecdsa,
+    Rsa,
+}
+
+impl KeyType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyType::Ed25519 => "ed25519",
+            KeyType::Ecdsa => "ecdsa",
+            KeyType::Rsa => "rsa",
+        }
+    }
+}
+
+impl fmt::Display for KeyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for KeyType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ed25519" => Ok(KeyType::Ed25519),
+            "ecdsa" => Ok(KeyType::Ecdsa),
+            "rsa" => Ok(KeyType::Rsa),
+            other => Err(format!("unsupported key type '{}' (expected ed25519, ecdsa or rsa)", other)),
+        }
+    }
+}
+
+// Function to get the user's ~/.ssh directory
+pub fn ssh_dir() -> PathBuf {
+    home_dir().join(".ssh")
+}
+
+// Function to get the public half of a private key file
+pub fn public_key_path(key: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.pub", key.display()))
+}
+
+// Function to read an SSH public key as "<type> <base64 key>", from the .pub file or the private
+// key next to it
+pub fn read_public_key(key: &Path) -> Result<String, GitError> {
+    let key = if key.extension().is_some_and(|extension| extension == "pub") {
+        key.to_path_buf()
+    } else {
+        public_key_path(key)
+    };
+    let public_key = fs::read_to_string(&key)
+        .map_err(|e| GitError::InvalidInput(format!("can't read the public key {}: {}", key.display(), e)))?;
+    // <type> <base64 key> [comment]
+    match public_key.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [kind, data, ..] if kind.starts_with("ssh-") || kind.starts_with("ecdsa-") || kind.starts_with("sk-") => {
+            Ok(format!("{} {}", kind, data))
+        }
+        _ => Err(GitError::InvalidInput(format!("{} is not an SSH public key", key.display()))),
+    }
+}
+
+// Function to generate a new SSH key for a profile and record it in the profile
+pub fn generate_ssh_key(profile_name: &str, key_type: KeyType) -> Result<PathBuf, GitError> {
+    // The email may be inherited, but the key is recorded in the profile itself
+    let email = get_profile(profile_name)?.email;
+    let _lock = lock_config()?;
+    let mut profiles = load_profiles()?;
+    let profile = profiles
+        .profiles
+        .get_mut(profile_name)
+        .ok_or_else(|| GitError::ProfileNotFound(profile_name.to_string()))?;
+
+    let dir = ssh_dir();
+    create_dir_all(&dir)?;
+    let path = dir.join(format!("id_{}_{}", key_type, profile_name));
+    if path.exists() {
+        return Err(GitError::Io(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("SSH key {} already exists", path.display()),
+        )));
+    }
+
+    let path_str = path.to_string_lossy();
+    let mut args = vec!["-t", key_type.as_str(), "-C", &email, "-f", &path_str, "-N", ""];
+    if key_type == KeyType::Rsa {
+        args.extend(["-b", "4096"]);
+    }
+    run_mutating("ssh-keygen", &args)?;
+
+    profile.ssh_key = Some(path.clone());
+    save_profiles(&profiles)?;
+    Ok(path)
+}
+
+// Function to list private keys in ~/.ssh that have a matching .pub file
+pub fn list_ssh_keys() -> Result<Vec<PathBuf>, GitError> {
+    let dir = ssh_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut keys = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "pub") {
+            let private = path.with_extension("");
+            if private.exists() {
+                keys.push(private);
+            }
+        }
+    }
+    keys.sort();
+    Ok(keys)
+}
+
+// Function to add a private key to the running ssh-agent
+pub fn add_to_agent(key: &Path) -> Result<(), GitError> {
+    run_mutating("ssh-add", &[&key.to_string_lossy()])
+}
+
+// Function to add (or replace) a Host block in ~/.ssh/config that uses a profile's key,
+// returning the alias to use in place of the real host name
+pub fn write_host_alias(profile_name: &str, host: &str) -> Result<String, GitError> {
+    let profile = get_profile(profile_name)?;
+    let key = profile.ssh_key.ok_or_else(|| {
+        GitError::InvalidInput(format!("profile '{}' has no SSH key", profile_name))
+    })?;
+
+    let alias = format!("{}-{}", host, profile_name);
+    let block = format!(
+        "Host {}\n    HostName {}\n    User git\n    IdentityFile {}\n    IdentitiesOnly yes\n",
+        alias,
+        host,
+        key.display()
+    );
+
+    let path = ssh_dir().join("config");
+    let existing = if path.exists() {
+        fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+
+    // Replace a block written earlier for the same alias, otherwise append
+    let contents = upsert_block(&existing, &alias, &block);
+
+    create_dir_all(&ssh_dir())?;
+    write_file(&path, &contents)?;
+    Ok(alias)
+}
+
+// Function to point a repository remote at a host alias, returning the new URL
+pub fn rewrite_remote(repo: &Path, remote: &str, host: &str, alias: &str) -> Result<String, GitError> {
+    let repo = repo.to_string_lossy();
+    let output = run_checked("git", &["-C", &repo, "remote", "get-url", remote])?;
+    let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    let new_url = alias_url(&url, host, alias).ok_or_else(|| {
+        GitError::InvalidInput(format!("remote '{}' ({}) is not hosted on {}", remote, url, host))
+    })?;
+    run_mutating("git", &["-C", &repo, "remote", "set-url", remote, &new_url])?;
+    Ok(new_url)
+}
+
+// Rewrite an SSH or HTTPS remote URL for `host` into the scp-like form using `alias`
+pub(crate) fn alias_url(url: &str, host: &str, alias: &str) -> Option<String> {
+    let path = if let Some(rest) = url.strip_prefix("git@") {
+        rest.strip_prefix(host)?.strip_prefix(':')?
+    } else {
+        let rest = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("ssh://git@"))?;
+        rest.strip_prefix(host)?.strip_prefix('/')?
+    };
+    Some(format!("git@{}:{}", alias, path))
+}
+
+// What `check_ssh_connection` found
+#[derive(Debug, Clone)]
+pub struct SshConnection {
+    pub host: String,
+    pub authenticated: bool,
+    // The account the host recognised the key as, when it says
+    pub username: Option<String>,
+    // What the host said, or why the connection failed
+    pub message: String,
+}
+
+// Function to check that a Git host accepts an SSH key with `ssh -T git@<host>`, only using `key`
+// (a private key) when given. Hosts refuse a shell and exit with an error even when the key is
+// accepted, so their greeting is read instead of the exit code
+pub fn check_ssh_connection(host: &str, key: Option<&Path>) -> Result<SshConnection, GitError> {
+    let key = key.map(|key| key.to_string_lossy().into_owned());
+    let target = format!("git@{}", host);
+    // BatchMode fails instead of asking for a passphrase or password; a first connection trusts
+    // the host key, as a first clone would after asking
+    let mut args = vec![
+        "-T",
+        "-o",
+        "BatchMode=yes",
+        "-o",
+        "StrictHostKeyChecking=accept-new",
+        "-o",
+        "ConnectTimeout=15",
+    ];
+    if let Some(key) = &key {
+        args.extend(["-i", key, "-o", "IdentitiesOnly=yes"]);
+    }
+    args.push(&target);
+    let output = run_command("ssh", &args).map_err(|e| GitError::spawn_failed("ssh", e))?;
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stderr),
+        String::from_utf8_lossy(&output.stdout)
+    );
+    Ok(parse_greeting(host, &text))
+}
+
+// Read the outcome of `ssh -T` from what the host and ssh printed
+fn parse_greeting(host: &str, text: &str) -> SshConnection {
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    if let Some((line, username)) = lines.iter().find_map(|line| greeting(line).map(|username| (line, username))) {
+        return SshConnection {
+            host: host.to_string(),
+            authenticated: true,
+            username,
+            message: line.to_string(),
+        };
+    }
+
+    let message = if text.contains("Permission denied") {
+        format!("{} did not accept the key (Permission denied)", host)
+    } else if text.contains("Host key verification failed") || text.contains("REMOTE HOST IDENTIFICATION HAS CHANGED") {
+        format!("the host key of {} doesn't match the one in ~/.ssh/known_hosts", host)
+    } else {
+        lines
+            .last()
+            .map_or_else(|| format!("no answer from {}", host), |line| line.to_string())
+    };
+    SshConnection {
+        host: host.to_string(),
+        authenticated: false,
+        username: None,
+        message,
+    }
+}
+
+// The user name in a host's greeting, or Some(None) for a greeting without one:
+// GitHub: "Hi <user>! You've successfully authenticated, but GitHub does not provide shell access."
+// GitLab: "Welcome to GitLab, @<user>!"
+// Bitbucket: "authenticated via ssh key." or, from older servers, "logged in as <user>."
+fn greeting(line: &str) -> Option<Option<String>> {
+    let github = line.strip_prefix("Hi ").and_then(|rest| rest.split_once("! You've successfully authenticated"));
+    if let Some((username, _)) = github {
+        return Some(Some(username.to_string()));
+    }
+    if let Some(rest) = line.strip_prefix("Welcome to GitLab, ") {
+        return Some(Some(rest.trim_start_matches('@').trim_end_matches('!').to_string()));
+    }
+    if let Some((_, username)) = line.split_once("logged in as ") {
+        return Some(Some(username.trim_end_matches('.').to_string()));
+    }
+    line.contains("authenticated via ssh key").then_some(None)
+}
+
+// The host keys `trust_host_keys` pinned for a provider
+#[derive(Debug, Clone)]
+pub struct TrustedHost {
+    pub host: String,
+    // "<type> <SHA256 fingerprint>" of each key pinned
+    pub keys: Vec<String>,
+    // Keys the provider published whose fingerprints gitup doesn't know, which were left out
+    pub skipped: Vec<String>,
+    pub known_hosts: PathBuf,
+}
+
+// Function to pin a provider's SSH host keys in ~/.ssh/known_hosts, so the first clone over SSH
+// doesn't stop to ask whether to trust the host. The keys are fetched from where the provider
+// publishes them (GitHub's meta API, Bitbucket's known_hosts page, and ssh-keyscan for GitLab,
+// which has neither) and only pinned when their fingerprint is one gitup ships with, so a
+// tampered download or an impostor host can't slip in a key. They replace the host's earlier
+// entries, which may be keys the provider has since rotated out
+pub fn trust_host_keys(provider: Provider) -> Result<TrustedHost, GitError> {
+    let host = provider.default_host();
+    let pinned = pinned_fingerprints(provider);
+    let mut keys = Vec::new();
+    let mut skipped = Vec::new();
+    let mut lines = String::new();
+    for key in fetch_host_keys(provider)? {
+        let Some(fingerprint) = fingerprint(&key) else {
+            continue;
+        };
+        let kind = key.split_whitespace().next().unwrap_or_default();
+        if pinned.contains(&fingerprint.as_str()) {
+            keys.push(format!("{} {}", kind, fingerprint));
+            lines.push_str(&format!("{} {}\n", host, key));
+        } else {
+            skipped.push(format!("{} {}", kind, fingerprint));
+        }
+    }
+    if keys.is_empty() {
+        return Err(GitError::InvalidInput(format!(
+            "none of the host keys published for {} match the fingerprints gitup knows; compare them with the fingerprints {} documents before trusting them",
+            host, host
+        )));
+    }
+
+    let path = ssh_dir().join("known_hosts");
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+    // Plain entries for the host outside gitup's block; hashed ones can't be told apart
+    let existing: String = existing
+        .lines()
+        .filter(|line| !line.split_whitespace().next().is_some_and(|hosts| hosts.split(',').any(|name| name == host)))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    create_dir_all(&ssh_dir())?;
+    write_file(&path, &upsert_block(&existing, host, &lines))?;
+    Ok(TrustedHost {
+        host: host.to_string(),
+        keys,
+        skipped,
+        known_hosts: path,
+    })
+}
+
+// The providers' host key fingerprints, as they publish them
+fn pinned_fingerprints(provider: Provider) -> &'static [&'static str] {
+    match provider {
+        Provider::GitHub => &[
+            "SHA256:+DiY3wvvV6TuJJhbpZisF/zLDA0zPMSvHdkr4UvCOqU",
+            "SHA256:p2QAMXNIC1TJYWeIOttrVc98/R1BUFWu3/LiyKgUfQM",
+            "SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s",
+        ],
+        Provider::GitLab => &[
+            "SHA256:eUXGGm1YGsMAS7vkcx6JOJdOGHPem5gQp4taiCfCLB8",
+            "SHA256:HbW3g8zUjNSksFbqTiUWPWg2Bq1x8xdGUrliXFzSnUw",
+            "SHA256:ROQFvPThGrW4RuWLoL9tq9I9zJ42fK4XywyRtbOz/EQ",
+        ],
+        Provider::Bitbucket => &[
+            "SHA256:ybgmFkzwOSotHTHLJgHO0QN8L0xErw6vd0VhFA9m3SM",
+            "SHA256:FC73VB6C4OQLSCrjEayhMp9UMxS97caD/Yyi2bhW/J0",
+            "SHA256:46OSHA1Rmj8E8ERTC6xkNcmGOw9oFxYr0WF6zWW8l1E",
+        ],
+    }
+}
+
+// A provider's host keys as "<type> <base64 key>"
+fn fetch_host_keys(provider: Provider) -> Result<Vec<String>, GitError> {
+    let curl = |url: &str| -> Result<String, GitError> {
+        let output = run_checked("curl", &["--silent", "--show-error", "--fail", "--location", url])?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    let text = match provider {
+        Provider::GitHub => {
+            let meta: Value = serde_json::from_str(&curl("https://api.github.com/meta")?)
+                .map_err(|e| GitError::Parse(format!("GitHub's meta API: {}", e)))?;
+            let keys = meta["ssh_keys"].as_array().into_iter().flatten().filter_map(Value::as_str);
+            return Ok(keys.map(str::to_string).collect());
+        }
+        Provider::GitLab => {
+            let host = provider.default_host();
+            String::from_utf8_lossy(&run_checked("ssh-keyscan", &["-t", "rsa,ecdsa,ed25519", host])?.stdout).into_owned()
+        }
+        Provider::Bitbucket => curl("https://bitbucket.org/site/ssh")?,
+    };
+    // known_hosts lines: <host> <type> <base64 key>
+    Ok(text
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [_, kind, data, ..] => Some(format!("{} {}", kind, data)),
+            _ => None,
+        })
+        .collect())
+}
+
+// The SHA-256 fingerprint of a "<type> <base64 key>" public key, as `ssh-keygen -l` shows it
+fn fingerprint(key: &str) -> Option<String> {
+    let blob = STANDARD.decode(key.split_whitespace().nth(1)?).ok()?;
+    Some(format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(&blob))))
+}
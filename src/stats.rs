@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::dry_run::{remove_file, write_file, DryRun};
+use crate::git::repository_root;
+use crate::{config_dir, GitError};
+
+// Usage is only recorded while this file exists; nothing is ever sent anywhere
+const STATS_FILE: &str = "stats.jsonl";
+
+// Something gitup saw happen
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum StatsEvent {
+    // A profile was used; `repo` is set when it was applied to a single repository
+    Switch {
+        profile: String,
+        scope: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        repo: Option<PathBuf>,
+    },
+    // The identity guard checked a commit; `matched` is false when it stopped one made with
+    // another profile's email
+    Commit {
+        repo: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        profile: Option<String>,
+        matched: bool,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Record {
+    // Seconds since the Unix epoch
+    time: u64,
+    #[serde(flatten)]
+    event: StatsEvent,
+}
+
+// A profile's use over the period of a report
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProfileUsage {
+    pub switches: usize,
+    // Repositories it was applied to on their own
+    pub repositories: Vec<PathBuf>,
+    pub commits: usize,
+    // Commits the identity guard stopped because they'd have been made with another email
+    pub mismatches: usize,
+    pub last_used: Option<u64>,
+}
+
+// What the recorded usage adds up to since `since`
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsReport {
+    pub since: u64,
+    pub switches: usize,
+    pub commits: usize,
+    pub mismatches: usize,
+    // By profile; commits in repositories no profile is meant for are counted in the totals only
+    pub profiles: BTreeMap<String, ProfileUsage>,
+    // Repositories with a mismatch, and how many
+    pub mismatched_repositories: BTreeMap<PathBuf, usize>,
+}
+
+// Function to get the file usage is recorded in
+pub fn stats_path() -> PathBuf {
+    config_dir().join(STATS_FILE)
+}
+
+// Function to check whether usage is being recorded
+pub fn stats_enabled() -> bool {
+    stats_path().exists()
+}
+
+// Function to start recording usage
+pub fn enable_stats() -> Result<PathBuf, GitError> {
+    let path = stats_path();
+    if !path.exists() {
+        fs::create_dir_all(config_dir())?;
+        write_file(&path, "")?;
+    }
+    Ok(path)
+}
+
+// Function to stop recording usage, deleting what was recorded
+pub fn disable_stats() -> Result<(), GitError> {
+    let path = stats_path();
+    if path.exists() {
+        remove_file(&path)?;
+    }
+    Ok(())
+}
+
+// Function to record an event, if recording is on. Statistics are only a convenience, so a
+// failure to write them is ignored rather than failing the command that caused the event
+pub fn record(event: StatsEvent) {
+    let path = stats_path();
+    if DryRun::is_enabled() || !path.exists() {
+        return;
+    }
+    let event = match event {
+        // Nested directories of one repository count as the repository
+        StatsEvent::Switch { profile, scope, repo } => StatsEvent::Switch {
+            profile,
+            scope,
+            repo: repo.map(|repo| canonical_repo(&repo)),
+        },
+        StatsEvent::Commit { repo, profile, matched } => StatsEvent::Commit {
+            repo: canonical_repo(&repo),
+            profile,
+            matched,
+        },
+    };
+    let record = Record { time: now(), event };
+    if let Ok(line) = serde_json::to_string(&record) {
+        let _ = OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+    }
+}
+
+// Function to sum up the usage recorded over the last `days` days
+pub fn stats_report(days: u64) -> Result<StatsReport, GitError> {
+    let path = stats_path();
+    if !path.exists() {
+        return Err(GitError::InvalidInput(
+            "usage isn't being recorded; start with `gitup stats enable`".to_string(),
+        ));
+    }
+    let since = now().saturating_sub(days * 86_400);
+    let mut report = StatsReport {
+        since,
+        switches: 0,
+        commits: 0,
+        mismatches: 0,
+        profiles: BTreeMap::new(),
+        mismatched_repositories: BTreeMap::new(),
+    };
+    let contents = fs::read_to_string(&path)?;
+    // A line cut short by a full disk, or from a newer gitup, is skipped
+    let records = contents.lines().filter_map(|line| serde_json::from_str::<Record>(line).ok());
+    for record in records.filter(|record| record.time >= since) {
+        match record.event {
+            StatsEvent::Switch { profile, repo, .. } => {
+                report.switches += 1;
+                let usage = report.profiles.entry(profile).or_default();
+                usage.switches += 1;
+                usage.last_used = Some(record.time);
+                if let Some(repo) = repo.filter(|repo| !usage.repositories.contains(repo)) {
+                    usage.repositories.push(repo);
+                }
+            }
+            StatsEvent::Commit { repo, profile, matched } => {
+                report.commits += 1;
+                if !matched {
+                    report.mismatches += 1;
+                    *report.mismatched_repositories.entry(repo).or_default() += 1;
+                }
+                if let Some(profile) = profile {
+                    let usage = report.profiles.entry(profile).or_default();
+                    usage.commits += 1;
+                    usage.mismatches += usize::from(!matched);
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn canonical_repo(dir: &Path) -> PathBuf {
+    repository_root(dir)
+        .ok()
+        .or_else(|| fs::canonicalize(dir).ok())
+        .unwrap_or_else(|| dir.to_path_buf())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,136 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::git::run_checked;
+use crate::profile::list_profiles;
+use crate::GitError;
+
+// The profile a repository's remote URL points to
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    pub profile: String,
+    pub remote: String,
+    pub url: String,
+    // The profile pattern that matched; None when the URL uses the profile's SSH host alias
+    pub pattern: Option<String>,
+}
+
+// Function to find the profile for a repository from its remotes, checking origin first
+pub fn suggest_profile(repo: &Path) -> Result<Option<Suggestion>, GitError> {
+    let dir = repo.to_string_lossy();
+    let output = run_checked("git", &["-C", &dir, "remote"])?;
+    let mut remotes: Vec<String> = String::from_utf8_lossy(&output.stdout).lines().map(String::from).collect();
+    remotes.sort_by_key(|remote| remote != "origin");
+
+    for remote in remotes {
+        let output = run_checked("git", &["-C", &dir, "remote", "get-url", &remote])?;
+        let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if let Some((profile, pattern)) = profile_for_url(&url)? {
+            return Ok(Some(Suggestion {
+                profile,
+                remote,
+                url,
+                pattern,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+// Function to find the profile for a remote URL, along with the pattern that matched. A host
+// alias written by `gitup ssh host` (github.com-work) names its profile outright; otherwise
+// the most specific matching pattern wins
+pub fn profile_for_url(url: &str) -> Result<Option<(String, Option<String>)>, GitError> {
+    let normalized = normalize_remote_url(url);
+    let host = normalized.split('/').next().unwrap_or_default();
+    let profiles = list_profiles()?;
+
+    let alias_of = |name: &str| {
+        host.strip_suffix(&format!("-{}", name.to_lowercase()))
+            .is_some_and(|real_host| real_host.contains('.'))
+    };
+    if let Some((name, _)) = profiles.iter().find(|(name, _)| alias_of(name)) {
+        return Ok(Some((name.clone(), None)));
+    }
+    Ok(profiles
+        .into_iter()
+        .flat_map(|(name, profile)| profile.remotes.into_iter().map(move |pattern| (name.clone(), pattern)))
+        .filter(|(_, pattern)| glob_match(&normalize_remote_url(pattern), &normalized))
+        .max_by_key(|(_, pattern)| pattern.len())
+        .map(|(name, pattern)| (name, Some(pattern))))
+}
+
+// Function to reduce the URL forms git accepts to host/path, lowercased, so
+// git@github.com:Corp/api.git, ssh://git@github.com:22/corp/api and
+// https://user@github.com/corp/api/ all become github.com/corp/api
+pub fn normalize_remote_url(url: &str) -> String {
+    let url = url.trim().to_lowercase();
+    let (rest, scp_like) = match url.split_once("://") {
+        Some((_, rest)) => (rest.to_string(), false),
+        None => (url.clone(), true),
+    };
+    // Drop user@ in front of the host
+    let rest = match rest.split_once('@') {
+        Some((user, host_path)) if !user.contains('/') => host_path.to_string(),
+        _ => rest,
+    };
+    let (host, path) = if scp_like {
+        rest.split_once(':').unwrap_or((&rest, ""))
+    } else {
+        rest.split_once('/').unwrap_or((&rest, ""))
+    };
+    let host = host.split(':').next().unwrap_or(host);
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    if path.is_empty() {
+        host.to_string()
+    } else {
+        format!("{}/{}", host, path)
+    }
+}
+
+// '*' matches any run of characters, '/' included, so github.com/corp/* also covers GitLab subgroups
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    // Where the last '*' was and how much text it has swallowed so far
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_urls_normalize_to_host_and_path() {
+        for url in [
+            "git@github.com:Corp/api.git",
+            "ssh://git@github.com:22/corp/api",
+            "https://user@github.com/corp/api/",
+            "https://github.com/corp/api.git",
+            "  HTTPS://GitHub.com/Corp/API  ",
+            "github.com:corp/api",
+        ] {
+            assert_eq!(normalize_remote_url(url), "github.com/corp/api", "{}", url);
+        }
+        assert_eq!(normalize_remote_url("https://gitlab.com/group/sub/repo.git"), "gitlab.com/group/sub/repo");
+        assert_eq!(normalize_remote_url("https://example.com"), "example.com");
+    }
+}
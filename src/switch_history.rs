@@ -0,0 +1,142 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::dry_run::{write_file, DryRun};
+use crate::git::{repository_root, Scope};
+use crate::{config_dir, GitError};
+
+const HISTORY_FILE: &str = "switches.jsonl";
+
+// A profile being applied to a config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Switch {
+    // Seconds since the Unix epoch
+    pub time: u64,
+    pub profile: String,
+    // The profile the config's identity matched before, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<String>,
+    pub scope: String,
+    // The repository (or worktree) of a local or worktree switch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo: Option<PathBuf>,
+}
+
+// Function to get the file profile switches are logged in
+pub fn history_path() -> PathBuf {
+    config_dir().join(HISTORY_FILE)
+}
+
+// Function to log a profile switch. The log only serves `previous_profile`, so failing to
+// write it doesn't fail the switch
+pub fn record_switch(profile: &str, previous: Option<&str>, scope: &Scope) {
+    if DryRun::is_enabled() {
+        return;
+    }
+    let switch = Switch {
+        time: now(),
+        profile: profile.to_string(),
+        previous: previous.map(str::to_string),
+        scope: scope.name().to_string(),
+        repo: scope.repo().map(canonical_repo),
+    };
+    if let Ok(line) = serde_json::to_string(&switch) {
+        let _ = fs::create_dir_all(config_dir()).and_then(|()| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(history_path())
+                .and_then(|mut file| writeln!(file, "{}", line))
+        });
+    }
+}
+
+// Function to read the logged switches, oldest first; with a scope, only the switches of that
+// config (the same repository for local and worktree scopes)
+pub fn switch_history(scope: Option<&Scope>) -> Result<Vec<Switch>, GitError> {
+    let path = history_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let repo = scope.and_then(Scope::repo).map(canonical_repo);
+    let contents = fs::read_to_string(&path)?;
+    // A line cut short by a full disk, or from a newer gitup, is skipped
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Switch>(line).ok())
+        .filter(|switch| scope.is_none_or(|scope| switch.scope == scope.name() && switch.repo == repo))
+        .collect())
+}
+
+// Function to rewrite the logged switches to and from a renamed profile, so `previous_profile`
+// still finds it
+pub fn rename_in_history(old_name: &str, new_name: &str) -> Result<(), GitError> {
+    let path = history_path();
+    if !path.exists() {
+        return Ok(());
+    }
+    let rename = |profile: &mut String| {
+        if profile == old_name {
+            *profile = new_name.to_string();
+        }
+    };
+    let mut contents = String::new();
+    for line in fs::read_to_string(&path)?.lines() {
+        // Lines this version can't read are kept as they are
+        let Ok(mut switch) = serde_json::from_str::<Switch>(line) else {
+            contents.push_str(line);
+            contents.push('\n');
+            continue;
+        };
+        rename(&mut switch.profile);
+        if let Some(previous) = &mut switch.previous {
+            rename(previous);
+        }
+        contents.push_str(&serde_json::to_string(&switch).map_err(|e| GitError::Parse(e.to_string()))?);
+        contents.push('\n');
+    }
+    write_file(&path, &contents)
+}
+
+// Function to find the profile a config used before its last switch, for `profile use -`: the
+// profile it was switched from, or else the most recent earlier switch to another profile
+pub fn previous_profile(scope: &Scope) -> Result<String, GitError> {
+    let history = switch_history(Some(scope))?;
+    let Some((last, earlier)) = history.split_last() else {
+        return Err(GitError::InvalidInput(format!(
+            "no profile has been switched to in the {} config yet",
+            scope.name()
+        )));
+    };
+    last.previous
+        .iter()
+        .chain(earlier.iter().rev().map(|switch| &switch.profile))
+        .find(|profile| **profile != last.profile)
+        .cloned()
+        .ok_or_else(|| {
+            GitError::InvalidInput(format!(
+                "the {} config has only used profile '{}' so far",
+                scope.name(),
+                last.profile
+            ))
+        })
+}
+
+// Nested directories of one repository count as the repository
+fn canonical_repo(dir: &Path) -> PathBuf {
+    repository_root(dir)
+        .ok()
+        .or_else(|| fs::canonicalize(dir).ok())
+        .unwrap_or_else(|| dir.to_path_buf())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,267 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::file::ConfigFile;
+use crate::encryption::{decrypt, is_encrypted, passphrase};
+use crate::git::{run_checked, run_git, run_mutating};
+use crate::profile::{load_profiles, parse_profiles, profiles_path, Profiles};
+use crate::validate::is_unsafe_setting;
+use crate::{home_dir, config_dir, GitError};
+
+// Branch gitup state is kept on in the sync repository
+const BRANCH: &str = "main";
+
+// What `sync_push` or `sync_pull` did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncOutcome {
+    UpToDate,
+    Pushed { files: Vec<String> },
+    Pulled { files: Vec<String> },
+}
+
+// Function to get the bare repository that tracks gitup's files, with the home directory as work tree
+pub fn sync_repo() -> PathBuf {
+    config_dir().join("sync.git")
+}
+
+// Function to start syncing with a remote repository; local files are left alone until the
+// first pull merges them with whatever the remote already has
+pub fn sync_init(url: &str) -> Result<(), GitError> {
+    let repo = sync_repo();
+    if repo.exists() {
+        return Err(GitError::InvalidInput(format!(
+            "sync is already set up in {}",
+            repo.display()
+        )));
+    }
+
+    let repo_str = repo.to_string_lossy().into_owned();
+    run_mutating("git", &["init", "--quiet", "--bare", &repo_str])?;
+    git_mutating(&["config", "core.bare", "false"])?;
+    git_mutating(&["config", "core.worktree", &home_dir().to_string_lossy()])?;
+    // Everything else in the home directory is none of this repository's business
+    git_mutating(&["config", "status.showUntrackedFiles", "no"])?;
+    git_mutating(&["symbolic-ref", "HEAD", &format!("refs/heads/{}", BRANCH)])?;
+    git_mutating(&["remote", "add", "origin", url])?;
+    git_mutating(&["fetch", "--quiet", "origin"])
+}
+
+// Function to commit local gitup state and push it, refusing when the remote has newer changes
+pub fn sync_push() -> Result<SyncOutcome, GitError> {
+    ensure_initialized()?;
+    commit_local("gitup sync push")?;
+
+    git_mutating(&["fetch", "--quiet", "origin"])?;
+    let upstream = format!("origin/{}", BRANCH);
+    let files = if remote_branch_exists()? {
+        if !has_commits()? || !git_output(&["rev-list", &format!("HEAD..{}", upstream)])?.is_empty() {
+            return Err(GitError::Conflict(
+                "the remote has changes that aren't here yet; run `gitup sync pull` first".to_string(),
+            ));
+        }
+        if git_output(&["rev-list", &format!("{}..HEAD", upstream)])?.is_empty() {
+            return Ok(SyncOutcome::UpToDate);
+        }
+        lines(&git_output(&["diff", "--name-only", &upstream, "HEAD"])?)
+    } else if has_commits()? {
+        lines(&git_output(&["ls-files"])?)
+    } else {
+        return Ok(SyncOutcome::UpToDate);
+    };
+    git_mutating(&["push", "--quiet", "origin", &format!("HEAD:refs/heads/{}", BRANCH)])?;
+    Ok(SyncOutcome::Pushed { files })
+}
+
+// Function to merge remote gitup state into the local files; with `force` the remote
+// side wins conflicts instead of aborting. Whoever can push to the remote could make git run
+// their commands here through shared hooks or settings, so remote changes to hook scripts, or
+// that add settings that run commands, are refused unless `allow_unsafe` is set
+pub fn sync_pull(force: bool, allow_unsafe: bool) -> Result<SyncOutcome, GitError> {
+    ensure_initialized()?;
+    git_mutating(&["fetch", "--quiet", "origin"])?;
+    if !remote_branch_exists()? {
+        return Ok(SyncOutcome::UpToDate);
+    }
+    let upstream = format!("origin/{}", BRANCH);
+    if has_commits()? && git_output(&["rev-list", &format!("HEAD..{}", upstream)])?.is_empty() {
+        return Ok(SyncOutcome::UpToDate);
+    }
+
+    commit_local("gitup sync pull")?;
+    let had_commits = has_commits()?;
+    if !allow_unsafe {
+        let unsafe_changes = unsafe_changes(&upstream)?;
+        if !unsafe_changes.is_empty() {
+            return Err(GitError::InvalidInput(format!(
+                "the remote changes could run commands on this machine: {}; check them and re-run with --allow-unsafe",
+                unsafe_changes.join(", ")
+            )));
+        }
+    }
+    let mut args = vec!["merge", "--quiet", "--no-edit", "--allow-unrelated-histories"];
+    if force {
+        args.extend(["-X", "theirs"]);
+    }
+    args.push(&upstream);
+    if let Err(e) = git_mutating(&args) {
+        let conflicts = git_output(&["diff", "--name-only", "--diff-filter=U"]).unwrap_or_default();
+        if conflicts.is_empty() {
+            return Err(e);
+        }
+        git_mutating(&["merge", "--abort"])?;
+        return Err(GitError::Conflict(format!(
+            "local and remote both changed {} (re-run with --force to take the remote version)",
+            lines(&conflicts).join(", ")
+        )));
+    }
+    // ORIG_HEAD is where the branch was before the merge; a first pull into an empty
+    // branch has none and brings in every file
+    let files = if had_commits {
+        git_output(&["diff", "--name-only", "ORIG_HEAD", "HEAD"])
+    } else {
+        git_output(&["ls-files"])
+    };
+    Ok(SyncOutcome::Pulled { files: lines(&files.unwrap_or_default()) })
+}
+
+// What the remote brings in that could run commands here: changed hook scripts, and settings
+// `is_unsafe_setting` flags in config fragments and profiles that the local version doesn't
+// already have
+fn unsafe_changes(upstream: &str) -> Result<Vec<String>, GitError> {
+    // Only the remote side's changes since the histories split; all of it when they never met
+    let base = has_commits()?
+        .then(|| git_output(&["merge-base", "HEAD", upstream]).ok())
+        .flatten()
+        .filter(|base| !base.is_empty());
+    let changed = match &base {
+        Some(base) => git_output(&["diff", "--name-only", "--diff-filter=d", base, upstream])?,
+        None => git_output(&["ls-tree", "-r", "--name-only", upstream])?,
+    };
+    let home = home_dir();
+    let relative = |path: PathBuf| path.strip_prefix(&home).map(Path::to_path_buf).unwrap_or(path);
+    let hooks = relative(config_dir().join("hooks"));
+    let profiles = relative(profiles_path());
+
+    let mut found = Vec::new();
+    for file in lines(&changed) {
+        let path = Path::new(&file);
+        if path.starts_with(&hooks) {
+            found.push(format!("hook {}", file));
+        } else if path == profiles {
+            let remote = read_profiles(&git_output(&["show", &format!("{}:{}", upstream, file)])?)?;
+            let local = load_profiles()?;
+            for (name, profile) in &remote.profiles {
+                let known = local.profiles.get(name).map(|profile| profile.settings()).unwrap_or_default();
+                for (key, value) in profile.settings() {
+                    if is_unsafe_setting(&key, &value) && !known.contains(&(key.clone(), value)) {
+                        found.push(format!("{} in profile '{}'", key, name));
+                    }
+                }
+            }
+        } else if path.extension().is_some_and(|extension| extension == "gitconfig") {
+            let remote = ConfigFile::parse(&git_output(&["show", &format!("{}:{}", upstream, file)])?);
+            let local = ConfigFile::parse(&git_output(&["show", &format!("HEAD:{}", file)]).unwrap_or_default());
+            let known = local.entries();
+            for (key, value) in remote.entries() {
+                if is_unsafe_setting(&key, &value) && !known.contains(&(key.clone(), value)) {
+                    found.push(format!("{} in {}", key, file));
+                }
+            }
+        }
+    }
+    Ok(found)
+}
+
+// A profiles file as pushed, decrypted with the same passphrase as the local one
+fn read_profiles(contents: &str) -> Result<Profiles, GitError> {
+    if is_encrypted(contents) {
+        return parse_profiles(&decrypt(contents, &passphrase()?)?);
+    }
+    parse_profiles(contents)
+}
+
+// Files under the home directory that are synced: profiles, per-profile config
+// fragments, installed templates and shared hooks
+fn synced_paths() -> Result<Vec<PathBuf>, GitError> {
+    let state = config_dir();
+    let mut paths = vec![profiles_path(), state.join("templates"), state.join("hooks")];
+    if state.is_dir() {
+        for entry in fs::read_dir(&state)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "gitconfig") {
+                paths.push(path);
+            }
+        }
+    }
+    Ok(paths.into_iter().filter(|path| path.exists()).collect())
+}
+
+// Stage and commit changes to synced files, returning the files that changed
+fn commit_local(message: &str) -> Result<Vec<String>, GitError> {
+    // git rejects pathspecs that match nothing, so only existing files are named;
+    // files that are already tracked are added too so their deletion is picked up
+    let home = home_dir();
+    let mut pathspecs: BTreeSet<String> = lines(&git_output(&["ls-files"])?).into_iter().collect();
+    for path in synced_paths()? {
+        let relative = path.strip_prefix(&home).unwrap_or(&path);
+        pathspecs.insert(relative.to_string_lossy().into_owned());
+    }
+    if pathspecs.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut args = vec!["add", "--all", "--"];
+    args.extend(pathspecs.iter().map(String::as_str));
+    git_mutating(&args)?;
+
+    let staged = lines(&git_output(&["diff", "--cached", "--name-only"])?);
+    if !staged.is_empty() {
+        git_mutating(&["commit", "--quiet", "-m", message])?;
+    }
+    Ok(staged)
+}
+
+fn ensure_initialized() -> Result<(), GitError> {
+    if sync_repo().exists() {
+        Ok(())
+    } else {
+        Err(GitError::InvalidInput(
+            "sync is not set up; run `gitup sync init <url>` first".to_string(),
+        ))
+    }
+}
+
+fn remote_branch_exists() -> Result<bool, GitError> {
+    let output = git(&["rev-parse", "--verify", "--quiet", &format!("refs/remotes/origin/{}", BRANCH)])?;
+    Ok(output.status.success())
+}
+
+fn has_commits() -> Result<bool, GitError> {
+    Ok(git(&["rev-parse", "--verify", "--quiet", "HEAD"])?.status.success())
+}
+
+fn git_args<'a>(repo: &'a str, args: &[&'a str]) -> Vec<&'a str> {
+    let mut full = vec!["--git-dir", repo];
+    full.extend_from_slice(args);
+    full
+}
+
+fn git(args: &[&str]) -> Result<std::process::Output, GitError> {
+    let repo = sync_repo().to_string_lossy().into_owned();
+    run_git(&git_args(&repo, args))
+}
+
+fn git_output(args: &[&str]) -> Result<String, GitError> {
+    let repo = sync_repo().to_string_lossy().into_owned();
+    let output = run_checked("git", &git_args(&repo, args))?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn git_mutating(args: &[&str]) -> Result<(), GitError> {
+    let repo = sync_repo().to_string_lossy().into_owned();
+    run_mutating("git", &git_args(&repo, args))
+}
+
+fn lines(output: &str) -> Vec<String> {
+    output.lines().filter(|line| !line.is_empty()).map(String::from).collect()
+}
@@ -0,0 +1,96 @@
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::GitError;
+
+// A temporary directory for files other programs are handed: install scripts, signatures, keys.
+// Another user could create a directory with a fixed name in the shared temp directory first and
+// swap the files between writing and use, so each one is new, has a name nobody can guess and is
+// only open to its owner. It is deleted, with its files, when dropped
+#[derive(Debug)]
+pub struct PrivateDir {
+    path: PathBuf,
+}
+
+impl PrivateDir {
+    // Function to create a new private directory in the system's temp directory
+    pub fn new(purpose: &str) -> Result<Self, GitError> {
+        let mut builder = DirBuilder::new();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        for _ in 0..16 {
+            let path = env::temp_dir().join(format!("gitup-{}-{}-{:016x}", purpose, process::id(), random()));
+            match builder.create(&path) {
+                Ok(()) => return Ok(PrivateDir { path }),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let message = format!("couldn't create a temporary directory in {}", env::temp_dir().display());
+        Err(io::Error::new(ErrorKind::AlreadyExists, message).into())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Function to write a new file into the directory, readable only by its owner. Anything
+    // already there under the name is an error rather than something to overwrite or follow
+    pub fn write(&self, name: &str, contents: &[u8]) -> Result<PathBuf, GitError> {
+        let path = self.path.join(name);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(&path)?.write_all(contents)?;
+        Ok(path)
+    }
+}
+
+impl Drop for PrivateDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+// Hashers are seeded randomly for every process, which is random enough for a name
+fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_dirs_are_new_closed_to_others_and_removed_when_dropped() {
+        let dir = PrivateDir::new("test").unwrap();
+        let other = PrivateDir::new("test").unwrap();
+        assert_ne!(dir.path(), other.path());
+
+        let path = dir.write("file", b"contents").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"contents");
+        assert!(dir.write("file", b"again").is_err());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(dir.path()).unwrap().permissions().mode() & 0o777, 0o700);
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let kept = dir.path().to_path_buf();
+        drop(dir);
+        assert!(!kept.exists());
+    }
+}
@@ -0,0 +1,56 @@
+// Stand-ins for the process-wide config directory and command runner, for tests. They are kept
+// per thread, and every test runs on a thread of its own, so tests running in parallel never
+// write into each other's directory or have their commands recorded by another test's runner
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::runner::{CommandRunner, RecordingRunner};
+
+thread_local! {
+    static CONFIG_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+    static RUNNER: RefCell<Option<Arc<dyn CommandRunner>>> = const { RefCell::new(None) };
+}
+
+// The config directory of the sandbox this thread is in, if any
+pub(crate) fn config_dir() -> Option<PathBuf> {
+    CONFIG_DIR.with(|dir| dir.borrow().clone())
+}
+
+// The runner of the sandbox this thread is in, if any
+pub(crate) fn command_runner() -> Option<Arc<dyn CommandRunner>> {
+    RUNNER.with(|runner| runner.borrow().clone())
+}
+
+// An empty config directory and a RecordingRunner for the test running on this thread; dropping
+// it puts the real ones back and deletes the directory
+pub(crate) struct Sandbox {
+    pub dir: PathBuf,
+    pub runner: Arc<RecordingRunner>,
+}
+
+impl Sandbox {
+    pub fn new() -> Sandbox {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "gitup-test-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(dir.join("config")).unwrap();
+        let runner = Arc::new(RecordingRunner::new());
+        CONFIG_DIR.with(|current| *current.borrow_mut() = Some(dir.join("config")));
+        RUNNER.with(|current| *current.borrow_mut() = Some(runner.clone() as Arc<dyn CommandRunner>));
+        Sandbox { dir, runner }
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        CONFIG_DIR.with(|current| *current.borrow_mut() = None);
+        RUNNER.with(|current| *current.borrow_mut() = None);
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
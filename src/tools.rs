@@ -0,0 +1,288 @@
+use serde::Serialize;
+
+use crate::config::GitConfig;
+use crate::install::command_exists;
+use crate::GitError;
+
+// An editor or diff/merge tool gitup knows how to set up
+struct KnownTool {
+    name: &'static str,
+    // Commands it is started with, in order of preference; the first one installed is used
+    programs: &'static [&'static str],
+    // core.editor, with the program left out; editors that fork need to be told to wait
+    editor: Option<&'static str>,
+    diff: Option<Support>,
+    merge: Option<Support>,
+}
+
+// How git runs a tool as a diff or merge tool
+#[derive(Clone, Copy)]
+enum Support {
+    // git has it built in (`git difftool --tool-help`); only the name needs setting
+    Builtin(&'static str),
+    // A command line git runs with $LOCAL, $REMOTE, $BASE and $MERGED filled in, with the
+    // program left out. Merge commands say whether the exit code shows an unresolved merge
+    Command { args: &'static str, trust_exit_code: bool },
+}
+
+const KNOWN_TOOLS: &[KnownTool] = &[
+    KnownTool {
+        name: "vscode",
+        programs: &["code"],
+        editor: Some("--wait"),
+        diff: Some(Support::Command {
+            args: r#"--wait --diff "$LOCAL" "$REMOTE""#,
+            trust_exit_code: false,
+        }),
+        merge: Some(Support::Command {
+            args: r#"--wait --merge "$REMOTE" "$LOCAL" "$BASE" "$MERGED""#,
+            trust_exit_code: false,
+        }),
+    },
+    KnownTool {
+        name: "vscodium",
+        programs: &["codium"],
+        editor: Some("--wait"),
+        diff: Some(Support::Command {
+            args: r#"--wait --diff "$LOCAL" "$REMOTE""#,
+            trust_exit_code: false,
+        }),
+        merge: Some(Support::Command {
+            args: r#"--wait --merge "$REMOTE" "$LOCAL" "$BASE" "$MERGED""#,
+            trust_exit_code: false,
+        }),
+    },
+    KnownTool {
+        name: "idea",
+        programs: &["idea", "idea64"],
+        editor: Some("--wait"),
+        diff: Some(Support::Command {
+            args: r#"diff "$LOCAL" "$REMOTE""#,
+            trust_exit_code: false,
+        }),
+        merge: Some(Support::Command {
+            args: r#"merge "$LOCAL" "$REMOTE" "$BASE" "$MERGED""#,
+            trust_exit_code: true,
+        }),
+    },
+    KnownTool {
+        name: "sublime",
+        programs: &["subl"],
+        editor: Some("-n -w"),
+        diff: None,
+        merge: None,
+    },
+    KnownTool {
+        name: "zed",
+        programs: &["zed"],
+        editor: Some("--wait"),
+        diff: None,
+        merge: None,
+    },
+    KnownTool {
+        name: "vim",
+        programs: &["vim"],
+        editor: Some(""),
+        diff: Some(Support::Builtin("vimdiff")),
+        merge: Some(Support::Builtin("vimdiff")),
+    },
+    KnownTool {
+        name: "nvim",
+        programs: &["nvim"],
+        editor: Some(""),
+        diff: Some(Support::Builtin("nvimdiff")),
+        merge: Some(Support::Builtin("nvimdiff")),
+    },
+    KnownTool {
+        name: "emacs",
+        programs: &["emacs"],
+        editor: Some(""),
+        diff: None,
+        merge: Some(Support::Builtin("emerge")),
+    },
+    KnownTool {
+        name: "helix",
+        programs: &["hx", "helix"],
+        editor: Some(""),
+        diff: None,
+        merge: None,
+    },
+    KnownTool {
+        name: "nano",
+        programs: &["nano"],
+        editor: Some(""),
+        diff: None,
+        merge: None,
+    },
+    KnownTool {
+        name: "notepad++",
+        programs: &["notepad++"],
+        editor: Some("-multiInst -notabbar -nosession -noPlugin"),
+        diff: None,
+        merge: None,
+    },
+    KnownTool {
+        name: "meld",
+        programs: &["meld"],
+        editor: None,
+        diff: Some(Support::Builtin("meld")),
+        merge: Some(Support::Builtin("meld")),
+    },
+    KnownTool {
+        name: "kdiff3",
+        programs: &["kdiff3"],
+        editor: None,
+        diff: Some(Support::Builtin("kdiff3")),
+        merge: Some(Support::Builtin("kdiff3")),
+    },
+    KnownTool {
+        name: "bc",
+        programs: &["bcompare"],
+        editor: None,
+        diff: Some(Support::Builtin("bc")),
+        merge: Some(Support::Builtin("bc")),
+    },
+    KnownTool {
+        name: "p4merge",
+        programs: &["p4merge"],
+        editor: None,
+        diff: Some(Support::Builtin("p4merge")),
+        merge: Some(Support::Builtin("p4merge")),
+    },
+    KnownTool {
+        name: "smerge",
+        programs: &["smerge"],
+        editor: None,
+        diff: Some(Support::Builtin("smerge")),
+        merge: Some(Support::Builtin("smerge")),
+    },
+    KnownTool {
+        name: "opendiff",
+        programs: &["opendiff"],
+        editor: None,
+        diff: Some(Support::Builtin("opendiff")),
+        merge: Some(Support::Builtin("opendiff")),
+    },
+    KnownTool {
+        name: "winmerge",
+        programs: &["WinMergeU"],
+        editor: None,
+        diff: Some(Support::Builtin("winmerge")),
+        merge: Some(Support::Builtin("winmerge")),
+    },
+];
+
+// A known tool, what it can be used as, and whether it is installed here
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolInfo {
+    pub name: &'static str,
+    pub programs: Vec<&'static str>,
+    pub editor: bool,
+    pub difftool: bool,
+    pub mergetool: bool,
+    // The program found on PATH, if any
+    pub installed: Option<&'static str>,
+}
+
+// Function to list the editors and diff/merge tools gitup can set up, with the ones installed
+pub fn known_tools() -> Vec<ToolInfo> {
+    KNOWN_TOOLS
+        .iter()
+        .map(|tool| ToolInfo {
+            name: tool.name,
+            programs: tool.programs.to_vec(),
+            editor: tool.editor.is_some(),
+            difftool: tool.diff.is_some(),
+            mergetool: tool.merge.is_some(),
+            installed: installed_program(tool),
+        })
+        .collect()
+}
+
+// Function to set the editor (core.editor), diff tool (diff.tool) and merge tool (merge.tool)
+// in the global config, each given by tool name or by its command (`code` for vscode). Tools
+// git doesn't have built in also get the difftool.<name>.cmd or mergetool.<name>.cmd entry
+// that runs them. Every tool has to be installed; the settings are returned in the order set
+pub fn set_tools(
+    editor: Option<&str>,
+    difftool: Option<&str>,
+    mergetool: Option<&str>,
+) -> Result<Vec<(String, String)>, GitError> {
+    let mut settings = Vec::new();
+    if let Some(name) = editor {
+        let (tool, program) = find_installed(name, "an editor", |tool| tool.editor.is_some())?;
+        let args = tool.editor.unwrap_or_default();
+        let command = if args.is_empty() { program.to_string() } else { format!("{} {}", program, args) };
+        settings.push(("core.editor".to_string(), command));
+    }
+    if let Some(name) = difftool {
+        let (tool, program) = find_installed(name, "a diff tool", |tool| tool.diff.is_some())?;
+        settings.extend(tool_settings("diff", "difftool", tool, program, tool.diff));
+    }
+    if let Some(name) = mergetool {
+        let (tool, program) = find_installed(name, "a merge tool", |tool| tool.merge.is_some())?;
+        settings.extend(tool_settings("merge", "mergetool", tool, program, tool.merge));
+    }
+    GitConfig::global().set_many(&settings)?;
+    Ok(settings)
+}
+
+// diff.tool or merge.tool, and for tools git doesn't know, the command that runs them
+fn tool_settings(
+    section: &str,
+    tool_section: &str,
+    tool: &KnownTool,
+    program: &str,
+    support: Option<Support>,
+) -> Vec<(String, String)> {
+    match support {
+        Some(Support::Builtin(name)) => vec![(format!("{}.tool", section), name.to_string())],
+        Some(Support::Command { args, trust_exit_code }) => {
+            let mut settings = vec![
+                (format!("{}.tool", section), tool.name.to_string()),
+                (format!("{}.{}.cmd", tool_section, tool.name), format!("{} {}", program, args)),
+            ];
+            if tool_section == "mergetool" {
+                settings.push((format!("mergetool.{}.trustExitCode", tool.name), trust_exit_code.to_string()));
+            }
+            settings
+        }
+        None => Vec::new(),
+    }
+}
+
+// The known tool called `name`, started as `name` or built into git as `name` (vimdiff), that
+// can play the role and is installed
+fn find_installed(
+    name: &str,
+    role: &str,
+    supports: impl Fn(&KnownTool) -> bool,
+) -> Result<(&'static KnownTool, &'static str), GitError> {
+    let name = name.to_lowercase();
+    let tool = KNOWN_TOOLS
+        .iter()
+        .find(|tool| {
+            tool.name == name
+                || tool.programs.iter().any(|program| program.to_lowercase() == name)
+                || [tool.diff, tool.merge]
+                    .iter()
+                    .any(|support| matches!(support, Some(Support::Builtin(builtin)) if *builtin == name))
+        })
+        .filter(|tool| supports(tool))
+        .ok_or_else(|| {
+            let candidates: Vec<&str> = KNOWN_TOOLS.iter().filter(|tool| supports(tool)).map(|tool| tool.name).collect();
+            GitError::InvalidInput(format!("'{}' isn't known as {}; use one of {}", name, role, candidates.join(", ")))
+        })?;
+    let program = installed_program(tool).ok_or_else(|| {
+        GitError::InvalidInput(format!(
+            "{} isn't installed ({} is not on PATH)",
+            tool.name,
+            tool.programs.join(" or ")
+        ))
+    })?;
+    Ok((tool, program))
+}
+
+fn installed_program(tool: &KnownTool) -> Option<&'static str> {
+    tool.programs.iter().copied().find(|program| command_exists(program))
+}
@@ -0,0 +1,303 @@
+// `gitup tui`: a keyboard-driven dashboard over profiles, the effective configuration,
+// directory bindings and snapshots. Only built with the `tui` feature
+use std::path::Path;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs};
+use ratatui::{DefaultTerminal, Frame};
+
+use gitup::binding::{list_bindings, Binding};
+use gitup::git::{list_config_entries, ConfigEntry, Scope};
+use gitup::profile::{active_profile, create_profile, delete_profile, list_profiles, use_profile, GitProfile};
+use gitup::scripts::{self, run_event};
+use gitup::snapshot::{list_snapshots, take_snapshot, Snapshot};
+use gitup::GitError;
+
+use crate::format_timestamp;
+
+const TABS: &[&str] = &["Profiles", "Config", "Bindings", "Snapshots"];
+
+const CREATE_FIELDS: &[&str] = &["Profile", "Name", "Email"];
+
+enum Mode {
+    Browse,
+    ConfirmDelete(String),
+    // The field being edited and the values typed so far
+    Create(usize, [String; 3]),
+}
+
+struct App {
+    tab: usize,
+    lists: [ListState; 4],
+    profiles: Vec<(String, GitProfile)>,
+    active: Option<String>,
+    config: Vec<ConfigEntry>,
+    bindings: Vec<Binding>,
+    snapshots: Vec<Snapshot>,
+    mode: Mode,
+    status: String,
+    quit: bool,
+}
+
+// Function to run the dashboard until the user quits, restoring the terminal afterwards
+pub fn run() -> Result<(), GitError> {
+    let mut app = App {
+        tab: 0,
+        lists: Default::default(),
+        profiles: Vec::new(),
+        active: None,
+        config: Vec::new(),
+        bindings: Vec::new(),
+        snapshots: Vec::new(),
+        mode: Mode::Browse,
+        status: "Tab switches views, arrows select, q quits".to_string(),
+        quit: false,
+    };
+    app.reload()?;
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), GitError> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                // Windows also reports key releases
+                if key.kind == KeyEventKind::Press {
+                    self.handle_key(key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn reload(&mut self) -> Result<(), GitError> {
+        self.profiles = list_profiles()?;
+        self.active = active_profile()?;
+        // Outside a repository this is just the system and global configuration
+        self.config = list_config_entries(Path::new("."))?;
+        self.bindings = list_bindings()?;
+        self.snapshots = list_snapshots()?;
+        for (tab, list) in self.lists.iter_mut().enumerate() {
+            let len = [self.profiles.len(), self.config.len(), self.bindings.len(), self.snapshots.len()][tab];
+            list.select(match list.selected() {
+                _ if len == 0 => None,
+                Some(selected) => Some(selected.min(len - 1)),
+                None => Some(0),
+            });
+        }
+        Ok(())
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => self.browse(key.code),
+            Mode::ConfirmDelete(profile) => {
+                if key.code == KeyCode::Char('y') {
+                    let result = take_snapshot("tui").and_then(|_| delete_profile(&profile));
+                    self.report(result.map(|_| format!("Deleted profile '{}'", profile)));
+                } else {
+                    self.status = "Nothing was deleted".to_string();
+                }
+            }
+            Mode::Create(field, mut values) => match key.code {
+                KeyCode::Esc => self.status = "No profile was created".to_string(),
+                KeyCode::Backspace => {
+                    values[field].pop();
+                    self.mode = Mode::Create(field, values);
+                }
+                KeyCode::Enter | KeyCode::Tab if field + 1 < CREATE_FIELDS.len() => {
+                    self.mode = Mode::Create(field + 1, values)
+                }
+                KeyCode::Enter => {
+                    let [profile, name, email] = values;
+                    let git_profile = GitProfile {
+                        name,
+                        email,
+                        ..Default::default()
+                    };
+                    let result = create_profile(&profile, git_profile);
+                    self.report(result.map(|_| format!("Created profile '{}'", profile)));
+                }
+                KeyCode::Char(c) => {
+                    values[field].push(c);
+                    self.mode = Mode::Create(field, values);
+                }
+                _ => self.mode = Mode::Create(field, values),
+            },
+        }
+    }
+
+    fn browse(&mut self, code: KeyCode) {
+        let list = &mut self.lists[self.tab];
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Tab | KeyCode::Right => self.tab = (self.tab + 1) % TABS.len(),
+            KeyCode::BackTab | KeyCode::Left => self.tab = (self.tab + TABS.len() - 1) % TABS.len(),
+            KeyCode::Down | KeyCode::Char('j') => list.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => list.select_previous(),
+            KeyCode::Char('r') => {
+                let result = self.reload();
+                self.report(result.map(|_| "Reloaded".to_string()));
+            }
+            KeyCode::Char('n') if self.tab == 0 => self.mode = Mode::Create(0, Default::default()),
+            KeyCode::Enter | KeyCode::Char('u') | KeyCode::Char('d') if self.tab == 0 => {
+                let Some((profile, _)) = list.selected().and_then(|i| self.profiles.get(i)) else {
+                    return;
+                };
+                let profile = profile.clone();
+                if code == KeyCode::Char('d') {
+                    self.mode = Mode::ConfirmDelete(profile);
+                } else {
+                    let result = take_snapshot("tui").and_then(|_| switch_profile(&profile));
+                    self.report(result);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Show the outcome of an action and pick up whatever it changed
+    fn report(&mut self, result: Result<String, GitError>) {
+        self.status = match result.and_then(|message| self.reload().map(|_| message)) {
+            Ok(message) => message,
+            Err(e) => format!("Error: {}", e),
+        };
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [tabs_area, body, help, status] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let tabs = Tabs::new(TABS.iter().copied())
+            .select(self.tab)
+            .block(Block::default().borders(Borders::ALL).title(" gitup "))
+            .highlight_style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED));
+        frame.render_widget(tabs, tabs_area);
+
+        let items: Vec<ListItem> = match self.tab {
+            0 => self
+                .profiles
+                .iter()
+                .map(|(profile, p)| {
+                    let marker = if self.active.as_ref() == Some(profile) { "*" } else { " " };
+                    ListItem::new(format!("{} {}: {} <{}>", marker, profile, p.name, p.email))
+                })
+                .collect(),
+            1 => self
+                .config
+                .iter()
+                .map(|entry| ListItem::new(format!("{:<8} {}={}", entry.scope, entry.key, entry.value)))
+                .collect(),
+            2 => self
+                .bindings
+                .iter()
+                .map(|binding| ListItem::new(format!("{} -> {}", binding.directory, binding.profile)))
+                .collect(),
+            _ => self
+                .snapshots
+                .iter()
+                .map(|snapshot| {
+                    ListItem::new(format!(
+                        "{}  {}  before {}",
+                        snapshot.id,
+                        format_timestamp(snapshot.timestamp),
+                        snapshot.reason
+                    ))
+                })
+                .collect(),
+        };
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(format!(" {} ", TABS[self.tab])))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, body, &mut self.lists[self.tab]);
+
+        let keys = match self.tab {
+            0 => "Enter/u use globally  n new  d delete  r reload  q quit",
+            _ => "Tab/←/→ switch view  ↑/↓ select  r reload  q quit",
+        };
+        frame.render_widget(Paragraph::new(keys).dim(), help);
+        frame.render_widget(Paragraph::new(self.status.as_str()), status);
+
+        match &self.mode {
+            Mode::Browse => {}
+            Mode::ConfirmDelete(profile) => {
+                let popup = popup_area(frame, 3);
+                frame.render_widget(Clear, popup);
+                frame.render_widget(
+                    Paragraph::new(format!("Delete profile '{}' and its bindings? (y/n)", profile))
+                        .block(Block::default().borders(Borders::ALL).title(" Delete ")),
+                    popup,
+                );
+            }
+            Mode::Create(field, values) => {
+                let popup = popup_area(frame, CREATE_FIELDS.len() as u16 + 2);
+                let lines: Vec<Line> = CREATE_FIELDS
+                    .iter()
+                    .zip(values)
+                    .enumerate()
+                    .map(|(i, (label, value))| {
+                        let cursor = if i == *field { "_" } else { "" };
+                        let line = Line::from(format!("{:<8} {}{}", label, value, cursor));
+                        if i == *field {
+                            line.bold()
+                        } else {
+                            line
+                        }
+                    })
+                    .collect();
+                frame.render_widget(Clear, popup);
+                frame.render_widget(
+                    Paragraph::new(lines).block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title(" New profile (Enter next/create, Esc cancel) "),
+                    ),
+                    popup,
+                );
+            }
+        }
+    }
+}
+
+// A box across the middle of the screen, `height` rows tall
+fn popup_area(frame: &Frame, height: u16) -> ratatui::layout::Rect {
+    let [_, middle, _] =
+        Layout::vertical([Constraint::Fill(1), Constraint::Length(height), Constraint::Fill(1)]).areas(frame.area());
+    let [_, popup, _] =
+        Layout::horizontal([Constraint::Percentage(10), Constraint::Percentage(80), Constraint::Percentage(10)])
+            .areas(middle);
+    popup
+}
+
+// Switch the global profile with the profile switch scripts around it, as `gitup profile use`
+// does. What the scripts print would break up the screen, so it's dropped; a failing
+// post-profile-switch script is shown after the switch instead
+fn switch_profile(profile: &str) -> Result<String, GitError> {
+    let previous = active_profile().ok().flatten().unwrap_or_default();
+    let mut context = vec![
+        ("profile", profile.to_string()),
+        ("previous", previous),
+        ("scope", Scope::Global.name().to_string()),
+    ];
+    run_event(scripts::Event::PreProfileSwitch, &context, &mut |_, _| {})?;
+    let p = use_profile(profile, Scope::Global)?;
+    let message = format!("Switched to '{}': {} <{}>", profile, p.name, p.email);
+    context.extend([("name", p.name), ("email", p.email)]);
+    Ok(match run_event(scripts::Event::PostProfileSwitch, &context, &mut |_, _| {}) {
+        Ok(_) => message,
+        Err(e) => format!("{} ({})", message, e),
+    })
+}
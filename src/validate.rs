@@ -0,0 +1,258 @@
+use crate::suggest::glob_match;
+use crate::GitError;
+
+// Characters that have no business in a config key and could change the meaning of a
+// command line if the key ever ended up in one
+const DANGEROUS_KEY_CHARS: &[char] = &['\n', '\r', '\0', ';', '|', '&', '$', '`', '<', '>', '"', '\''];
+
+// Config keys whose value git runs as a command, that point git at programs or hooks to run, or
+// that pull in other config files, as globs over lowercased keys. Settings from someone else's backup, bundle or template with
+// these keys would run their commands on this machine the next time git reads them
+const UNSAFE_KEYS: &[&str] = &[
+    "core.fsmonitor",
+    "core.pager",
+    "pager.*",
+    "core.editor",
+    "sequence.editor",
+    "core.sshcommand",
+    "core.askpass",
+    "core.gitproxy",
+    "core.hookspath",
+    "core.alternaterefscommand",
+    "uploadpack.packobjectshook",
+    "include.path",
+    "includeif.*.path",
+    "credential.helper",
+    "credential.*.helper",
+    "gpg.program",
+    "gpg.*.program",
+    "gpg.ssh.defaultkeycommand",
+    "filter.*.clean",
+    "filter.*.smudge",
+    "filter.*.process",
+    "diff.external",
+    "diff.*.command",
+    "diff.*.textconv",
+    "difftool.*.cmd",
+    "difftool.*.path",
+    "merge.*.driver",
+    "mergetool.*.cmd",
+    "mergetool.*.path",
+    "interactive.difffilter",
+    "trailer.*.cmd",
+    "trailer.*.command",
+    "sendemail.*cmd",
+    "sendemail.smtpserver",
+    "sendemail.*.smtpserver",
+    "browser.*.cmd",
+    "browser.*.path",
+    "man.*.cmd",
+    "man.*.path",
+    // The programs run on the other end of a fetch or push, which for a local remote is here
+    "remote.*.uploadpack",
+    "remote.*.receivepack",
+    // `always` lets remote URLs such as ext::<command> run commands
+    "protocol.allow",
+    "protocol.*.allow",
+];
+
+// Credential helpers that come with git or its installers, which credential.helper may name
+const BUILTIN_CREDENTIAL_HELPERS: &[&str] = &[
+    "cache",
+    "store",
+    "osxkeychain",
+    "wincred",
+    "libsecret",
+    "manager",
+    "manager-core",
+];
+
+// Function to check whether a setting lets whoever wrote it run commands on this machine: a key
+// from UNSAFE_KEYS with a value, except an fsmonitor that is only switched on or off (git's
+// built-in one), a credential helper that names one of git's own, a protocol that is never
+// allowed and an SMTP server given by host name, or an alias that runs a shell command (one
+// starting with '!')
+pub fn is_unsafe_setting(key: &str, value: &str) -> bool {
+    let key = key.to_lowercase();
+    if key.starts_with("alias.") {
+        return value.trim_start().starts_with('!');
+    }
+    if value.trim().is_empty() || !UNSAFE_KEYS.iter().any(|pattern| glob_match(pattern, &key)) {
+        return false;
+    }
+    if key == "core.fsmonitor" {
+        return !matches!(value.trim().to_lowercase().as_str(), "true" | "false" | "yes" | "no" | "on" | "off" | "1" | "0");
+    }
+    if key.starts_with("protocol.") {
+        return !value.trim().eq_ignore_ascii_case("never");
+    }
+    // A host name is fine; a path is a sendmail-like program to run
+    if key.starts_with("sendemail.") && key.ends_with(".smtpserver") {
+        return value.trim_start().starts_with(['/', '~']);
+    }
+    if key.starts_with("credential.") {
+        let helper = value.split_whitespace().next().unwrap_or_default();
+        return !BUILTIN_CREDENTIAL_HELPERS.contains(&helper);
+    }
+    true
+}
+
+// Function to check a profile name. Profile names become file names in the config directory
+// (`<profile>.gitconfig`, commit templates), so they can't hold path separators or `..`
+pub fn validate_profile_name(profile_name: &str) -> Result<(), GitError> {
+    if profile_name.trim().is_empty() {
+        return Err(GitError::InvalidInput("profile name must not be empty".to_string()));
+    }
+    if profile_name.contains(['/', '\\']) || profile_name.contains("..") || profile_name.contains(char::is_control) {
+        return Err(GitError::InvalidInput(format!(
+            "profile name '{}' must not contain '/', '\\', '..' or control characters",
+            profile_name.escape_debug()
+        )));
+    }
+    Ok(())
+}
+
+// Function to check a value for user.name; git refuses names that are empty or contain
+// the angle brackets that delimit the email in commit headers
+pub fn validate_name(name: &str) -> Result<(), GitError> {
+    if name.trim().is_empty() {
+        return Err(GitError::InvalidInput("name must not be empty".to_string()));
+    }
+    if name.contains(['<', '>', '\n', '\r', '\0']) {
+        return Err(GitError::InvalidInput(format!(
+            "name '{}' must not contain '<', '>' or line breaks",
+            name.escape_debug()
+        )));
+    }
+    Ok(())
+}
+
+// Function to check a value for user.email
+pub fn validate_email(email: &str) -> Result<(), GitError> {
+    if email.trim().is_empty() {
+        return Err(GitError::InvalidInput("email must not be empty".to_string()));
+    }
+    let valid = match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.is_empty()
+                && !domain.contains('@')
+                && !email.contains(|c: char| c.is_whitespace() || c == '<' || c == '>' || c == '\0')
+        }
+        None => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(GitError::InvalidInput(format!(
+            "'{}' is not a valid email address",
+            email.escape_debug()
+        )))
+    }
+}
+
+// Function to check that a key has git's section[.subsection].name shape: the section and
+// name are alphanumeric (plus '-'), the name starts with a letter, and the subsection,
+// which may hold paths or URLs, has no shell-dangerous characters
+pub fn validate_config_key(key: &str) -> Result<(), GitError> {
+    let invalid = |reason: &str| {
+        Err(GitError::InvalidInput(format!(
+            "invalid config key '{}': {}",
+            key.escape_debug(),
+            reason
+        )))
+    };
+    let (Some((section, rest)), Some((_, name))) = (key.split_once('.'), key.rsplit_once('.')) else {
+        return invalid("expected section.name");
+    };
+    let subsection = rest.rsplit_once('.').map(|(subsection, _)| subsection);
+
+    if section.is_empty() || !section.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return invalid("the section may only contain letters, digits and '-'");
+    }
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic())
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return invalid("the name must start with a letter and contain only letters, digits and '-'");
+    }
+    if subsection.is_some_and(|subsection| subsection.contains(DANGEROUS_KEY_CHARS)) {
+        return invalid("the subsection contains quotes, line breaks or shell metacharacters");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_names_that_escape_the_config_directory_are_rejected() {
+        for name in ["work", "client-a.eu", "Jane Doe", "über"] {
+            assert!(validate_profile_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", "  ", "../x", "..", "a/b", "a\\b", "a\nb", "a\0b"] {
+            assert!(validate_profile_name(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn settings_that_run_commands_are_unsafe() {
+        for (key, value) in [
+            ("core.sshCommand", "ssh -i key"),
+            ("core.fsmonitor", "./watch.sh"),
+            ("credential.https://example.com.helper", "!cat ~/.token"),
+            ("credential.helper", "/tmp/helper"),
+            ("filter.lfs.smudge", "git-lfs smudge -- %f"),
+            ("alias.co", "!sh -c 'id'"),
+            ("alias.co", "  !id"),
+            ("diff.external", "./diff.sh"),
+            ("difftool.mine.cmd", "./diff.sh"),
+            ("mergetool.mine.cmd", "./merge.sh"),
+            ("include.path", "~/other.gitconfig"),
+            ("includeIf.gitdir:~/work/.path", "~/work.gitconfig"),
+            ("gpg.ssh.defaultKeyCommand", "ssh-add -L"),
+            ("PAGER.log", "less"),
+            ("core.alternateRefsCommand", "./refs.sh"),
+            ("uploadpack.packObjectsHook", "./pack.sh"),
+            ("trailer.sign.cmd", "./trailer.sh"),
+            ("trailer.sign.command", "./trailer.sh"),
+            ("interactive.diffFilter", "./filter.sh"),
+            ("mergetool.mine.path", "/tmp/merge"),
+            ("difftool.mine.path", "/tmp/diff"),
+            ("sendemail.toCmd", "./to.sh"),
+            ("sendemail.ccCmd", "./cc.sh"),
+            ("sendemail.work.headerCmd", "./headers.sh"),
+            ("sendemail.smtpServer", "/tmp/sendmail"),
+            ("sendemail.work.smtpServer", "~/bin/sendmail"),
+            ("browser.mine.cmd", "./open.sh"),
+            ("browser.mine.path", "/tmp/browser"),
+            ("man.mine.cmd", "./man.sh"),
+            ("man.mine.path", "/tmp/man"),
+            ("remote.origin.uploadpack", "./upload.sh"),
+            ("remote.origin.receivepack", "./receive.sh"),
+            ("protocol.ext.allow", "always"),
+            ("protocol.ext.allow", "user"),
+            ("protocol.allow", "always"),
+        ] {
+            assert!(is_unsafe_setting(key, value), "{} = {}", key, value);
+        }
+    }
+
+    #[test]
+    fn ordinary_settings_are_safe() {
+        for (key, value) in [
+            ("user.name", "Jane Doe"),
+            ("alias.co", "checkout"),
+            ("core.fsmonitor", "true"),
+            ("core.fsmonitor", "OFF"),
+            ("credential.helper", "osxkeychain"),
+            ("credential.helper", "cache --timeout=3600"),
+            ("core.pager", ""),
+            ("core.autocrlf", "!input"),
+            ("sendemail.smtpServer", "smtp.example.com"),
+            ("protocol.ext.allow", "never"),
+        ] {
+            assert!(!is_unsafe_setting(key, value), "{} = {}", key, value);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::config::file::{find_git_dir, windows_install_dir, ConfigFile};
+use crate::config::GitConfig;
+use crate::git::run_command;
+use crate::GitError;
+
+// Settings people expect to be the same on both sides. Line endings and credential helpers
+// are left out: they differ on purpose
+const SHARED_KEYS: &[&str] = &[
+    "user.name",
+    "user.email",
+    "init.defaultBranch",
+    "pull.rebase",
+    "push.autoSetupRemote",
+    "commit.gpgsign",
+];
+
+// Where Git Credential Manager lives on the Windows side, relative to the Git for Windows
+// install: recent releases put it in mingw64\bin, older ones in libexec\git-core, and before
+// 2.39 it was called git-credential-manager-core
+const CREDENTIAL_MANAGER_PATHS: &[&str] = &[
+    r"mingw64\bin\git-credential-manager.exe",
+    r"mingw64\libexec\git-core\git-credential-manager.exe",
+    r"mingw64\libexec\git-core\git-credential-manager-core.exe",
+];
+
+// The standalone Git Credential Manager installer, for people without Git for Windows
+const STANDALONE_CREDENTIAL_MANAGER: &str = r"C:\Program Files (x86)\Git Credential Manager\git-credential-manager.exe";
+
+// What `setup_wsl` changed, and what it found but left for the user
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WslSetup {
+    pub changes: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+// Function to tell whether gitup is running inside the Windows Subsystem for Linux. WSL sets
+// WSL_DISTRO_NAME for the processes it starts; the kernel release names Microsoft as well,
+// which still works when sudo or `env -i` dropped the environment
+pub fn is_wsl() -> bool {
+    if cfg!(not(target_os = "linux")) {
+        return false;
+    }
+    env::var_os("WSL_DISTRO_NAME").is_some_and(|name| !name.is_empty())
+        || fs::read_to_string("/proc/sys/kernel/osrelease")
+            .is_ok_and(|release| release.to_lowercase().contains("microsoft"))
+}
+
+// Function to find Git Credential Manager on the Windows side, as a Linux path
+pub fn windows_credential_manager() -> Option<PathBuf> {
+    let install = windows_install_dir().unwrap_or_else(|| windows_path(r"C:\Program Files\Git"));
+    CREDENTIAL_MANAGER_PATHS
+        .iter()
+        .map(|path| install.join(path.replace('\\', "/")))
+        .chain([windows_path(STANDALONE_CREDENTIAL_MANAGER)])
+        .find(|path| path.is_file())
+}
+
+// Function to set Git inside WSL up to work alongside Git for Windows:
+// - credential.helper runs the Windows Git Credential Manager, so both sides share the
+//   credentials in the Windows credential store and sign-ins go through the Windows browser
+// - core.autocrlf is input, so files committed from WSL never get CRLF line endings
+// - core.fileMode is off in the repository at `repo` when it is on a Windows drive, where
+//   the executable bit isn't stored and every file would otherwise show as changed
+// Settings that differ from the Windows side's global config are reported as warnings
+pub fn setup_wsl(repo: &Path) -> Result<WslSetup, GitError> {
+    if !is_wsl() {
+        return Err(GitError::InvalidInput("this isn't WSL; `gitup wsl setup` only runs inside WSL".to_string()));
+    }
+    let mut setup = WslSetup::default();
+    let config = GitConfig::global();
+
+    let mut wanted = vec![("core.autocrlf".to_string(), "input".to_string())];
+    match windows_credential_manager() {
+        Some(manager) => wanted.push(("credential.helper".to_string(), shell_quote(&manager.to_string_lossy()))),
+        None => setup.warnings.push(
+            "Git Credential Manager wasn't found on the Windows side; install Git for Windows and run this again"
+                .to_string(),
+        ),
+    }
+    let mut entries = Vec::new();
+    for (key, value) in wanted {
+        // Every credential.helper value is replaced: WSL's own helpers would be asked first
+        let current = config.get_all(&key)?;
+        if current.len() == 1 && current[0] == value {
+            continue;
+        }
+        setup.changes.push(match current.as_slice() {
+            [] => format!("set {} = {}", key, value),
+            current => format!("changed {}: {} -> {}", key, current.join(", "), value),
+        });
+        entries.push((key, value));
+    }
+    config.set_many(&entries)?;
+
+    if find_git_dir(repo).is_some() && on_windows_drive(repo) {
+        let local = GitConfig::repo(repo);
+        if local.get("core.fileMode").as_deref() != Some("false") {
+            local.set("core.fileMode", "false")?;
+            setup.changes.push(format!("set core.fileMode = false in {}", repo.display()));
+        }
+    }
+
+    match windows_global_config() {
+        Some(windows) => setup.warnings.extend(divergence(&config, &windows)),
+        None => setup.warnings.push(
+            "the Windows side's global config couldn't be read, so it wasn't compared".to_string(),
+        ),
+    }
+    Ok(setup)
+}
+
+// Shared settings that are set differently, or only on one side
+fn divergence(config: &GitConfig, windows: &ConfigFile) -> Vec<String> {
+    SHARED_KEYS
+        .iter()
+        .filter_map(|key| match (config.get(key), windows.get(key)) {
+            (Some(here), Some(there)) if here != there => {
+                Some(format!("{} is '{}' in WSL but '{}' on Windows", key, here, there))
+            }
+            (Some(_), None) => Some(format!("{} is set in WSL but not on Windows", key)),
+            (None, Some(_)) => Some(format!("{} is set on Windows but not in WSL", key)),
+            _ => None,
+        })
+        .collect()
+}
+
+// The global config of Git for Windows, %USERPROFILE%\.gitconfig, read from this side.
+// Includes aren't followed, as with any file read without git
+fn windows_global_config() -> Option<ConfigFile> {
+    // cmd.exe complains about starting in a Linux directory on stderr, and carries on
+    let output = run_command("cmd.exe", &["/C", "echo %USERPROFILE%"]).ok()?;
+    let profile = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || profile.is_empty() || profile.contains('%') {
+        return None;
+    }
+    let path = windows_path(&profile).join(".gitconfig");
+    if path.is_file() {
+        ConfigFile::load(&path).ok()
+    } else {
+        Some(ConfigFile::default())
+    }
+}
+
+// A Windows path (C:\Users\me) as a path on this side. wslpath knows where the drives are
+// mounted; without it they are assumed to be in /mnt, WSL's default
+fn windows_path(path: &str) -> PathBuf {
+    if let Ok(output) = run_command("wslpath", &["-u", path]) {
+        let converted = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !converted.is_empty() {
+            return PathBuf::from(converted);
+        }
+    }
+    match path.split_once(":\\") {
+        Some((drive, rest)) => Path::new("/mnt").join(drive.to_lowercase()).join(rest.replace('\\', "/")),
+        None => PathBuf::from(path),
+    }
+}
+
+// Whether `path` is on a Windows drive: WSL 1 mounts those as drvfs, WSL 2 as 9p with
+// aname=drvfs. The mount with the longest matching mount point is the one `path` is on
+fn on_windows_drive(path: &Path) -> bool {
+    let (Ok(path), Ok(mounts)) = (fs::canonicalize(path), fs::read_to_string("/proc/mounts")) else {
+        return false;
+    };
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [_, mount_point, fs_type, options, ..] => Some((mount_point.replace("\\040", " "), *fs_type, *options)),
+                _ => None,
+            }
+        })
+        .filter(|(mount_point, _, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _, _)| mount_point.len())
+        .is_some_and(|(_, fs_type, options)| fs_type == "drvfs" || options.contains("aname=drvfs"))
+}
+
+// Git runs credential helpers through the shell, so spaces in the path (Program Files) and
+// other special characters are escaped
+fn shell_quote(path: &str) -> String {
+    let mut quoted = String::with_capacity(path.len());
+    for c in path.chars() {
+        if !(c.is_ascii_alphanumeric() || matches!(c, '/' | '.' | '_' | '-')) {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted
+}